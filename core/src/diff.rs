@@ -0,0 +1,227 @@
+use crate::status::{FileEntry, Section};
+use anyhow::Result;
+use git2::{Diff, DiffFile, DiffFindOptions, DiffFormat, DiffOptions, Repository};
+use std::fs;
+
+/// Options the user can tweak at runtime. Staging must use the same settings
+/// as the displayed diff or hunk indices would not line up.
+#[derive(Clone, Copy, Default)]
+pub struct DiffSettings {
+    pub context_lines: u32,
+    /// `git diff -w`
+    pub ignore_all_space: bool,
+    /// `git diff -b`
+    pub ignore_space_change: bool,
+    /// `git diff --ignore-blank-lines`
+    pub ignore_blank_lines: bool,
+}
+
+impl DiffSettings {
+    /// Whether the diff leaves out changes that are there, which makes its
+    /// hunks unfit for staging.
+    pub fn ignores_whitespace(&self) -> bool {
+        self.ignore_all_space || self.ignore_space_change || self.ignore_blank_lines
+    }
+}
+
+/// The git2 diff behind `entry`, optionally reversed (used to unstage).
+pub fn file_diff<'r>(
+    repo: &'r Repository,
+    entry: &FileEntry,
+    settings: DiffSettings,
+    reverse: bool,
+) -> Result<Diff<'r>> {
+    let mut opts = DiffOptions::new();
+    opts.context_lines(settings.context_lines);
+    opts.ignore_whitespace(settings.ignore_all_space);
+    opts.ignore_whitespace_change(settings.ignore_space_change);
+    opts.ignore_blank_lines(settings.ignore_blank_lines);
+    opts.pathspec(&entry.path);
+    if let Some(rename) = &entry.rename {
+        opts.pathspec(&rename.from);
+    }
+    opts.disable_pathspec_match(true);
+    opts.include_untracked(true);
+    opts.recurse_untracked_dirs(true);
+    opts.show_untracked_content(true);
+    opts.reverse(reverse);
+
+    let mut diff = match entry.section {
+        Section::Index => {
+            let head = repo.head().ok();
+            let tree = head.and_then(|h| h.peel_to_tree().ok());
+            repo.diff_tree_to_index(tree.as_ref(), None, Some(&mut opts))?
+        }
+        Section::Worktree | Section::Conflicted => {
+            repo.diff_index_to_workdir(None, Some(&mut opts))?
+        }
+    };
+
+    // Pair the old and new paths up so the diff shows the edits, not a delete and an add
+    if entry.rename.is_some() {
+        diff.find_similar(Some(
+            DiffFindOptions::new().renames(true).for_untracked(true),
+        ))?;
+    }
+
+    Ok(diff)
+}
+
+/// `entry`'s file on the two sides its diff compares: HEAD and the index for
+/// a staged change, the index and the worktree for an unstaged one. A side
+/// the file isn't on, or is bigger than `limit` bytes on, is `None`.
+pub fn file_contents(
+    repo: &Repository,
+    entry: &FileEntry,
+    limit: u64,
+) -> Result<[Option<Vec<u8>>; 2]> {
+    let diff = file_diff(repo, entry, DiffSettings::default(), false)?;
+    let Some(delta) = diff
+        .deltas()
+        .find(|d| d.new_file().path() == Some(entry.path.as_path()))
+    else {
+        return Ok([None, None]);
+    };
+    Ok([
+        side(repo, &delta.old_file(), limit),
+        side(repo, &delta.new_file(), limit),
+    ])
+}
+
+/// One side of a delta: its blob, or the worktree file, which isn't hashed.
+fn side(repo: &Repository, file: &DiffFile, limit: u64) -> Option<Vec<u8>> {
+    if !file.exists() || file.size() > limit {
+        return None;
+    }
+    match repo.find_blob(file.id()) {
+        // A tree's side of a diff doesn't always carry its size
+        Ok(blob) if blob.size() as u64 > limit => None,
+        Ok(blob) => Some(blob.content().to_vec()),
+        Err(_) => {
+            let path = repo.workdir()?.join(file.path()?);
+            if fs::metadata(&path).ok()?.len() > limit {
+                return None;
+            }
+            fs::read(&path).ok()
+        }
+    }
+}
+
+/// One line of a patch, as git prints it.
+pub struct DiffLine {
+    /// `+`, `-` or ` ` in a hunk, `F` for the file header (all of it in one
+    /// line), `H` for a hunk header, and git's own origins for the rest
+    pub origin: char,
+    /// The text after the origin, with its newline
+    pub content: String,
+    /// Index of the hunk the line belongs to; `None` in the file header
+    pub hunk: Option<usize>,
+}
+
+/// `entry`'s diff as the patch `git diff` prints, line by line.
+pub struct DiffText {
+    pub lines: Vec<DiffLine>,
+}
+
+impl DiffText {
+    pub fn load(repo: &Repository, entry: &FileEntry, settings: DiffSettings) -> Result<Self> {
+        let diff = file_diff(repo, entry, settings, false)?;
+        let mut lines = Vec::new();
+        let mut hunk: Option<usize> = None;
+
+        diff.print(DiffFormat::Patch, |_, _, line| {
+            if line.origin() == 'H' {
+                hunk = Some(hunk.map_or(0, |i| i + 1));
+            }
+            lines.push(DiffLine {
+                origin: line.origin(),
+                content: String::from_utf8_lossy(line.content()).into_owned(),
+                hunk,
+            });
+            true
+        })?;
+
+        Ok(DiffText { lines })
+    }
+
+    /// The patch text, which `git apply` takes back: all of it, or only hunk
+    /// `hunk` under the file header. `None` when there's no such hunk, or
+    /// nothing at all.
+    pub fn patch(&self, hunk: Option<usize>) -> Option<String> {
+        if self.lines.is_empty()
+            || hunk.is_some_and(|i| !self.lines.iter().any(|l| l.hunk == Some(i)))
+        {
+            return None;
+        }
+
+        let mut text = String::new();
+        for line in &self.lines {
+            if hunk.is_none() || line.hunk.is_none() || line.hunk == hunk {
+                if matches!(line.origin, '+' | '-' | ' ') {
+                    text.push(line.origin);
+                }
+                text.push_str(&line.content);
+            }
+        }
+        Some(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{commit, repo, stage, write};
+    use std::path::PathBuf;
+
+    fn entry(path: &str, section: Section) -> FileEntry {
+        FileEntry {
+            path: PathBuf::from(path),
+            section,
+            rename: None,
+        }
+    }
+
+    #[test]
+    fn patch_of_one_hunk_keeps_the_header() {
+        let (_dir, repo) = repo();
+        let lines: Vec<String> = (1..=20).map(|n| format!("line {}\n", n)).collect();
+        write(&repo, "a.txt", &lines.concat());
+        commit(&repo, "first");
+        let mut edited = lines.clone();
+        edited[1] = String::from("changed 2\n");
+        edited[18] = String::from("changed 19\n");
+        write(&repo, "a.txt", &edited.concat());
+
+        let settings = DiffSettings {
+            context_lines: 3,
+            ..Default::default()
+        };
+        let text = DiffText::load(&repo, &entry("a.txt", Section::Worktree), settings).unwrap();
+        let second = text.patch(Some(1)).unwrap();
+        assert!(second.starts_with("diff --git a/a.txt b/a.txt"));
+        assert!(second.contains("+changed 19"));
+        assert!(!second.contains("+changed 2\n"));
+        assert!(text.patch(Some(2)).is_none());
+    }
+
+    #[test]
+    fn contents_are_the_two_sides_compared() {
+        let (_dir, repo) = repo();
+        write(&repo, "a.txt", "old\n");
+        commit(&repo, "first");
+        write(&repo, "a.txt", "staged\n");
+        stage(&repo, "a.txt");
+        write(&repo, "a.txt", "worktree\n");
+
+        let [old, new] = file_contents(&repo, &entry("a.txt", Section::Index), 1024).unwrap();
+        assert_eq!(old.as_deref(), Some(&b"old\n"[..]));
+        assert_eq!(new.as_deref(), Some(&b"staged\n"[..]));
+        let [old, new] = file_contents(&repo, &entry("a.txt", Section::Worktree), 1024).unwrap();
+        assert_eq!(old.as_deref(), Some(&b"staged\n"[..]));
+        assert_eq!(new.as_deref(), Some(&b"worktree\n"[..]));
+
+        // Past the limit a side is left out
+        let [old, _] = file_contents(&repo, &entry("a.txt", Section::Index), 2).unwrap();
+        assert!(old.is_none());
+    }
+}
@@ -0,0 +1,125 @@
+use anyhow::{Context, Result, bail};
+use git2::{AttrCheckFlags, Repository};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// The first line of every pointer file.
+const VERSION: &str = "version https://git-lfs.github.com/spec/v1";
+
+/// What git stores for a file kept in Git LFS: a few lines naming the object
+/// the LFS server has, in place of the content.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pointer {
+    /// The object's SHA-256, in hex
+    pub oid: String,
+    /// The content's size in bytes
+    pub size: u64,
+}
+
+impl Pointer {
+    /// The pointer `content` is, if it's one. Pointers are small and start
+    /// with the spec's version line; anything else is content.
+    pub fn parse(content: &[u8]) -> Option<Self> {
+        if content.len() > 1024 {
+            return None;
+        }
+        let text = std::str::from_utf8(content).ok()?;
+        let mut lines = text.lines();
+        if lines.next()? != VERSION {
+            return None;
+        }
+        let (mut oid, mut size) = (None, None);
+        for line in lines {
+            if let Some(hex) = line.strip_prefix("oid sha256:") {
+                oid = Some(hex.to_string());
+            } else if let Some(bytes) = line.strip_prefix("size ") {
+                size = bytes.parse().ok();
+            }
+        }
+        let oid = oid.filter(|o| o.len() == 64 && o.bytes().all(|b| b.is_ascii_hexdigit()))?;
+        Some(Pointer { oid, size: size? })
+    }
+
+    /// The pointer's text, as git stores it.
+    pub fn text(&self) -> String {
+        format!("{}\noid sha256:{}\nsize {}\n", VERSION, self.oid, self.size)
+    }
+
+    /// Where the object goes once downloaded: `lfs/objects/ab/cd/abcd…` in
+    /// the git directory, or wherever `lfs.storage` says.
+    pub fn object_path(&self, repo: &Repository) -> PathBuf {
+        // Shared by every worktree, like the objects git keeps itself
+        let git_dir = repo.commondir();
+        let storage = match repo.config().and_then(|c| c.get_path("lfs.storage")) {
+            Ok(dir) if dir.is_relative() => git_dir.join(dir),
+            Ok(dir) => dir,
+            Err(_) => git_dir.join("lfs"),
+        };
+        storage
+            .join("objects")
+            .join(&self.oid[..2])
+            .join(&self.oid[2..4])
+            .join(&self.oid)
+    }
+
+    /// Whether the object has been downloaded.
+    pub fn fetched(&self, repo: &Repository) -> bool {
+        self.object_path(repo).is_file()
+    }
+}
+
+/// Whether `.gitattributes` has `path` kept in Git LFS (`filter=lfs`).
+pub fn tracked(repo: &Repository, path: &Path) -> bool {
+    repo.get_attr(path, "filter", AttrCheckFlags::FILE_THEN_INDEX)
+        .is_ok_and(|filter| filter == Some("lfs"))
+}
+
+/// `content`, or the object it points to when it's a pointer whose object
+/// has been downloaded and is at most `limit` bytes.
+pub fn resolve(repo: &Repository, content: Vec<u8>, limit: u64) -> Vec<u8> {
+    match Pointer::parse(&content) {
+        Some(pointer) if pointer.size <= limit => {
+            fs::read(pointer.object_path(repo)).unwrap_or(content)
+        }
+        _ => content,
+    }
+}
+
+/// Downloads the objects of `pointers` not downloaded yet, as a checkout
+/// would: `git lfs smudge` fetches each one into the object store. The
+/// worktree is left alone. Returns how many were downloaded.
+pub fn fetch(repo: &Repository, path: &Path, pointers: &[Pointer]) -> Result<usize> {
+    let workdir = repo.workdir().unwrap_or(repo.path());
+    let mut fetched = 0;
+    for pointer in pointers.iter().filter(|p| !p.fetched(repo)) {
+        let mut child = Command::new("git")
+            .args(["lfs", "smudge", "--"])
+            .arg(path)
+            .current_dir(workdir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("cannot run git lfs")?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(pointer.text().as_bytes())?;
+        }
+
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            match stderr.lines().find(|l| !l.trim().is_empty()) {
+                // Plain git answers `lfs` with its unknown-command message
+                Some(line) if line.contains("'lfs' is not a git command") => {
+                    bail!("git-lfs is not installed")
+                }
+                Some(line) => bail!("git lfs smudge failed: {}", line.trim()),
+                None => bail!("git lfs smudge failed"),
+            }
+        }
+        fetched += 1;
+    }
+    Ok(fetched)
+}
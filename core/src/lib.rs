@@ -0,0 +1,12 @@
+//! The parts of helper-git that need no terminal: opening the repository and
+//! summarising HEAD, the status model, diffs as git prints them, and Git LFS
+//! pointers. The `hg` binary draws its views on top of these, and other tools
+//! can reuse them.
+
+pub mod diff;
+pub mod lfs;
+pub mod repo;
+pub mod status;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
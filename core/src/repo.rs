@@ -0,0 +1,236 @@
+use anyhow::{Result, anyhow};
+use git2::{Branch, ErrorCode, Repository};
+use std::fmt;
+use std::path::Path;
+
+pub fn open_repo() -> Result<Repository> {
+    open_repo_in(Path::new("."))
+}
+
+/// The repository `dir` is in, as git finds it from there.
+pub fn open_repo_in(dir: &Path) -> Result<Repository> {
+    match Repository::discover(dir) {
+        Ok(repo) => Ok(repo),
+        Err(e) if e.code() == ErrorCode::NotFound => Err(anyhow!(
+            "fatal: not a git repository (or any parent directory)"
+        )),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Fails for a bare repository, which `command` can't run in without a
+/// worktree; history, refs and the worktree list all work without one.
+pub fn require_worktree(repo: &Repository, command: &str) -> Result<()> {
+    if repo.is_bare() {
+        return Err(anyhow!(
+            "fatal: {} is a bare repository, and `hg {}` needs a worktree; run it in a clone, or add a worktree with `hg worktree`",
+            repo.path().display(),
+            command
+        ));
+    }
+    Ok(())
+}
+
+/// The repository at a glance: where HEAD is (a branch, with how far it is
+/// from its upstream, or a detached commit), how many stashes there are and
+/// what the last commit was.
+#[derive(Clone)]
+pub struct RepoSummary {
+    /// The worktree's directory name
+    pub name: String,
+    /// The branch name, or the short SHA when HEAD is detached
+    pub head: String,
+    pub detached: bool,
+    /// The branch has no commits yet, so there's nothing at HEAD to diff
+    /// against
+    pub unborn: bool,
+    /// How the branch compares with its upstream; `None` off a branch, or
+    /// before its first commit
+    pub upstream: Option<Upstream>,
+    pub stashes: usize,
+    /// First line of HEAD's message; `None` before the first commit
+    pub last_commit: Option<String>,
+}
+
+/// What the checked-out branch tracks.
+#[derive(Clone)]
+pub enum Upstream {
+    /// Nothing configured, so fetch, pull and push have nowhere set to go
+    Unset,
+    /// Configured, but the remote branch no longer exists
+    Gone(String),
+    Tracking {
+        /// Short name, like `origin/main`
+        name: String,
+        ahead: usize,
+        behind: usize,
+    },
+}
+
+impl RepoSummary {
+    pub fn load(repo: &Repository) -> Self {
+        // A bare repository has no worktree, so it goes by its git directory
+        let dir = repo.workdir().unwrap_or_else(|| repo.path());
+        let name = dir
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        // Every stash is an entry in the stash ref's reflog
+        let stashes = repo.reflog("refs/stash").map_or(0, |log| log.len());
+        let last_commit = repo
+            .head()
+            .and_then(|h| h.peel_to_commit())
+            .ok()
+            .and_then(|c| c.summary().map(String::from));
+
+        let summary = |head: String, detached| RepoSummary {
+            name: name.clone(),
+            head,
+            detached,
+            unborn: false,
+            upstream: None,
+            stashes,
+            last_commit: last_commit.clone(),
+        };
+
+        let head = match repo.head() {
+            Ok(head) => head,
+            // Unborn branch: HEAD still names it even though it has no commits
+            Err(_) => {
+                let name = repo
+                    .find_reference("HEAD")
+                    .ok()
+                    .and_then(|r| r.symbolic_target().map(str::to_string))
+                    .map(|target| target.trim_start_matches("refs/heads/").to_string())
+                    .unwrap_or_else(|| String::from("HEAD"));
+                return RepoSummary {
+                    unborn: true,
+                    ..summary(name, false)
+                };
+            }
+        };
+
+        if !head.is_branch() {
+            let sha = head
+                .target()
+                .map(|oid| oid.to_string()[..7].to_string())
+                .unwrap_or_else(|| String::from("HEAD"));
+            return summary(sha, true);
+        }
+
+        let name = head.shorthand().unwrap_or("HEAD").to_string();
+        RepoSummary {
+            upstream: upstream(repo, Branch::wrap(head)),
+            ..summary(name, false)
+        }
+    }
+
+    /// Commits ahead of and behind the upstream, when the branch tracks one.
+    pub fn ahead_behind(&self) -> Option<(usize, usize)> {
+        match self.upstream {
+            Some(Upstream::Tracking { ahead, behind, .. }) => Some((ahead, behind)),
+            _ => None,
+        }
+    }
+}
+
+fn upstream(repo: &Repository, branch: Branch) -> Option<Upstream> {
+    let local = branch.get().target()?;
+    let Ok(configured) = repo.branch_upstream_name(branch.get().name()?) else {
+        return Some(Upstream::Unset);
+    };
+    let configured = configured.as_str().unwrap_or("").to_string();
+    let name = configured
+        .strip_prefix("refs/remotes/")
+        .or_else(|| configured.strip_prefix("refs/heads/"))
+        .unwrap_or(&configured)
+        .to_string();
+
+    let Some(target) = branch.upstream().ok().and_then(|b| b.get().target()) else {
+        return Some(Upstream::Gone(name));
+    };
+    let (ahead, behind) = repo.graph_ahead_behind(local, target).ok()?;
+    Some(Upstream::Tracking {
+        name,
+        ahead,
+        behind,
+    })
+}
+
+/// Short description for panel titles: `main ↑2 ↓1`, or the short SHA when
+/// HEAD is detached.
+impl fmt::Display for RepoSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.ahead_behind() {
+            Some((ahead, behind)) => write!(f, "{} ↑{} ↓{}", self.head, ahead, behind),
+            None => write!(f, "{}", self.head),
+        }
+    }
+}
+
+/// [`RepoSummary`] as its panel title text.
+pub fn head_summary(repo: &Repository) -> String {
+    RepoSummary::load(repo).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{commit, repo, write};
+
+    #[test]
+    fn open_outside_a_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        let error = open_repo_in(dir.path()).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "fatal: not a git repository (or any parent directory)"
+        );
+    }
+
+    #[test]
+    fn open_from_a_subdirectory_and_a_bare_repository() {
+        let (dir, _repo) = repo();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        let found = open_repo_in(&dir.path().join("sub")).unwrap();
+        assert_eq!(
+            found.workdir().unwrap().canonicalize().unwrap(),
+            dir.path().canonicalize().unwrap()
+        );
+
+        require_worktree(&found, "status").unwrap();
+
+        let bare = tempfile::tempdir().unwrap();
+        Repository::init_bare(bare.path()).unwrap();
+        let found = open_repo_in(bare.path()).unwrap();
+        assert!(found.is_bare());
+        let error = require_worktree(&found, "status").err().unwrap();
+        assert!(error.to_string().contains("is a bare repository"));
+    }
+
+    #[test]
+    fn summary_of_an_unborn_branch() {
+        let (_dir, repo) = repo();
+        let summary = RepoSummary::load(&repo);
+        assert!(summary.unborn);
+        assert!(!summary.detached);
+        assert!(summary.last_commit.is_none());
+        assert_eq!(summary.stashes, 0);
+    }
+
+    #[test]
+    fn summary_after_a_commit() {
+        let (_dir, repo) = repo();
+        write(&repo, "a.txt", "a\n");
+        let oid = commit(&repo, "First commit\n\nWith a body");
+        let summary = RepoSummary::load(&repo);
+        assert!(!summary.unborn);
+        assert_eq!(summary.last_commit.as_deref(), Some("First commit"));
+        assert!(matches!(summary.upstream, Some(Upstream::Unset)));
+
+        repo.set_head_detached(oid).unwrap();
+        let summary = RepoSummary::load(&repo);
+        assert!(summary.detached);
+        assert_eq!(summary.head, oid.to_string()[..7]);
+    }
+}
@@ -0,0 +1,1238 @@
+use anyhow::{Result, anyhow};
+use git2::{
+    DiffFindOptions, DiffFormat, DiffOptions, Oid, Pathspec, PathspecFlags, Repository, Status,
+    StatusOptions, SubmoduleIgnore, SubmoduleStatus,
+};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Which diff a file is looked at through: HEAD against the index, or the
+/// index against the worktree. A conflict has neither until it's resolved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Section {
+    /// Unmerged paths; these only appear during a merge, rebase or cherry-pick
+    Conflicted,
+    Index,
+    Worktree,
+}
+
+/// One side of a status entry as the views list it, enough to find its diff.
+/// Paths are kept as git stores them, which needn't be UTF-8; they are only
+/// made readable (lossily) for display.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileEntry {
+    pub path: PathBuf,
+    pub section: Section,
+    /// Where the file was renamed from, in this section's diff
+    pub rename: Option<Rename>,
+}
+
+impl FileEntry {
+    /// An untracked or ignored directory that git lists whole, as `dir/`.
+    pub fn is_directory(&self) -> bool {
+        self.path.as_os_str().as_encoded_bytes().ends_with(b"/")
+    }
+}
+
+/// What happened to a file on one side of the status, index or worktree.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+    TypeChange,
+    /// Only in the worktree; the index side of a new file is `Added`
+    Untracked,
+    Ignored,
+}
+
+impl ChangeKind {
+    /// Lowercase name, as `--format json` spells it.
+    pub fn name(self) -> &'static str {
+        match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Modified => "modified",
+            ChangeKind::Deleted => "deleted",
+            ChangeKind::Renamed => "renamed",
+            ChangeKind::TypeChange => "typechange",
+            ChangeKind::Untracked => "untracked",
+            ChangeKind::Ignored => "ignored",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rename {
+    pub from: PathBuf,
+    /// Content similarity in percent, as git reports it
+    pub similarity: Option<u16>,
+}
+
+/// One side of a status entry. A rename moves the path, so each side
+/// carries its own.
+#[derive(Clone)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub path: PathBuf,
+    pub rename: Option<Rename>,
+}
+
+/// A file in `git status`, split like its two `--short` columns: `staged` is
+/// the index against HEAD, `unstaged` the worktree against the index.
+#[derive(Clone)]
+pub struct StatusEntry {
+    /// The path git reports, which is the old name of a rename
+    pub path: PathBuf,
+    pub staged: Option<Change>,
+    pub unstaged: Option<Change>,
+    /// Unmerged; neither side means anything until it's resolved
+    pub conflicted: bool,
+    /// Which versions of a conflicted file the index holds
+    pub stages: Stages,
+    /// Set for a submodule, with what changed inside it
+    pub submodule: Option<SubmoduleState>,
+}
+
+/// The versions of an unmerged path the index holds, its stages 1 to 3.
+/// One side deleting the file, or both adding it, leaves some out.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stages {
+    pub ancestor: bool,
+    pub ours: bool,
+    pub theirs: bool,
+}
+
+impl Stages {
+    /// The `XY` `git status --porcelain` gives the conflict.
+    pub fn code(self) -> &'static str {
+        match (self.ancestor, self.ours, self.theirs) {
+            (true, false, false) => "DD",
+            (false, true, false) => "AU",
+            (true, true, false) => "UD",
+            (false, false, true) => "UA",
+            (true, false, true) => "DU",
+            (false, true, true) => "AA",
+            _ => "UU",
+        }
+    }
+}
+
+/// What `git status` says about a submodule's worktree, in its words.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SubmoduleState {
+    /// Checked out at another commit than the one in the index
+    pub new_commits: bool,
+    /// Changes of its own, staged or not
+    pub modified_content: bool,
+    pub untracked_content: bool,
+}
+
+impl SubmoduleState {
+    /// As `git status` puts it, e.g. `new commits, modified content`; empty
+    /// when the worktree matches.
+    pub fn describe(&self) -> String {
+        [
+            (self.new_commits, "new commits"),
+            (self.modified_content, "modified content"),
+            (self.untracked_content, "untracked content"),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, text)| *text)
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+}
+
+impl StatusEntry {
+    /// Where the file is now: the newest side's path, past any rename.
+    pub fn current_path(&self) -> &Path {
+        [&self.unstaged, &self.staged]
+            .iter()
+            .find_map(|side| side.as_ref())
+            .map_or(&self.path, |c| &c.path)
+    }
+
+    /// `git status --porcelain` style line, e.g. `RM old -> new`.
+    pub fn porcelain(&self) -> String {
+        if self.conflicted {
+            return format!("{} {}", self.stages.code(), quoted(&self.path));
+        }
+
+        let code = |change: &Option<Change>| match change.as_ref().map(|c| c.kind) {
+            None => ' ',
+            Some(ChangeKind::Added) => 'A',
+            Some(ChangeKind::Modified) => 'M',
+            Some(ChangeKind::Deleted) => 'D',
+            Some(ChangeKind::Renamed) => 'R',
+            Some(ChangeKind::TypeChange) => 'T',
+            Some(ChangeKind::Untracked) => '?',
+            Some(ChangeKind::Ignored) => '!',
+        };
+        let (x, y) = match self.unstaged.as_ref().map(|c| c.kind) {
+            // git doubles these up rather than leaving the index column blank
+            Some(ChangeKind::Untracked) => ('?', '?'),
+            Some(ChangeKind::Ignored) => ('!', '!'),
+            _ => (code(&self.staged), code(&self.unstaged)),
+        };
+
+        // Newest path last, with where it was renamed from in front
+        let newest = self.current_path();
+        let from = [&self.staged, &self.unstaged]
+            .iter()
+            .find_map(|side| side.as_ref()?.rename.as_ref())
+            .map(|r| &r.from);
+
+        match from {
+            Some(from) => format!("{}{} {} -> {}", x, y, quoted(from), quoted(newest)),
+            None => format!("{}{} {}", x, y, quoted(newest)),
+        }
+    }
+}
+
+/// `path` as `git status --porcelain` writes it: as is, unless it has
+/// control characters, quotes, backslashes or any non-ASCII byte, in which
+/// case it's put in quotes with those escaped C-style (`"caf\303\251.txt"`).
+fn quoted(path: &Path) -> String {
+    let bytes = path.as_os_str().as_encoded_bytes();
+    if !bytes
+        .iter()
+        .any(|&b| !(0x20..0x7f).contains(&b) || b == b'"' || b == b'\\')
+    {
+        return path.display().to_string();
+    }
+
+    let mut text = String::from("\"");
+    for &b in bytes {
+        match b {
+            b'"' => text.push_str("\\\""),
+            b'\\' => text.push_str("\\\\"),
+            b'\t' => text.push_str("\\t"),
+            b'\n' => text.push_str("\\n"),
+            b'\r' => text.push_str("\\r"),
+            0x20..0x7f => text.push(b as char),
+            _ => text.push_str(&format!("\\{:03o}", b)),
+        }
+    }
+    text.push('"');
+    text
+}
+
+/// Narrows the status the way `git status <pathspec>...` does, and to one
+/// side of it.
+#[derive(Clone, Default, PartialEq)]
+pub struct StatusFilter {
+    /// Git pathspecs relative to the repository root; none (and no globs)
+    /// matches everything
+    pub pathspecs: Vec<String>,
+    /// `:(glob)` pathspecs relative to the root, whose `*` stops at `/` where
+    /// libgit2's crosses it; see [`glob_matches`]
+    pub globs: Vec<String>,
+    /// What `:!` pathspecs leave out, plain and glob ones; libgit2 has no
+    /// magic of its own for either
+    pub excludes: Vec<String>,
+    pub exclude_globs: Vec<String>,
+    /// [`Section::Index`] keeps the staged side only, [`Section::Worktree`]
+    /// the unstaged one. Conflicts are kept either way, since they block both.
+    pub only: Option<Section>,
+}
+
+impl StatusFilter {
+    /// Whether it lets everything through.
+    pub fn is_empty(&self) -> bool {
+        self.pathspecs.is_empty()
+            && self.globs.is_empty()
+            && self.excludes.is_empty()
+            && self.exclude_globs.is_empty()
+            && self.only.is_none()
+    }
+
+    /// Pathspecs for libgit2 that take in at least what the filter does.
+    /// libgit2 globs its own way, so globs go in as the directory they're
+    /// all in, and are matched once it's done.
+    fn narrowing(&self) -> Vec<&str> {
+        let mut pathspecs: Vec<&str> = self.pathspecs.iter().map(String::as_str).collect();
+        for glob in &self.globs {
+            let wildcard = glob.find(['*', '?', '[', '\\']).unwrap_or(glob.len());
+            match glob[..wildcard].rfind('/') {
+                Some(end) => pathspecs.push(&glob[..end]),
+                // Anywhere in the worktree
+                None => return Vec::new(),
+            }
+        }
+        pathspecs
+    }
+
+    /// Drops the side `only` leaves out, and files left with neither.
+    fn apply(&self, files: &mut Vec<StatusEntry>) {
+        match self.only {
+            Some(Section::Index) => files.iter_mut().for_each(|file| file.unstaged = None),
+            Some(Section::Worktree) => files.iter_mut().for_each(|file| file.staged = None),
+            Some(Section::Conflicted) | None => return,
+        }
+        files.retain(|file| file.conflicted || file.staged.is_some() || file.unstaged.is_some());
+    }
+}
+
+/// Every changed file, in the order git lists them, with renames paired up.
+/// Ignored files are only included when `include_ignored` is set.
+pub fn collect_status(
+    repo: &Repository,
+    include_ignored: bool,
+    filter: &StatusFilter,
+) -> Result<Vec<StatusEntry>> {
+    scan(repo, include_ignored, filter, &[], true)
+}
+
+/// [`collect_status`] the way `git status --porcelain` lists it: a directory
+/// with nothing tracked in it is one untracked entry ending in `/`, not a
+/// line for every file under it.
+pub fn collect_status_collapsed(
+    repo: &Repository,
+    include_ignored: bool,
+    filter: &StatusFilter,
+) -> Result<Vec<StatusEntry>> {
+    // libgit2 drops a directory outright when a pathspec points inside it,
+    // where git lists the files matched, so the directories it does collapse
+    // stand in for their files in the full list
+    let untracked = |file: &StatusEntry| {
+        file.unstaged
+            .as_ref()
+            .is_some_and(|c| c.kind == ChangeKind::Untracked)
+    };
+    let is_dir = |file: &StatusEntry| file.path.as_os_str().as_encoded_bytes().ends_with(b"/");
+    let dirs: Vec<StatusEntry> = scan(repo, include_ignored, filter, &[], false)?
+        .into_iter()
+        .filter(|file| untracked(file) && is_dir(file))
+        .collect();
+
+    let mut files = Vec::new();
+    let mut listed = HashSet::new();
+    for file in collect_status(repo, include_ignored, filter)? {
+        let dir = dirs
+            .iter()
+            .find(|d| untracked(&file) && file.path.starts_with(&d.path));
+        match dir {
+            // In place of the first file in it
+            Some(dir) if listed.insert(&dir.path) => files.push(dir.clone()),
+            Some(_) => {}
+            None => files.push(file),
+        }
+    }
+    Ok(files)
+}
+
+/// [`collect_status`], limited to `paths` and whatever is under them when
+/// there are any; untracked directories are listed file by file when
+/// `recurse_untracked` is set.
+fn scan(
+    repo: &Repository,
+    include_ignored: bool,
+    filter: &StatusFilter,
+    paths: &[PathBuf],
+    recurse_untracked: bool,
+) -> Result<Vec<StatusEntry>> {
+    let mut opts = StatusOptions::new();
+    if paths.is_empty() {
+        for pathspec in filter.narrowing() {
+            opts.pathspec(pathspec);
+        }
+    } else {
+        // Literal paths let libgit2 skip every directory that none of them is in
+        for path in paths {
+            opts.pathspec(path);
+        }
+        opts.disable_pathspec_match(true);
+    }
+    opts.include_untracked(true);
+    opts.recurse_untracked_dirs(recurse_untracked);
+    // Ignored directories stay collapsed, like `git status --ignored`
+    opts.include_ignored(include_ignored);
+    opts.renames_head_to_index(true);
+    opts.renames_index_to_workdir(true);
+
+    let statuses = repo.statuses(Some(&mut opts))?;
+
+    let similarity = if statuses.iter().any(|e| {
+        e.status()
+            .intersects(Status::INDEX_RENAMED | Status::WT_RENAMED)
+    }) {
+        rename_similarity(repo, &filter.narrowing())?
+    } else {
+        HashMap::new()
+    };
+
+    // By path, since that's what the status has; their state is looked up by name
+    let submodules: HashMap<PathBuf, String> = repo
+        .submodules()?
+        .iter()
+        .filter_map(|sm| Some((sm.path().to_path_buf(), sm.name()?.to_string())))
+        .collect();
+    let submodule_state = |name: &str| -> Result<SubmoduleState> {
+        // Unspecified honours `submodule.<name>.ignore`, as git status does
+        let status = repo.submodule_status(name, SubmoduleIgnore::Unspecified)?;
+        Ok(SubmoduleState {
+            new_commits: status.contains(SubmoduleStatus::WD_MODIFIED),
+            modified_content: status
+                .intersects(SubmoduleStatus::WD_INDEX_MODIFIED | SubmoduleStatus::WD_WD_MODIFIED),
+            untracked_content: status.contains(SubmoduleStatus::WD_UNTRACKED),
+        })
+    };
+
+    // Each conflict's stages, read from the index only when there are any
+    let mut stages: HashMap<PathBuf, Stages> = HashMap::new();
+    if statuses
+        .iter()
+        .any(|e| e.status().contains(Status::CONFLICTED))
+    {
+        for conflict in repo.index()?.conflicts()? {
+            let conflict = conflict?;
+            let Some(entry) = [&conflict.ancestor, &conflict.our, &conflict.their]
+                .into_iter()
+                .find_map(|side| side.as_ref())
+            else {
+                continue;
+            };
+            stages.insert(
+                bytes_path(&entry.path),
+                Stages {
+                    ancestor: conflict.ancestor.is_some(),
+                    ours: conflict.our.is_some(),
+                    theirs: conflict.their.is_some(),
+                },
+            );
+        }
+    }
+
+    let mut files = Vec::new();
+
+    for entry in statuses.iter() {
+        let path = bytes_path(entry.path_bytes());
+        let status = entry.status();
+
+        // `path` is the old name of a rename; each side has its own new one
+        let change = |delta: Option<git2::DiffDelta>, kind: Option<ChangeKind>| {
+            let kind = kind?;
+            let new = delta
+                .as_ref()
+                .and_then(|d| d.new_file().path().map(Path::to_path_buf))
+                .unwrap_or_else(|| path.clone());
+            let rename = match kind {
+                ChangeKind::Renamed => delta
+                    .as_ref()
+                    .and_then(|d| d.old_file().path().map(Path::to_path_buf))
+                    .map(|from| Rename {
+                        from,
+                        similarity: similarity.get(&new).copied(),
+                    }),
+                _ => None,
+            };
+            Some(Change {
+                kind,
+                path: new,
+                rename,
+            })
+        };
+
+        let conflicted = status.contains(Status::CONFLICTED);
+        let (staged, unstaged) = if conflicted {
+            (None, None)
+        } else {
+            (
+                change(entry.head_to_index(), index_kind(status)),
+                change(entry.index_to_workdir(), worktree_kind(status)),
+            )
+        };
+
+        files.push(StatusEntry {
+            submodule: submodules
+                .get(&path)
+                .map(|name| submodule_state(name))
+                .transpose()?,
+            stages: stages.get(&path).copied().unwrap_or_default(),
+            path,
+            staged,
+            unstaged,
+            conflicted,
+        });
+    }
+
+    // The literal paths went in instead of the filter's, or globs libgit2
+    // matched too loosely, so it still has to match
+    if !filter.globs.is_empty() || !paths.is_empty() && !filter.pathspecs.is_empty() {
+        let pathspec = Pathspec::new(&filter.pathspecs)?;
+        let matches = |path: &Path| matches_any(&pathspec, &filter.pathspecs, &filter.globs, path);
+        files.retain(|file| matches(&file.path) || matches(file.current_path()));
+    }
+    if !filter.excludes.is_empty() || !filter.exclude_globs.is_empty() {
+        let pathspec = Pathspec::new(&filter.excludes)?;
+        let matches =
+            |path: &Path| matches_any(&pathspec, &filter.excludes, &filter.exclude_globs, path);
+        files.retain(|file| !matches(&file.path) && !matches(file.current_path()));
+    }
+    filter.apply(&mut files);
+
+    Ok(files)
+}
+
+/// Whether `path` matches one of the pathspecs `plain`, compiled as
+/// `pathspec`, or one of `globs`.
+fn matches_any(pathspec: &Pathspec, plain: &[String], globs: &[String], path: &Path) -> bool {
+    !plain.is_empty() && pathspec.matches_path(path, PathspecFlags::DEFAULT)
+        || globs.iter().any(|glob| glob_matches(glob, path))
+}
+
+/// Whether `path` matches `pattern` as git's `:(glob)` magic has it: `*`, `?`
+/// and `[...]` stop at `/`, a `**` between slashes crosses any number of
+/// directories, and a pattern without wildcards takes in what's under it.
+pub fn glob_matches(pattern: &str, path: &Path) -> bool {
+    let pattern = pattern.as_bytes();
+    let path = path.as_os_str().as_encoded_bytes();
+    // A collapsed directory
+    let path = path.strip_suffix(b"/").unwrap_or(path);
+    if !pattern.iter().any(|c| b"*?[\\".contains(c)) {
+        let pattern = pattern.strip_suffix(b"/").unwrap_or(pattern);
+        return path == pattern
+            || path.starts_with(pattern) && path.get(pattern.len()) == Some(&b'/');
+    }
+    wildmatch(pattern, path, true)
+}
+
+/// [`glob_matches`] for a pattern with wildcards; `component` says whether
+/// `pattern` starts a path component, as a `**` has to.
+fn wildmatch(pattern: &[u8], path: &[u8], component: bool) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        [b'*', b'*'] if component => true,
+        [b'*', b'*', b'/', rest @ ..] if component => {
+            // No directories, or everything up to one of the slashes
+            wildmatch(rest, path, true)
+                || (path.iter().enumerate())
+                    .any(|(i, &c)| c == b'/' && wildmatch(rest, &path[i + 1..], true))
+        }
+        [b'*', rest @ ..] => {
+            let rest = &rest[rest.iter().take_while(|&&c| c == b'*').count()..];
+            let within = path.iter().position(|&c| c == b'/').unwrap_or(path.len());
+            (0..=within).any(|i| wildmatch(rest, &path[i..], false))
+        }
+        [b'?', rest @ ..] => {
+            matches!(path.first(), Some(&c) if c != b'/') && wildmatch(rest, &path[1..], false)
+        }
+        [b'[', rest @ ..] => {
+            let (negated, set) = match rest {
+                [b'!' | b'^', set @ ..] => (true, set),
+                set => (false, set),
+            };
+            // A `]` straight after the `[` is one of the set
+            let Some(end) = set.iter().skip(1).position(|&c| c == b']') else {
+                return path.first() == Some(&b'[') && wildmatch(rest, &path[1..], false);
+            };
+            let (set, rest) = (&set[..end + 1], &set[end + 2..]);
+            let Some(&c) = path.first() else {
+                return false;
+            };
+            let mut found = false;
+            let mut i = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == b'-' {
+                    found |= (set[i]..=set[i + 2]).contains(&c);
+                    i += 3;
+                } else {
+                    found |= set[i] == c;
+                    i += 1;
+                }
+            }
+            c != b'/' && found != negated && wildmatch(rest, &path[1..], false)
+        }
+        [b'\\', c, rest @ ..] | [c, rest @ ..] => {
+            path.first() == Some(c) && wildmatch(rest, &path[1..], *c == b'/')
+        }
+    }
+}
+
+/// Lines added and removed in each file on each side of the status, as
+/// `git diff --numstat` counts them, keyed by the path the side has now.
+/// Binary files and submodules have no counts.
+pub fn line_counts(
+    repo: &Repository,
+    entries: &[StatusEntry],
+) -> Result<HashMap<(PathBuf, Section), (usize, usize)>> {
+    let mut counts = HashMap::new();
+    if entries.is_empty() || entries.len() > COUNT_LIMIT {
+        return Ok(counts);
+    }
+
+    // Only the listed files, renames' old names included, are diffed
+    let mut opts = DiffOptions::new();
+    for entry in entries {
+        opts.pathspec(&entry.path);
+        for change in [&entry.staged, &entry.unstaged].into_iter().flatten() {
+            opts.pathspec(&change.path);
+        }
+    }
+    opts.disable_pathspec_match(true)
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .show_untracked_content(true)
+        .ignore_submodules(true)
+        .context_lines(0);
+
+    let tree = repo.head().ok().and_then(|h| h.peel_to_tree().ok());
+    let diffs = [
+        (
+            Section::Index,
+            repo.diff_tree_to_index(tree.as_ref(), None, Some(&mut opts))?,
+        ),
+        (
+            Section::Worktree,
+            repo.diff_index_to_workdir(None, Some(&mut opts))?,
+        ),
+    ];
+    for (section, mut diff) in diffs {
+        // Paired up the way the status pairs its renames
+        diff.find_similar(Some(
+            DiffFindOptions::new().renames(true).for_untracked(true),
+        ))?;
+        diff.print(DiffFormat::Patch, |delta, _, line| {
+            let (added, removed) = match line.origin() {
+                '+' => (1, 0),
+                '-' => (0, 1),
+                _ => return true,
+            };
+            if let Some(path) = delta.new_file().path() {
+                let count = counts
+                    .entry((path.to_path_buf(), section))
+                    .or_insert((0, 0));
+                count.0 += added;
+                count.1 += removed;
+            }
+            true
+        })?;
+    }
+
+    Ok(counts)
+}
+
+/// Changed files past which [`line_counts`] isn't worth the diffs it takes.
+const COUNT_LIMIT: usize = 1000;
+
+/// Changed paths past which scanning them one by one stops paying off.
+const RESCAN_LIMIT: usize = 256;
+
+/// The last [`collect_status`] result, so a refresh after a few edits only
+/// rescans the paths that changed. Whatever can move entries all over the
+/// tree (the index or HEAD changing, ignore rules, submodules, a rename
+/// pairing up with a file elsewhere) has it scan everything again.
+#[derive(Default)]
+pub struct StatusCache {
+    scanned: Option<Scanned>,
+}
+
+struct Scanned {
+    include_ignored: bool,
+    filter: StatusFilter,
+    stamp: Stamp,
+    entries: Vec<StatusEntry>,
+}
+
+/// The index file's modification time and size, and HEAD's commit, as they
+/// were at a scan.
+#[derive(PartialEq)]
+struct Stamp {
+    index: Option<(SystemTime, u64)>,
+    head: Option<Oid>,
+}
+
+impl Stamp {
+    fn read(repo: &Repository) -> Self {
+        let index = fs::metadata(repo.path().join("index"))
+            .ok()
+            .and_then(|meta| Some((meta.modified().ok()?, meta.len())));
+        Stamp {
+            index,
+            head: repo.head().ok().and_then(|head| head.target()),
+        }
+    }
+}
+
+impl StatusCache {
+    /// Every changed file, like [`collect_status`]. With `changed` (paths
+    /// relative to the worktree that were written since the last call) only
+    /// those are scanned again, when nothing else can have moved; `None`
+    /// scans everything.
+    pub fn status(
+        &mut self,
+        repo: &Repository,
+        include_ignored: bool,
+        filter: &StatusFilter,
+        changed: Option<&[PathBuf]>,
+    ) -> Result<Vec<StatusEntry>> {
+        // Read first, so a commit landing mid-scan still counts as a change next time
+        let stamp = Stamp::read(repo);
+        if let Some(changed) = changed
+            && let Some(scanned) = &mut self.scanned
+            && scanned.include_ignored == include_ignored
+            && scanned.filter == *filter
+            && scanned.stamp == stamp
+            && let Some(entries) = rescan(repo, &scanned.entries, include_ignored, filter, changed)?
+        {
+            scanned.entries = entries;
+            return Ok(scanned.entries.clone());
+        }
+
+        let entries = collect_status(repo, include_ignored, filter)?;
+        self.scanned = Some(Scanned {
+            include_ignored,
+            filter: filter.clone(),
+            stamp,
+            entries: entries.clone(),
+        });
+        Ok(entries)
+    }
+}
+
+/// `entries` with the ones at or under `changed` scanned again; `None` when
+/// the change can reach past those paths.
+fn rescan(
+    repo: &Repository,
+    entries: &[StatusEntry],
+    include_ignored: bool,
+    filter: &StatusFilter,
+    changed: &[PathBuf],
+) -> Result<Option<Vec<StatusEntry>>> {
+    if changed.len() > RESCAN_LIMIT
+        || changed.iter().any(|path| {
+            path.file_name().is_some_and(|name| name == ".gitignore")
+                || path == Path::new(".gitmodules")
+        })
+    {
+        return Ok(None);
+    }
+    if changed.is_empty() {
+        return Ok(Some(entries.to_vec()));
+    }
+
+    // The scan stops at a submodule, so a write inside one rescans its gitlink
+    let submodules: Vec<PathBuf> = repo
+        .submodules()?
+        .iter()
+        .map(|sm| sm.path().to_path_buf())
+        .collect();
+    let changed: Vec<PathBuf> = changed
+        .iter()
+        .map(|path| {
+            submodules
+                .iter()
+                .find(|sm| path.starts_with(sm))
+                .unwrap_or(path)
+                .clone()
+        })
+        .collect();
+    let changed = changed.as_slice();
+
+    let touched = |entry: &StatusEntry| {
+        let mut paths = vec![&entry.path];
+        for change in [&entry.staged, &entry.unstaged].into_iter().flatten() {
+            paths.push(&change.path);
+            paths.extend(change.rename.as_ref().map(|r| &r.from));
+        }
+        paths
+            .iter()
+            .any(|path| changed.iter().any(|c| path.starts_with(c)))
+    };
+    let (old, mut kept): (Vec<_>, Vec<_>) = entries.iter().cloned().partition(touched);
+    let fresh = scan(repo, include_ignored, filter, changed, true)?;
+
+    // Worktree renames pair a deleted file with a new one anywhere in the
+    // tree, which a scan of some paths can't see; staged ones wait for the index
+    let has = |list: &[StatusEntry], kind: ChangeKind| {
+        list.iter()
+            .any(|entry| entry.unstaged.as_ref().is_some_and(|c| c.kind == kind))
+    };
+    if has(&old, ChangeKind::Renamed)
+        || has(&fresh, ChangeKind::Renamed)
+        || (has(&fresh, ChangeKind::Deleted) && has(&kept, ChangeKind::Untracked))
+        || (has(&fresh, ChangeKind::Untracked) && has(&kept, ChangeKind::Deleted))
+    {
+        return Ok(None);
+    }
+
+    // In git's order, which compares the paths byte by byte
+    kept.extend(fresh);
+    kept.sort_by(|a, b| {
+        let (a, b) = (a.path.as_os_str(), b.path.as_os_str());
+        a.as_encoded_bytes().cmp(b.as_encoded_bytes())
+    });
+    Ok(Some(kept))
+}
+
+/// A path `git clean -d` would remove: an untracked file, or a directory with
+/// nothing tracked in it, which goes whole and ends in `/`.
+#[derive(Clone, PartialEq)]
+pub struct Cleanable {
+    pub path: PathBuf,
+    /// Only listed when asked for, like `git clean -x`
+    pub ignored: bool,
+}
+
+impl Cleanable {
+    pub fn is_directory(&self) -> bool {
+        self.path.as_os_str().as_encoded_bytes().ends_with(b"/")
+    }
+}
+
+/// Everything `git clean -d` would remove, plus ignored paths when
+/// `include_ignored` is set, in path order. Nested repositories are left
+/// out, as git leaves them unless forced twice.
+pub fn cleanable(repo: &Repository, include_ignored: bool) -> Result<Vec<Cleanable>> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("repository has no working directory"))?;
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(true)
+        .recurse_untracked_dirs(false)
+        .include_ignored(include_ignored)
+        .recurse_ignored_dirs(false)
+        .exclude_submodules(true);
+
+    let mut paths = Vec::new();
+    for entry in repo.statuses(Some(&mut opts))?.iter() {
+        if !entry.status().intersects(Status::WT_NEW | Status::IGNORED) {
+            continue;
+        }
+        let found = Cleanable {
+            path: bytes_path(entry.path_bytes()),
+            ignored: entry.status().contains(Status::IGNORED),
+        };
+        if found.is_directory() && holds_repository(&workdir.join(&found.path)) {
+            // Everything in it but the repositories, as git goes in after them
+            let path = found.path.clone();
+            cleanable_around_repositories(repo, &path, found.ignored, include_ignored, &mut paths)?;
+        } else {
+            paths.push(found);
+        }
+    }
+    paths.sort_by(|a, b| a.path.as_os_str().cmp(b.path.as_os_str()));
+    Ok(paths)
+}
+
+/// Whether `dir` is a repository or has one anywhere under it. Links aren't
+/// followed, as removing one doesn't touch what it points at.
+pub fn holds_repository(dir: &Path) -> bool {
+    if dir.join(".git").exists() {
+        return true;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return false;
+    };
+    entries
+        .flatten()
+        .any(|entry| entry.file_type().is_ok_and(|t| t.is_dir()) && holds_repository(&entry.path()))
+}
+
+/// What's in the untracked directory `dir` (relative to the workdir) that
+/// `git clean -d` would remove, with nested repositories left out and the
+/// directories holding them gone into.
+fn cleanable_around_repositories(
+    repo: &Repository,
+    dir: &Path,
+    ignored: bool,
+    include_ignored: bool,
+    paths: &mut Vec<Cleanable>,
+) -> Result<()> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("repository has no working directory"))?;
+    for entry in fs::read_dir(workdir.join(dir))? {
+        let entry = entry?;
+        let path = dir.join(entry.file_name());
+        let full = entry.path();
+        let is_dir = entry.file_type()?.is_dir();
+        // Under an ignored directory everything is
+        let ignored = ignored || repo.is_path_ignored(&path)?;
+        if is_dir && full.join(".git").exists() || ignored && !include_ignored {
+            continue;
+        }
+        if is_dir && holds_repository(&full) {
+            cleanable_around_repositories(repo, &path, ignored, include_ignored, paths)?;
+        } else if is_dir {
+            let mut path = path.into_os_string();
+            path.push("/");
+            paths.push(Cleanable {
+                path: path.into(),
+                ignored,
+            });
+        } else {
+            paths.push(Cleanable { path, ignored });
+        }
+    }
+    Ok(())
+}
+
+/// A path from git's raw bytes. Unix paths are bytes too, so nothing is lost;
+/// elsewhere git only writes UTF-8 ones.
+pub fn bytes_path(bytes: &[u8]) -> PathBuf {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+    }
+    #[cfg(not(unix))]
+    {
+        PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+fn index_kind(status: Status) -> Option<ChangeKind> {
+    if status.contains(Status::INDEX_RENAMED) {
+        Some(ChangeKind::Renamed)
+    } else if status.contains(Status::INDEX_NEW) {
+        Some(ChangeKind::Added)
+    } else if status.contains(Status::INDEX_MODIFIED) {
+        Some(ChangeKind::Modified)
+    } else if status.contains(Status::INDEX_DELETED) {
+        Some(ChangeKind::Deleted)
+    } else if status.contains(Status::INDEX_TYPECHANGE) {
+        Some(ChangeKind::TypeChange)
+    } else {
+        None
+    }
+}
+
+fn worktree_kind(status: Status) -> Option<ChangeKind> {
+    if status.contains(Status::IGNORED) {
+        Some(ChangeKind::Ignored)
+    } else if status.contains(Status::WT_RENAMED) {
+        Some(ChangeKind::Renamed)
+    } else if status.contains(Status::WT_NEW) {
+        Some(ChangeKind::Untracked)
+    } else if status.contains(Status::WT_MODIFIED) {
+        Some(ChangeKind::Modified)
+    } else if status.contains(Status::WT_DELETED) {
+        Some(ChangeKind::Deleted)
+    } else if status.contains(Status::WT_TYPECHANGE) {
+        Some(ChangeKind::TypeChange)
+    } else {
+        None
+    }
+}
+
+/// Similarity percent of every detected rename, keyed by new path. git2 does
+/// not expose it on deltas, but the patch header carries it.
+fn rename_similarity(repo: &Repository, pathspecs: &[&str]) -> Result<HashMap<PathBuf, u16>> {
+    let mut opts = DiffOptions::new();
+    for pathspec in pathspecs {
+        opts.pathspec(pathspec);
+    }
+    opts.include_untracked(true);
+    opts.recurse_untracked_dirs(true);
+
+    let tree = repo.head().ok().and_then(|h| h.peel_to_tree().ok());
+    let mut diffs = [
+        repo.diff_tree_to_index(tree.as_ref(), None, Some(&mut opts))?,
+        repo.diff_index_to_workdir(None, Some(&mut opts))?,
+    ];
+
+    let mut similarity = HashMap::new();
+    for diff in &mut diffs {
+        diff.find_similar(Some(
+            DiffFindOptions::new().renames(true).for_untracked(true),
+        ))?;
+
+        diff.print(DiffFormat::PatchHeader, |delta, _, line| {
+            let header = String::from_utf8_lossy(line.content());
+            let percent = header
+                .lines()
+                .find_map(|l| l.strip_prefix("similarity index "))
+                .and_then(|p| p.trim_end_matches('%').parse().ok());
+
+            if let Some(percent) = percent
+                && let Some(path) = delta.new_file().path()
+            {
+                similarity.insert(path.to_path_buf(), percent);
+            }
+            true
+        })?;
+    }
+
+    Ok(similarity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{commit, conflict, repo, stage, write};
+
+    fn status(repo: &Repository) -> Vec<StatusEntry> {
+        collect_status(repo, false, &StatusFilter::default()).unwrap()
+    }
+
+    fn kinds(entry: &StatusEntry) -> (Option<ChangeKind>, Option<ChangeKind>) {
+        (
+            entry.staged.as_ref().map(|c| c.kind),
+            entry.unstaged.as_ref().map(|c| c.kind),
+        )
+    }
+
+    #[test]
+    fn new_files_are_untracked_until_staged() {
+        let (_dir, repo) = repo();
+        write(&repo, "a.txt", "a\n");
+        let files = status(&repo);
+        assert_eq!(files.len(), 1);
+        assert!(kinds(&files[0]) == (None, Some(ChangeKind::Untracked)));
+        assert_eq!(files[0].porcelain(), "?? a.txt");
+
+        stage(&repo, "a.txt");
+        let files = status(&repo);
+        assert!(kinds(&files[0]) == (Some(ChangeKind::Added), None));
+        assert_eq!(files[0].porcelain(), "A  a.txt");
+    }
+
+    #[test]
+    fn staged_then_modified_has_both_sides() {
+        let (_dir, repo) = repo();
+        write(&repo, "a.txt", "one\n");
+        commit(&repo, "first");
+        write(&repo, "a.txt", "two\n");
+        stage(&repo, "a.txt");
+        write(&repo, "a.txt", "three\n");
+
+        let files = status(&repo);
+        assert_eq!(files.len(), 1);
+        assert!(kinds(&files[0]) == (Some(ChangeKind::Modified), Some(ChangeKind::Modified)));
+        assert_eq!(files[0].porcelain(), "MM a.txt");
+    }
+
+    #[test]
+    fn deleted_files_are_listed() {
+        let (dir, repo) = repo();
+        write(&repo, "a.txt", "a\n");
+        write(&repo, "b.txt", "b\n");
+        commit(&repo, "first");
+        fs::remove_file(dir.path().join("a.txt")).unwrap();
+        fs::remove_file(dir.path().join("b.txt")).unwrap();
+        stage(&repo, "b.txt");
+
+        let files = status(&repo);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, Path::new("a.txt"));
+        assert!(kinds(&files[0]) == (None, Some(ChangeKind::Deleted)));
+        assert_eq!(files[0].porcelain(), " D a.txt");
+        assert_eq!(files[1].path, Path::new("b.txt"));
+        assert!(kinds(&files[1]) == (Some(ChangeKind::Deleted), None));
+        assert_eq!(files[1].porcelain(), "D  b.txt");
+    }
+
+    #[test]
+    fn merge_conflicts_are_conflicted() {
+        let (_dir, repo) = repo();
+        write(&repo, "a.txt", "base\n");
+        let base = repo.find_commit(commit(&repo, "base")).unwrap();
+        let main = repo.head().unwrap().name().unwrap().to_string();
+
+        // The other side of the merge, from the same base
+        repo.branch("other", &base, false).unwrap();
+        repo.set_head("refs/heads/other").unwrap();
+        write(&repo, "a.txt", "theirs\n");
+        let theirs = commit(&repo, "theirs");
+
+        repo.set_head(&main).unwrap();
+        repo.checkout_head(Some(git2::build::CheckoutBuilder::new().force()))
+            .unwrap();
+        write(&repo, "a.txt", "ours\n");
+        commit(&repo, "ours");
+
+        let theirs = repo.find_annotated_commit(theirs).unwrap();
+        repo.merge(&[&theirs], None, None).unwrap();
+
+        let files = status(&repo);
+        assert_eq!(files.len(), 1);
+        assert!(files[0].conflicted);
+        assert!(kinds(&files[0]) == (None, None));
+        assert_eq!(files[0].porcelain(), "UU a.txt");
+    }
+
+    #[test]
+    fn renamed_with_an_edit_is_one_entry() {
+        let (dir, repo) = repo();
+        let lines: String = (1..=20).map(|n| format!("line {}\n", n)).collect();
+        write(&repo, "old.txt", &lines);
+        commit(&repo, "first");
+        fs::remove_file(dir.path().join("old.txt")).unwrap();
+        write(&repo, "new.txt", &lines.replace("line 7\n", "line seven\n"));
+        stage(&repo, "old.txt");
+        stage(&repo, "new.txt");
+
+        let files = status(&repo);
+        assert_eq!(files.len(), 1);
+        assert!(kinds(&files[0]) == (Some(ChangeKind::Renamed), None));
+        let staged = files[0].staged.as_ref().unwrap();
+        assert_eq!(staged.path, Path::new("new.txt"));
+        let rename = staged.rename.as_ref().unwrap();
+        assert_eq!(rename.from, Path::new("old.txt"));
+        assert!(rename.similarity.is_some_and(|s| (90..100).contains(&s)));
+        assert_eq!(files[0].porcelain(), "R  old.txt -> new.txt");
+    }
+
+    #[test]
+    fn conflicts_are_coded_by_their_stages() {
+        let (_dir, repo) = repo();
+        for (path, versions, code) in [
+            ("both-deleted", [Some("a"), None, None], "DD"),
+            ("added-by-us", [None, Some("b"), None], "AU"),
+            ("deleted-by-them", [Some("a"), Some("b"), None], "UD"),
+            ("added-by-them", [None, None, Some("c")], "UA"),
+            ("deleted-by-us", [Some("a"), None, Some("c")], "DU"),
+            ("both-added", [None, Some("b"), Some("c")], "AA"),
+            ("both-modified", [Some("a"), Some("b"), Some("c")], "UU"),
+        ] {
+            write(&repo, path, "worktree");
+            conflict(&repo, path, versions);
+            let files = status(&repo);
+            let file = files.iter().find(|f| f.path == Path::new(path)).unwrap();
+            assert!(file.conflicted, "{}", path);
+            assert_eq!(file.porcelain(), format!("{} {}", code, path));
+        }
+    }
+
+    #[test]
+    fn filter_keeps_one_side() {
+        let (_dir, repo) = repo();
+        write(&repo, "a.txt", "a\n");
+        commit(&repo, "first");
+        write(&repo, "a.txt", "b\n");
+        write(&repo, "b.txt", "b\n");
+        stage(&repo, "b.txt");
+
+        let only = |section| StatusFilter {
+            only: Some(section),
+            ..StatusFilter::default()
+        };
+        let staged = collect_status(&repo, false, &only(Section::Index)).unwrap();
+        assert_eq!(staged.len(), 1);
+        assert_eq!(staged[0].path, Path::new("b.txt"));
+        let unstaged = collect_status(&repo, false, &only(Section::Worktree)).unwrap();
+        assert_eq!(unstaged.len(), 1);
+        assert_eq!(unstaged[0].path, Path::new("a.txt"));
+    }
+
+    #[test]
+    fn porcelain_collapses_untracked_directories() {
+        let (_dir, repo) = repo();
+        write(&repo, "a.txt", "a\n");
+        commit(&repo, "first");
+        write(&repo, "a.txt", "b\n");
+        write(&repo, "u/f", "f\n");
+        write(&repo, "u/v/g", "g\n");
+
+        let lines = |pathspecs: &[&str]| {
+            let filter = StatusFilter {
+                pathspecs: pathspecs.iter().map(|p| p.to_string()).collect(),
+                ..StatusFilter::default()
+            };
+            collect_status_collapsed(&repo, false, &filter)
+                .unwrap()
+                .iter()
+                .map(StatusEntry::porcelain)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(lines(&[]), [" M a.txt", "?? u/"]);
+        assert_eq!(lines(&["u"]), ["?? u/"]);
+        // Like git, a pathspec inside the directory lists what it matches
+        assert_eq!(lines(&["u/f"]), ["?? u/f"]);
+        assert_eq!(status(&repo).len(), 3);
+    }
+
+    #[test]
+    fn clean_goes_around_nested_repositories() {
+        let (dir, repo) = repo();
+        write(&repo, ".gitignore", "*.log\n");
+        commit(&repo, "first");
+        write(&repo, "foo/x", "x\n");
+        write(&repo, "foo/d/y", "y\n");
+        write(&repo, "foo/z.log", "z\n");
+        write(&repo, "foo/bar/inner", "kept\n");
+        Repository::init(dir.path().join("foo/bar")).unwrap();
+        write(&repo, "other/sub/.git", "gitdir: elsewhere\n");
+
+        let paths = |include_ignored| {
+            cleanable(&repo, include_ignored)
+                .unwrap()
+                .into_iter()
+                .map(|c| (c.path.to_string_lossy().into_owned(), c.ignored))
+                .collect::<Vec<_>>()
+        };
+        let owned = |list: &[(&str, bool)]| {
+            list.iter()
+                .map(|&(p, i)| (p.to_string(), i))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(paths(false), owned(&[("foo/d/", false), ("foo/x", false)]));
+        assert_eq!(
+            paths(true),
+            owned(&[("foo/d/", false), ("foo/x", false), ("foo/z.log", true)])
+        );
+    }
+
+    #[test]
+    fn globs_stop_at_slashes() {
+        let matches = |pattern, path| glob_matches(pattern, Path::new(path));
+        assert!(matches("*.rs", "a.rs"));
+        assert!(!matches("*.rs", "d/y.rs"));
+        assert!(matches("d/*", "d/y.rs"));
+        assert!(!matches("d/*", "d/e/z.rs"));
+        assert!(matches("d/**/*.rs", "d/y.rs"));
+        assert!(matches("d/**/*.rs", "d/e/z.rs"));
+        assert!(matches("**/z.rs", "d/e/z.rs"));
+        assert!(matches("d/**", "d/e/z.rs"));
+        assert!(matches("d/[xy].rs", "d/y.rs"));
+        assert!(!matches("d/[!xy].rs", "d/y.rs"));
+        assert!(matches("d/?.rs", "d/y.rs"));
+        assert!(!matches("d?y.rs", "d/y.rs"));
+        // Without wildcards it's a path, and what's under it
+        assert!(matches("d", "d/e/z.rs"));
+        assert!(matches("d/e", "d/e/"));
+        assert!(!matches("d", "de"));
+    }
+
+    #[test]
+    fn excludes_and_globs_narrow_the_status() {
+        let (_dir, repo) = repo();
+        write(&repo, "a.rs", "a\n");
+        write(&repo, "d/y.rs", "y\n");
+        write(&repo, "d/e/z.rs", "z\n");
+        write(&repo, "w/x", "x\n");
+
+        let paths = |filter: StatusFilter| {
+            collect_status(&repo, false, &filter)
+                .unwrap()
+                .into_iter()
+                .map(|f| f.path.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+        let owned = |list: &[&str]| list.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        let excludes = StatusFilter {
+            excludes: owned(&["w"]),
+            exclude_globs: owned(&["d/*"]),
+            ..StatusFilter::default()
+        };
+        assert_eq!(paths(excludes), ["a.rs", "d/e/z.rs"]);
+        let globs = StatusFilter {
+            pathspecs: owned(&["w"]),
+            globs: owned(&["*.rs"]),
+            ..StatusFilter::default()
+        };
+        assert_eq!(paths(globs), ["a.rs", "w/x"]);
+    }
+
+    #[test]
+    fn odd_paths_are_quoted_like_git() {
+        assert_eq!(quoted(Path::new("plain.txt")), "plain.txt");
+        assert_eq!(quoted(Path::new("café.txt")), "\"caf\\303\\251.txt\"");
+        assert_eq!(quoted(Path::new("a\"b")), "\"a\\\"b\"");
+    }
+}
@@ -0,0 +1,92 @@
+//! Throwaway repositories for the tests, with an identity set so commits
+//! work whatever the machine's git config says.
+
+use git2::{IndexAddOption, IndexEntry, IndexTime, Oid, Repository, Signature};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// A new repository in a temporary directory, gone when the `TempDir` is.
+pub fn repo() -> (TempDir, Repository) {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = Repository::init(dir.path()).unwrap();
+    let mut config = repo.config().unwrap();
+    config.set_str("user.name", "Test").unwrap();
+    config.set_str("user.email", "test@example.com").unwrap();
+    (dir, repo)
+}
+
+/// Writes `content` to `path` in the worktree, making its directories.
+pub fn write(repo: &Repository, path: &str, content: &str) {
+    let path = repo.workdir().unwrap().join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+/// Stages `path` as it is in the worktree, or its removal when it's gone.
+pub fn stage(repo: &Repository, path: &str) {
+    let mut index = repo.index().unwrap();
+    if repo.workdir().unwrap().join(path).exists() {
+        index.add_path(Path::new(path)).unwrap();
+    } else {
+        index.remove_path(Path::new(path)).unwrap();
+    }
+    index.write().unwrap();
+}
+
+/// Stages everything in the worktree and commits it on HEAD.
+pub fn commit(repo: &Repository, message: &str) -> Oid {
+    let mut index = repo.index().unwrap();
+    index.add_all(["*"], IndexAddOption::DEFAULT, None).unwrap();
+    index.update_all(["*"], None).unwrap();
+    index.write().unwrap();
+    commit_index(repo, message)
+}
+
+/// Commits the index as it is on HEAD.
+pub fn commit_index(repo: &Repository, message: &str) -> Oid {
+    let mut index = repo.index().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = Signature::now("Test", "test@example.com").unwrap();
+    let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+    let parents: Vec<_> = parent.iter().collect();
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parents,
+    )
+    .unwrap()
+}
+
+/// Puts `path` in the index as a conflict with these versions for its
+/// ancestor, ours and theirs (stages 1 to 3), as a merge leaves one.
+pub fn conflict(repo: &Repository, path: &str, versions: [Option<&str>; 3]) {
+    let mut index = repo.index().unwrap();
+    let _ = index.remove_path(Path::new(path));
+    for (stage, content) in (1..=3).zip(versions) {
+        let Some(content) = content else {
+            continue;
+        };
+        index
+            .add(&IndexEntry {
+                ctime: IndexTime::new(0, 0),
+                mtime: IndexTime::new(0, 0),
+                dev: 0,
+                ino: 0,
+                mode: 0o100644,
+                uid: 0,
+                gid: 0,
+                file_size: content.len() as u32,
+                id: repo.blob(content.as_bytes()).unwrap(),
+                // The stage goes in bits 12 and 13, the path's length below
+                flags: (stage << 12) | path.len() as u16,
+                flags_extended: 0,
+                path: path.as_bytes().to_vec(),
+            })
+            .unwrap();
+    }
+    index.write().unwrap();
+}
@@ -0,0 +1,448 @@
+mod session;
+
+use crate::help;
+use crate::keymap::{Action, Keymap, Scope};
+use crate::log;
+use crate::panes::{self, Panes};
+use crate::theme::Theme;
+use crate::tui::{TICK, Tui};
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode};
+use git2::{Oid, Repository};
+use ratatui::{
+    prelude::*,
+    widgets::{ListItem, ListState, Paragraph},
+};
+use session::{Mark, Progress, Session};
+
+/// The revisions being typed on the helper line to start a bisect.
+enum Prompt {
+    Bad(String),
+    Good { bad: String, good: String },
+}
+
+/// A bisect session: the commits that could still be the first bad one,
+/// with the one checked out to test marked, and how many steps are left in
+/// the header. Marking HEAD good, bad or skipped checks out the next commit
+/// to test, until the first bad one is found. `revisions` starts a session
+/// when none is running, the first bad and the rest good.
+pub fn bisect(
+    tui: &mut Tui,
+    repo: &Repository,
+    revisions: &[String],
+    list_width: u16,
+    keymap: &Keymap,
+    theme: &Theme,
+) -> Result<()> {
+    let mut message: Option<String> = None;
+    let mut session = match revisions.split_first() {
+        Some((bad, good)) => Some(Session::start(repo, bad, good)?),
+        None => Session::load(repo)?,
+    };
+    let mut progress = match &session {
+        Some(session) => Some(advance(repo, session, &mut message)?),
+        None => None,
+    };
+    let mut panes: Panes<Oid> = Panes::new(list_width);
+    select_head(repo, progress.as_ref(), &mut panes.list_state);
+
+    // ---------- UI State ----------
+    let mut prompt: Option<Prompt> = None;
+    let mut confirm_reset = false;
+    let mut show_help = false;
+
+    // ---------- Main Loop ----------
+    loop {
+        let remaining = progress.as_ref().map(|p| p.remaining()).unwrap_or(&[]);
+        let selected = panes.selected().and_then(|i| remaining.get(i)).copied();
+
+        // ---------- Preview ----------
+        panes.preview(
+            selected,
+            |&oid| oid,
+            |oid| match log::show_commit(repo, oid, theme) {
+                Ok(commit) => commit.lines,
+                Err(e) => vec![Line::from(format!("Error: {}", e))],
+            },
+        );
+
+        // ---------- Helper line ----------
+        let help_line = match (&prompt, message.clone()) {
+            (Some(current), _) => {
+                let (label, text) = match current {
+                    Prompt::Bad(bad) => ("Bad revision: ", bad),
+                    Prompt::Good { good, .. } => ("Good revisions: ", good),
+                };
+                Line::from(vec![
+                    Span::styled(label, Style::default().fg(theme.accent)),
+                    Span::raw(text.clone()),
+                    Span::styled("█", Style::default().fg(theme.accent)),
+                ])
+            }
+            (None, _) if confirm_reset => Line::styled(
+                match &session {
+                    Some(session) => format!(
+                        "End the bisect and go back to {}? (y/n)",
+                        short(&session.start)
+                    ),
+                    None => String::new(),
+                },
+                Style::default()
+                    .fg(theme.warning)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            (None, Some(text)) => Line::from(text),
+            (None, None) => help_line(keymap, session.is_some()),
+        };
+
+        let header = match (&session, &progress) {
+            (Some(session), Some(progress)) => header_line(session, progress, theme),
+            _ => Line::styled(
+                " No bisect in progress",
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+        };
+
+        let head = repo.head().ok().and_then(|h| h.target());
+        let items: Vec<ListItem> = match &session {
+            Some(session) => remaining
+                .iter()
+                .map(|oid| ListItem::new(commit_line(repo, session, *oid, head, theme)))
+                .collect(),
+            None => Vec::new(),
+        };
+        let empty = if session.is_some() {
+            "Mark the commits the range runs between"
+        } else {
+            "Start a bisect to find where a bug came in"
+        };
+        let list = panes::list(items, empty, theme);
+
+        tui.draw(|frame| {
+            let area = frame.area();
+
+            // ---------- Header, panels, helper line ----------
+            let outer_chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Length(1), // top: where the bisect is
+                    Constraint::Min(0),    // middle: main panels
+                    Constraint::Length(1), // bottom: help line
+                ])
+                .split(area);
+
+            frame.render_widget(Paragraph::new(header), outer_chunks[0]);
+
+            panes.render(
+                frame,
+                outer_chunks[1],
+                &list,
+                " Could be the first bad commit ",
+                " Commit ",
+                theme,
+            );
+            let asking = prompt.is_some() || confirm_reset;
+            panes::render_help(frame, outer_chunks[2], help_line, asking);
+
+            if show_help {
+                help::render(frame, keymap, theme);
+            }
+        })?;
+
+        // ---------- Input Handling ----------
+        if !event::poll(TICK)? {
+            continue;
+        }
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        message = None;
+        let remaining_len = remaining.len();
+
+        // A pending reset swallows every key until it is answered
+        if confirm_reset {
+            match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    if let Some(ended) = session.take() {
+                        message = Some(match ended.reset(repo) {
+                            Ok(text) => text,
+                            Err(e) => format!("Error: {}", e),
+                        });
+                        // A failed reset leaves the bisect as it was
+                        session = Session::load(repo)?;
+                        progress = match &session {
+                            Some(session) => Some(advance(repo, session, &mut None)?),
+                            None => None,
+                        };
+                        select_head(repo, progress.as_ref(), &mut panes.list_state);
+                    }
+                    confirm_reset = false;
+                }
+                KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => confirm_reset = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        if let Some(current) = &mut prompt {
+            let text = match current {
+                Prompt::Bad(text) | Prompt::Good { good: text, .. } => text,
+            };
+            match key.code {
+                KeyCode::Esc => prompt = None,
+                KeyCode::Backspace => {
+                    text.pop();
+                }
+                KeyCode::Char(c) => text.push(c),
+                KeyCode::Enter => match prompt.take() {
+                    Some(Prompt::Bad(bad)) => {
+                        prompt = Some(Prompt::Good {
+                            bad: bad.trim().to_string(),
+                            good: String::new(),
+                        });
+                    }
+                    Some(Prompt::Good { bad, good }) => {
+                        let good: Vec<String> = good.split_whitespace().map(String::from).collect();
+                        match Session::start(repo, &bad, &good) {
+                            Ok(started) => {
+                                progress = Some(advance(repo, &started, &mut message)?);
+                                session = Some(started);
+                                select_head(repo, progress.as_ref(), &mut panes.list_state);
+                            }
+                            Err(e) => message = Some(format!("Error: {}", e)),
+                        }
+                    }
+                    None => {}
+                },
+                _ => {}
+            }
+            continue;
+        }
+
+        if show_help {
+            if matches!(
+                keymap.resolve(&key, Scope::Bisect),
+                Some(Action::Help | Action::Quit)
+            ) {
+                show_help = false;
+            }
+            continue;
+        }
+
+        let action = keymap.resolve(&key, Scope::Bisect);
+        match action {
+            Some(Action::Quit) => break,
+
+            Some(Action::Help) => show_help = true,
+
+            Some(Action::StartBisect) => match session {
+                Some(_) => message = Some(String::from("Error: a bisect is already in progress")),
+                None => prompt = Some(Prompt::Bad(String::from("HEAD"))),
+            },
+
+            Some(Action::MarkGood | Action::MarkBad | Action::SkipBisect) => {
+                let Some(active) = &mut session else {
+                    message = Some(String::from("Error: no bisect in progress"));
+                    continue;
+                };
+                let mark = match action {
+                    Some(Action::MarkGood) => Mark::Good,
+                    Some(Action::MarkBad) => Mark::Bad,
+                    _ => Mark::Skip,
+                };
+                let Some(head) = repo.head().ok().and_then(|h| h.target()) else {
+                    continue;
+                };
+                match active.mark(repo, head, mark) {
+                    Ok(()) => {
+                        progress = Some(advance(repo, active, &mut message)?);
+                        select_head(repo, progress.as_ref(), &mut panes.list_state);
+                    }
+                    Err(e) => message = Some(format!("Error: {}", e)),
+                }
+            }
+
+            Some(Action::ResetBisect) if session.is_some() => confirm_reset = true,
+
+            Some(Action::Refresh) => {
+                session = Session::load(repo)?;
+                progress = match &session {
+                    Some(session) => Some(advance(repo, session, &mut None)?),
+                    None => None,
+                };
+                select_head(repo, progress.as_ref(), &mut panes.list_state);
+                panes.forget();
+            }
+
+            Some(action) => {
+                panes.navigate(action, remaining_len);
+            }
+
+            None => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// `hg bisect [<bad> [<good>...]]` outside the status view.
+pub fn run(
+    repo: &Repository,
+    revisions: &[String],
+    list_width: u16,
+    keymap: &Keymap,
+    theme: &Theme,
+) -> Result<()> {
+    let mut tui = Tui::new()?;
+    bisect(&mut tui, repo, revisions, list_width, keymap, theme)
+}
+
+/// Works out where the bisect is and checks out the next commit to test,
+/// saying so in `message` (or why it couldn't).
+fn advance(repo: &Repository, session: &Session, message: &mut Option<String>) -> Result<Progress> {
+    let progress = session.progress(repo)?;
+    if let Progress::Testing { candidate, .. } = &progress {
+        let head = repo.head().ok().and_then(|h| h.target());
+        if head != Some(*candidate) {
+            *message = Some(match session::checkout(repo, *candidate) {
+                Ok(()) => {
+                    let summary = repo
+                        .find_commit(*candidate)
+                        .ok()
+                        .and_then(|c| c.summary().map(String::from))
+                        .unwrap_or_default();
+                    format!(
+                        "Checked out {:.7} {}; test it, then mark it",
+                        candidate, summary
+                    )
+                }
+                Err(e) => format!("Error: cannot check out {:.7}: {}", candidate, e),
+            });
+        }
+    }
+    if let Progress::Found(oid) = &progress {
+        *message = Some(format!(
+            "{:.7} is the first bad commit; X ends the bisect",
+            oid
+        ));
+    }
+    Ok(progress)
+}
+
+/// Puts the selection on HEAD's row, or the first one.
+fn select_head(repo: &Repository, progress: Option<&Progress>, list_state: &mut ListState) {
+    let head = repo.head().ok().and_then(|h| h.target());
+    let row = progress
+        .and_then(|p| p.remaining().iter().position(|oid| Some(*oid) == head))
+        .unwrap_or(0);
+    list_state.select(Some(row));
+}
+
+/// A commit id to name it by, or the branch name as it is.
+fn short(start: &str) -> &str {
+    match Oid::from_str(start) {
+        Ok(_) if start.len() == 40 => &start[..7],
+        _ => start,
+    }
+}
+
+/// ` Bisecting: 6 revisions left to test after this (roughly 3 steps) · bad 1a2b3c4 · good 5d6e7f8`
+fn header_line(session: &Session, progress: &Progress, theme: &Theme) -> Line<'static> {
+    let mut spans = vec![Span::styled(
+        format!(" {}", progress.summary()),
+        Style::default().add_modifier(Modifier::BOLD),
+    )];
+    if let Some(bad) = session.bad {
+        spans.push(Span::raw(" · bad "));
+        spans.push(Span::styled(
+            format!("{:.7}", bad),
+            Style::default().fg(theme.deletion),
+        ));
+    }
+    if !session.good.is_empty() {
+        spans.push(Span::raw(" · good "));
+        let good: Vec<String> = session
+            .good
+            .iter()
+            .map(|oid| format!("{:.7}", oid))
+            .collect();
+        spans.push(Span::styled(
+            good.join(", "),
+            Style::default().fg(theme.addition),
+        ));
+    }
+    if !session.skip.is_empty() {
+        spans.push(Span::styled(
+            format!(" · {} skipped", session.skip.len()),
+            Style::default().dim(),
+        ));
+    }
+    spans.push(Span::styled(
+        format!(" · started on {}", short(&session.start)),
+        Style::default().dim(),
+    ));
+    Line::from(spans)
+}
+
+/// `● 1a2b3c4 bad  Fix the parser`, the dot on the commit checked out
+fn commit_line(
+    repo: &Repository,
+    session: &Session,
+    oid: Oid,
+    head: Option<Oid>,
+    theme: &Theme,
+) -> Line<'static> {
+    let summary = repo
+        .find_commit(oid)
+        .ok()
+        .and_then(|c| c.summary().map(String::from))
+        .unwrap_or_default();
+    let mut spans = vec![
+        Span::styled(
+            if head == Some(oid) { "● " } else { "  " },
+            Style::default().fg(theme.accent),
+        ),
+        Span::styled(format!("{:.7}", oid), Style::default().fg(theme.commit_sha)),
+        Span::raw(" "),
+    ];
+    if session.bad == Some(oid) {
+        spans.push(Span::styled("bad ", Style::default().fg(theme.deletion)));
+    } else if session.skip.contains(&oid) {
+        spans.push(Span::styled("skip ", Style::default().dim()));
+    }
+    spans.push(Span::raw(summary));
+    Line::from(spans)
+}
+
+fn help_line(keymap: &Keymap, active: bool) -> Line<'static> {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let mut spans = vec![Span::raw(" ")];
+
+    let actions: &[(Action, &str)] = if active {
+        &[
+            (Action::MarkGood, "good"),
+            (Action::MarkBad, "bad"),
+            (Action::SkipBisect, "skip"),
+            (Action::ResetBisect, "end"),
+            (Action::SwitchFocus, "switch focus"),
+            (Action::Help, "help"),
+            (Action::Quit, "quit"),
+        ]
+    } else {
+        &[
+            (Action::StartBisect, "start"),
+            (Action::Help, "help"),
+            (Action::Quit, "quit"),
+        ]
+    };
+    for (i, (action, label)) in actions.iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::raw(" • "));
+        }
+        spans.push(Span::styled(keymap.label(*action), bold));
+        spans.push(Span::raw(format!(" {}", label)));
+    }
+
+    Line::from(spans)
+}
@@ -0,0 +1,324 @@
+use crate::rebase::todo;
+use anyhow::{Result, anyhow, bail};
+use git2::{Oid, Repository, Sort, build::CheckoutBuilder};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+
+/// The terms git uses when none were given to `git bisect start`.
+const TERMS: &str = "bad\ngood\n";
+
+/// Files git keeps in the git directory while a bisect is on.
+const STATE_FILES: &[&str] = &[
+    "BISECT_START",
+    "BISECT_LOG",
+    "BISECT_TERMS",
+    "BISECT_NAMES",
+    "BISECT_EXPECTED_REV",
+    "BISECT_ANCESTORS_OK",
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mark {
+    Good,
+    Bad,
+    Skip,
+}
+
+impl Mark {
+    pub fn name(self) -> &'static str {
+        match self {
+            Mark::Good => "good",
+            Mark::Bad => "bad",
+            Mark::Skip => "skip",
+        }
+    }
+}
+
+/// A bisect in progress, kept the way `git bisect` keeps it (`BISECT_START`
+/// and the refs under `refs/bisect/`), so either can carry on what the
+/// other started.
+pub struct Session {
+    /// What HEAD was when it started, a branch name or a commit id, to go
+    /// back to at the end
+    pub start: String,
+    pub bad: Option<Oid>,
+    pub good: Vec<Oid>,
+    pub skip: Vec<Oid>,
+}
+
+/// Where the bisect has got to.
+pub enum Progress {
+    NeedBad,
+    NeedGood,
+    /// `candidate` is the commit to test next, out of `remaining` (newest
+    /// first), the commits that could still be the first bad one
+    Testing {
+        candidate: Oid,
+        remaining: Vec<Oid>,
+        /// Left to test after the candidate, as `git bisect` counts them
+        left: usize,
+        steps: u32,
+    },
+    Found(Oid),
+    /// Every commit left to test was skipped; the first bad commit is one of
+    /// these
+    OnlySkipped(Vec<Oid>),
+}
+
+impl Progress {
+    /// The commits that could still be the first bad one.
+    pub fn remaining(&self) -> &[Oid] {
+        match self {
+            Progress::Testing { remaining, .. } | Progress::OnlySkipped(remaining) => remaining,
+            Progress::Found(oid) => std::slice::from_ref(oid),
+            Progress::NeedBad | Progress::NeedGood => &[],
+        }
+    }
+
+    /// One line for the header, like the ones `git bisect` prints.
+    pub fn summary(&self) -> String {
+        match self {
+            Progress::NeedBad => String::from("Bisecting: mark a bad commit"),
+            Progress::NeedGood => String::from("Bisecting: mark a good commit"),
+            Progress::Testing { left, steps, .. } => format!(
+                "Bisecting: {} revision{} left to test after this (roughly {} step{})",
+                left,
+                if *left == 1 { "" } else { "s" },
+                steps,
+                if *steps == 1 { "" } else { "s" }
+            ),
+            Progress::Found(oid) => format!("{:.7} is the first bad commit", oid),
+            Progress::OnlySkipped(remaining) => format!(
+                "Only skipped commits left to test; the first bad commit is one of {}",
+                remaining.len()
+            ),
+        }
+    }
+}
+
+impl Session {
+    /// The bisect in progress, if there is one.
+    pub fn load(repo: &Repository) -> Result<Option<Session>> {
+        let Ok(start) = fs::read_to_string(repo.path().join("BISECT_START")) else {
+            return Ok(None);
+        };
+
+        let mut session = Session {
+            start: start.trim().to_string(),
+            bad: None,
+            good: Vec::new(),
+            skip: Vec::new(),
+        };
+        for reference in repo.references_glob("refs/bisect/*")? {
+            let reference = reference?;
+            let (Some(name), Some(oid)) = (reference.name(), reference.target()) else {
+                continue;
+            };
+            match name.trim_start_matches("refs/bisect/") {
+                "bad" => session.bad = Some(oid),
+                rest if rest.starts_with("good-") => session.good.push(oid),
+                rest if rest.starts_with("skip-") => session.skip.push(oid),
+                _ => {}
+            }
+        }
+        Ok(Some(session))
+    }
+
+    /// Starts a bisect with `bad` and every one of `good`, like `git bisect
+    /// start <bad> <good>...`.
+    pub fn start(repo: &Repository, bad: &str, good: &[String]) -> Result<Session> {
+        if Session::load(repo)?.is_some() {
+            bail!("a bisect is already in progress");
+        }
+        todo::check(repo, &[])?;
+        let bad = repo.revparse_single(bad)?.peel_to_commit()?.id();
+        let good = good
+            .iter()
+            .map(|rev| Ok(repo.revparse_single(rev)?.peel_to_commit()?.id()))
+            .collect::<Result<Vec<_>>>()?;
+
+        let head = repo.head()?;
+        let start = if head.is_branch() {
+            head.shorthand().unwrap_or("HEAD").to_string()
+        } else {
+            head.target()
+                .ok_or_else(|| anyhow!("HEAD does not point at a commit"))?
+                .to_string()
+        };
+        fs::write(repo.path().join("BISECT_START"), format!("{}\n", start))?;
+        fs::write(repo.path().join("BISECT_TERMS"), TERMS)?;
+        append_log(repo, &format!("git bisect start {}", start))?;
+
+        let mut session = Session {
+            start,
+            bad: None,
+            good: Vec::new(),
+            skip: Vec::new(),
+        };
+        session.mark(repo, bad, Mark::Bad)?;
+        for oid in good {
+            session.mark(repo, oid, Mark::Good)?;
+        }
+        Ok(session)
+    }
+
+    /// Marks `oid`, like `git bisect good|bad|skip <oid>`.
+    pub fn mark(&mut self, repo: &Repository, oid: Oid, mark: Mark) -> Result<()> {
+        let refname = match mark {
+            Mark::Bad => String::from("refs/bisect/bad"),
+            Mark::Good => format!("refs/bisect/good-{}", oid),
+            Mark::Skip => format!("refs/bisect/skip-{}", oid),
+        };
+        repo.reference(&refname, oid, true, &format!("bisect: {}", mark.name()))?;
+
+        let summary = repo
+            .find_commit(oid)
+            .ok()
+            .and_then(|c| c.summary().map(String::from))
+            .unwrap_or_default();
+        append_log(
+            repo,
+            &format!(
+                "# {}: [{}] {}\ngit bisect {} {}",
+                mark.name(),
+                oid,
+                summary,
+                mark.name(),
+                oid
+            ),
+        )?;
+
+        match mark {
+            Mark::Bad => self.bad = Some(oid),
+            Mark::Good => self.good.push(oid),
+            Mark::Skip => self.skip.push(oid),
+        }
+        Ok(())
+    }
+
+    /// The commits between the good ones and the bad one, and which of them
+    /// splits them most evenly, the one `git bisect` would check out next.
+    pub fn progress(&self, repo: &Repository) -> Result<Progress> {
+        let Some(bad) = self.bad else {
+            return Ok(Progress::NeedBad);
+        };
+        if self.good.is_empty() {
+            return Ok(Progress::NeedGood);
+        }
+
+        // Everything that could be the first bad commit: what the bad one
+        // reaches, less what any good one does, newest first
+        let mut walk = repo.revwalk()?;
+        walk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+        walk.push(bad)?;
+        for good in &self.good {
+            walk.hide(*good)?;
+        }
+        let remaining = walk.collect::<Result<Vec<Oid>, _>>()?;
+        let all = remaining.len();
+        if all <= 1 {
+            return Ok(Progress::Found(bad));
+        }
+
+        // How many of them each one reaches, itself included, as bitsets
+        // built up from the oldest, whose parents have theirs already
+        let index: HashMap<Oid, usize> =
+            remaining.iter().enumerate().map(|(i, o)| (*o, i)).collect();
+        let words = all.div_ceil(64);
+        let mut reach = vec![vec![0u64; words]; all];
+        for i in (0..all).rev() {
+            reach[i][i / 64] |= 1 << (i % 64);
+            for parent in repo.find_commit(remaining[i])?.parent_ids() {
+                if let Some(&p) = index.get(&parent) {
+                    let (done, rest) = reach.split_at_mut(p);
+                    for (word, theirs) in done[i].iter_mut().zip(&rest[0]) {
+                        *word |= theirs;
+                    }
+                }
+            }
+        }
+
+        // The most even split, counted both ways; the bad commit splits
+        // nothing, since it's known bad already
+        let best = remaining
+            .iter()
+            .enumerate()
+            .filter(|(_, oid)| **oid != bad && !self.skip.contains(oid))
+            .map(|(i, oid)| {
+                let count: usize = reach[i].iter().map(|w| w.count_ones() as usize).sum();
+                (count.min(all - count), count, *oid)
+            })
+            .max_by_key(|(even, ..)| *even);
+
+        Ok(match best {
+            Some((_, count, candidate)) => Progress::Testing {
+                candidate,
+                left: all - count - 1,
+                steps: estimate_steps(all),
+                remaining,
+            },
+            None => Progress::OnlySkipped(remaining),
+        })
+    }
+
+    /// Ends the bisect, going back to where it started as `git bisect reset`
+    /// does.
+    pub fn reset(self, repo: &Repository) -> Result<String> {
+        let branch = format!("refs/heads/{}", self.start);
+        let (oid, on_branch) = match repo.refname_to_id(&branch) {
+            Ok(oid) => (oid, true),
+            Err(_) => (Oid::from_str(&self.start)?, false),
+        };
+        let commit = repo.find_commit(oid)?;
+        repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().safe()))?;
+        if on_branch {
+            repo.set_head(&branch)?;
+        } else {
+            repo.set_head_detached(oid)?;
+        }
+
+        for reference in repo.references_glob("refs/bisect/*")? {
+            reference?.delete()?;
+        }
+        for file in STATE_FILES {
+            let path = repo.path().join(file);
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(if on_branch {
+            format!("Bisect ended; back on {}", self.start)
+        } else {
+            format!("Bisect ended; back at {:.7}", oid)
+        })
+    }
+}
+
+/// Detaches HEAD at `oid`, leaving local changes be, as `git bisect` does
+/// for each commit to test.
+pub fn checkout(repo: &Repository, oid: Oid) -> Result<()> {
+    let commit = repo.find_commit(oid)?;
+    repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().safe()))?;
+    repo.set_head_detached(oid)?;
+    Ok(())
+}
+
+/// `git bisect`'s own estimate of the steps left for `all` commits.
+fn estimate_steps(all: usize) -> u32 {
+    if all < 3 {
+        return 0;
+    }
+    let n = all.ilog2();
+    let e = 1usize << n;
+    if e < 3 * (all - e) { n } else { n - 1 }
+}
+
+fn append_log(repo: &Repository, line: &str) -> Result<()> {
+    let mut log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(repo.path().join("BISECT_LOG"))?;
+    writeln!(log, "{}", line)?;
+    Ok(())
+}
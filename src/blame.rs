@@ -0,0 +1,345 @@
+use crate::keymap::{Action, Keymap, Scope};
+use crate::log;
+use crate::theme::Theme;
+use crate::tui::{TICK, Tui};
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use crossterm::event::{self, Event};
+use git2::{Blame, BlameOptions, Oid, Repository, Status};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Paragraph},
+};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Full-screen blame of `path` as of HEAD, until the user quits back to the
+/// caller's view. Re-blaming at a line's parent commit pushes onto a stack
+/// that can be walked back.
+pub fn blame(
+    tui: &mut Tui,
+    repo: &Repository,
+    path: &Path,
+    keymap: &Keymap,
+    theme: &Theme,
+) -> Result<()> {
+    let mut view = BlameView::load(repo, path, None);
+    // Earlier blames with their cursor, newest last
+    let mut history: Vec<(BlameView, usize)> = Vec::new();
+
+    let mut cursor: usize = 0;
+    let mut scroll: usize = 0;
+    let mut height: usize = 0;
+    let mut message: Option<String> = None;
+    // Commit details opened from a line, with their own scroll
+    let mut details: Option<(Vec<Line<'static>>, usize)> = None;
+
+    loop {
+        let total = view.as_ref().map_or(1, |view| view.content.len());
+
+        tui.draw(|frame| {
+            let area = frame.area();
+            let [main, help] =
+                Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(area);
+
+            height = main.height.saturating_sub(2) as usize;
+            scroll = scroll.min(total.saturating_sub(height));
+
+            let help_line = match &message {
+                Some(text) => Line::from(text.clone()),
+                None if details.is_some() => Line::from(format!(
+                    " {} {} scroll • {} back to blame",
+                    keymap.label(Action::NavigateUp),
+                    keymap.label(Action::NavigateDown),
+                    keymap.label(Action::Quit),
+                )),
+                None => Line::from(format!(
+                    " {} {} move • {} commit • {} blame parent • {} newer • {} back",
+                    keymap.label(Action::NavigateUp),
+                    keymap.label(Action::NavigateDown),
+                    keymap.label(Action::Select),
+                    keymap.label(Action::BlameParent),
+                    keymap.label(Action::BlameBack),
+                    keymap.label(Action::Quit),
+                )),
+            };
+            frame.render_widget(
+                Paragraph::new(help_line)
+                    .alignment(Alignment::Center)
+                    .style(Style::default().dim()),
+                help,
+            );
+
+            if let Some((lines, offset)) = &mut details {
+                *offset = (*offset).min(lines.len().saturating_sub(height));
+                let block = Block::default()
+                    .title(" Commit ")
+                    .borders(Borders::ALL)
+                    .border_style(theme.focus());
+                let end = (*offset + height).min(lines.len());
+                frame.render_widget(
+                    Paragraph::new(lines[*offset..end].to_vec()).block(block),
+                    main,
+                );
+                return;
+            }
+
+            let title = match view.as_ref().ok().and_then(|v| v.rev.map(|rev| (v, rev))) {
+                Some((view, rev)) => format!(" Blame · {} @ {:.7} ", view.path.display(), rev),
+                None => format!(" Blame · {} ", path.display()),
+            };
+            let block = Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_style(theme.focus());
+
+            // Only the rows on screen are formatted, so huge files stay cheap to scroll
+            let lines = match &view {
+                Ok(view) => view.lines(scroll, height, cursor, theme),
+                Err(e) => vec![Line::from(format!("Blame unavailable: {}", e))],
+            };
+
+            frame.render_widget(Paragraph::new(lines).block(block), main);
+        })?;
+
+        if !event::poll(TICK)? {
+            continue;
+        }
+
+        // Anything but a key, a resize included, just redraws at the current size
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        message = None;
+        let page = height.saturating_sub(1).max(1);
+
+        if let Some((lines, offset)) = &mut details {
+            let bottom = lines.len().saturating_sub(height);
+            match keymap.resolve(&key, Scope::Blame) {
+                Some(Action::Quit) => details = None,
+                Some(Action::NavigateUp) => *offset = offset.saturating_sub(1),
+                Some(Action::NavigateDown) => *offset = (*offset + 1).min(bottom),
+                Some(Action::PageUp) => *offset = offset.saturating_sub(page),
+                Some(Action::PageDown) => *offset = (*offset + page).min(bottom),
+                Some(Action::Top) => *offset = 0,
+                Some(Action::Bottom) => *offset = bottom,
+                _ => {}
+            }
+            continue;
+        }
+
+        let last = total.saturating_sub(1);
+        match keymap.resolve(&key, Scope::Blame) {
+            Some(Action::Quit) => break,
+            Some(Action::NavigateUp) => cursor = cursor.saturating_sub(1),
+            Some(Action::NavigateDown) => cursor = (cursor + 1).min(last),
+            Some(Action::PageUp) => cursor = cursor.saturating_sub(page),
+            Some(Action::PageDown) => cursor = (cursor + page).min(last),
+            Some(Action::Top) => cursor = 0,
+            Some(Action::Bottom) => cursor = last,
+
+            Some(Action::Select) => {
+                if let Ok(view) = &view
+                    && let Some(hunk) = view.blame.get_line(cursor + 1)
+                {
+                    details = Some((
+                        log::show_commit(repo, hunk.final_commit_id(), theme)
+                            .map(|commit| commit.lines)
+                            .unwrap_or_else(|e| vec![Line::from(format!("Error: {}", e))]),
+                        0,
+                    ));
+                }
+            }
+
+            Some(Action::BlameParent) => {
+                let Ok(current) = &view else {
+                    continue;
+                };
+                match current.parent_blame(cursor) {
+                    Ok((parent, line)) => {
+                        let previous = std::mem::replace(&mut view, Ok(parent));
+                        if let Ok(previous) = previous {
+                            history.push((previous, cursor));
+                        }
+                        cursor = line;
+                    }
+                    Err(e) => message = Some(format!("Error: {}", e)),
+                }
+            }
+
+            Some(Action::BlameBack) => match history.pop() {
+                Some((previous, line)) => {
+                    view = Ok(previous);
+                    cursor = line;
+                }
+                None => message = Some(String::from("Already at the newest blame")),
+            },
+
+            _ => {}
+        }
+
+        // Keep the cursor on screen
+        if cursor < scroll {
+            scroll = cursor;
+        } else if height > 0 && cursor >= scroll + height {
+            scroll = cursor + 1 - height;
+        }
+    }
+
+    Ok(())
+}
+
+/// `hg blame <path>` outside the status view.
+pub fn run(repo: &Repository, path: &Path, keymap: &Keymap, theme: &Theme) -> Result<()> {
+    let mut tui = Tui::new()?;
+    blame(&mut tui, repo, path, keymap, theme)
+}
+
+struct BlameView<'r> {
+    repo: &'r Repository,
+    blame: Blame<'r>,
+    content: Vec<String>,
+    /// The file's path as of `rev`, which a rename may have changed
+    path: PathBuf,
+    /// The commit blamed from, `None` for HEAD
+    rev: Option<Oid>,
+}
+
+impl<'r> BlameView<'r> {
+    fn load(repo: &'r Repository, path: &Path, rev: Option<Oid>) -> Result<Self> {
+        let tree = match rev {
+            Some(rev) => repo.find_commit(rev)?.tree()?,
+            None => {
+                let status = repo.status_file(path)?;
+                if status.intersects(Status::WT_NEW | Status::INDEX_NEW) {
+                    return Err(anyhow!("{} is not committed yet", path.display()));
+                }
+                if status.intersects(Status::WT_DELETED | Status::INDEX_DELETED) {
+                    return Err(anyhow!("{} has been deleted", path.display()));
+                }
+                repo.head()?.peel_to_tree()?
+            }
+        };
+
+        // Blame describes the committed file, so show that content beside it
+        let entry = tree.get_path(path).map_err(|_| match rev {
+            Some(rev) => anyhow!("{} does not exist at {:.7}", path.display(), rev),
+            None => anyhow!("{} is not in HEAD", path.display()),
+        })?;
+        let blob = repo.find_blob(entry.id())?;
+        let content = String::from_utf8_lossy(blob.content())
+            .lines()
+            .map(String::from)
+            .collect();
+
+        let mut opts = BlameOptions::new();
+        if let Some(rev) = rev {
+            opts.newest_commit(rev);
+        }
+        let blame = repo.blame_file(path, Some(&mut opts))?;
+
+        Ok(BlameView {
+            repo,
+            blame,
+            content,
+            path: path.to_path_buf(),
+            rev,
+        })
+    }
+
+    /// The blame as of the parent of the commit that last touched line
+    /// `line`, and roughly where that line sits in it.
+    fn parent_blame(&self, line: usize) -> Result<(BlameView<'r>, usize)> {
+        let hunk = self
+            .blame
+            .get_line(line + 1)
+            .ok_or_else(|| anyhow!("no blame for this line"))?;
+        let commit = self.repo.find_commit(hunk.final_commit_id())?;
+        let parent = commit
+            .parent_id(0)
+            .map_err(|_| anyhow!("{:.7} is the first commit", commit.id()))?;
+
+        let path = hunk.path().unwrap_or(&self.path).to_path_buf();
+        let view = BlameView::load(self.repo, &path, Some(parent))?;
+
+        let offset = line + 1 - hunk.final_start_line();
+        let near = (hunk.orig_start_line() + offset).saturating_sub(1);
+        let near = near.min(view.content.len().saturating_sub(1));
+        Ok((view, near))
+    }
+
+    fn lines(&self, from: usize, count: usize, cursor: usize, theme: &Theme) -> Vec<Line<'static>> {
+        let mut summaries: HashMap<Oid, (String, String, i64)> = HashMap::new();
+        let width = self.content.len().to_string().len();
+
+        self.content
+            .iter()
+            .enumerate()
+            .skip(from)
+            .take(count)
+            .map(|(i, code)| {
+                let (sha, author, date, age) = match self.blame.get_line(i + 1) {
+                    Some(hunk) => {
+                        let id = hunk.final_commit_id();
+                        let (author, date, age) = summaries
+                            .entry(id)
+                            .or_insert_with(|| self.describe(id))
+                            .clone();
+                        (format!("{:.7}", id), author, date, age)
+                    }
+                    None => (String::from("       "), String::new(), String::new(), 0),
+                };
+
+                let line = Line::from(vec![
+                    Span::styled(sha, Style::default().fg(theme.commit_sha)),
+                    Span::raw(" "),
+                    Span::styled(
+                        format!("{:<14.14}", author),
+                        Style::default().fg(theme.commit_author),
+                    ),
+                    Span::raw(" "),
+                    Span::styled(
+                        format!("{:<10}", date),
+                        Style::default().fg(age_color(age, theme)),
+                    ),
+                    Span::styled(
+                        format!(" {:>width$} │ ", i + 1),
+                        Style::default().fg(theme.gutter),
+                    ),
+                    Span::raw(code.clone()),
+                ]);
+
+                if i == cursor {
+                    line.style(Style::default().bg(theme.highlight_bg))
+                } else {
+                    line
+                }
+            })
+            .collect()
+    }
+
+    /// Author name, commit date and age in days of `id`.
+    fn describe(&self, id: Oid) -> (String, String, i64) {
+        let Ok(commit) = self.repo.find_commit(id) else {
+            return (String::from("unknown"), String::new(), 0);
+        };
+
+        let author = commit.author().name().unwrap_or("unknown").to_string();
+        let when = DateTime::from_timestamp(commit.time().seconds(), 0);
+        let date = when
+            .map(|d| d.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        let age = when.map_or(0, |d| (Utc::now() - d).num_days());
+
+        (author, date, age)
+    }
+}
+
+/// Fresh changes stand out and old ones fade, like `git blame --color-by-age`.
+fn age_color(days: i64, theme: &Theme) -> Color {
+    match days {
+        d if d < 30 => theme.addition,
+        d if d < 365 => theme.commit_date,
+        _ => theme.gutter,
+    }
+}
@@ -0,0 +1,381 @@
+mod branch;
+
+use crate::help;
+use crate::keymap::{Action, Keymap, Scope};
+use crate::theme::Theme;
+use crate::tui::{TICK, Tui};
+use anyhow::Result;
+use branch::{BranchInfo, Tracking};
+use crossterm::event::{self, Event, KeyCode};
+use git2::{BranchType, Repository};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, HighlightSpacing, List, ListItem, ListState, Paragraph},
+};
+
+/// A branch name being typed on the helper line.
+enum Prompt {
+    Create(String),
+    Rename(String),
+}
+
+pub fn branches(tui: &mut Tui, repo: &Repository, keymap: &Keymap, theme: &Theme) -> Result<()> {
+    let mut branches = branch::load(repo)?;
+    let mut list_state = ListState::default();
+    list_state.select(branches.iter().position(|b| b.is_head).or(Some(0)));
+
+    // ---------- UI State ----------
+    let mut message: Option<String> = None;
+    let mut prompt: Option<Prompt> = None;
+    let mut confirm_delete: Option<String> = None;
+    let mut show_help = false;
+    // Rows inside the list's borders at the last draw, for paging
+    let mut page_height: u16 = 0;
+
+    // ---------- Main Loop ----------
+    loop {
+        let selected = list_state.selected().and_then(|i| branches.get(i));
+
+        // ---------- Helper line ----------
+        let help_line = match (&prompt, &confirm_delete, message.clone()) {
+            (Some(Prompt::Create(name) | Prompt::Rename(name)), ..) => {
+                let label = match (&prompt, selected) {
+                    (Some(Prompt::Create(_)), Some(from)) => {
+                        format!("New branch from {}: ", from.name)
+                    }
+                    (_, Some(branch)) => format!("Rename {} to: ", branch.name),
+                    _ => String::new(),
+                };
+                Line::from(vec![
+                    Span::styled(label, Style::default().fg(theme.accent)),
+                    Span::raw(name.clone()),
+                    Span::styled("█", Style::default().fg(theme.accent)),
+                ])
+            }
+            (None, Some(text), _) => Line::styled(
+                text.clone(),
+                Style::default()
+                    .fg(theme.warning)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            (None, None, Some(text)) => Line::from(text),
+            (None, None, None) => help_line(keymap),
+        };
+
+        let width = branches
+            .iter()
+            .map(|b| b.name.chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut items: Vec<ListItem> = branches
+            .iter()
+            .map(|b| ListItem::new(branch_line(b, width, theme)))
+            .collect();
+        if items.is_empty() {
+            items.push(ListItem::new("No branches yet"));
+        }
+
+        tui.draw(|frame| {
+            let area = frame.area();
+
+            // ---------- Reserve bottom line for helper ----------
+            let outer_chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Min(0),    // top: branch list
+                    Constraint::Length(1), // bottom: help line
+                ])
+                .split(area);
+
+            let block = Block::default()
+                .title(" Branches ")
+                .borders(Borders::ALL)
+                .border_style(theme.focus());
+
+            let list = List::new(items)
+                .block(block)
+                .highlight_style(
+                    Style::default()
+                        .bg(theme.highlight_bg)
+                        .add_modifier(Modifier::BOLD),
+                )
+                .highlight_symbol("➜ ")
+                .highlight_spacing(HighlightSpacing::Always);
+
+            frame.render_stateful_widget(list, outer_chunks[0], &mut list_state);
+            page_height = outer_chunks[0].height.saturating_sub(2);
+
+            // ---------- Helper Line ----------
+            let help_paragraph = Paragraph::new(help_line)
+                .alignment(Alignment::Center)
+                .style(if prompt.is_some() || confirm_delete.is_some() {
+                    Style::default()
+                } else {
+                    Style::default().dim()
+                });
+
+            frame.render_widget(help_paragraph, outer_chunks[1]);
+
+            if show_help {
+                help::render(frame, keymap, theme);
+            }
+        })?;
+
+        // ---------- Input Handling ----------
+        if !event::poll(TICK)? {
+            continue;
+        }
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        message = None;
+
+        let selected = list_state.selected().and_then(|i| branches.get(i));
+
+        // A pending delete swallows every key until it is answered
+        if confirm_delete.is_some() {
+            match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    if let Some(branch) = selected
+                        && let Err(e) = branch::delete(repo, branch)
+                    {
+                        message = Some(format!("Error: {}", e));
+                    }
+                    confirm_delete = None;
+                    reload(repo, &mut branches, &mut list_state, None)?;
+                }
+                KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                    confirm_delete = None;
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        if let Some(Prompt::Create(name) | Prompt::Rename(name)) = &mut prompt {
+            match key.code {
+                KeyCode::Esc => prompt = None,
+                KeyCode::Backspace => {
+                    name.pop();
+                }
+                KeyCode::Char(c) => name.push(c),
+                KeyCode::Enter => {
+                    let name = name.trim().to_string();
+                    let result = match (&prompt, selected) {
+                        (Some(Prompt::Create(_)), Some(from)) => branch::create(repo, &name, from),
+                        (Some(Prompt::Rename(_)), Some(branch)) => {
+                            branch::rename(repo, branch, &name)
+                        }
+                        _ => Ok(()),
+                    };
+                    prompt = None;
+                    match result {
+                        Ok(()) => reload(repo, &mut branches, &mut list_state, Some(&name))?,
+                        Err(e) => message = Some(format!("Error: {}", e)),
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        if show_help {
+            if matches!(
+                keymap.resolve(&key, Scope::Branches),
+                Some(Action::Help | Action::Quit)
+            ) {
+                show_help = false;
+            }
+            continue;
+        }
+
+        match keymap.resolve(&key, Scope::Branches) {
+            Some(Action::Quit) => break,
+
+            Some(Action::Help) => show_help = true,
+
+            Some(Action::Refresh) => {
+                let name = selected.map(|b| b.name.clone());
+                reload(repo, &mut branches, &mut list_state, name.as_deref())?;
+            }
+
+            Some(Action::CheckoutBranch) => {
+                if let Some(branch) = selected {
+                    match branch::checkout(repo, branch) {
+                        Ok(text) => {
+                            message = Some(text);
+                            // A remote branch was switched to under its new local name
+                            let head = repo
+                                .head()
+                                .ok()
+                                .and_then(|h| h.shorthand().map(String::from));
+                            reload(repo, &mut branches, &mut list_state, head.as_deref())?;
+                        }
+                        Err(e) => message = Some(format!("Error: {}", e)),
+                    }
+                }
+            }
+
+            Some(Action::NewBranch) if selected.is_some() => {
+                prompt = Some(Prompt::Create(String::new()));
+            }
+
+            Some(Action::RenameBranch) => match selected {
+                Some(branch) if branch.kind == BranchType::Remote => {
+                    message = Some(String::from("Error: remote branches can't be renamed here"));
+                }
+                Some(branch) => prompt = Some(Prompt::Rename(branch.name.clone())),
+                None => {}
+            },
+
+            Some(Action::DeleteBranch) => {
+                if let Some(branch) = selected {
+                    match branch::delete_prompt(repo, branch) {
+                        Ok(text) => confirm_delete = Some(text),
+                        Err(e) => message = Some(format!("Error: {}", e)),
+                    }
+                }
+            }
+
+            Some(Action::NavigateUp) => {
+                if let Some(i) = list_state.selected()
+                    && i > 0
+                {
+                    list_state.select(Some(i - 1));
+                }
+            }
+
+            Some(Action::NavigateDown) => {
+                if let Some(i) = list_state.selected()
+                    && i < branches.len().saturating_sub(1)
+                {
+                    list_state.select(Some(i + 1));
+                }
+            }
+
+            Some(action @ (Action::PageUp | Action::PageDown)) => {
+                let page = page_height.saturating_sub(1).max(1) as usize;
+                if let Some(i) = list_state.selected() {
+                    let last = branches.len().saturating_sub(1);
+                    list_state.select(Some(if action == Action::PageDown {
+                        (i + page).min(last)
+                    } else {
+                        i.saturating_sub(page)
+                    }));
+                }
+            }
+
+            Some(Action::Top) => list_state.select(Some(0)),
+
+            Some(Action::Bottom) => list_state.select(Some(branches.len().saturating_sub(1))),
+
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// `hg branches` outside the status view.
+pub fn run(repo: &Repository, keymap: &Keymap, theme: &Theme) -> Result<()> {
+    let mut tui = Tui::new()?;
+    branches(&mut tui, repo, keymap, theme)
+}
+
+/// Reloads the branches, selecting `name` if it still exists, or else the
+/// same row (clamped).
+fn reload(
+    repo: &Repository,
+    branches: &mut Vec<BranchInfo>,
+    list_state: &mut ListState,
+    name: Option<&str>,
+) -> Result<()> {
+    *branches = branch::load(repo)?;
+
+    let by_name = name.and_then(|name| branches.iter().position(|b| b.name == name));
+    let row = list_state
+        .selected()
+        .unwrap_or(0)
+        .min(branches.len().saturating_sub(1));
+    list_state.select(Some(by_name.unwrap_or(row)));
+    Ok(())
+}
+
+/// `* main  1a2b3c4 [origin/main ↑1 ↓2] summary`
+fn branch_line(branch: &BranchInfo, width: usize, theme: &Theme) -> Line<'static> {
+    let (marker, name_style) = match (branch.is_head, branch.kind) {
+        (true, _) => (
+            "* ",
+            Style::default()
+                .fg(theme.staged)
+                .add_modifier(Modifier::BOLD),
+        ),
+        (false, BranchType::Local) => ("  ", Style::default()),
+        (false, BranchType::Remote) => ("  ", Style::default().fg(theme.deletion)),
+    };
+    let sha = branch
+        .target
+        .map(|oid| oid.to_string()[..7].to_string())
+        .unwrap_or_default();
+
+    let mut spans = vec![
+        Span::styled(marker, name_style),
+        Span::styled(format!("{:<width$}", branch.name), name_style),
+        Span::raw(" "),
+        Span::styled(sha, Style::default().fg(theme.commit_sha)),
+        Span::raw(" "),
+    ];
+
+    match &branch.tracking {
+        Tracking::None => {}
+        Tracking::Gone(name) => spans.push(Span::styled(
+            format!("[{}: gone] ", name),
+            Style::default().fg(theme.warning),
+        )),
+        Tracking::Upstream {
+            name,
+            ahead,
+            behind,
+        } => {
+            let counts = match (ahead, behind) {
+                (0, 0) => String::new(),
+                (ahead, 0) => format!(" ↑{}", ahead),
+                (0, behind) => format!(" ↓{}", behind),
+                (ahead, behind) => format!(" ↑{} ↓{}", ahead, behind),
+            };
+            spans.push(Span::styled(
+                format!("[{}{}] ", name, counts),
+                Style::default().fg(theme.accent),
+            ));
+        }
+    }
+
+    spans.push(Span::raw(branch.summary.clone()));
+    Line::from(spans)
+}
+
+fn help_line(keymap: &Keymap) -> Line<'static> {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let mut spans = vec![Span::raw(" ")];
+
+    for (i, (action, label)) in [
+        (Action::CheckoutBranch, "checkout"),
+        (Action::NewBranch, "new"),
+        (Action::RenameBranch, "rename"),
+        (Action::DeleteBranch, "delete"),
+        (Action::Help, "help"),
+        (Action::Quit, "quit"),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        if i > 0 {
+            spans.push(Span::raw(" • "));
+        }
+        spans.push(Span::styled(keymap.label(action), bold));
+        spans.push(Span::raw(format!(" {}", label)));
+    }
+
+    Line::from(spans)
+}
@@ -0,0 +1,233 @@
+use anyhow::{Result, anyhow, bail};
+use git2::{
+    Branch, BranchType, ErrorCode, Oid, Repository, RepositoryState, build::CheckoutBuilder,
+};
+
+/// How a branch compares with the upstream it tracks.
+pub enum Tracking {
+    None,
+    /// Configured, but the remote branch no longer exists
+    Gone(String),
+    Upstream {
+        name: String,
+        ahead: usize,
+        behind: usize,
+    },
+}
+
+/// One row of the branches view, like a line of `git branch -avv`.
+pub struct BranchInfo {
+    /// Short name, `main` or `origin/main`
+    pub name: String,
+    pub kind: BranchType,
+    pub is_head: bool,
+    pub target: Option<Oid>,
+    pub summary: String,
+    pub tracking: Tracking,
+}
+
+/// Local branches, then remote-tracking ones, each sorted by name. The
+/// remotes' symbolic `HEAD` refs are left out.
+pub fn load(repo: &Repository) -> Result<Vec<BranchInfo>> {
+    let mut branches = Vec::new();
+
+    for kind in [BranchType::Local, BranchType::Remote] {
+        let mut found = Vec::new();
+
+        for branch in repo.branches(Some(kind))? {
+            let (branch, _) = branch?;
+            let Some(name) = branch.name()?.map(String::from) else {
+                continue;
+            };
+            if kind == BranchType::Remote && name.ends_with("/HEAD") {
+                continue;
+            }
+
+            let target = branch.get().target();
+            let summary = target
+                .and_then(|oid| repo.find_commit(oid).ok())
+                .and_then(|c| c.summary().map(String::from))
+                .unwrap_or_default();
+
+            found.push(BranchInfo {
+                tracking: tracking(repo, &branch, target),
+                is_head: branch.is_head(),
+                name,
+                kind,
+                target,
+                summary,
+            });
+        }
+
+        found.sort_by(|a, b| a.name.cmp(&b.name));
+        branches.extend(found);
+    }
+
+    Ok(branches)
+}
+
+fn tracking(repo: &Repository, branch: &Branch, target: Option<Oid>) -> Tracking {
+    let Some(refname) = branch.get().name() else {
+        return Tracking::None;
+    };
+    // Only local branches have upstream config
+    let Ok(configured) = repo.branch_upstream_name(refname) else {
+        return Tracking::None;
+    };
+    let configured = configured.as_str().unwrap_or("").to_string();
+    let short = configured
+        .strip_prefix("refs/remotes/")
+        .or_else(|| configured.strip_prefix("refs/heads/"))
+        .unwrap_or(&configured)
+        .to_string();
+
+    let upstream = branch.upstream().ok().and_then(|b| b.get().target());
+    match (target, upstream) {
+        (Some(local), Some(upstream)) => match repo.graph_ahead_behind(local, upstream) {
+            Ok((ahead, behind)) => Tracking::Upstream {
+                name: short,
+                ahead,
+                behind,
+            },
+            Err(_) => Tracking::None,
+        },
+        _ => Tracking::Gone(short),
+    }
+}
+
+/// Switches to `branch` like `git switch`. Local changes are carried over
+/// as long as the checkout doesn't touch them; otherwise nothing changes.
+/// A remote branch gets a local branch of the same name that tracks it.
+pub fn checkout(repo: &Repository, branch: &BranchInfo) -> Result<String> {
+    if branch.is_head {
+        bail!("already on {}", branch.name);
+    }
+    if repo.state() != RepositoryState::Clean {
+        let state = format!("{:?}", repo.state()).to_lowercase();
+        bail!("finish or abort the {} in progress first", state);
+    }
+    if repo.index()?.has_conflicts() {
+        bail!("resolve the conflicts before switching branches");
+    }
+
+    let local = match branch.kind {
+        BranchType::Local => branch.name.clone(),
+        BranchType::Remote => {
+            let remote = repo.branch_remote_name(&format!("refs/remotes/{}", branch.name))?;
+            let remote = remote.as_str().unwrap_or("");
+            let local = branch
+                .name
+                .strip_prefix(&format!("{}/", remote))
+                .unwrap_or(&branch.name)
+                .to_string();
+            if repo.find_branch(&local, BranchType::Local).is_ok() {
+                bail!("a local branch {} already exists; switch to that", local);
+            }
+            local
+        }
+    };
+
+    let oid = branch
+        .target
+        .ok_or_else(|| anyhow!("{} does not point at a commit", branch.name))?;
+    let commit = repo.find_commit(oid)?;
+
+    // Safe mode leaves the worktree alone and fails if a local change is in the way
+    repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().safe()))
+        .map_err(|e| match e.code() {
+            ErrorCode::Conflict => anyhow!(
+                "local changes would be overwritten by switching to {}; commit or stash them first",
+                branch.name
+            ),
+            _ => e.into(),
+        })?;
+
+    if branch.kind == BranchType::Remote {
+        let mut created = repo.branch(&local, &commit, false)?;
+        created.set_upstream(Some(&branch.name))?;
+    }
+    repo.set_head(&format!("refs/heads/{}", local))?;
+
+    Ok(match branch.kind {
+        BranchType::Local => format!("Switched to {}", local),
+        BranchType::Remote => format!("Switched to new branch {} tracking {}", local, branch.name),
+    })
+}
+
+/// Creates local branch `name` at `from`'s commit, without switching to it.
+pub fn create(repo: &Repository, name: &str, from: &BranchInfo) -> Result<()> {
+    validate(name)?;
+    let oid = from
+        .target
+        .ok_or_else(|| anyhow!("{} does not point at a commit", from.name))?;
+    repo.branch(name, &repo.find_commit(oid)?, false)
+        .map_err(|e| exists_error(e, name))?;
+    Ok(())
+}
+
+pub fn rename(repo: &Repository, branch: &BranchInfo, new_name: &str) -> Result<()> {
+    if branch.kind == BranchType::Remote {
+        bail!("remote branches can't be renamed here");
+    }
+    validate(new_name)?;
+    repo.find_branch(&branch.name, BranchType::Local)?
+        .rename(new_name, false)
+        .map_err(|e| exists_error(e, new_name))?;
+    Ok(())
+}
+
+/// Confirmation text for deleting `branch`, warning when its commits aren't
+/// merged into its upstream (or HEAD, without one) and would be lost.
+pub fn delete_prompt(repo: &Repository, branch: &BranchInfo) -> Result<String> {
+    if branch.kind == BranchType::Remote {
+        bail!("remote branches are deleted by pushing; this only deletes local ones");
+    }
+    if branch.is_head {
+        bail!(
+            "{} is checked out; switch to another branch first",
+            branch.name
+        );
+    }
+
+    let base = match &branch.tracking {
+        Tracking::Upstream { .. } => repo
+            .find_branch(&branch.name, BranchType::Local)?
+            .upstream()?
+            .get()
+            .target(),
+        _ => repo.head().ok().and_then(|h| h.target()),
+    };
+    let merged = match (branch.target, base) {
+        (Some(tip), Some(base)) => tip == base || repo.graph_descendant_of(base, tip)?,
+        _ => false,
+    };
+
+    Ok(if merged {
+        format!("Delete branch {}? (y/n)", branch.name)
+    } else {
+        format!(
+            "{} is not fully merged; delete it and its commits anyway? (y/n)",
+            branch.name
+        )
+    })
+}
+
+pub fn delete(repo: &Repository, branch: &BranchInfo) -> Result<()> {
+    repo.find_branch(&branch.name, BranchType::Local)?
+        .delete()?;
+    Ok(())
+}
+
+fn validate(name: &str) -> Result<()> {
+    if !Branch::name_is_valid(name)? {
+        bail!("\"{}\" is not a valid branch name", name);
+    }
+    Ok(())
+}
+
+fn exists_error(e: git2::Error, name: &str) -> anyhow::Error {
+    match e.code() {
+        ErrorCode::Exists => anyhow!("a branch named {} already exists", name),
+        _ => e.into(),
+    }
+}
@@ -0,0 +1,302 @@
+mod entry;
+
+use crate::help;
+use crate::keymap::{Action, Keymap, Scope};
+use crate::panes::{self, Panes};
+use crate::theme::Theme;
+use crate::tui::{TICK, Tui};
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode};
+use entry::Preview;
+use git2::Repository;
+use helper_git_core::status::{self, Cleanable};
+use ratatui::{
+    prelude::*,
+    widgets::{ListItem, ListState},
+};
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// A visual `git clean -fd`: untracked files and directories (ignored ones
+/// too when `include_ignored` is set, or once `i` is pressed) to mark, with
+/// what would go shown before anything does.
+pub fn clean(
+    tui: &mut Tui,
+    repo: &Repository,
+    list_width: u16,
+    keymap: &Keymap,
+    theme: &Theme,
+    include_ignored: bool,
+) -> Result<()> {
+    let mut include_ignored = include_ignored;
+    let mut entries = status::cleanable(repo, include_ignored)?;
+    let mut marked: HashSet<PathBuf> = HashSet::new();
+    let mut panes: Panes<Vec<PathBuf>> = Panes::new(list_width);
+
+    // ---------- UI State ----------
+    // The dry run's counts, its lines being the right panel's
+    let mut preview = Preview::default();
+    let mut message: Option<String> = None;
+    let mut confirm_remove: Option<String> = None;
+    let mut show_help = false;
+
+    // ---------- Main Loop ----------
+    loop {
+        let chosen = targets(&entries, &marked, panes.selected());
+
+        // ---------- Preview ----------
+        // Made again when the marks or the selection change
+        panes.preview(
+            Some(&chosen),
+            |chosen| chosen.iter().map(|e| e.path.clone()).collect(),
+            |chosen| {
+                preview = entry::preview(repo, chosen, theme).unwrap_or_else(|e| Preview {
+                    lines: vec![Line::from(format!("Error: {}", e))],
+                    ..Preview::default()
+                });
+                std::mem::take(&mut preview.lines)
+            },
+        );
+        let preview_title = format!(" Dry run: {} ", preview.summary());
+
+        // ---------- Helper line ----------
+        let help_line = match (&confirm_remove, message.clone()) {
+            (Some(prompt), _) => Line::styled(
+                prompt.clone(),
+                Style::default()
+                    .fg(theme.warning)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            (None, Some(text)) => Line::from(text),
+            (None, None) => help_line(keymap),
+        };
+
+        let items: Vec<ListItem> = entries
+            .iter()
+            .map(|e| {
+                let mut spans = Vec::new();
+                // A column for the marks, only while anything is marked
+                if !marked.is_empty() {
+                    spans.push(if marked.contains(&e.path) {
+                        Span::styled("● ", Style::default().add_modifier(Modifier::BOLD))
+                    } else {
+                        Span::raw("  ")
+                    });
+                }
+                let color = if e.ignored {
+                    theme.ignored
+                } else {
+                    theme.untracked
+                };
+                spans.push(Span::styled(
+                    e.path.to_string_lossy().into_owned(),
+                    Style::default().fg(color),
+                ));
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        let list = panes::list(items, "Nothing to clean", theme);
+        let title = match (include_ignored, marked.len()) {
+            (false, 0) => String::from(" Untracked "),
+            (true, 0) => String::from(" Untracked and ignored "),
+            (false, n) => format!(" Untracked ({} marked) ", n),
+            (true, n) => format!(" Untracked and ignored ({} marked) ", n),
+        };
+
+        tui.draw(|frame| {
+            let (main, help) = panes::split(frame.area());
+            panes.render(frame, main, &list, &title, &preview_title, theme);
+            panes::render_help(frame, help, help_line, confirm_remove.is_some());
+
+            if show_help {
+                help::render(frame, keymap, theme);
+            }
+        })?;
+
+        // ---------- Input Handling ----------
+        if !event::poll(TICK)? {
+            continue;
+        }
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        message = None;
+
+        // A pending removal swallows every key until it is answered
+        if confirm_remove.is_some() {
+            match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    let chosen = targets(&entries, &marked, panes.selected());
+                    message = Some(
+                        entry::remove(repo, &chosen).unwrap_or_else(|e| format!("Error: {}", e)),
+                    );
+                    confirm_remove = None;
+                    marked.clear();
+                    reload(
+                        repo,
+                        include_ignored,
+                        &mut entries,
+                        &mut marked,
+                        &mut panes.list_state,
+                    )?;
+                }
+                KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                    confirm_remove = None;
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        if show_help {
+            if matches!(
+                keymap.resolve(&key, Scope::Clean),
+                Some(Action::Help | Action::Quit)
+            ) {
+                show_help = false;
+            }
+            continue;
+        }
+
+        match keymap.resolve(&key, Scope::Clean) {
+            // Esc drops the marks before it quits
+            Some(Action::Quit) if key.code == KeyCode::Esc && !marked.is_empty() => {
+                marked.clear();
+            }
+
+            Some(Action::Quit) => break,
+
+            Some(Action::Help) => show_help = true,
+
+            Some(Action::Refresh) => {
+                reload(
+                    repo,
+                    include_ignored,
+                    &mut entries,
+                    &mut marked,
+                    &mut panes.list_state,
+                )?;
+                // Files inside a directory can change without its entry changing
+                panes.forget();
+            }
+
+            Some(Action::ToggleIgnored) => {
+                include_ignored = !include_ignored;
+                reload(
+                    repo,
+                    include_ignored,
+                    &mut entries,
+                    &mut marked,
+                    &mut panes.list_state,
+                )?;
+            }
+
+            Some(Action::MarkToRemove) => {
+                if let Some(entry) = panes.selected().and_then(|i| entries.get(i))
+                    && !marked.remove(&entry.path)
+                {
+                    marked.insert(entry.path.clone());
+                }
+            }
+
+            Some(Action::MarkAllToRemove) => {
+                if marked.len() == entries.len() {
+                    marked.clear();
+                } else {
+                    marked = entries.iter().map(|e| e.path.clone()).collect();
+                }
+            }
+
+            Some(Action::RemoveFiles) if !chosen.is_empty() => {
+                let directories = chosen.iter().filter(|e| e.is_directory()).count();
+                confirm_remove = Some(format!(
+                    "Delete {} ({})? This can't be undone (y/n)",
+                    entry::describe(chosen.len() - directories, directories),
+                    preview.summary()
+                ));
+            }
+
+            Some(action) => {
+                panes.navigate(action, entries.len());
+            }
+
+            None => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// `hg clean` outside the status view.
+pub fn run(
+    repo: &Repository,
+    list_width: u16,
+    keymap: &Keymap,
+    theme: &Theme,
+    include_ignored: bool,
+) -> Result<()> {
+    let mut tui = Tui::new()?;
+    clean(&mut tui, repo, list_width, keymap, theme, include_ignored)
+}
+
+/// What a removal would take: the marked entries, or the selected one when
+/// nothing is marked.
+fn targets<'e>(
+    entries: &'e [Cleanable],
+    marked: &HashSet<PathBuf>,
+    selected: Option<usize>,
+) -> Vec<&'e Cleanable> {
+    if marked.is_empty() {
+        selected.and_then(|i| entries.get(i)).into_iter().collect()
+    } else {
+        entries
+            .iter()
+            .filter(|e| marked.contains(&e.path))
+            .collect()
+    }
+}
+
+/// Lists the candidates again, keeping the marks that still apply and the
+/// selected row (clamped).
+fn reload(
+    repo: &Repository,
+    include_ignored: bool,
+    entries: &mut Vec<Cleanable>,
+    marked: &mut HashSet<PathBuf>,
+    list_state: &mut ListState,
+) -> Result<()> {
+    *entries = status::cleanable(repo, include_ignored)?;
+    marked.retain(|path| entries.iter().any(|e| &e.path == path));
+    let row = list_state
+        .selected()
+        .unwrap_or(0)
+        .min(entries.len().saturating_sub(1));
+    list_state.select(Some(row));
+    Ok(())
+}
+
+fn help_line(keymap: &Keymap) -> Line<'static> {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let mut spans = vec![Span::raw(" ")];
+
+    for (i, (action, label)) in [
+        (Action::MarkToRemove, "mark"),
+        (Action::MarkAllToRemove, "mark all"),
+        (Action::RemoveFiles, "delete"),
+        (Action::ToggleIgnored, "ignored files"),
+        (Action::SwitchFocus, "switch focus"),
+        (Action::Quit, "quit"),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        if i > 0 {
+            spans.push(Span::raw(" • "));
+        }
+        spans.push(Span::styled(keymap.label(action), bold));
+        spans.push(Span::raw(format!(" {}", label)));
+    }
+
+    Line::from(spans)
+}
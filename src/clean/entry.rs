@@ -0,0 +1,160 @@
+use crate::status::diff::human_size;
+use crate::theme::Theme;
+use anyhow::{Result, anyhow};
+use git2::Repository;
+use helper_git_core::status::{self, Cleanable};
+use ratatui::{style::Style, text::Line};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Files listed under each directory of a preview; past this they're only counted.
+const LISTED_FILES: usize = 200;
+
+/// What removing some entries would delete, like `git clean -n` but with the
+/// files inside each directory listed under it.
+#[derive(Default)]
+pub struct Preview {
+    pub lines: Vec<Line<'static>>,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+impl Preview {
+    /// `12 files, 3.4 KiB`
+    pub fn summary(&self) -> String {
+        format!(
+            "{}, {}",
+            count(self.files, "file", "files"),
+            human_size(self.bytes)
+        )
+    }
+}
+
+pub fn preview(repo: &Repository, entries: &[&Cleanable], theme: &Theme) -> Result<Preview> {
+    let workdir = workdir(repo)?;
+    let mut preview = Preview {
+        lines: Vec::new(),
+        files: 0,
+        bytes: 0,
+    };
+
+    for entry in entries {
+        let color = if entry.ignored {
+            theme.ignored
+        } else {
+            theme.untracked
+        };
+        preview.lines.push(Line::styled(
+            format!("Would remove {}", entry.path.display()),
+            Style::default().fg(color),
+        ));
+
+        let full = workdir.join(&entry.path);
+        if !entry.is_directory() {
+            preview.files += 1;
+            preview.bytes += fs::symlink_metadata(&full).map_or(0, |m| m.len());
+            continue;
+        }
+
+        let mut inside = Vec::new();
+        walk(&full, &entry.path, &mut inside, &mut preview.bytes)?;
+        preview.files += inside.len();
+        for path in inside.iter().take(LISTED_FILES) {
+            preview.lines.push(Line::styled(
+                format!("    {}", path.display()),
+                Style::default().fg(color).dim(),
+            ));
+        }
+        if inside.len() > LISTED_FILES {
+            preview.lines.push(Line::styled(
+                format!("    … and {} more", inside.len() - LISTED_FILES),
+                Style::default().dim(),
+            ));
+        }
+    }
+
+    Ok(preview)
+}
+
+/// Every file under `dir`, in name order and as paths starting with
+/// `relative`, adding up their sizes. Symlinks count as files and aren't
+/// followed.
+fn walk(dir: &Path, relative: &Path, files: &mut Vec<PathBuf>, bytes: &mut u64) -> Result<()> {
+    let mut children = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    children.sort_by_key(|child| child.file_name());
+
+    for child in children {
+        let meta = fs::symlink_metadata(child.path())?;
+        let path = relative.join(child.file_name());
+        if meta.is_dir() {
+            walk(&child.path(), &path, files, bytes)?;
+        } else {
+            *bytes += meta.len();
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Deletes `entries` from the worktree, directories with everything in
+/// them. Returns what to tell the user.
+pub fn remove(repo: &Repository, entries: &[&Cleanable]) -> Result<String> {
+    let workdir = workdir(repo)?;
+    let directories = entries.iter().filter(|e| e.is_directory()).count();
+
+    // One may have been cloned into since it was listed, and that's not ours
+    // to delete, as git won't without being forced twice
+    if let Some(entry) = entries
+        .iter()
+        .find(|e| e.is_directory() && status::holds_repository(&workdir.join(&e.path)))
+    {
+        return Err(anyhow!(
+            "{} has a repository in it now; left everything alone",
+            entry.path.display()
+        ));
+    }
+
+    for entry in entries {
+        let full = workdir.join(&entry.path);
+        let removed = if entry.is_directory() {
+            fs::remove_dir_all(&full)
+        } else {
+            fs::remove_file(&full)
+        };
+        match removed {
+            // Already gone, which is what was asked for
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(anyhow!("cannot remove {}: {}", entry.path.display(), e)),
+            Ok(()) => {}
+        }
+    }
+
+    Ok(format!(
+        "Removed {}",
+        describe(entries.len() - directories, directories)
+    ))
+}
+
+/// `2 files and 1 directory`, leaving out whichever there are none of.
+pub fn describe(files: usize, directories: usize) -> String {
+    match (files, directories) {
+        (_, 0) => count(files, "file", "files"),
+        (0, _) => count(directories, "directory", "directories"),
+        _ => format!(
+            "{} and {}",
+            count(files, "file", "files"),
+            count(directories, "directory", "directories")
+        ),
+    }
+}
+
+fn count(n: usize, one: &str, many: &str) -> String {
+    format!("{} {}", n, if n == 1 { one } else { many })
+}
+
+fn workdir(repo: &Repository) -> Result<&Path> {
+    repo.workdir()
+        .ok_or_else(|| anyhow!("repository has no working directory"))
+}
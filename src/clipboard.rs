@@ -0,0 +1,58 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use std::env;
+use std::io::{Write, stdout};
+
+/// System clipboard, opened on first use so a headless session only fails
+/// when something is actually copied. Over SSH, or when there is no system
+/// clipboard to open, the text goes to the terminal as an OSC 52 sequence
+/// instead, which most terminals put on the clipboard of the machine they
+/// run on.
+#[derive(Default)]
+pub struct Clipboard {
+    // On X11 the copied text is served by this process, so the handle has to
+    // outlive the copy for other programs to be able to paste it
+    inner: Option<arboard::Clipboard>,
+}
+
+impl Clipboard {
+    pub fn copy(&mut self, text: &str) -> Result<()> {
+        // The system clipboard of a remote session is the wrong machine's
+        if env::var_os("SSH_TTY").is_some() || env::var_os("SSH_CONNECTION").is_some() {
+            return osc52(text);
+        }
+
+        self.system(text).or_else(|_| osc52(text))
+    }
+
+    fn system(&mut self, text: &str) -> Result<()> {
+        let clipboard = match &mut self.inner {
+            Some(clipboard) => clipboard,
+            None => self
+                .inner
+                .insert(arboard::Clipboard::new().context("clipboard unavailable")?),
+        };
+
+        clipboard
+            .set_text(text)
+            .context("cannot copy to clipboard")?;
+        Ok(())
+    }
+}
+
+/// Asks the terminal to set its clipboard. tmux only passes the sequence on
+/// wrapped in one of its own.
+fn osc52(text: &str) -> Result<()> {
+    let sequence = format!("\x1b]52;c;{}\x07", STANDARD.encode(text));
+    let sequence = if env::var_os("TMUX").is_some() {
+        format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+    } else {
+        sequence
+    };
+
+    let mut out = stdout();
+    out.write_all(sequence.as_bytes())
+        .and_then(|()| out.flush())
+        .context("cannot copy to clipboard")
+}
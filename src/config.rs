@@ -0,0 +1,240 @@
+use crate::image::Graphics;
+use crate::keymap::Keymap;
+use crate::theme::Theme;
+use anyhow::{Context, Result, bail};
+use helper_git_core::diff::DiffSettings;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// On-disk shape of `$XDG_CONFIG_HOME/helper-git/config.toml`:
+///
+/// ```toml
+/// theme = "light"
+/// keymap = "vim"
+/// syntax_highlighting = false
+/// list_width = 30
+/// images = "sixel"
+/// tab_width = 4
+/// show_whitespace = true
+///
+/// [diff]
+/// context = 5
+/// ignore_all_space = true
+///
+/// [colors]
+/// addition = "#50fa7b"
+/// hunk_bg = "236"
+///
+/// [keys]
+/// navigate_down = ["n", "Down"]
+/// navigate_up = "e"
+/// top = "gg"
+/// ```
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    theme: Option<String>,
+    /// Preset the `[keys]` build on: default, vim or standard
+    keymap: Option<String>,
+    syntax_highlighting: Option<bool>,
+    list_width: Option<u16>,
+    /// Image protocol: auto, kitty, iterm2, sixel or off
+    images: Option<String>,
+    /// Tab width where neither `.editorconfig` nor `core.whitespace` says
+    tab_width: Option<u8>,
+    show_whitespace: Option<bool>,
+    diff: DiffFile,
+    /// Single colors of the theme, by field name
+    colors: HashMap<String, String>,
+    keys: HashMap<String, Keys>,
+}
+
+/// The `[diff]` table; whatever is left out keeps git's defaults.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct DiffFile {
+    context: Option<u32>,
+    ignore_all_space: bool,
+    ignore_space_change: bool,
+    ignore_blank_lines: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Keys {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Everything the config file controls, with the defaults filled in for
+/// what it leaves out.
+pub struct Config {
+    pub keymap: Keymap,
+    pub theme: Theme,
+    /// Off for terminals, or machines, where highlighting diffs is too slow
+    pub syntax_highlighting: bool,
+    /// Percentage of the screen width the list takes in two-panel views
+    pub list_width: u16,
+    /// How changed images are previewed, if at all; found out from the
+    /// terminal unless the file names one
+    pub images: Option<Graphics>,
+    /// How wide a tab is in diffs, unless the file's `.editorconfig` or
+    /// `core.whitespace` says otherwise
+    pub tab_width: u8,
+    /// `→` for tabs and `·` for trailing spaces in diffs
+    pub show_whitespace: bool,
+    /// What the status view's diff starts with; `+`/`-`, `W`, `B` and `L`
+    /// still change it at runtime
+    pub diff: DiffSettings,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            keymap: Keymap::default(),
+            theme: Theme::default(),
+            syntax_highlighting: true,
+            list_width: 40,
+            images: Graphics::detect(),
+            tab_width: 8,
+            show_whitespace: false,
+            diff: DiffSettings {
+                context_lines: 3,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl Config {
+    /// Reads `path`, or the default config file if none was given. Only a
+    /// missing default file means defaults; a missing `path` is an error.
+    /// A `theme` named on the command line replaces the file's, and the
+    /// file's `[colors]` still go on top of it.
+    pub fn load(path: Option<&Path>, theme: Option<&str>) -> Result<Self> {
+        let base = theme.map(Theme::by_name).transpose()?;
+        let defaults = |base: Option<Theme>| Config {
+            theme: base.unwrap_or_default(),
+            ..Config::default()
+        };
+        let (path, explicit) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match config_path() {
+                Some(path) => (path, false),
+                None => return Ok(defaults(base)),
+            },
+        };
+
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound && !explicit => {
+                return Ok(defaults(base));
+            }
+            Err(e) => return Err(e).with_context(|| format!("cannot read {}", path.display())),
+        };
+
+        let file: ConfigFile =
+            toml::from_str(&text).with_context(|| format!("invalid config {}", path.display()))?;
+
+        let overrides = file
+            .keys
+            .into_iter()
+            .map(|(action, keys)| match keys {
+                Keys::One(key) => (action, vec![key]),
+                Keys::Many(keys) => (action, keys),
+            })
+            .collect();
+
+        let keymap = Keymap::preset(file.keymap.as_deref().unwrap_or("default"))
+            .with_context(|| format!("invalid keymap in {}", path.display()))?
+            .with_overrides(&overrides)
+            .with_context(|| format!("invalid [keys] in {}", path.display()))?;
+
+        let mut theme = match (base, file.theme) {
+            (Some(theme), _) => theme,
+            (None, Some(name)) => Theme::by_name(&name)
+                .with_context(|| format!("invalid theme in {}", path.display()))?,
+            (None, None) => Theme::default(),
+        };
+        for (name, value) in &file.colors {
+            theme
+                .set_color(name, value)
+                .with_context(|| format!("invalid [colors] in {}", path.display()))?;
+        }
+
+        let list_width = file.list_width.unwrap_or(40);
+        if !(10..=90).contains(&list_width) {
+            bail!(
+                "invalid list_width in {}: {} is not between 10 and 90",
+                path.display(),
+                list_width
+            );
+        }
+
+        let tab_width = file.tab_width.unwrap_or(8);
+        if !(1..=16).contains(&tab_width) {
+            bail!(
+                "invalid tab_width in {}: {} is not between 1 and 16",
+                path.display(),
+                tab_width
+            );
+        }
+
+        let images = match file.images {
+            Some(name) => Graphics::by_name(&name)
+                .with_context(|| format!("invalid images in {}", path.display()))?,
+            None => Graphics::detect(),
+        };
+
+        Ok(Config {
+            keymap,
+            theme,
+            syntax_highlighting: file.syntax_highlighting.unwrap_or(true),
+            list_width,
+            images,
+            tab_width,
+            show_whitespace: file.show_whitespace.unwrap_or(false),
+            diff: DiffSettings {
+                context_lines: file.diff.context.unwrap_or(3),
+                ignore_all_space: file.diff.ignore_all_space,
+                ignore_space_change: file.diff.ignore_space_change,
+                ignore_blank_lines: file.diff.ignore_blank_lines,
+            },
+        })
+    }
+}
+
+/// `$XDG_CONFIG_HOME/helper-git/config.toml`, where `XDG_CONFIG_HOME`
+/// defaults to `~/.config` as the spec says.
+fn config_path() -> Option<PathBuf> {
+    let config_home = match std::env::var_os("XDG_CONFIG_HOME") {
+        // The spec says relative paths are invalid and should be ignored
+        Some(dir) if Path::new(&dir).is_absolute() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_home.join("helper-git/config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::style::Color;
+
+    #[test]
+    fn colors_go_on_top_of_the_command_line_theme() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "theme = \"dark\"\n\n[colors]\naccent = \"21\"\n").unwrap();
+
+        let config = Config::load(Some(&path), Some("light")).unwrap();
+        assert_eq!(config.theme.accent, Color::Indexed(21));
+        assert_eq!(config.theme.addition, Theme::light().addition);
+
+        let config = Config::load(Some(&path), None).unwrap();
+        assert_eq!(config.theme.accent, Color::Indexed(21));
+        assert_eq!(config.theme.addition, Theme::dark().addition);
+    }
+}
@@ -0,0 +1,400 @@
+mod entry;
+
+use crate::help;
+use crate::keymap::{Action, Keymap, Scope};
+use crate::status;
+use crate::theme::Theme;
+use crate::tui::{TICK, Tui};
+use anyhow::{Result, bail};
+use crossterm::event::{self, Event};
+use entry::RepoInfo;
+use git2::Repository;
+use helper_git_core::diff::DiffSettings;
+use helper_git_core::repo::Upstream;
+use helper_git_core::status::StatusFilter;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, HighlightSpacing, List, ListItem, ListState, Paragraph},
+};
+use std::path::{Path, PathBuf};
+
+/// Every repository under a directory, one row each with its branch, how
+/// many files are dirty, how far it is from its upstream and how many
+/// stashes it has. Returns the repository picked to open the status view
+/// in, if any; `selected` is the row to start on, and `message` what to
+/// say first.
+fn dash(
+    tui: &mut Tui,
+    dir: &Path,
+    selected: Option<&Path>,
+    mut message: Option<String>,
+    keymap: &Keymap,
+    theme: &Theme,
+) -> Result<Option<PathBuf>> {
+    let mut repos = entry::scan(dir)?;
+    let mut list_state = ListState::default();
+    list_state.select(Some(
+        selected
+            .and_then(|path| repos.iter().position(|r| r.path == path))
+            .unwrap_or(0),
+    ));
+
+    // ---------- UI State ----------
+    let mut show_help = false;
+    // Rows inside the panel's borders at the last draw, for paging
+    let mut page_height: u16 = 0;
+
+    // ---------- Main Loop ----------
+    loop {
+        let widths = Widths::of(&repos);
+        let items: Vec<ListItem> = repos
+            .iter()
+            .map(|r| ListItem::new(repo_line(r, &widths, theme)))
+            .collect();
+        let dirty = repos.iter().filter(|r| r.dirty > 0).count();
+        let title = format!(
+            " {} ({} repositor{}, {} dirty) ",
+            dir.display(),
+            repos.len(),
+            if repos.len() == 1 { "y" } else { "ies" },
+            dirty
+        );
+        let help_line = match message.clone() {
+            Some(text) => Line::from(text),
+            None => help_line(keymap),
+        };
+
+        tui.draw(|frame| {
+            let area = frame.area();
+
+            // ---------- Reserve bottom line for helper ----------
+            let outer_chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Min(0),    // top: the table
+                    Constraint::Length(1), // bottom: help line
+                ])
+                .split(area);
+
+            let block = Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_style(theme.focus());
+            let inner = block.inner(outer_chunks[0]);
+            frame.render_widget(block, outer_chunks[0]);
+
+            // ---------- Column headings ----------
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(1), Constraint::Min(0)])
+                .split(inner);
+            frame.render_widget(
+                Paragraph::new(header_line(&widths)).style(Style::default().dim()),
+                chunks[0],
+            );
+
+            // ---------- Repositories ----------
+            if repos.is_empty() {
+                frame.render_widget(
+                    Paragraph::new(format!("  No git repositories in {}", dir.display()))
+                        .style(Style::default().dim()),
+                    chunks[1],
+                );
+            } else {
+                let list = List::new(items)
+                    .highlight_style(
+                        Style::default()
+                            .bg(theme.highlight_bg)
+                            .add_modifier(Modifier::BOLD),
+                    )
+                    .highlight_symbol("➜ ")
+                    .highlight_spacing(HighlightSpacing::Always);
+                frame.render_stateful_widget(list, chunks[1], &mut list_state);
+            }
+            page_height = chunks[1].height;
+
+            // ---------- Helper Line ----------
+            let help_paragraph = Paragraph::new(help_line)
+                .alignment(Alignment::Center)
+                .style(Style::default().dim());
+            frame.render_widget(help_paragraph, outer_chunks[1]);
+
+            if show_help {
+                help::render(frame, keymap, theme);
+            }
+        })?;
+
+        // ---------- Input Handling ----------
+        if !event::poll(TICK)? {
+            continue;
+        }
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        message = None;
+
+        if show_help {
+            if matches!(
+                keymap.resolve(&key, Scope::Dash),
+                Some(Action::Help | Action::Quit)
+            ) {
+                show_help = false;
+            }
+            continue;
+        }
+
+        let selected = list_state.selected().and_then(|i| repos.get(i));
+        match keymap.resolve(&key, Scope::Dash) {
+            Some(Action::Quit) => break,
+
+            Some(Action::Help) => show_help = true,
+
+            Some(Action::Refresh) => {
+                let path = selected.map(|r| r.path.clone());
+                repos = entry::scan(dir)?;
+                let row = path
+                    .and_then(|path| repos.iter().position(|r| r.path == path))
+                    .or(list_state.selected())
+                    .unwrap_or(0);
+                list_state.select(Some(row.min(repos.len().saturating_sub(1))));
+            }
+
+            Some(Action::OpenRepo) => match selected {
+                Some(RepoInfo {
+                    name,
+                    problem: Some(problem),
+                    ..
+                }) => message = Some(format!("Error: can't open {}: {}", name, problem)),
+                Some(repo) => return Ok(Some(repo.path.clone())),
+                None => {}
+            },
+
+            Some(Action::NavigateUp) => {
+                if let Some(i) = list_state.selected()
+                    && i > 0
+                {
+                    list_state.select(Some(i - 1));
+                }
+            }
+
+            Some(Action::NavigateDown) => {
+                if let Some(i) = list_state.selected()
+                    && i < repos.len().saturating_sub(1)
+                {
+                    list_state.select(Some(i + 1));
+                }
+            }
+
+            Some(action @ (Action::PageUp | Action::PageDown)) => {
+                let page = page_height.saturating_sub(1).max(1) as usize;
+                if let Some(i) = list_state.selected() {
+                    let last = repos.len().saturating_sub(1);
+                    list_state.select(Some(if action == Action::PageDown {
+                        (i + page).min(last)
+                    } else {
+                        i.saturating_sub(page)
+                    }));
+                }
+            }
+
+            Some(Action::Top) => list_state.select(Some(0)),
+
+            Some(Action::Bottom) => list_state.select(Some(repos.len().saturating_sub(1))),
+
+            _ => {}
+        }
+    }
+
+    Ok(None)
+}
+
+/// `hg dash [dir]`: the dashboard for `dir`, or the current directory.
+/// A repository picked with Enter gets the status view, and quitting that
+/// comes back to the dashboard, scanned again, on the same row.
+pub fn run(
+    dir: Option<&Path>,
+    settings: DiffSettings,
+    list_width: u16,
+    keymap: &Keymap,
+    theme: &Theme,
+) -> Result<()> {
+    let dir = match dir {
+        Some(dir) => dir.to_path_buf(),
+        None => std::env::current_dir()?,
+    };
+    // Before the screen is taken over, so the error is left to read
+    if !dir.is_dir() {
+        bail!("cannot read {}: not a directory", dir.display());
+    }
+    let (mut selected, mut message) = (None, None);
+    loop {
+        let picked = {
+            let mut tui = Tui::new()?;
+            dash(&mut tui, &dir, selected.as_deref(), message, keymap, theme)?
+        };
+        let Some(path) = picked else {
+            return Ok(());
+        };
+
+        // Whatever goes wrong in there is shown back on the dashboard
+        let result = Repository::open(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|repo| {
+                let theme = Theme {
+                    whitespace: theme.whitespace.for_repo(&repo),
+                    ..theme.clone()
+                };
+                status::status(
+                    &repo,
+                    settings,
+                    list_width,
+                    keymap,
+                    &theme,
+                    StatusFilter::default(),
+                    false,
+                )
+            });
+        message = result.err().map(|e| format!("Error: {}", e));
+        selected = Some(path);
+    }
+}
+
+/// How wide the name and branch columns are, to line the rows up.
+struct Widths {
+    name: usize,
+    branch: usize,
+    upstream: usize,
+}
+
+impl Widths {
+    fn of(repos: &[RepoInfo]) -> Self {
+        let widest = |column: &dyn Fn(&RepoInfo) -> String, heading: &str| {
+            repos
+                .iter()
+                .map(|r| column(r).chars().count())
+                .chain([heading.chars().count()])
+                .max()
+                .unwrap_or(0)
+        };
+        Widths {
+            name: widest(&|r| r.name.clone(), "Repository"),
+            branch: widest(&branch, "Branch"),
+            upstream: widest(&|r| upstream(r).0, "Upstream"),
+        }
+    }
+}
+
+fn header_line(widths: &Widths) -> Line<'static> {
+    // Past the list's highlight symbol, as the rows are
+    Line::from(format!(
+        "  {:<name$}  {:<branch$}  {:<upstream$}  {:<7}  Stashes",
+        "Repository",
+        "Branch",
+        "Upstream",
+        "Changes",
+        name = widths.name,
+        branch = widths.branch,
+        upstream = widths.upstream,
+    ))
+}
+
+/// `api  main  ↑2 ↓1  3 dirty  1`, or the problem in place of the last three
+fn repo_line(repo: &RepoInfo, widths: &Widths, theme: &Theme) -> Line<'static> {
+    let mut spans = vec![
+        Span::styled(
+            format!("{:<width$}", repo.name, width = widths.name),
+            Style::default().fg(theme.accent),
+        ),
+        Span::raw("  "),
+        Span::styled(
+            format!("{:<width$}", branch(repo), width = widths.branch),
+            Style::default().fg(theme.staged),
+        ),
+        Span::raw("  "),
+    ];
+
+    if let Some(problem) = &repo.problem {
+        spans.push(Span::styled(
+            problem.clone(),
+            Style::default().fg(theme.warning),
+        ));
+        return Line::from(spans);
+    }
+
+    let (text, behind) = upstream(repo);
+    spans.push(Span::styled(
+        format!("{:<width$}", text, width = widths.upstream),
+        if behind {
+            Style::default().fg(theme.warning)
+        } else {
+            Style::default().dim()
+        },
+    ));
+    spans.push(Span::raw("  "));
+    spans.push(match repo.dirty {
+        0 => Span::styled(format!("{:<7}", "clean"), Style::default().dim()),
+        n => Span::styled(
+            format!("{:<7}", format!("{} dirty", n)),
+            Style::default().fg(theme.modified),
+        ),
+    });
+    spans.push(Span::raw("  "));
+    if let Some(head) = &repo.head
+        && head.stashes > 0
+    {
+        spans.push(Span::raw(head.stashes.to_string()));
+    }
+    Line::from(spans)
+}
+
+/// The branch, `detached at abc1234`, or `main (no commits)`.
+fn branch(repo: &RepoInfo) -> String {
+    match &repo.head {
+        Some(head) if head.detached => format!("detached at {}", head.head),
+        Some(head) if head.unborn => format!("{} (no commits)", head.head),
+        Some(head) => head.head.clone(),
+        None => String::new(),
+    }
+}
+
+/// `↑2 ↓1`, `up to date`, `no upstream` or `origin/x gone`, and whether
+/// there's anything to pull.
+fn upstream(repo: &RepoInfo) -> (String, bool) {
+    match repo.head.as_ref().and_then(|head| head.upstream.as_ref()) {
+        Some(Upstream::Tracking {
+            ahead: 0,
+            behind: 0,
+            ..
+        }) => (String::from("up to date"), false),
+        Some(Upstream::Tracking { ahead, behind, .. }) => {
+            (format!("↑{} ↓{}", ahead, behind), *behind > 0)
+        }
+        Some(Upstream::Gone(name)) => (format!("{} gone", name), false),
+        Some(Upstream::Unset) => (String::from("no upstream"), false),
+        None => (String::new(), false),
+    }
+}
+
+fn help_line(keymap: &Keymap) -> Line<'static> {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let mut spans = vec![Span::raw(" ")];
+
+    for (i, (action, label)) in [
+        (Action::OpenRepo, "open"),
+        (Action::Refresh, "rescan"),
+        (Action::Help, "help"),
+        (Action::Quit, "quit"),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        if i > 0 {
+            spans.push(Span::raw(" • "));
+        }
+        spans.push(Span::styled(keymap.label(action), bold));
+        spans.push(Span::raw(format!(" {}", label)));
+    }
+
+    Line::from(spans)
+}
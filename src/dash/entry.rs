@@ -0,0 +1,93 @@
+use anyhow::{Context, Result};
+use git2::Repository;
+use helper_git_core::repo::RepoSummary;
+use helper_git_core::status::{StatusFilter, collect_status};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How many directories deep repositories are looked for below the one
+/// scanned: `~/src/github/owner/project` from `~/src`.
+const DEPTH: usize = 3;
+
+/// One row of the dashboard.
+pub struct RepoInfo {
+    pub path: PathBuf,
+    /// `path` as it's shown, relative to the directory scanned
+    pub name: String,
+    /// Where HEAD is, how far from its upstream and how many stashes; `None`
+    /// when the repository couldn't be read
+    pub head: Option<RepoSummary>,
+    /// How many files `git status` lists
+    pub dirty: usize,
+    /// Why it can't be opened: a bare repository, or the error reading it
+    pub problem: Option<String>,
+}
+
+/// Every repository in `dir`, the directory itself included, by path. A
+/// repository's own directories aren't looked in, so its submodules and
+/// vendored checkouts stay in its status view.
+pub fn scan(dir: &Path) -> Result<Vec<RepoInfo>> {
+    let mut found = Vec::new();
+    if is_repository(dir) {
+        found.push(dir.to_path_buf());
+    } else {
+        walk(dir, DEPTH, &mut found).with_context(|| format!("cannot read {}", dir.display()))?;
+    }
+    found.sort();
+    Ok(found.iter().map(|path| load(dir, path)).collect())
+}
+
+fn walk(dir: &Path, depth: usize, found: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        // Hidden directories are caches and tool state, not checkouts
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if hidden || !entry.file_type().is_ok_and(|t| t.is_dir()) {
+            continue;
+        }
+        if is_repository(&path) {
+            found.push(path);
+        } else if depth > 1 {
+            // A directory that can't be read is left out, not the whole scan
+            let _ = walk(&path, depth - 1, found);
+        }
+    }
+    Ok(())
+}
+
+/// A worktree with its `.git`, a directory or the file a linked worktree
+/// has, or a bare repository's git directory.
+fn is_repository(dir: &Path) -> bool {
+    dir.join(".git").exists() || (dir.join("HEAD").is_file() && dir.join("objects").is_dir())
+}
+
+fn load(root: &Path, path: &Path) -> RepoInfo {
+    let name = match path.strip_prefix(root) {
+        Ok(relative) if relative.as_os_str().is_empty() => String::from("."),
+        Ok(relative) => relative.to_string_lossy().into_owned(),
+        Err(_) => path.to_string_lossy().into_owned(),
+    };
+    let mut info = RepoInfo {
+        path: path.to_path_buf(),
+        name,
+        head: None,
+        dirty: 0,
+        problem: None,
+    };
+
+    match Repository::open(path) {
+        Ok(repo) => {
+            info.head = Some(RepoSummary::load(&repo));
+            if repo.is_bare() {
+                info.problem = Some(String::from("bare, no worktree"));
+            } else {
+                match collect_status(&repo, false, &StatusFilter::default()) {
+                    Ok(files) => info.dirty = files.len(),
+                    Err(e) => info.problem = Some(e.to_string()),
+                }
+            }
+        }
+        Err(e) => info.problem = Some(e.message().to_string()),
+    }
+    info
+}
@@ -0,0 +1,105 @@
+use crate::keymap::{Action, Keymap, Scope};
+use crate::theme::Theme;
+use ratatui::{
+    layout::Flex,
+    prelude::*,
+    widgets::{Block, Borders, Clear, Padding, Paragraph},
+};
+
+/// Centered popup listing every action with its current keys, grouped by the
+/// panel it applies to. Groups flow into further columns when they don't fit
+/// the terminal's height.
+pub fn render(frame: &mut Frame, keymap: &Keymap, theme: &Theme) {
+    let key_width = Action::ALL
+        .iter()
+        .map(|(action, ..)| keymap.label(*action).chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut groups: Vec<Vec<Line>> = Vec::new();
+    for (title, scope) in [
+        ("Global", Scope::Global),
+        ("Status list", Scope::List),
+        ("Diff", Scope::Diff),
+        ("Log and history", Scope::Log),
+        ("Branches", Scope::Branches),
+        ("Tags", Scope::Tags),
+        ("Remotes", Scope::Remotes),
+        ("Worktrees", Scope::Worktree),
+        ("Reflog", Scope::Reflog),
+        ("Bisect", Scope::Bisect),
+        ("Apply", Scope::Apply),
+        ("Stash", Scope::Stash),
+        ("Clean", Scope::Clean),
+        ("Rebase and cherry-pick", Scope::Rebase),
+        ("Conflicts", Scope::Resolve),
+        ("Blame", Scope::Blame),
+        ("Dashboard", Scope::Dash),
+    ] {
+        let mut lines = vec![Line::styled(
+            title,
+            Style::default()
+                .fg(theme.accent)
+                .add_modifier(Modifier::BOLD),
+        )];
+
+        for (action, ..) in Action::ALL.iter().filter(|(_, _, s, ..)| *s == scope) {
+            lines.push(Line::from(vec![
+                Span::styled(
+                    format!("  {:<width$}", keymap.label(*action), width = key_width),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::raw("  "),
+                Span::raw(action.description()),
+            ]));
+        }
+        groups.push(lines);
+    }
+
+    // Inside the borders; a group taller than that is cut off at the bottom
+    let room = frame.area().height.saturating_sub(2) as usize;
+    let mut columns: Vec<Vec<Line>> = vec![Vec::new()];
+    for group in groups {
+        let column = columns.last_mut().expect("starts with one column");
+        let gap = usize::from(!column.is_empty());
+        if !column.is_empty() && column.len() + gap + group.len() > room {
+            columns.push(group);
+        } else {
+            if gap > 0 {
+                column.push(Line::from(""));
+            }
+            column.extend(group);
+        }
+    }
+
+    let widths: Vec<u16> = columns
+        .iter()
+        .map(|lines| lines.iter().map(Line::width).max().unwrap_or(0) as u16 + 2)
+        .collect();
+    let width = widths.iter().sum::<u16>() + 2;
+    let height = columns.iter().map(Vec::len).max().unwrap_or(0) as u16 + 2;
+
+    let [area] = Layout::horizontal([Constraint::Length(width)])
+        .flex(Flex::Center)
+        .areas(frame.area());
+    let [area] = Layout::vertical([Constraint::Length(height)])
+        .flex(Flex::Center)
+        .areas(area);
+
+    let block = Block::default()
+        .title(format!(" Keys · {} to close ", keymap.label(Action::Help)))
+        .borders(Borders::ALL)
+        .border_style(theme.focus());
+
+    frame.render_widget(Clear, area);
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let areas = Layout::horizontal(widths.iter().map(|&w| Constraint::Length(w))).split(inner);
+    for (lines, area) in columns.into_iter().zip(areas.iter()) {
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::default().padding(Padding::horizontal(1))),
+            *area,
+        );
+    }
+}
@@ -0,0 +1,247 @@
+use crate::clipboard::Clipboard;
+use crate::help;
+use crate::keymap::{Action, Keymap, Scope};
+use crate::log;
+use crate::panes::{self, Panes};
+use crate::status::diff;
+use crate::theme::Theme;
+use crate::tui::{TICK, Tui};
+use anyhow::Result;
+use crossterm::event::{self, Event};
+use git2::{Delta, DiffFindOptions, DiffOptions, Oid, Repository, Sort, Tree};
+use ratatui::{prelude::*, widgets::ListItem};
+use std::path::{Path, PathBuf};
+
+/// A commit that changed the file, like one entry of `git log --follow`.
+struct Revision {
+    id: Oid,
+    /// The file's path in this commit
+    path: PathBuf,
+    /// The path in the parent, when this commit renamed the file
+    renamed_from: Option<PathBuf>,
+}
+
+/// Full-screen history of `path`, newest first, with each commit's diff of
+/// just that file. Quitting returns to the caller's view.
+pub fn history(
+    tui: &mut Tui,
+    repo: &Repository,
+    path: &Path,
+    list_width: u16,
+    keymap: &Keymap,
+    theme: &Theme,
+) -> Result<()> {
+    let revisions = load(repo, path)?;
+
+    let mut items: Vec<ListItem> = Vec::new();
+    for revision in &revisions {
+        items.push(ListItem::new(log::commit_line(
+            &repo.find_commit(revision.id)?,
+            theme,
+        )));
+    }
+    // Built once; every frame only borrows it
+    let empty = format!("No commits touch {}", path.display());
+    let list = panes::list(items, empty, theme);
+    let title = format!(" History · {} ", path.display());
+
+    let mut panes: Panes<Oid> = Panes::new(list_width);
+
+    // ---------- UI State ----------
+    let mut show_help = false;
+    let mut message: Option<String> = None;
+    let mut clipboard = Clipboard::default();
+
+    // ---------- Main Loop ----------
+    loop {
+        let selected = panes.selected().and_then(|i| revisions.get(i));
+
+        // ---------- Preview ----------
+        panes.preview(
+            selected,
+            |r| r.id,
+            |revision| {
+                show(repo, revision, theme)
+                    .unwrap_or_else(|e| vec![Line::from(format!("Error: {}", e))])
+            },
+        );
+
+        // ---------- Helper line ----------
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let help_line = match &message {
+            Some(text) => Line::from(text.as_str()),
+            None => Line::from(vec![
+                Span::raw(" "),
+                Span::styled(
+                    format!(
+                        "{} {}",
+                        keymap.label(Action::NavigateUp),
+                        keymap.label(Action::NavigateDown)
+                    ),
+                    bold,
+                ),
+                Span::raw(" navigate"),
+                Span::raw(" • "),
+                Span::styled(keymap.label(Action::CopyHash), bold),
+                Span::raw(" copy hash"),
+                Span::raw(" • "),
+                Span::styled(keymap.label(Action::SwitchFocus), bold),
+                Span::raw(" switch focus"),
+                Span::raw(" • "),
+                Span::styled(keymap.label(Action::Quit), bold),
+                Span::raw(" back"),
+            ]),
+        };
+
+        tui.draw(|frame| {
+            let (main, help) = panes::split(frame.area());
+            panes.render(frame, main, &list, &title, " Changes ", theme);
+            panes::render_help(frame, help, help_line, false);
+
+            if show_help {
+                help::render(frame, keymap, theme);
+            }
+        })?;
+
+        // ---------- Input Handling ----------
+        if !event::poll(TICK)? {
+            continue;
+        }
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        // A message stands until the next key
+        message = None;
+
+        if show_help {
+            if matches!(
+                keymap.resolve(&key, Scope::Global),
+                Some(Action::Help | Action::Quit)
+            ) {
+                show_help = false;
+            }
+            continue;
+        }
+
+        match keymap.resolve(&key, Scope::Log) {
+            Some(Action::Quit) => break,
+
+            Some(Action::CopyHash) => {
+                if let Some(revision) = panes.selected().and_then(|i| revisions.get(i)) {
+                    message = Some(match clipboard.copy(&revision.id.to_string()) {
+                        Ok(()) => format!("Copied: {}", revision.id),
+                        Err(e) => format!("Error: {}", e),
+                    });
+                }
+            }
+
+            Some(Action::Help) => show_help = true,
+
+            Some(action) => {
+                panes.navigate(action, revisions.len());
+            }
+
+            None => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// The commits reachable from HEAD that changed `path`, newest first. A
+/// commit that renamed the file switches the walk over to the old name.
+fn load(repo: &Repository, path: &Path) -> Result<Vec<Revision>> {
+    let mut revisions = Vec::new();
+
+    // An unborn HEAD (fresh `git init`) has nothing to walk
+    if repo.head().is_err() {
+        return Ok(revisions);
+    }
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+    revwalk.push_head()?;
+
+    let mut path = path.to_path_buf();
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+        let tree = commit.tree()?;
+        let ours = entry_id(&tree, &path);
+
+        let parents = commit
+            .parents()
+            .map(|p| p.tree())
+            .collect::<Result<Vec<_>, _>>()?;
+        // Like `git log`, a merge that kept one side's version changed nothing
+        if parents.iter().any(|parent| entry_id(parent, &path) == ours)
+            || (parents.is_empty() && ours.is_none())
+        {
+            continue;
+        }
+
+        let renamed_from = match parents.first() {
+            Some(parent) if ours.is_some() && entry_id(parent, &path).is_none() => {
+                rename_source(repo, parent, &tree, &path)?
+            }
+            _ => None,
+        };
+
+        revisions.push(Revision {
+            id: commit.id(),
+            path: path.clone(),
+            renamed_from: renamed_from.clone(),
+        });
+
+        if let Some(old) = renamed_from {
+            path = old;
+        }
+    }
+
+    Ok(revisions)
+}
+
+fn entry_id(tree: &Tree, path: &Path) -> Option<Oid> {
+    tree.get_path(path).ok().map(|entry| entry.id())
+}
+
+/// The path `path` had in `old`, if the change to `new` renamed it.
+fn rename_source(
+    repo: &Repository,
+    old: &Tree,
+    new: &Tree,
+    path: &Path,
+) -> Result<Option<PathBuf>> {
+    let mut diff = repo.diff_tree_to_tree(Some(old), Some(new), None)?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+
+    Ok(diff
+        .deltas()
+        .filter(|delta| delta.status() == Delta::Renamed)
+        .find(|delta| delta.new_file().path() == Some(path))
+        .and_then(|delta| delta.old_file().path().map(Path::to_path_buf)))
+}
+
+/// Header and message of `revision`, then its diff of the file alone.
+fn show(repo: &Repository, revision: &Revision, theme: &Theme) -> Result<Vec<Line<'static>>> {
+    let commit = repo.find_commit(revision.id)?;
+    let mut lines = log::commit_header(&commit, theme);
+
+    let mut opts = DiffOptions::new();
+    opts.disable_pathspec_match(true).pathspec(&revision.path);
+    if let Some(old) = &revision.renamed_from {
+        opts.pathspec(old);
+    }
+
+    let parent_tree = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree()?),
+        Err(_) => None,
+    };
+    let mut diff =
+        repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), Some(&mut opts))?;
+    // Pairs the old and new path up so a rename shows as one
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+
+    lines.extend(diff::render_diff(&diff, theme)?.lines);
+    Ok(lines)
+}
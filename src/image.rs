@@ -0,0 +1,499 @@
+use anyhow::{Context, Result, bail};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use git2::Repository;
+use helper_git_core::diff::file_contents;
+use helper_git_core::lfs;
+use helper_git_core::status::{FileEntry, Section};
+use ratatui::layout::Rect;
+use std::env;
+use std::io::Cursor;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Images bigger than this are left as their binary diff.
+const SIZE_LIMIT: u64 = 16 << 20;
+
+/// Pixels a PNG may decode to for sixel.
+const PIXEL_LIMIT: usize = 16 << 20;
+
+/// The escape sequence kitty takes every image off the screen with.
+pub const KITTY_CLEAR: &str = "\x1b_Ga=d,d=A,q=2\x1b\\";
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// The ways a terminal can be asked to draw an image.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Graphics {
+    /// kitty's graphics protocol, which Ghostty speaks too; PNGs only
+    Kitty,
+    /// iTerm2's inline images, which WezTerm takes as well; any format the
+    /// terminal can read
+    Iterm,
+    /// Sixel, as foot, mlterm and a configured xterm draw it; PNGs only,
+    /// decoded here
+    Sixel,
+}
+
+impl Graphics {
+    pub const NAMES: &[&str] = &["auto", "kitty", "iterm2", "sixel", "off"];
+
+    /// `auto` for whatever the terminal looks like it takes, `off` for none.
+    pub fn by_name(name: &str) -> Result<Option<Self>> {
+        match name {
+            "auto" => Ok(Self::detect()),
+            "kitty" => Ok(Some(Graphics::Kitty)),
+            "iterm2" => Ok(Some(Graphics::Iterm)),
+            "sixel" => Ok(Some(Graphics::Sixel)),
+            "off" => Ok(None),
+            _ => bail!(
+                "unknown image protocol \"{}\" (available: {})",
+                name,
+                Self::NAMES.join(", ")
+            ),
+        }
+    }
+
+    /// What the terminal takes, going by what it sets in the environment.
+    /// tmux passes no image through as it is, so there it's none.
+    pub fn detect() -> Option<Self> {
+        if env::var_os("TMUX").is_some() {
+            return None;
+        }
+        let term = env::var("TERM").unwrap_or_default();
+        let program = env::var("TERM_PROGRAM").unwrap_or_default();
+        if env::var_os("KITTY_WINDOW_ID").is_some()
+            || term == "xterm-kitty"
+            || term == "xterm-ghostty"
+        {
+            Some(Graphics::Kitty)
+        } else if program == "iTerm.app" || program == "WezTerm" {
+            Some(Graphics::Iterm)
+        } else if term.starts_with("foot") || term.starts_with("mlterm") || term.contains("sixel") {
+            Some(Graphics::Sixel)
+        } else {
+            None
+        }
+    }
+
+    fn draws(self, format: Format) -> bool {
+        self == Graphics::Iterm || format == Format::Png
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Png,
+    Jpeg,
+    Gif,
+    Webp,
+}
+
+/// An image as its file has it, ready to be drawn.
+pub struct Picture {
+    /// Tells pictures apart, so one is only sent again once it has moved
+    pub id: u64,
+    pub width: u32,
+    pub height: u32,
+    data: Vec<u8>,
+    /// RGBA pixels, for sixel, which draws pixels rather than files
+    pixels: Option<Vec<u8>>,
+}
+
+impl Picture {
+    fn load(data: Vec<u8>, graphics: Graphics) -> Option<Self> {
+        let (format, width, height) = dimensions(&data)?;
+        if !graphics.draws(format) || width == 0 || height == 0 {
+            return None;
+        }
+        let pixels = match graphics {
+            Graphics::Sixel => Some(decode_png(&data).ok()?),
+            Graphics::Kitty | Graphics::Iterm => None,
+        };
+        // The header read for the size has to agree with what was decoded
+        if pixels
+            .as_ref()
+            .is_some_and(|p| p.len() != width as usize * height as usize * 4)
+        {
+            return None;
+        }
+        Some(Picture {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            width,
+            height,
+            data,
+            pixels,
+        })
+    }
+}
+
+/// A changed image as it was and as it is; a new file has no old side, a
+/// deleted one no new side.
+pub struct Preview {
+    pub old: Option<Picture>,
+    pub new: Option<Picture>,
+}
+
+/// `entry`'s old and new image when it's one `graphics` draws, from the
+/// same sides its diff compares. A Git LFS pointer stands for its object,
+/// when that has been downloaded.
+pub fn preview(repo: &Repository, entry: &FileEntry, graphics: Graphics) -> Option<Preview> {
+    if entry.section == Section::Conflicted || entry.is_directory() {
+        return None;
+    }
+    let [old, new] = file_contents(repo, entry, SIZE_LIMIT).ok()?;
+    let load = |data: Vec<u8>| Picture::load(lfs::resolve(repo, data, SIZE_LIMIT), graphics);
+    let (old, new) = (old.and_then(load), new.and_then(load));
+    (old.is_some() || new.is_some()).then_some(Preview { old, new })
+}
+
+/// The format and size in pixels of an image, from its header.
+fn dimensions(data: &[u8]) -> Option<(Format, u32, u32)> {
+    let bytes = |i: usize, n: usize| data.get(i..i + n);
+    let be16 = |i| bytes(i, 2).map(|b| u16::from_be_bytes([b[0], b[1]]) as u32);
+    let be32 = |i| bytes(i, 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]));
+    let le16 = |i| bytes(i, 2).map(|b| u16::from_le_bytes([b[0], b[1]]) as u32);
+    let le24 = |i| bytes(i, 3).map(|b| u32::from_le_bytes([b[0], b[1], b[2], 0]));
+    let le32 = |i| bytes(i, 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some((Format::Png, be32(16)?, be32(20)?));
+    }
+    if data.starts_with(b"GIF8") {
+        return Some((Format::Gif, le16(6)?, le16(8)?));
+    }
+    if data.starts_with(b"RIFF") && bytes(8, 4) == Some(b"WEBP") {
+        return match bytes(12, 4)? {
+            b"VP8X" => Some((Format::Webp, le24(24)? + 1, le24(27)? + 1)),
+            b"VP8 " => Some((Format::Webp, le16(26)? & 0x3fff, le16(28)? & 0x3fff)),
+            b"VP8L" => {
+                let bits = le32(21)?;
+                Some((
+                    Format::Webp,
+                    (bits & 0x3fff) + 1,
+                    ((bits >> 14) & 0x3fff) + 1,
+                ))
+            }
+            _ => None,
+        };
+    }
+    if data.starts_with(b"\xFF\xD8") {
+        // Segment by segment up to the start of frame, which has the size
+        let mut i = 2;
+        while *data.get(i)? == 0xFF {
+            let marker = *data.get(i + 1)?;
+            if (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker) {
+                return Some((Format::Jpeg, be16(i + 7)?, be16(i + 5)?));
+            }
+            i += 2 + be16(i + 2)? as usize;
+        }
+    }
+    None
+}
+
+/// Where `picture` goes in `area`: as big as fits without stretching it or
+/// blowing it up past its own size, centred across. `cell` is the size of a
+/// cell in pixels.
+pub fn fit(picture: &Picture, area: Rect, cell: (u32, u32)) -> Rect {
+    let (cell_width, cell_height) = (f64::from(cell.0), f64::from(cell.1));
+    let (width, height) = (f64::from(picture.width), f64::from(picture.height));
+    let scale = (f64::from(area.width) * cell_width / width)
+        .min(f64::from(area.height) * cell_height / height)
+        .min(1.0);
+    let columns = ((width * scale / cell_width).ceil() as u16).clamp(1, area.width.max(1));
+    let rows = ((height * scale / cell_height).ceil() as u16).clamp(1, area.height.max(1));
+    Rect {
+        x: area.x + (area.width.saturating_sub(columns)) / 2,
+        y: area.y,
+        width: columns,
+        height: rows,
+    }
+}
+
+/// The size of a cell in pixels, or a usual one when the terminal won't say.
+pub fn cell_size() -> (u32, u32) {
+    match crossterm::terminal::window_size() {
+        Ok(size) if size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0 => (
+            (u32::from(size.width) / u32::from(size.columns)).max(1),
+            (u32::from(size.height) / u32::from(size.rows)).max(1),
+        ),
+        _ => (8, 16),
+    }
+}
+
+/// What draws `picture` over `area`, sent with the cursor at its top left.
+pub fn sequence(picture: &Picture, graphics: Graphics, area: Rect, cell: (u32, u32)) -> String {
+    match graphics {
+        Graphics::Kitty => {
+            // Sent in chunks, as the protocol wants; kitty is asked not to reply
+            let encoded = STANDARD.encode(&picture.data);
+            let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(4096).collect();
+            let mut out = String::new();
+            for (i, chunk) in chunks.iter().enumerate() {
+                let more = u8::from(i + 1 < chunks.len());
+                if i == 0 {
+                    out.push_str(&format!(
+                        "\x1b_Ga=T,f=100,t=d,q=2,C=1,c={},r={},m={};",
+                        area.width, area.height, more
+                    ));
+                } else {
+                    out.push_str(&format!("\x1b_Gm={};", more));
+                }
+                out.push_str(&String::from_utf8_lossy(chunk));
+                out.push_str("\x1b\\");
+            }
+            out
+        }
+        Graphics::Iterm => format!(
+            "\x1b]1337;File=inline=1;size={};width={};height={};preserveAspectRatio=1;doNotMoveCursor=1:{}\x07",
+            picture.data.len(),
+            area.width,
+            area.height,
+            STANDARD.encode(&picture.data)
+        ),
+        Graphics::Sixel => match &picture.pixels {
+            Some(pixels) => sixel(
+                pixels,
+                (picture.width, picture.height),
+                (
+                    (u32::from(area.width) * cell.0).min(picture.width),
+                    (u32::from(area.height) * cell.1).min(picture.height),
+                ),
+            ),
+            None => String::new(),
+        },
+    }
+}
+
+/// RGBA `pixels` of `size` as sixels, scaled to `to` and in the colors of
+/// the 6×6×6 cube. Mostly transparent pixels are left out.
+fn sixel(pixels: &[u8], size: (u32, u32), to: (u32, u32)) -> String {
+    let (width, height) = (to.0.max(1) as usize, to.1.max(1) as usize);
+    let colors: Vec<Option<u8>> = (0..width * height)
+        .map(|n| {
+            let x = (n % width) * size.0 as usize / width;
+            let y = (n / width) * size.1 as usize / height;
+            let p = &pixels[(y * size.0 as usize + x) * 4..][..4];
+            let level = |c: u8| (u16::from(c) * 5 + 127) / 255;
+            (p[3] >= 128).then(|| (level(p[0]) * 36 + level(p[1]) * 6 + level(p[2])) as u8)
+        })
+        .collect();
+
+    // Transparent where nothing is drawn, one pixel per pixel
+    let mut out = format!("\x1bP0;1;0q\"1;1;{};{}", width, height);
+    for i in 0..216u16 {
+        let percent = |level: u16| level * 20;
+        out.push_str(&format!(
+            "#{};2;{};{};{}",
+            i,
+            percent(i / 36),
+            percent(i / 6 % 6),
+            percent(i % 6)
+        ));
+    }
+    // Six rows at a time, one pass per color in them
+    for band in (0..height).step_by(6) {
+        let rows = band..(band + 6).min(height);
+        let mut used = [false; 216];
+        for y in rows.clone() {
+            for color in colors[y * width..(y + 1) * width].iter().flatten() {
+                used[*color as usize] = true;
+            }
+        }
+        let mut first = true;
+        for color in (0..216).filter(|&c| used[c]) {
+            if !first {
+                out.push('$');
+            }
+            first = false;
+            out.push_str(&format!("#{}", color));
+
+            let mut run: Option<(char, usize)> = None;
+            for x in 0..width {
+                let bits = rows.clone().fold(0u8, |bits, y| {
+                    if colors[y * width + x] == Some(color as u8) {
+                        bits | 1 << (y - band)
+                    } else {
+                        bits
+                    }
+                });
+                let c = char::from(63 + bits);
+                run = match run {
+                    Some((last, n)) if last == c => Some((c, n + 1)),
+                    Some((last, n)) => {
+                        push_run(&mut out, last, n);
+                        Some((c, 1))
+                    }
+                    None => Some((c, 1)),
+                };
+            }
+            if let Some((last, n)) = run {
+                push_run(&mut out, last, n);
+            }
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+    out
+}
+
+fn push_run(out: &mut String, c: char, n: usize) {
+    if n > 3 {
+        out.push_str(&format!("!{}{}", n, c));
+    } else {
+        out.extend(std::iter::repeat_n(c, n));
+    }
+}
+
+/// A PNG's pixels as RGBA, eight bits a channel whatever the file has. A
+/// broken one is an error, as is one bigger than [`PIXEL_LIMIT`], which is
+/// checked before anything is allocated for it.
+fn decode_png(data: &[u8]) -> Result<Vec<u8>> {
+    // Room for the pixels as decoded, and the decoder's own buffers
+    let limits = png::Limits {
+        bytes: PIXEL_LIMIT * 8,
+    };
+    let mut decoder = png::Decoder::new_with_limits(Cursor::new(data), limits);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let (width, height) = reader.info().size();
+    if width as usize * height as usize > PIXEL_LIMIT {
+        bail!("PNG too big to preview");
+    }
+    let mut buffer = vec![
+        0;
+        reader
+            .output_buffer_size()
+            .context("PNG too big to preview")?
+    ];
+    let frame = reader.next_frame(&mut buffer)?;
+    if (frame.width, frame.height) != (width, height) {
+        bail!("PNG frame smaller than the image");
+    }
+    buffer.truncate(frame.buffer_size());
+
+    Ok(match frame.color_type {
+        png::ColorType::Rgba => buffer,
+        png::ColorType::Rgb => buffer
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buffer
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => buffer.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+        // Expanded to RGB by the transformations
+        png::ColorType::Indexed => bail!("PNG palette left unexpanded"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(
+        width: u32,
+        height: u32,
+        color: png::ColorType,
+        depth: png::BitDepth,
+        data: &[u8],
+    ) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, width, height);
+        encoder.set_color(color);
+        encoder.set_depth(depth);
+        if color == png::ColorType::Indexed {
+            encoder.set_palette(vec![255, 0, 0, 0, 0, 255]);
+            encoder.set_trns(vec![255, 0]);
+        }
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(data).unwrap();
+        writer.finish().unwrap();
+        out
+    }
+
+    #[test]
+    fn every_color_type_decodes_to_rgba() {
+        use png::{BitDepth, ColorType};
+        let cases: [(ColorType, BitDepth, &[u8], [u8; 8]); 6] = [
+            (
+                ColorType::Grayscale,
+                BitDepth::Eight,
+                &[0, 255],
+                [0, 0, 0, 255, 255, 255, 255, 255],
+            ),
+            (
+                ColorType::Grayscale,
+                BitDepth::One,
+                &[0b0100_0000],
+                [0, 0, 0, 255, 255, 255, 255, 255],
+            ),
+            (
+                ColorType::Grayscale,
+                BitDepth::Sixteen,
+                &[0, 0, 255, 255],
+                [0, 0, 0, 255, 255, 255, 255, 255],
+            ),
+            (
+                ColorType::GrayscaleAlpha,
+                BitDepth::Eight,
+                &[9, 0, 9, 255],
+                [9, 9, 9, 0, 9, 9, 9, 255],
+            ),
+            (
+                ColorType::Rgb,
+                BitDepth::Eight,
+                &[1, 2, 3, 4, 5, 6],
+                [1, 2, 3, 255, 4, 5, 6, 255],
+            ),
+            (
+                ColorType::Indexed,
+                BitDepth::Two,
+                &[0b0001_0000],
+                [255, 0, 0, 255, 0, 0, 255, 0],
+            ),
+        ];
+        for (color, depth, data, rgba) in cases {
+            let pixels = decode_png(&encode(2, 1, color, depth, data)).unwrap();
+            assert_eq!(pixels, rgba, "{:?} at {:?}", color, depth);
+        }
+    }
+
+    #[test]
+    fn broken_pngs_are_errors() {
+        let png = encode(2, 2, png::ColorType::Rgba, png::BitDepth::Eight, &[7; 16]);
+        assert_eq!(decode_png(&png).unwrap(), [7; 16]);
+        for end in [0, 8, 20, png.len() - 20] {
+            assert!(decode_png(&png[..end]).is_err());
+        }
+        let mut corrupt = png.clone();
+        let last = corrupt.len() - 16;
+        corrupt[last] ^= 0xff;
+        assert!(decode_png(&corrupt).is_err());
+    }
+
+    #[test]
+    fn huge_pngs_are_refused_before_they_are_decoded() {
+        // A header that says 65536 × 65536, with nothing after it
+        let mut png = encode(1, 1, png::ColorType::Rgba, png::BitDepth::Eight, &[0; 4]);
+        png[16..24].copy_from_slice(&[0, 1, 0, 0, 0, 1, 0, 0]);
+        let crc = crc(&png[12..29]);
+        png[29..33].copy_from_slice(&crc.to_be_bytes());
+        assert!(decode_png(&png).is_err());
+    }
+
+    fn crc(bytes: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for &byte in bytes {
+            crc ^= u32::from(byte);
+            for _ in 0..8 {
+                crc = if crc & 1 == 1 {
+                    crc >> 1 ^ 0xedb8_8320
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+        !crc
+    }
+}
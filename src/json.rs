@@ -0,0 +1,36 @@
+use serde::Serializer;
+use std::path::Path;
+
+/// A path for `--format json`, as a string when it's UTF-8, which JSON
+/// strings have to be, and otherwise as the array of its bytes, so no two
+/// paths come out the same. For `#[serde(serialize_with = "json::path")]`.
+pub fn path<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    match path.to_str() {
+        Some(path) => serializer.serialize_str(path),
+        None => serializer.collect_seq(path.as_os_str().as_encoded_bytes()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    struct File<'a> {
+        #[serde(serialize_with = "path")]
+        path: &'a Path,
+    }
+
+    #[test]
+    fn paths_that_are_not_utf8_are_their_bytes() {
+        let json = |path| serde_json::to_string(&File { path }).unwrap();
+        assert_eq!(json(Path::new("a \"b\".txt")), r#"{"path":"a \"b\".txt"}"#);
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            let path = Path::new(std::ffi::OsStr::from_bytes(b"bad\xffname"));
+            assert_eq!(json(path), r#"{"path":[98,97,100,255,110,97,109,101]}"#);
+        }
+    }
+}
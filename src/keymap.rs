@@ -0,0 +1,644 @@
+use anyhow::{Result, anyhow, bail};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Where an action applies. Panel scopes are checked before global ones, so
+/// the same key can mean different things in the list and the diff.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Scope {
+    Global,
+    List,
+    Diff,
+    Log,
+    Branches,
+    Tags,
+    Remotes,
+    Worktree,
+    Reflog,
+    Bisect,
+    Apply,
+    Stash,
+    Clean,
+    Rebase,
+    Resolve,
+    Blame,
+    Dash,
+}
+
+/// `(action, config name, scope, description, default keys)`
+type ActionInfo = (
+    Action,
+    &'static str,
+    Scope,
+    &'static str,
+    &'static [&'static str],
+);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Help,
+    Palette,
+    SwitchFocus,
+    NavigateUp,
+    NavigateDown,
+    PageUp,
+    PageDown,
+    Top,
+    Bottom,
+    Refresh,
+    Filter,
+    Select,
+    Discard,
+    Edit,
+    DiffTool,
+    Blame,
+    History,
+    CopyPath,
+    CopyAbsolutePath,
+    CopyHunk,
+    CopyDiff,
+    CopyHash,
+    MarkCommit,
+    CherryPick,
+    Revert,
+    ExportPatches,
+    ResetToCommit,
+    ScrollLeft,
+    ScrollRight,
+    SearchDiff,
+    NextMatch,
+    PrevMatch,
+    NextHunk,
+    PrevHunk,
+    NextFile,
+    PrevFile,
+    Stage,
+    StageAll,
+    UnstageAll,
+    Amend,
+    Undo,
+    Fetch,
+    Pull,
+    Push,
+    SetUpstream,
+    FetchLfs,
+    MoreContext,
+    LessContext,
+    IgnoreAllSpace,
+    IgnoreSpaceChange,
+    IgnoreBlankLines,
+    ToggleWrap,
+    ToggleSplit,
+    LoadMore,
+    ToggleLineNumbers,
+    ToggleIgnored,
+    ToggleTree,
+    Mark,
+    StashFiles,
+    OpenSubmodule,
+    Ignore,
+    Branches,
+    Tags,
+    Remotes,
+    Worktrees,
+    Reflog,
+    Bisect,
+    Stashes,
+    Clean,
+    StageFile,
+    LineMode,
+    Commit,
+    CheckoutBranch,
+    NewBranch,
+    RenameBranch,
+    DeleteBranch,
+    NewTag,
+    NewSignedTag,
+    DeleteTag,
+    PushTag,
+    DeleteRemoteTag,
+    AddRemote,
+    RenameRemote,
+    RemoveRemote,
+    SetRemoteUrl,
+    PruneRemote,
+    OpenWorktree,
+    AddWorktree,
+    RemoveWorktree,
+    PruneWorktrees,
+    OpenRepo,
+    SwitchReflog,
+    BranchAtEntry,
+    ResetToEntry,
+    StartBisect,
+    MarkGood,
+    MarkBad,
+    SkipBisect,
+    ResetBisect,
+    ApplyPatch,
+    ApplyPatchCached,
+    ApplyPatchIndex,
+    StashSave,
+    StashUntracked,
+    StashApply,
+    StashPop,
+    StashDrop,
+    MarkToRemove,
+    MarkAllToRemove,
+    RemoveFiles,
+    RebasePick,
+    RebaseReword,
+    RebaseSquash,
+    RebaseFixup,
+    RebaseDrop,
+    MoveUp,
+    MoveDown,
+    StartRebase,
+    ContinueRebase,
+    Resolve,
+    PickOurs,
+    PickTheirs,
+    PickBoth,
+    ClearPick,
+    WriteResolution,
+    BlameParent,
+    BlameBack,
+}
+
+impl Action {
+    /// Every action with its config name, scope, description and default keys.
+    #[rustfmt::skip]
+    pub const ALL: &[ActionInfo] = &[
+        (Action::Quit, "quit", Scope::Global, "quit", &["q", "Esc"]),
+        (Action::Help, "help", Scope::Global, "show this help", &["?"]),
+        (Action::Palette, "command_palette", Scope::Global, "run a command by name", &[":"]),
+        (Action::SwitchFocus, "switch_focus", Scope::Global, "switch focus", &["Tab"]),
+        (Action::NavigateUp, "navigate_up", Scope::Global, "move up", &["Up", "k"]),
+        (Action::NavigateDown, "navigate_down", Scope::Global, "move down", &["Down", "j"]),
+        (Action::PageUp, "page_up", Scope::Global, "page up", &["PageUp", "Ctrl-u"]),
+        (Action::PageDown, "page_down", Scope::Global, "page down", &["PageDown", "Ctrl-d"]),
+        (Action::Top, "top", Scope::Global, "jump to top", &["g", "Home"]),
+        (Action::Bottom, "bottom", Scope::Global, "jump to bottom", &["G", "End"]),
+        (Action::Refresh, "refresh", Scope::Global, "refresh", &["r"]),
+        (Action::Filter, "filter", Scope::Global, "filter files", &["/"]),
+        (Action::Select, "select", Scope::Global, "select", &["Enter"]),
+        (Action::Edit, "edit", Scope::Global, "open file in $EDITOR", &["e"]),
+        (Action::DiffTool, "diff_tool", Scope::Global, "open in diff.tool (merge.tool if conflicted)", &["D"]),
+        (Action::Blame, "blame", Scope::Global, "blame the selected file", &["b"]),
+        (Action::History, "history", Scope::Global, "commits that touched the file", &["h"]),
+        (Action::CopyPath, "copy_path", Scope::Global, "copy file path", &["y"]),
+        (Action::CopyAbsolutePath, "copy_absolute_path", Scope::Global, "copy absolute path", &["Y"]),
+        (Action::Discard, "discard", Scope::List, "discard changes", &["d"]),
+        (Action::StageFile, "stage_file", Scope::List, "stage/unstage file", &["Space"]),
+        (Action::Resolve, "resolve", Scope::List, "resolve conflicts in the file", &["m"]),
+        (Action::ToggleTree, "toggle_tree", Scope::List, "directory tree or flat list", &["t"]),
+        (Action::Mark, "mark", Scope::List, "mark/unmark for a batch stage, discard or stash", &["v"]),
+        (Action::StashFiles, "stash_files", Scope::List, "stash the file, or the marked ones", &["S"]),
+        (Action::OpenSubmodule, "open_submodule", Scope::List, "open the submodule in a status view of its own", &["o"]),
+        (Action::Ignore, "ignore", Scope::List, "add the untracked file to .gitignore", &["I"]),
+        (Action::ScrollLeft, "scroll_left", Scope::Diff, "scroll left", &["Left", "h"]),
+        (Action::ScrollRight, "scroll_right", Scope::Diff, "scroll right", &["Right", "l"]),
+        (Action::SearchDiff, "search_diff", Scope::Diff, "search the diff", &["/"]),
+        (Action::NextMatch, "next_match", Scope::Diff, "next search match (next hunk before a search)", &["n"]),
+        (Action::PrevMatch, "prev_match", Scope::Diff, "previous search match (previous hunk before a search)", &["N"]),
+        (Action::NextHunk, "next_hunk", Scope::Diff, "next hunk", &["n"]),
+        (Action::PrevHunk, "prev_hunk", Scope::Diff, "previous hunk", &["p"]),
+        (Action::NextFile, "next_file", Scope::Diff, "next file of a diff with several", &["}"]),
+        (Action::PrevFile, "prev_file", Scope::Diff, "previous file of a diff with several", &["{"]),
+        (Action::Stage, "stage", Scope::Diff, "stage/unstage hunk", &["Space"]),
+        (Action::LineMode, "line_mode", Scope::Diff, "pick single lines to stage", &["v"]),
+        (Action::CopyHunk, "copy_hunk", Scope::Diff, "copy the hunk as a patch", &["y"]),
+        (Action::CopyDiff, "copy_diff", Scope::Diff, "copy the whole diff", &["Y"]),
+        (Action::CopyHash, "copy_hash", Scope::Log, "copy the commit hash", &["y"]),
+        (Action::MarkCommit, "mark_commit", Scope::Log, "mark/unmark the commit to cherry-pick", &["Space", "v"]),
+        (Action::CherryPick, "cherry_pick", Scope::Log, "cherry-pick the marked commits, or the selected one, onto HEAD", &["C"]),
+        (Action::Revert, "revert", Scope::Log, "revert the commit, with a message to edit first", &["R"]),
+        (Action::ExportPatches, "export_patches", Scope::Log, "write the marked commits, or the selected one, as patch files", &["F"]),
+        (Action::ResetToCommit, "reset_to_commit", Scope::Log, "reset the current branch to the commit, soft, mixed or hard", &["X"]),
+        (Action::StageAll, "stage_all", Scope::Global, "stage all changes", &["a"]),
+        (Action::UnstageAll, "unstage_all", Scope::Global, "unstage everything", &["A"]),
+        (Action::Commit, "commit", Scope::Global, "commit staged changes", &["c"]),
+        (Action::Amend, "amend", Scope::Global, "amend last commit", &["C"]),
+        (Action::Undo, "undo", Scope::Global, "undo the last stage, unstage, discard, stash or commit", &["u"]),
+        (Action::Fetch, "fetch", Scope::Global, "fetch from the remote", &["f"]),
+        (Action::Pull, "pull", Scope::Global, "pull (merge or rebase per pull.rebase)", &["p"]),
+        (Action::Push, "push", Scope::Global, "push the current branch", &["P"]),
+        (Action::SetUpstream, "set_upstream", Scope::Global, "track the remote branch of the same name", &["U"]),
+        (Action::FetchLfs, "fetch_lfs", Scope::Global, "download the selected file's Git LFS content", &["F"]),
+        (Action::MoreContext, "more_context", Scope::Diff, "more context", &["+"]),
+        (Action::LessContext, "less_context", Scope::Diff, "less context", &["-"]),
+        (Action::IgnoreAllSpace, "ignore_all_space", Scope::Diff, "ignore all whitespace", &["W"]),
+        (Action::IgnoreSpaceChange, "ignore_space_change", Scope::Diff, "ignore changes in amount of whitespace", &["B"]),
+        (Action::IgnoreBlankLines, "ignore_blank_lines", Scope::Diff, "ignore blank lines", &["L"]),
+        (Action::ToggleWrap, "toggle_wrap", Scope::Diff, "toggle line wrap", &["w"]),
+        (Action::ToggleSplit, "toggle_split", Scope::Diff, "side-by-side or unified diff", &["s"]),
+        (Action::LoadMore, "load_more", Scope::Diff, "show more of a long new or deleted file", &["m"]),
+        (Action::ToggleLineNumbers, "toggle_line_numbers", Scope::Diff, "show/hide line numbers", &["#"]),
+        (Action::ToggleIgnored, "toggle_ignored", Scope::Global, "show/hide ignored files", &["i"]),
+        (Action::Branches, "branches", Scope::Global, "switch, create or delete branches", &[]),
+        (Action::Tags, "tags", Scope::Global, "create, delete or push tags", &[]),
+        (Action::Remotes, "remotes", Scope::Global, "add, rename, remove or prune remotes", &[]),
+        (Action::Worktrees, "worktrees", Scope::Global, "add, remove or open worktrees", &[]),
+        (Action::Reflog, "reflog", Scope::Global, "where HEAD and the branches have been", &[]),
+        (Action::Bisect, "bisect", Scope::Global, "find the commit that brought a bug in", &[]),
+        (Action::Stashes, "stashes", Scope::Global, "stash changes or apply a stash", &[]),
+        (Action::Clean, "clean", Scope::Global, "delete untracked files, picked from a list", &[]),
+        (Action::CheckoutBranch, "checkout_branch", Scope::Branches, "switch to the branch", &["Enter"]),
+        (Action::NewBranch, "new_branch", Scope::Branches, "new branch from the selected one", &["n"]),
+        (Action::RenameBranch, "rename_branch", Scope::Branches, "rename branch", &["R"]),
+        (Action::DeleteBranch, "delete_branch", Scope::Branches, "delete branch", &["d"]),
+        (Action::NewTag, "new_tag", Scope::Tags, "tag a commit, lightweight or annotated", &["n"]),
+        (Action::NewSignedTag, "new_signed_tag", Scope::Tags, "tag a commit with a signed annotated tag", &["N"]),
+        (Action::DeleteTag, "delete_tag", Scope::Tags, "delete tag", &["d"]),
+        (Action::PushTag, "push_tag", Scope::Tags, "push the tag to the remote", &["P"]),
+        (Action::DeleteRemoteTag, "delete_remote_tag", Scope::Tags, "delete the tag on the remote", &["X"]),
+        (Action::AddRemote, "add_remote", Scope::Remotes, "add a remote", &["a"]),
+        (Action::RenameRemote, "rename_remote", Scope::Remotes, "rename remote", &["R"]),
+        (Action::RemoveRemote, "remove_remote", Scope::Remotes, "remove remote", &["d"]),
+        (Action::SetRemoteUrl, "set_remote_url", Scope::Remotes, "change the remote's URL", &["u"]),
+        (Action::PruneRemote, "prune_remote", Scope::Remotes, "delete branches gone from the remote", &["p"]),
+        (Action::OpenWorktree, "open_worktree", Scope::Worktree, "open hg in the worktree", &["Enter"]),
+        (Action::AddWorktree, "add_worktree", Scope::Worktree, "check a branch out in a new worktree", &["a"]),
+        (Action::RemoveWorktree, "remove_worktree", Scope::Worktree, "remove worktree", &["d"]),
+        (Action::PruneWorktrees, "prune_worktrees", Scope::Worktree, "forget worktrees whose directory is gone", &["p"]),
+        (Action::OpenRepo, "open_repo", Scope::Dash, "open the status view in the repository", &["Enter"]),
+        (Action::SwitchReflog, "switch_reflog", Scope::Reflog, "show the next branch's reflog", &["b"]),
+        (Action::BranchAtEntry, "branch_at_entry", Scope::Reflog, "create a branch at the entry", &["n"]),
+        (Action::ResetToEntry, "reset_to_entry", Scope::Reflog, "reset the current branch to the entry", &["R"]),
+        (Action::StartBisect, "start_bisect", Scope::Bisect, "start a bisect between a bad and good revisions", &["n"]),
+        (Action::MarkGood, "mark_good", Scope::Bisect, "mark the checked-out commit good", &["g"]),
+        (Action::MarkBad, "mark_bad", Scope::Bisect, "mark the checked-out commit bad", &["b"]),
+        (Action::SkipBisect, "skip_bisect", Scope::Bisect, "skip the checked-out commit", &["s"]),
+        (Action::ResetBisect, "reset_bisect", Scope::Bisect, "end the bisect and go back to where it started", &["X"]),
+        (Action::ApplyPatch, "apply_patch", Scope::Apply, "apply the patch to the worktree", &["a"]),
+        (Action::ApplyPatchCached, "apply_patch_cached", Scope::Apply, "apply the patch to the index only", &["c"]),
+        (Action::ApplyPatchIndex, "apply_patch_index", Scope::Apply, "apply the patch to the worktree and the index", &["i"]),
+        (Action::StashSave, "stash", Scope::Stash, "stash local changes", &["s"]),
+        (Action::StashUntracked, "stash_untracked", Scope::Stash, "stash including untracked files", &["S"]),
+        (Action::StashApply, "apply_stash", Scope::Stash, "apply stash", &["a"]),
+        (Action::StashPop, "pop_stash", Scope::Stash, "apply and drop stash", &["p"]),
+        (Action::StashDrop, "drop_stash", Scope::Stash, "drop stash", &["d"]),
+        (Action::MarkToRemove, "mark_to_remove", Scope::Clean, "mark/unmark for deleting", &["Space", "v"]),
+        (Action::MarkAllToRemove, "mark_all_to_remove", Scope::Clean, "mark everything, or unmark it all", &["a"]),
+        (Action::RemoveFiles, "remove_files", Scope::Clean, "delete the marked files, or the selected one (asks first)", &["d"]),
+        (Action::RebasePick, "pick", Scope::Rebase, "use the commit", &["p"]),
+        (Action::RebaseReword, "reword", Scope::Rebase, "use the commit with a new message", &["r"]),
+        (Action::RebaseSquash, "squash", Scope::Rebase, "fold into the commit above, keeping both messages", &["s"]),
+        (Action::RebaseFixup, "fixup", Scope::Rebase, "fold into the commit above, keeping only its message", &["f"]),
+        (Action::RebaseDrop, "drop", Scope::Rebase, "leave the commit out", &["d"]),
+        (Action::MoveUp, "move_up", Scope::Rebase, "move the commit up", &["K"]),
+        (Action::MoveDown, "move_down", Scope::Rebase, "move the commit down", &["J"]),
+        (Action::StartRebase, "start_rebase", Scope::Rebase, "start the rebase (asks first)", &["w"]),
+        (Action::ContinueRebase, "continue_rebase", Scope::Rebase, "go on once the conflicts are resolved", &["c"]),
+        (Action::PickOurs, "pick_ours", Scope::Resolve, "take our side", &["o"]),
+        (Action::PickTheirs, "pick_theirs", Scope::Resolve, "take their side", &["t"]),
+        (Action::PickBoth, "pick_both", Scope::Resolve, "take both, ours first", &["b"]),
+        (Action::ClearPick, "clear_pick", Scope::Resolve, "undo the pick", &["u"]),
+        (Action::WriteResolution, "write_resolution", Scope::Resolve, "write the file and mark it resolved", &["w"]),
+        (Action::BlameParent, "blame_parent", Scope::Blame, "blame again before the line's commit", &["p"]),
+        (Action::BlameBack, "blame_back", Scope::Blame, "back to the newer blame", &["u"]),
+    ];
+
+    pub fn scope(self) -> Scope {
+        Self::info(self).2
+    }
+
+    pub fn description(self) -> &'static str {
+        Self::info(self).3
+    }
+
+    fn info(self) -> &'static ActionInfo {
+        Self::ALL
+            .iter()
+            .find(|(action, ..)| *action == self)
+            .expect("every action is listed in Action::ALL")
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    /// Parses `j`, `G`, `Space`, `PageDown`, `Ctrl-d`, `Alt-Enter`, ...
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+
+        // A lone "-" is the minus key, not a modifier separator
+        while let Some((prefix, tail)) = rest.split_once('-').filter(|(_, t)| !t.is_empty()) {
+            modifiers |= match prefix.to_lowercase().as_str() {
+                "ctrl" | "c" => KeyModifiers::CONTROL,
+                "alt" | "a" | "m" => KeyModifiers::ALT,
+                "shift" | "s" => KeyModifiers::SHIFT,
+                _ => bail!("invalid key \"{}\": unknown modifier \"{}\"", text, prefix),
+            };
+            rest = tail;
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_lowercase().as_str() {
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "enter" | "return" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "space" => KeyCode::Char(' '),
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" => KeyCode::Insert,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                name => match name.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                    Some(n) if (1..=12).contains(&n) => KeyCode::F(n),
+                    _ => bail!("invalid key \"{}\"", text),
+                },
+            },
+        };
+
+        Ok(KeyBinding { code, modifiers })
+    }
+
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        // Terminals report Shift inconsistently for characters ('G' vs Shift+'g'),
+        // so the character itself decides and only Ctrl/Alt must agree
+        let significant = |m: KeyModifiers| {
+            if matches!(self.code, KeyCode::Char(_)) {
+                m - KeyModifiers::SHIFT
+            } else {
+                m
+            }
+        };
+
+        self.code == key.code && significant(self.modifiers) == significant(key.modifiers)
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt-")?;
+        }
+
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::Up => write!(f, "↑"),
+            KeyCode::Down => write!(f, "↓"),
+            KeyCode::Left => write!(f, "←"),
+            KeyCode::Right => write!(f, "→"),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+/// Keys pressed one after another, like `gg`; most bindings are just one.
+#[derive(Clone, PartialEq)]
+pub struct Chord(Vec<KeyBinding>);
+
+impl Chord {
+    /// Parses one key (see [`KeyBinding::parse`]), keys separated by spaces
+    /// like `Ctrl-w j`, or a run of up to three plain characters like `gg`.
+    pub fn parse(text: &str) -> Result<Self> {
+        let parts: Vec<&str> = text.split_whitespace().collect();
+        if parts.len() > 1 {
+            return Ok(Chord(
+                parts
+                    .into_iter()
+                    .map(KeyBinding::parse)
+                    .collect::<Result<_>>()?,
+            ));
+        }
+
+        match KeyBinding::parse(text) {
+            Ok(key) => Ok(Chord(vec![key])),
+            // Key names are longer, so a short run can only be a chord
+            Err(_) if (2..=3).contains(&text.chars().count()) => Ok(Chord(
+                text.chars()
+                    .map(|c| KeyBinding {
+                        code: KeyCode::Char(c),
+                        modifiers: KeyModifiers::NONE,
+                    })
+                    .collect(),
+            )),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn matches(&self, keys: &[KeyEvent]) -> bool {
+        self.0.len() == keys.len() && self.0.iter().zip(keys).all(|(b, k)| b.matches(k))
+    }
+
+    /// Whether `keys` are the start of this chord, with more to come.
+    fn starts_with(&self, keys: &[KeyEvent]) -> bool {
+        self.0.len() > keys.len() && self.0.iter().zip(keys).all(|(b, k)| b.matches(k))
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let plain = self
+            .0
+            .iter()
+            .all(|k| matches!(k.code, KeyCode::Char(c) if c != ' ') && k.modifiers.is_empty());
+        let keys: Vec<String> = self.0.iter().map(KeyBinding::to_string).collect();
+        write!(f, "{}", keys.join(if plain { "" } else { " " }))
+    }
+}
+
+/// `(config name, keys)`
+type Override = (&'static str, &'static [&'static str]);
+
+/// What the presets change from the defaults in [`Action::ALL`], which mix
+/// arrows and vim keys. `vim` pages with Ctrl-b/f and Ctrl-u/d and jumps with
+/// `gg` and `G`; `standard` keeps only the arrows and PageUp/PageDown/Home/End,
+/// which frees the letters.
+#[rustfmt::skip]
+const PRESETS: &[(&str, &[Override])] = &[
+    ("default", &[]),
+    ("vim", &[
+        ("page_up", &["Ctrl-u", "Ctrl-b"]),
+        ("page_down", &["Ctrl-d", "Ctrl-f"]),
+        ("top", &["gg"]),
+        ("bottom", &["G"]),
+    ]),
+    ("standard", &[
+        ("navigate_up", &["Up"]),
+        ("navigate_down", &["Down"]),
+        ("page_up", &["PageUp"]),
+        ("page_down", &["PageDown"]),
+        ("top", &["Home"]),
+        ("bottom", &["End"]),
+        ("scroll_left", &["Left"]),
+        ("scroll_right", &["Right"]),
+    ]),
+];
+
+pub struct Keymap {
+    bindings: HashMap<Action, Vec<Chord>>,
+    // Keys typed so far of a chord that isn't finished yet
+    pending: RefCell<Vec<KeyEvent>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = Action::ALL
+            .iter()
+            .map(|(action, _, _, _, keys)| {
+                let keys = keys
+                    .iter()
+                    .map(|k| Chord::parse(k).expect("default keys parse"))
+                    .collect();
+                (*action, keys)
+            })
+            .collect();
+
+        Keymap {
+            bindings,
+            pending: RefCell::default(),
+        }
+    }
+}
+
+impl Keymap {
+    /// The defaults as changed by the preset called `name`.
+    pub fn preset(name: &str) -> Result<Self> {
+        let Some((_, changes)) = PRESETS.iter().find(|(n, _)| *n == name) else {
+            let names: Vec<_> = PRESETS.iter().map(|(n, _)| *n).collect();
+            bail!(
+                "unknown keymap \"{}\" (available: {})",
+                name,
+                names.join(", ")
+            );
+        };
+
+        let overrides = changes
+            .iter()
+            .map(|(action, keys)| {
+                (
+                    action.to_string(),
+                    keys.iter().map(|k| k.to_string()).collect(),
+                )
+            })
+            .collect();
+        Keymap::default().with_overrides(&overrides)
+    }
+
+    /// These bindings with the given `action name -> keys` overrides applied.
+    pub fn with_overrides(mut self, overrides: &HashMap<String, Vec<String>>) -> Result<Self> {
+        for (name, keys) in overrides {
+            let action = Action::ALL
+                .iter()
+                .find(|(_, n, ..)| n == name)
+                .map(|(action, ..)| *action)
+                .ok_or_else(|| anyhow!("unknown action \"{}\"", name))?;
+
+            let keys = keys
+                .iter()
+                .map(|k| Chord::parse(k).map_err(|e| anyhow!("{} for {}", e, name)))
+                .collect::<Result<Vec<_>>>()?;
+
+            self.bindings.insert(action, keys);
+        }
+
+        Ok(self)
+    }
+
+    fn keys(&self, action: Action) -> &[Chord] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// The action `key` triggers, looking at `scope` first and then at the
+    /// global bindings. A key that starts a longer chord triggers nothing
+    /// until the chord is finished; one that breaks it off starts over. A
+    /// binding that is complete wins over chords it begins.
+    pub fn resolve(&self, key: &KeyEvent, scope: Scope) -> Option<Action> {
+        let mut pending = self.pending.borrow_mut();
+        pending.push(*key);
+
+        let in_scope = |scope: Scope| {
+            Action::ALL
+                .iter()
+                .map(|(action, ..)| *action)
+                .filter(move |action| action.scope() == scope)
+        };
+        let find = |scope: Scope| {
+            in_scope(scope).find(|action| self.keys(*action).iter().any(|c| c.matches(&pending)))
+        };
+
+        if let Some(action) = find(scope).or_else(|| find(Scope::Global)) {
+            pending.clear();
+            return Some(action);
+        }
+
+        let unfinished = [scope, Scope::Global]
+            .into_iter()
+            .flat_map(in_scope)
+            .any(|action| self.keys(action).iter().any(|c| c.starts_with(&pending)));
+        if unfinished {
+            return None;
+        }
+
+        let broken_off = pending.len() > 1;
+        pending.clear();
+        drop(pending);
+        if broken_off {
+            self.resolve(key, scope)
+        } else {
+            None
+        }
+    }
+
+    /// Keys of `action` joined for display, e.g. `↑/k`.
+    pub fn label(&self, action: Action) -> String {
+        self.keys(action)
+            .iter()
+            .map(Chord::to_string)
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Actions that share a key on purpose, one standing in for the other
+    const SHARED: &[(Action, Action)] = &[(Action::NextMatch, Action::NextHunk)];
+
+    #[test]
+    fn no_two_actions_of_a_scope_share_a_key() {
+        for (preset, _) in PRESETS {
+            let keymap = Keymap::preset(preset).unwrap();
+            for (i, &(a, a_name, a_scope, ..)) in Action::ALL.iter().enumerate() {
+                for &(b, b_name, b_scope, ..) in &Action::ALL[i + 1..] {
+                    if a_scope != b_scope || SHARED.contains(&(a, b)) {
+                        continue;
+                    }
+                    let clash = keymap.keys(a).iter().any(|k| keymap.keys(b).contains(k));
+                    assert!(
+                        !clash,
+                        "{} and {} share a key in the {} keymap",
+                        a_name, b_name, preset
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,891 @@
+mod graph;
+
+use crate::clipboard::Clipboard;
+use crate::history;
+use crate::keymap::{Action, Keymap, Scope};
+use crate::panes::{self, Focus, Panes};
+use crate::patch::mailbox;
+use crate::rebase::sequencer::{Operation, Progress, Sequencer};
+use crate::rebase::stopped;
+use crate::rebase::todo::{self, Step, Verb};
+use crate::reset::{ResetAction, ResetPopup};
+use crate::sign::{self, Verification};
+use crate::status::diff::{self, FileDiff};
+use crate::status::message::{InputResult, MessageInput};
+use crate::theme::Theme;
+use crate::tui::{TICK, Tui};
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, Utc};
+use crossterm::event::{self, Event, KeyCode};
+use git2::{Commit, DiffFindOptions, Oid, Repository, Revwalk, Sort, Time};
+use graph::Graph;
+use ratatui::{
+    prelude::*,
+    widgets::{List, ListItem},
+};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// The history of `revisions`, or of HEAD when there are none, with the
+/// selected commit's diff alongside. Marked commits, or the selected one, can
+/// be cherry-picked onto HEAD or written out as patch files, and the branch
+/// reset to the selected one.
+pub fn log(
+    repo: &Repository,
+    revisions: &[String],
+    list_width: u16,
+    keymap: &Keymap,
+    theme: &Theme,
+) -> Result<()> {
+    let mut tui = Tui::new()?;
+
+    // ---------- Load History ----------
+    let mut history = History::new(repo, revisions)?;
+    let mut marked: HashSet<Oid> = HashSet::new();
+    history.load_to(repo, LOAD_AHEAD, theme)?;
+    // Built again only when the marks change or more is loaded; every frame
+    // only borrows it
+    let mut list = commit_list(&history.commits, &history.lines, &marked, theme);
+
+    let mut panes: Panes<Oid> = Panes::new(list_width);
+
+    // ---------- UI State ----------
+    // Where each file of the shown commit starts in its lines
+    let mut current_files: Vec<(usize, PathBuf)> = Vec::new();
+    let mut message: Option<String> = None;
+    let mut clipboard = Clipboard::default();
+    // Commits waiting on a yes to be cherry-picked, oldest first
+    let mut confirm_pick: Option<Vec<Step>> = None;
+    // A merge waiting to be told which parent to revert against
+    let mut ask_mainline: Option<Oid> = None;
+    // The revert's message being edited, for this commit and mainline
+    let mut revert_input: Option<(Oid, u32, MessageInput)> = None;
+    // Commits to export, oldest first, and the directory being typed
+    let mut export_input: Option<(Vec<Oid>, String)> = None;
+    // The reset being chosen, to the commit it was opened on
+    let mut reset_popup: Option<ResetPopup> = None;
+
+    // ---------- Main Loop ----------
+    loop {
+        // A page or more past the selection is always loaded, so the list
+        // never seems to end before the history does
+        let ahead = LOAD_AHEAD.max(2 * panes.page_height() as usize);
+        let wanted = panes.selected().unwrap_or(0) + ahead;
+        if history.commits.len() < wanted
+            && history.load_to(repo, wanted.next_multiple_of(LOAD_AHEAD), theme)?
+        {
+            list = commit_list(&history.commits, &history.lines, &marked, theme);
+        }
+
+        // ---------- Helper line ----------
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let prompt = Style::default()
+            .fg(theme.warning)
+            .add_modifier(Modifier::BOLD);
+        let help_line = match (&confirm_pick, &message) {
+            _ if let Some(oid) = ask_mainline => Line::styled(mainline_question(repo, oid), prompt),
+            _ if let Some((oids, dir)) = &export_input => Line::from(vec![
+                Span::styled(
+                    format!(
+                        "Export {} patch{} to: ",
+                        oids.len(),
+                        if oids.len() == 1 { "" } else { "es" }
+                    ),
+                    Style::default().fg(theme.accent),
+                ),
+                Span::raw(dir.clone()),
+                Span::styled("█", Style::default().fg(theme.accent)),
+            ]),
+            (Some(steps), _) => Line::styled(
+                format!(
+                    "Cherry-pick {} commit{} onto {}? (y/n)",
+                    steps.len(),
+                    if steps.len() == 1 { "" } else { "s" },
+                    head_name(repo)
+                ),
+                prompt,
+            ),
+            (None, Some(text)) => Line::from(text.as_str()),
+            (None, None) => Line::from(vec![
+                Span::raw(" "),
+                Span::styled(
+                    format!(
+                        "{} {}",
+                        keymap.label(Action::NavigateUp),
+                        keymap.label(Action::NavigateDown)
+                    ),
+                    bold,
+                ),
+                Span::raw(" navigate"),
+                Span::raw(" • "),
+                Span::styled(keymap.label(Action::Select), bold),
+                Span::raw(" show diff"),
+                Span::raw(" • "),
+                Span::styled(keymap.label(Action::History), bold),
+                Span::raw(" file history"),
+                Span::raw(" • "),
+                Span::styled(keymap.label(Action::MarkCommit), bold),
+                Span::raw(" mark"),
+                Span::raw(" • "),
+                Span::styled(keymap.label(Action::CherryPick), bold),
+                Span::raw(" cherry-pick"),
+                Span::raw(" • "),
+                Span::styled(keymap.label(Action::ExportPatches), bold),
+                Span::raw(" export"),
+                Span::raw(" • "),
+                Span::styled(keymap.label(Action::Revert), bold),
+                Span::raw(" revert"),
+                Span::raw(" • "),
+                Span::styled(keymap.label(Action::ResetToCommit), bold),
+                Span::raw(" reset"),
+                Span::raw(" • "),
+                Span::styled(keymap.label(Action::CopyHash), bold),
+                Span::raw(" copy hash"),
+                Span::raw(" • "),
+                Span::styled(keymap.label(Action::SwitchFocus), bold),
+                Span::raw(" switch focus"),
+                Span::raw(" • "),
+                Span::styled(keymap.label(Action::Quit), bold),
+                Span::raw(" quit"),
+            ]),
+        };
+
+        let mut title = match revisions {
+            [] => String::from(" Git Log "),
+            _ => format!(" Git Log {} ", revisions.join(" ")),
+        };
+        if !marked.is_empty() {
+            title.push_str(&format!("({} marked) ", marked.len()));
+        }
+
+        tui.draw(|frame| {
+            let (main, help) = panes::split(frame.area());
+            panes.render(frame, main, &list, &title, " Commit ", theme);
+            let asking = confirm_pick.is_some() || ask_mainline.is_some() || export_input.is_some();
+            panes::render_help(frame, help, help_line, asking);
+
+            if let Some((.., input)) = &revert_input {
+                input.render(frame, theme);
+            }
+            if let Some(popup) = &reset_popup {
+                popup.render(frame, theme);
+            }
+        })?;
+
+        // ---------- Input Handling ----------
+        if !event::poll(TICK)? {
+            continue;
+        }
+
+        let event = event::read()?;
+
+        // The next draw lays the panels out for the new size
+        if let Event::Resize(..) = event {
+            continue;
+        }
+
+        if let Event::Key(key) = event {
+            // A message stands until the next key
+            message = None;
+
+            // What a prompt below settled on carrying out
+            let mut replay: Option<(Operation, Vec<Step>)> = None;
+            // HEAD moved, so the list is loaded again
+            let mut moved = false;
+
+            // A question swallows every key until it is answered
+            if let Some(steps) = confirm_pick.take() {
+                match key.code {
+                    KeyCode::Char('y') | KeyCode::Char('Y') => {
+                        replay = Some((Operation::CherryPick, steps))
+                    }
+                    KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {}
+                    _ => confirm_pick = Some(steps),
+                }
+            } else if let Some(oid) = ask_mainline {
+                match key.code {
+                    KeyCode::Char(c @ '1'..='9') => {
+                        let commit = repo.find_commit(oid)?;
+                        let mainline = c as u32 - '0' as u32;
+                        if mainline as usize <= commit.parent_count() {
+                            revert_input = Some(revert_prompt(repo, &commit, mainline)?);
+                            ask_mainline = None;
+                        }
+                    }
+                    KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => ask_mainline = None,
+                    _ => {}
+                }
+            } else if let Some((oids, dir)) = &mut export_input {
+                match key.code {
+                    KeyCode::Esc => export_input = None,
+                    KeyCode::Backspace => {
+                        dir.pop();
+                    }
+                    KeyCode::Char(c) => dir.push(c),
+                    KeyCode::Enter if !dir.trim().is_empty() => {
+                        message = Some(
+                            match mailbox::export(repo, oids, &PathBuf::from(dir.trim())) {
+                                Ok(text) => text,
+                                Err(e) => format!("Error: {:#}", e),
+                            },
+                        );
+                        export_input = None;
+                    }
+                    _ => {}
+                }
+            } else if let Some(popup) = &mut reset_popup {
+                match popup.handle(&key) {
+                    ResetAction::Waiting => {}
+                    ResetAction::Cancelled => reset_popup = None,
+                    ResetAction::Reset(mode) => {
+                        message = Some(match popup.reset(repo, mode) {
+                            Ok(text) => text,
+                            Err(e) => format!("Error: {}", e),
+                        });
+                        reset_popup = None;
+                        moved = true;
+                    }
+                }
+            } else if let Some((oid, mainline, input)) = &mut revert_input {
+                match input.handle(&key) {
+                    InputResult::Editing => {}
+                    InputResult::Cancelled => revert_input = None,
+                    InputResult::Submitted(text) if text.trim().is_empty() => {
+                        message = Some(String::from("Error: the message can't be empty"));
+                    }
+                    InputResult::Submitted(text) => {
+                        let commit = repo.find_commit(*oid)?;
+                        replay = Some((
+                            Operation::Revert,
+                            vec![Step {
+                                verb: Verb::Revert {
+                                    mainline: *mainline,
+                                },
+                                oid: *oid,
+                                summary: commit.summary().unwrap_or("").to_string(),
+                                message: format!("{}\n", text.trim_end()),
+                            }],
+                        ));
+                        revert_input = None;
+                    }
+                }
+            } else {
+                match keymap.resolve(&key, Scope::Log) {
+                    Some(Action::Quit) if key.code == KeyCode::Esc && !marked.is_empty() => {
+                        marked.clear();
+                        list = commit_list(&history.commits, &history.lines, &marked, theme);
+                    }
+
+                    Some(Action::Quit) => break,
+
+                    Some(Action::MarkCommit) => {
+                        if let Some(i) = panes.selected()
+                            && let Some(&oid) = history.commits.get(i)
+                        {
+                            if !marked.remove(&oid) {
+                                marked.insert(oid);
+                            }
+                            list = commit_list(&history.commits, &history.lines, &marked, theme);
+                            // Marking goes down the list, as in the status view
+                            panes.select(Some((i + 1).min(history.commits.len() - 1)));
+                        }
+                    }
+
+                    Some(Action::CherryPick) => {
+                        let chosen = chosen(&history.commits, &marked, panes.selected());
+                        match pick_steps(repo, &chosen) {
+                            Ok(steps) if steps.is_empty() => {}
+                            Ok(steps) => confirm_pick = Some(steps),
+                            Err(e) => message = Some(format!("Error: {}", e)),
+                        }
+                    }
+
+                    Some(Action::ExportPatches) => {
+                        let chosen = chosen(&history.commits, &marked, panes.selected());
+                        if !chosen.is_empty() {
+                            export_input = Some((chosen, String::from("patches")));
+                        }
+                    }
+
+                    Some(Action::Revert) => {
+                        let Some(&oid) = panes.selected().and_then(|i| history.commits.get(i))
+                        else {
+                            continue;
+                        };
+                        let commit = repo.find_commit(oid)?;
+                        match todo::check(repo, &[]) {
+                            Err(e) => message = Some(format!("Error: {}", e)),
+                            Ok(()) if commit.parent_count() > 1 => ask_mainline = Some(oid),
+                            Ok(()) => revert_input = Some(revert_prompt(repo, &commit, 0)?),
+                        }
+                    }
+
+                    Some(Action::ResetToCommit) => {
+                        if let Some(&oid) = panes.selected().and_then(|i| history.commits.get(i)) {
+                            match ResetPopup::new(repo, oid, format!("{:.7}", oid)) {
+                                Ok(popup) => reset_popup = Some(popup),
+                                Err(e) => message = Some(format!("Error: {}", e)),
+                            }
+                        }
+                    }
+
+                    Some(Action::CopyHash) => {
+                        if let Some(&oid) = panes.selected().and_then(|i| history.commits.get(i)) {
+                            message = Some(match clipboard.copy(&oid.to_string()) {
+                                Ok(()) => format!("Copied: {}", oid),
+                                Err(e) => format!("Error: {}", e),
+                            });
+                        }
+                    }
+
+                    Some(Action::Select) => {
+                        if let Some(&oid) = panes.selected().and_then(|i| history.commits.get(i)) {
+                            let lines;
+                            (lines, current_files) = commit_pane(repo, oid, theme);
+                            panes.show(Some(oid), lines);
+                        }
+                    }
+
+                    Some(Action::History) => {
+                        let Some(&oid) = panes.selected().and_then(|i| history.commits.get(i))
+                        else {
+                            continue;
+                        };
+                        if panes.shown() != Some(&oid) {
+                            let lines;
+                            (lines, current_files) = commit_pane(repo, oid, theme);
+                            panes.show(Some(oid), lines);
+                        }
+
+                        // The file whose diff is at the top of the commit pane
+                        let path = current_files
+                            .iter()
+                            .rev()
+                            .find(|(start, _)| *start <= panes.scroll as usize)
+                            .or(current_files.first())
+                            .map(|(_, path)| path.clone());
+                        if let Some(path) = path {
+                            history::history(&mut tui, repo, &path, list_width, keymap, theme)?;
+                        }
+                    }
+
+                    // Bottom shows the end of the history, loading the rest first
+                    Some(Action::Bottom) if panes.focus == Focus::Left => {
+                        if history.load_to(repo, usize::MAX, theme)? {
+                            list = commit_list(&history.commits, &history.lines, &marked, theme);
+                        }
+                        panes.select(Some(list.len().saturating_sub(1)));
+                    }
+
+                    Some(action) => {
+                        panes.navigate(action, list.len());
+                    }
+
+                    None => {}
+                }
+            }
+
+            if let Some((operation, steps)) = replay {
+                message = Some(
+                    match replay_onto_head(
+                        &mut tui, repo, operation, steps, list_width, keymap, theme,
+                    ) {
+                        Ok(text) => text,
+                        Err(e) => format!("Error: {}", e),
+                    },
+                );
+                moved = true;
+            }
+            if moved {
+                // HEAD has moved, and may be among what's listed
+                history = History::new(repo, revisions)?;
+                let selected = panes.selected().unwrap_or(0);
+                history.load_to(repo, selected + LOAD_AHEAD, theme)?;
+                marked.clear();
+                list = commit_list(&history.commits, &history.lines, &marked, theme);
+                let last = list.len().saturating_sub(1);
+                panes.select(Some(panes.selected().unwrap_or(0).min(last)));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The marked commits, or the selected one when none are, oldest first: the
+/// order they're applied in.
+fn chosen(commits: &[Oid], marked: &HashSet<Oid>, selected: Option<usize>) -> Vec<Oid> {
+    if marked.is_empty() {
+        selected
+            .and_then(|i| commits.get(i))
+            .copied()
+            .into_iter()
+            .collect()
+    } else {
+        commits
+            .iter()
+            .rev()
+            .filter(|oid| marked.contains(oid))
+            .copied()
+            .collect()
+    }
+}
+
+/// The commits reachable from `revisions` (HEAD when there are none), in
+/// the order the log lists them, each with its graph and summary. A long
+/// history takes a while to format, so only as much of it is as the list
+/// has scrolled down to.
+struct History<'r> {
+    /// `None` once it has been walked to the end
+    walk: Option<Revwalk<'r>>,
+    graph: Graph,
+    commits: Vec<Oid>,
+    lines: Vec<Line<'static>>,
+}
+
+impl<'r> History<'r> {
+    fn new(repo: &'r Repository, revisions: &[String]) -> Result<Self> {
+        Ok(History {
+            walk: Some(revwalk(repo, revisions)?),
+            graph: Graph::default(),
+            commits: Vec::new(),
+            lines: Vec::new(),
+        })
+    }
+
+    /// Walks on until `count` commits are loaded, or all of them are when
+    /// there aren't that many. Returns whether any more were.
+    fn load_to(&mut self, repo: &Repository, count: usize, theme: &Theme) -> Result<bool> {
+        let before = self.commits.len();
+        while self.commits.len() < count
+            && let Some(walk) = &mut self.walk
+        {
+            let Some(oid) = walk.next() else {
+                self.walk = None;
+                break;
+            };
+            let oid = oid?;
+            let commit = repo.find_commit(oid)?;
+            let parents: Vec<Oid> = commit.parent_ids().collect();
+
+            let mut line = commit_line(&commit, theme);
+            line.spans
+                .splice(0..0, self.graph.row(oid, &parents, theme));
+
+            self.commits.push(oid);
+            self.lines.push(line);
+        }
+        Ok(self.commits.len() > before)
+    }
+}
+
+/// Commits loaded past the selection, and how many more are at a time.
+const LOAD_AHEAD: usize = 256;
+
+/// A walk over `revisions`, or over HEAD when there are none, children
+/// before their parents as the graph needs them.
+fn revwalk<'r>(repo: &'r Repository, revisions: &[String]) -> Result<Revwalk<'r>> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+    for revision in revisions {
+        let commit = repo.revparse_single(revision)?.peel_to_commit()?;
+        revwalk.push(commit.id())?;
+    }
+    // An unborn HEAD (fresh `git init`) has nothing to walk
+    if revisions.is_empty() && repo.head().is_ok() {
+        revwalk.push_head()?;
+    }
+    Ok(revwalk)
+}
+
+/// The log's list, with a column for the marks while anything is marked.
+fn commit_list(
+    commits: &[Oid],
+    lines: &[Line<'static>],
+    marked: &HashSet<Oid>,
+    theme: &Theme,
+) -> List<'static> {
+    let items: Vec<ListItem> = commits
+        .iter()
+        .zip(lines)
+        .map(|(oid, line)| {
+            let mut line = line.clone();
+            if !marked.is_empty() {
+                line.spans.insert(
+                    0,
+                    if marked.contains(oid) {
+                        // In the accent color, apart from the graph's own dots
+                        Span::styled(
+                            "● ",
+                            Style::default()
+                                .fg(theme.accent)
+                                .add_modifier(Modifier::BOLD),
+                        )
+                    } else {
+                        Span::raw("  ")
+                    },
+                );
+            }
+            ListItem::new(line)
+        })
+        .collect();
+
+    panes::list(items, "No commits yet", theme)
+}
+
+/// The todo list for cherry-picking `chosen`, once it's clear it can be
+/// carried out. Merges aren't picked: which parent they'd be picked against
+/// is a question `git cherry-pick -m` asks.
+fn pick_steps(repo: &Repository, chosen: &[Oid]) -> Result<Vec<Step>> {
+    if repo.head().is_err() {
+        anyhow::bail!("there is no commit yet to cherry-pick onto");
+    }
+
+    let mut steps = Vec::new();
+    for &oid in chosen {
+        let commit = repo.find_commit(oid)?;
+        if commit.parent_count() > 1 {
+            anyhow::bail!(
+                "{:.7} is a merge; only plain commits can be cherry-picked",
+                oid
+            );
+        }
+        steps.push(Step::pick(&commit));
+    }
+    todo::check(repo, &steps)?;
+    Ok(steps)
+}
+
+/// Carries out `steps` on top of HEAD, stopping for any conflicts to be
+/// resolved. Returns what to tell the user.
+fn replay_onto_head(
+    tui: &mut Tui,
+    repo: &Repository,
+    operation: Operation,
+    steps: Vec<Step>,
+    list_width: u16,
+    keymap: &Keymap,
+    theme: &Theme,
+) -> Result<String> {
+    let head = repo.head()?.peel_to_commit()?.id();
+    let mut sequencer = Sequencer::start(repo, operation, head, steps)?;
+    match sequencer.run(repo) {
+        Ok(Progress::Done(text)) => Ok(text),
+        Ok(Progress::Conflict(step, _)) => {
+            stopped::stopped(tui, repo, sequencer, step, list_width, keymap, theme)
+        }
+        Err(e) => {
+            // Nothing half done is left behind
+            sequencer.abort(repo)?;
+            Err(e)
+        }
+    }
+}
+
+/// `git revert`'s message for `commit`, ready to edit.
+fn revert_prompt(
+    repo: &Repository,
+    commit: &Commit,
+    mainline: u32,
+) -> Result<(Oid, u32, MessageInput)> {
+    let mut text = format!(
+        "Revert \"{}\"\n\nThis reverts commit {}",
+        commit.summary().unwrap_or(""),
+        commit.id()
+    );
+    if mainline > 0 {
+        let parent = repo.find_commit(commit.parent_id(mainline as usize - 1)?)?;
+        text.push_str(&format!(", reversing\nchanges made to {}", parent.id()));
+    }
+    text.push('.');
+
+    Ok((
+        commit.id(),
+        mainline,
+        MessageInput::new(format!("Revert {:.7}", commit.id()), text),
+    ))
+}
+
+/// `1a2b3c4 is a merge: revert it against which parent? 1 5d6e7f8 summary · 2 …`
+fn mainline_question(repo: &Repository, oid: Oid) -> String {
+    let Ok(commit) = repo.find_commit(oid) else {
+        return String::new();
+    };
+    let parents: Vec<String> = commit
+        .parents()
+        .enumerate()
+        .map(|(i, parent)| {
+            format!(
+                "{} {:.7} {}",
+                i + 1,
+                parent.id(),
+                parent.summary().unwrap_or("")
+            )
+        })
+        .collect();
+    format!(
+        "{:.7} is a merge: revert it against which parent? {} (Esc cancels)",
+        oid,
+        parents.join(" · ")
+    )
+}
+
+/// The branch HEAD names, or the commit it's detached at.
+fn head_name(repo: &Repository) -> String {
+    match repo.head() {
+        Ok(head) if head.is_branch() => head.shorthand().unwrap_or("HEAD").to_string(),
+        Ok(head) => head
+            .target()
+            .map_or_else(|| String::from("HEAD"), |oid| format!("{:.7}", oid)),
+        Err(_) => String::from("HEAD"),
+    }
+}
+
+/// The commit pane's lines for `oid`, and where each of its files starts.
+fn commit_pane(
+    repo: &Repository,
+    oid: Oid,
+    theme: &Theme,
+) -> (Vec<Line<'static>>, Vec<(usize, PathBuf)>) {
+    match show_commit(repo, oid, theme) {
+        Ok(commit) => (commit.lines, commit.files),
+        Err(e) => (vec![Line::from(format!("Error: {}", e))], Vec::new()),
+    }
+}
+
+pub fn commit_line(commit: &Commit, theme: &Theme) -> Line<'static> {
+    let sha = commit.id().to_string()[..7].to_string();
+    let author = commit.author().name().unwrap_or("unknown").to_string();
+    let date = relative_date(commit.time());
+    let summary = commit.summary().unwrap_or("").to_string();
+
+    Line::from(vec![
+        Span::styled(sha, Style::default().fg(theme.commit_sha)),
+        Span::raw(" "),
+        Span::styled(author, Style::default().fg(theme.commit_author)),
+        Span::raw(" "),
+        Span::styled(date, Style::default().fg(theme.commit_date)),
+        Span::raw(" "),
+        Span::raw(summary),
+    ])
+}
+
+/// Header, message and diff of commit `oid` against its first parent.
+pub fn show_commit(repo: &Repository, oid: Oid, theme: &Theme) -> Result<FileDiff> {
+    render_commit(repo, oid, false, theme)
+}
+
+/// Like [`show_commit`], with a `git show --stat` summary between the
+/// message and the patch.
+pub fn show_commit_stat(repo: &Repository, oid: Oid, theme: &Theme) -> Result<FileDiff> {
+    render_commit(repo, oid, true, theme)
+}
+
+fn render_commit(repo: &Repository, oid: Oid, stat: bool, theme: &Theme) -> Result<FileDiff> {
+    let commit = repo.find_commit(oid)?;
+    let mut lines = commit_header(&commit, theme);
+
+    // ---------- Signature, under the date ----------
+    let verified = match sign::verify(repo, oid) {
+        Ok(verification) => verification,
+        Err(e) => Some(Verification {
+            good: false,
+            text: format!("Can't check the signature: {}", e),
+        }),
+    };
+    if let Some(verified) = verified {
+        let color = if verified.good {
+            theme.staged
+        } else {
+            theme.warning
+        };
+        lines.insert(
+            3,
+            Line::styled(
+                format!("Sig:    {}", verified.text),
+                Style::default().fg(color),
+            ),
+        );
+    }
+
+    // ---------- Diff against first parent ----------
+    let tree = commit.tree()?;
+    let parent_tree = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree()?),
+        Err(_) => None,
+    };
+
+    let mut diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?;
+
+    if stat {
+        // Renames count as one file here, as in `git show`
+        diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+        lines.extend(diff::stat_lines(&diff, theme)?);
+    }
+
+    let mut rendered = diff::render_diff(&diff, theme)?;
+
+    // Everything below the header moves down by its height
+    let offset = lines.len();
+    for hunk in &mut rendered.hunks {
+        *hunk = hunk.start + offset..hunk.end + offset;
+    }
+    for line in &mut rendered.changes {
+        *line += offset;
+    }
+    for (start, _) in &mut rendered.files {
+        *start += offset;
+    }
+    rendered
+        .numbers
+        .splice(0..0, std::iter::repeat_n((None, None), offset));
+    // The commit header has no old and new side to lay out
+    rendered.split = None;
+    lines.append(&mut rendered.lines);
+    rendered.lines = lines;
+
+    Ok(rendered)
+}
+
+/// Prints every commit reachable from `revisions` (HEAD when there are none),
+/// in the order the log lists them, as a JSON array for scripts: hash,
+/// parents, author, date and message.
+pub fn json(repo: &Repository, revisions: &[String]) -> Result<()> {
+    let mut out = io::stdout().lock();
+    match write_json(repo, revisions, &mut out) {
+        // Piped into `head` or similar, which stopped reading
+        Err(e)
+            if e.downcast_ref::<io::Error>()
+                .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) =>
+        {
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+fn write_json(repo: &Repository, revisions: &[String], out: &mut impl Write) -> Result<()> {
+    write!(out, "[")?;
+
+    for (i, oid) in revwalk(repo, revisions)?.enumerate() {
+        let commit = repo.find_commit(oid?)?;
+        let author = commit.author();
+        let commit = JsonCommit {
+            id: commit.id().to_string(),
+            parents: commit.parent_ids().map(|id| id.to_string()).collect(),
+            author: author.name(),
+            email: author.email(),
+            date: iso_date(author.when()),
+            summary: commit.summary(),
+            message: commit.message(),
+        };
+        // One commit per line, so the array still reads line by line
+        let commit = serde_json::to_string(&commit)?;
+        write!(out, "{}\n{}", if i == 0 { "" } else { "," }, commit)?;
+    }
+
+    writeln!(out, "]")?;
+    Ok(())
+}
+
+/// A commit as [`json`] prints it; `null` where the name, email or message
+/// isn't UTF-8.
+#[derive(Serialize)]
+struct JsonCommit<'a> {
+    id: String,
+    parents: Vec<String>,
+    author: Option<&'a str>,
+    email: Option<&'a str>,
+    date: Option<String>,
+    summary: Option<&'a str>,
+    message: Option<&'a str>,
+}
+
+/// RFC 3339 in the time zone the date was recorded in, like `--date=iso-strict`.
+fn iso_date(time: Time) -> Option<String> {
+    let offset = FixedOffset::east_opt(time.offset_minutes() * 60)?;
+    let date = DateTime::from_timestamp(time.seconds(), 0)?;
+    Some(date.with_timezone(&offset).to_rfc3339())
+}
+
+/// `commit <sha>`, author, date and the indented message, like `git show`.
+pub fn commit_header(commit: &Commit, theme: &Theme) -> Vec<Line<'static>> {
+    let mut lines: Vec<Line<'static>> = Vec::new();
+    let author = commit.author();
+
+    lines.push(Line::from(Span::styled(
+        format!("commit {}", commit.id()),
+        Style::default().fg(theme.commit_sha),
+    )));
+    lines.push(Line::from(format!(
+        "Author: {} <{}>",
+        author.name().unwrap_or("unknown"),
+        author.email().unwrap_or("")
+    )));
+    lines.push(Line::from(format!(
+        "Date:   {}",
+        relative_date(commit.time())
+    )));
+    lines.push(Line::from(""));
+
+    for line in commit.message().unwrap_or("").lines() {
+        lines.push(Line::from(format!("    {}", line)));
+    }
+    lines.push(Line::from(""));
+
+    lines
+}
+
+pub fn relative_date(time: Time) -> String {
+    let Some(then) = DateTime::<Utc>::from_timestamp(time.seconds(), 0) else {
+        return String::from("unknown");
+    };
+
+    let seconds = (Utc::now() - then).num_seconds().max(0);
+
+    let (value, unit) = match seconds {
+        s if s < 60 => return String::from("just now"),
+        s if s < 3600 => (s / 60, "minute"),
+        s if s < 86_400 => (s / 3600, "hour"),
+        s if s < 604_800 => (s / 86_400, "day"),
+        s if s < 2_592_000 => (s / 604_800, "week"),
+        s if s < 31_536_000 => (s / 2_592_000, "month"),
+        s => (s / 31_536_000, "year"),
+    };
+
+    if value == 1 {
+        format!("1 {} ago", unit)
+    } else {
+        format!("{} {}s ago", value, unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helper_git_core::testing::{commit, repo, write};
+
+    #[test]
+    fn history_is_loaded_as_far_as_asked() {
+        let (_dir, repo) = repo();
+        let theme = Theme::default();
+        let mut oids = Vec::new();
+        for i in 0..5 {
+            write(&repo, "a.txt", &format!("{}\n", i));
+            oids.push(commit(&repo, &format!("commit {}", i)));
+        }
+
+        let mut history = History::new(&repo, &[]).unwrap();
+        assert!(history.load_to(&repo, 2, &theme).unwrap());
+        assert_eq!(history.commits, [oids[4], oids[3]]);
+        assert!(history.walk.is_some());
+
+        assert!(history.load_to(&repo, usize::MAX, &theme).unwrap());
+        assert_eq!(history.commits.len(), 5);
+        assert_eq!(history.lines.len(), 5);
+        assert!(history.walk.is_none());
+        assert!(!history.load_to(&repo, usize::MAX, &theme).unwrap());
+    }
+}
@@ -0,0 +1,112 @@
+use crate::theme::Theme;
+use git2::Oid;
+use ratatui::{
+    style::{Color, Style},
+    text::Span,
+};
+
+/// Lays out `git log --graph` style lanes one commit per row, in the order
+/// a topological revwalk yields them (children before their parents).
+#[derive(Default)]
+pub struct Graph {
+    /// The commit each lane is heading down to
+    lanes: Vec<Option<Oid>>,
+}
+
+impl Graph {
+    /// The graph cells in front of `id`'s row, advancing the lanes past it.
+    pub fn row(&mut self, id: Oid, parents: &[Oid], theme: &Theme) -> Vec<Span<'static>> {
+        let col = match self.lanes.iter().position(|lane| *lane == Some(id)) {
+            Some(col) => col,
+            None => self.free_lane(),
+        };
+
+        let mut cells: Vec<char> = self
+            .lanes
+            .iter()
+            .map(|lane| if lane.is_some() { '│' } else { ' ' })
+            .collect();
+        cells[col] = '●';
+        let mut joined = vec![col];
+
+        // Other children's lanes that were waiting for this commit end here
+        for (i, lane) in self.lanes.iter_mut().enumerate() {
+            if i != col && *lane == Some(id) {
+                *lane = None;
+                cells[i] = if i > col { '┘' } else { '└' };
+                joined.push(i);
+            }
+        }
+
+        self.lanes[col] = parents.first().copied();
+
+        // A merge's other parents join a lane already heading there, or open one
+        for &parent in parents.iter().skip(1) {
+            let (i, cell) = match self.lanes.iter().position(|lane| *lane == Some(parent)) {
+                Some(i) => (i, if i > col { '┤' } else { '├' }),
+                None => {
+                    let i = self.free_lane();
+                    self.lanes[i] = Some(parent);
+                    (i, if i > col { '┐' } else { '┌' })
+                }
+            };
+            cells.resize(self.lanes.len(), ' ');
+            cells[i] = cell;
+            joined.push(i);
+        }
+
+        let from = joined.iter().copied().min().unwrap_or(col);
+        let to = joined.iter().copied().max().unwrap_or(col);
+
+        let mut spans = Vec::new();
+        for (i, cell) in cells.iter().enumerate() {
+            // Lanes crossed by a connection get a horizontal run through them
+            let cell = match *cell {
+                '│' if i > from && i < to => '┼',
+                ' ' if i > from && i < to => '─',
+                cell => cell,
+            };
+            let gap = if i >= from && i < to { "─" } else { " " };
+            let color = if cell == '─' || cell == '┼' {
+                lane_color(col, theme)
+            } else {
+                lane_color(i, theme)
+            };
+
+            spans.push(Span::styled(cell.to_string(), Style::default().fg(color)));
+            spans.push(Span::styled(
+                gap,
+                Style::default().fg(lane_color(col, theme)),
+            ));
+        }
+
+        while self.lanes.last() == Some(&None) {
+            self.lanes.pop();
+        }
+
+        spans
+    }
+
+    fn free_lane(&mut self) -> usize {
+        match self.lanes.iter().position(Option::is_none) {
+            Some(i) => i,
+            None => {
+                self.lanes.push(None);
+                self.lanes.len() - 1
+            }
+        }
+    }
+}
+
+/// Lanes cycle through the theme's colors so neighbouring branches differ.
+fn lane_color(lane: usize, theme: &Theme) -> Color {
+    let palette = [
+        theme.file_header,
+        theme.addition,
+        theme.modified,
+        theme.conflict,
+        theme.deletion,
+        theme.commit_author,
+    ];
+    palette[lane % palette.len()]
+}
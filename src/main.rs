@@ -1,28 +1,327 @@
-mod repo;
+mod bisect;
+mod blame;
+mod branches;
+mod clean;
+mod clipboard;
+mod config;
+mod dash;
+mod help;
+mod history;
+mod image;
+mod json;
+mod keymap;
+mod log;
+mod panes;
+mod patch;
+mod rebase;
+mod reflog;
+mod remotes;
+mod reset;
+mod resolve;
+mod show;
+mod sign;
+mod stash;
 mod status;
+mod tags;
+mod theme;
+mod tui;
+mod whitespace;
+mod worktree;
 
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use git2::Repository;
+use helper_git_core::repo;
+use helper_git_core::status::{Section, StatusFilter};
+use std::io::{IsTerminal, stdout};
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(version, about)]
 struct Cli {
+    /// Color theme: dark, light or plain (overrides the config file)
+    #[arg(long, global = true)]
+    theme: Option<String>,
+
+    /// Config file to read instead of $XDG_CONFIG_HOME/helper-git/config.toml
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    Status,
-    // Log,
+    Status {
+        /// Number of context lines shown around each change (overrides the
+        /// config file; git's default is 3)
+        #[arg(long)]
+        context: Option<u32>,
+
+        /// Print the status as plain `XY path` lines instead of opening the UI
+        #[arg(long)]
+        porcelain: bool,
+
+        /// Print the status in a machine-readable format instead of opening the UI
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+
+        /// Print the status and diffs as text instead of opening the UI (the
+        /// default when stdout is not a terminal, which also drops the colors)
+        #[arg(long)]
+        no_tui: bool,
+
+        /// List ignored files too, like `git status --ignored` (`i` toggles
+        /// them in the UI)
+        #[arg(long)]
+        ignored: bool,
+
+        /// List only the staged side of each file
+        #[arg(long, conflicts_with = "unstaged_only")]
+        staged_only: bool,
+
+        /// List only the unstaged side of each file, untracked files included
+        #[arg(long)]
+        unstaged_only: bool,
+
+        /// Only list files that match these git pathspecs, taken relative to
+        /// the current directory like git takes them; `:/`, `:!`, `:(glob)`
+        /// and `:(literal)` magic work as in git
+        pathspecs: Vec<String>,
+    },
+    Log {
+        /// Print the commits in a machine-readable format instead of opening the UI
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+
+        /// Revisions to list the history of, like `git log main feature`;
+        /// HEAD when none are given
+        revisions: Vec<String>,
+    },
+    /// Show one commit's header, stat and patch
+    Show {
+        /// Any revision git understands: a branch, a tag, HEAD~2, a short hash
+        rev: String,
+    },
+    /// Blame a file line by line, digging back through its history
+    Blame {
+        /// Path of the file, relative to the repository root
+        path: PathBuf,
+    },
+    /// List, create, rename, delete and switch branches
+    Branches,
+    /// List, create, delete and push tags
+    Tags,
+    /// List remotes, add, rename and remove them, change URLs and prune stale branches
+    Remotes,
+    /// List, add, remove and prune worktrees, and open hg in one
+    Worktree,
+    /// Where HEAD or a branch has been, to branch or reset back to an entry
+    Reflog {
+        /// A branch to show the reflog of instead of HEAD's
+        branch: Option<String>,
+    },
+    /// Reset the current branch to a revision, soft, mixed or hard, after
+    /// seeing what each would keep and throw away
+    Reset {
+        /// Any revision git understands: a branch, a tag, HEAD~2, a short hash
+        rev: String,
+    },
+    /// Find the commit that brought a bug in, marking commits good or bad
+    Bisect {
+        /// Start a bisect: the bad revision first, then the good ones, as
+        /// `git bisect start` takes them; without any, carry on the one in
+        /// progress
+        revisions: Vec<String>,
+    },
+    /// Look over patch files and apply them to the worktree, the index or both
+    Apply {
+        /// Patch files, or directories of them like `hg log` exports
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Save, preview, apply, pop and drop stashes
+    Stash,
+    /// Pick untracked files and directories to delete, like a visual `git clean -fd`
+    Clean {
+        /// List ignored files too, like `git clean -x` (`i` toggles them)
+        #[arg(long)]
+        ignored: bool,
+    },
+    /// Reorder, reword, squash, fix up or drop the commits since <BASE>
+    Rebase {
+        /// Edit the todo list before anything is replayed, the only way
+        /// `hg rebase` runs
+        #[arg(short, long)]
+        interactive: bool,
+
+        /// The commit to replay onto: a branch, a tag, HEAD~3, a short hash
+        base: String,
+    },
+    /// Every repository under a directory with its branch, dirty files,
+    /// ahead/behind and stashes, to open the status view in one
+    Dash {
+        /// Where to look for repositories; the current directory when none
+        /// is given
+        dir: Option<PathBuf>,
+    },
+}
+
+impl Commands {
+    /// The subcommand's name when it changes or shows files, which a bare
+    /// repository hasn't got.
+    fn needs_worktree(&self) -> Option<&'static str> {
+        match self {
+            Commands::Status { .. } => Some("status"),
+            Commands::Stash => Some("stash"),
+            Commands::Clean { .. } => Some("clean"),
+            Commands::Rebase { .. } => Some("rebase"),
+            Commands::Reset { .. } => Some("reset"),
+            Commands::Bisect { .. } => Some("bisect"),
+            Commands::Apply { .. } => Some("apply"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Json,
 }
 
-fn main() -> Result<()> {
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{:#}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<()> {
     let cli = Cli::parse();
+    let config = config::Config::load(cli.config.as_deref(), cli.theme.as_deref())?;
+    let mut theme = config.theme;
+    if !config.syntax_highlighting {
+        theme.syntax = None;
+    }
+    theme.images = config.images;
+    theme.whitespace = whitespace::Whitespace::new(config.tab_width, config.show_whitespace);
+
+    // The dashboard runs outside any one repository
+    if let Commands::Dash { dir } = &cli.command {
+        return dash::run(
+            dir.as_deref(),
+            config.diff,
+            config.list_width,
+            &config.keymap,
+            &theme,
+        );
+    }
+
     let repo = repo::open_repo()?;
+    if let Some(command) = cli.command.needs_worktree() {
+        repo::require_worktree(&repo, command)?;
+    }
+    theme.whitespace = theme.whitespace.for_repo(&repo);
 
     match cli.command {
-        Commands::Status => status::status(&repo)?,
+        Commands::Status {
+            context,
+            porcelain,
+            no_tui,
+            format,
+            ignored,
+            staged_only,
+            unstaged_only,
+            pathspecs,
+        } => {
+            let mut settings = config.diff;
+            if let Some(context) = context {
+                settings.context_lines = context;
+            }
+            let only = match (staged_only, unstaged_only) {
+                (true, _) => Some(Section::Index),
+                (_, true) => Some(Section::Worktree),
+                _ => None,
+            };
+            let filter = status::filter(&repo, &pathspecs, only)?;
+            let terminal = stdout().is_terminal();
+            match format {
+                Some(Format::Json) => status::json(&repo, settings, &filter, ignored)?,
+                None if porcelain => status::porcelain(&repo, &filter, ignored)?,
+                None if no_tui || !terminal => {
+                    // Like git, NO_COLOR turns the colors off even on a terminal
+                    let color = terminal && std::env::var_os("NO_COLOR").is_none();
+                    status::text(&repo, settings, &theme, color, &filter, ignored)?
+                }
+                None => status::status(
+                    &repo,
+                    settings,
+                    config.list_width,
+                    &config.keymap,
+                    &theme,
+                    filter,
+                    ignored,
+                )?,
+            }
+        }
+        Commands::Log {
+            format: Some(Format::Json),
+            revisions,
+        } => log::json(&repo, &revisions)?,
+        Commands::Log {
+            format: None,
+            revisions,
+        } => log::log(&repo, &revisions, config.list_width, &config.keymap, &theme)?,
+        Commands::Show { rev } => show::show(&repo, &rev, &config.keymap, &theme)?,
+        Commands::Blame { path } => blame::run(&repo, &path, &config.keymap, &theme)?,
+        Commands::Branches => branches::run(&repo, &config.keymap, &theme)?,
+        Commands::Tags => tags::run(&repo, config.list_width, &config.keymap, &theme)?,
+        Commands::Remotes => remotes::run(&repo, config.list_width, &config.keymap, &theme)?,
+        Commands::Reflog { branch } => reflog::run(
+            &repo,
+            branch.as_deref(),
+            config.list_width,
+            &config.keymap,
+            &theme,
+        )?,
+        Commands::Reset { rev } => reset::run(&repo, &rev, &theme)?,
+        Commands::Bisect { revisions } => {
+            bisect::run(&repo, &revisions, config.list_width, &config.keymap, &theme)?
+        }
+        Commands::Apply { paths } => {
+            patch::run(&repo, &paths, config.list_width, &config.keymap, &theme)?
+        }
+        Commands::Worktree => {
+            if let Some(dir) = worktree::run(&repo, config.list_width, &config.keymap, &theme)? {
+                let inner = Repository::open(&dir)?;
+                let theme = theme::Theme {
+                    whitespace: theme.whitespace.for_repo(&inner),
+                    ..theme
+                };
+                status::status(
+                    &inner,
+                    config.diff,
+                    config.list_width,
+                    &config.keymap,
+                    &theme,
+                    StatusFilter::default(),
+                    false,
+                )?;
+            }
+        }
+        Commands::Stash => stash::run(&repo, config.list_width, &config.keymap, &theme)?,
+        Commands::Clean { ignored } => {
+            clean::run(&repo, config.list_width, &config.keymap, &theme, ignored)?
+        }
+        Commands::Rebase { interactive, base } => {
+            if !interactive {
+                anyhow::bail!("hg rebase only runs interactively: hg rebase -i {}", base);
+            }
+            rebase::run(&repo, &base, config.list_width, &config.keymap, &theme)?
+        }
+        // Run above, before a repository is needed
+        Commands::Dash { .. } => unreachable!(),
     };
 
     Ok(())
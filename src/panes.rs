@@ -0,0 +1,335 @@
+use crate::keymap::Action;
+use crate::theme::Theme;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, HighlightSpacing, List, ListItem, ListState, Paragraph},
+};
+
+/// Which of the two panels keys go to.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Focus {
+    Left,
+    Right,
+}
+
+/// A list on the left and the selected row shown in full on the right, the
+/// way most views are laid out: which panel has focus, the selection, the
+/// preview and how far it's scrolled. `K` tells one row's preview from
+/// another's, so it's only made again when the selection changes.
+pub struct Panes<K> {
+    pub focus: Focus,
+    pub list_state: ListState,
+    /// What the right panel shows
+    pub lines: Vec<Line<'static>>,
+    pub scroll: u16,
+    /// The row `lines` are for
+    shown: Option<K>,
+    /// Rows inside a panel's borders at the last draw, for paging
+    page_height: u16,
+    /// Percent of the width the list takes
+    list_width: u16,
+}
+
+impl<K: PartialEq> Panes<K> {
+    /// The list focused, with its first row selected.
+    pub fn new(list_width: u16) -> Self {
+        Panes {
+            focus: Focus::Left,
+            list_state: ListState::default().with_selected(Some(0)),
+            lines: Vec::new(),
+            scroll: 0,
+            shown: None,
+            page_height: 0,
+            list_width,
+        }
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        self.list_state.selected()
+    }
+
+    pub fn select(&mut self, row: Option<usize>) {
+        self.list_state.select(row);
+    }
+
+    /// The preview of `row`, from `load` unless `key` says that row is
+    /// already shown; a new one starts at its top.
+    pub fn preview<T>(
+        &mut self,
+        row: Option<T>,
+        key: impl FnOnce(&T) -> K,
+        load: impl FnOnce(T) -> Vec<Line<'static>>,
+    ) {
+        let shown = row.as_ref().map(key);
+        if shown != self.shown {
+            self.lines = row.map(load).unwrap_or_default();
+            self.scroll = 0;
+            self.shown = shown;
+        }
+    }
+
+    /// Shows `lines` as `row`'s preview, from the top, whatever was before.
+    pub fn show(&mut self, row: Option<K>, lines: Vec<Line<'static>>) {
+        self.lines = lines;
+        self.scroll = 0;
+        self.shown = row;
+    }
+
+    /// Rows inside a panel's borders at the last draw.
+    pub fn page_height(&self) -> u16 {
+        self.page_height
+    }
+
+    /// Whose preview is up.
+    pub fn shown(&self) -> Option<&K> {
+        self.shown.as_ref()
+    }
+
+    /// Makes the next [`Panes::preview`] load its row again, as it may have
+    /// changed.
+    pub fn forget(&mut self) {
+        self.shown = None;
+    }
+
+    /// Switches focus, moves the selection over `rows` rows or scrolls the
+    /// preview, whichever `action` asks for. Returns whether it was one of
+    /// those; any other is the view's to handle.
+    pub fn navigate(&mut self, action: Action, rows: usize) -> bool {
+        let last = rows.saturating_sub(1);
+        let page = self.page_height.saturating_sub(1).max(1);
+        let bottom = self.lines.len().saturating_sub(self.page_height as usize);
+        let selected = self.list_state.selected();
+        match (action, self.focus) {
+            (Action::SwitchFocus, Focus::Left) => self.focus = Focus::Right,
+            (Action::SwitchFocus, Focus::Right) => self.focus = Focus::Left,
+
+            (Action::NavigateUp, Focus::Left) => {
+                if let Some(i) = selected
+                    && i > 0
+                {
+                    self.list_state.select(Some(i - 1));
+                }
+            }
+            (Action::NavigateUp, Focus::Right) => self.scroll = self.scroll.saturating_sub(1),
+
+            (Action::NavigateDown, Focus::Left) => {
+                if let Some(i) = selected
+                    && i < last
+                {
+                    self.list_state.select(Some(i + 1));
+                }
+            }
+            (Action::NavigateDown, Focus::Right) => self.scroll = self.scroll.saturating_add(1),
+
+            (Action::PageUp | Action::PageDown, Focus::Left) => {
+                if let Some(i) = selected {
+                    self.list_state.select(Some(if action == Action::PageDown {
+                        (i + page as usize).min(last)
+                    } else {
+                        i.saturating_sub(page as usize)
+                    }));
+                }
+            }
+            (Action::PageDown, Focus::Right) => {
+                self.scroll = self.scroll.saturating_add(page).min(bottom as u16);
+            }
+            (Action::PageUp, Focus::Right) => self.scroll = self.scroll.saturating_sub(page),
+
+            (Action::Top, Focus::Left) => self.list_state.select(Some(0)),
+            (Action::Top, Focus::Right) => self.scroll = 0,
+
+            (Action::Bottom, Focus::Left) => self.list_state.select(Some(last)),
+            (Action::Bottom, Focus::Right) => self.scroll = bottom as u16,
+
+            _ => return false,
+        }
+        true
+    }
+
+    /// Draws `list` under `list_title` and the preview under `preview_title`
+    /// side by side over `area`.
+    pub fn render(
+        &mut self,
+        frame: &mut Frame,
+        area: Rect,
+        list: &List,
+        list_title: &str,
+        preview_title: &str,
+        theme: &Theme,
+    ) {
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Percentage(self.list_width),
+                Constraint::Percentage(100 - self.list_width),
+            ])
+            .split(area);
+        let block = |title, focus| {
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_style(if self.focus == focus {
+                    theme.focus()
+                } else {
+                    Style::default()
+                })
+        };
+
+        // ---------- Left Panel ----------
+        let left_block = block(list_title, Focus::Left);
+        let list_inner = left_block.inner(chunks[0]);
+        frame.render_widget(left_block, chunks[0]);
+        frame.render_stateful_widget(list, list_inner, &mut self.list_state);
+        self.page_height = list_inner.height;
+
+        // ---------- Right Panel ----------
+        let right_block = block(preview_title, Focus::Right);
+        // Re-clamp on every draw so a resize never leaves it scrolled past its end
+        let height = chunks[1].height.saturating_sub(2) as usize;
+        let bottom = self.lines.len().saturating_sub(height);
+        self.scroll = self.scroll.min(bottom.try_into().unwrap_or(u16::MAX));
+
+        // Only the lines on screen are copied into the paragraph
+        let top = (self.scroll as usize).min(self.lines.len());
+        let end = (top + height).min(self.lines.len());
+        let paragraph = Paragraph::new(self.lines[top..end].to_vec()).block(right_block);
+        frame.render_widget(paragraph, chunks[1]);
+    }
+}
+
+/// `items`, or `empty` when there are none, as the list [`Panes::render`]
+/// draws, the selected row highlighted.
+pub fn list<'a>(
+    mut items: Vec<ListItem<'a>>,
+    empty: impl Into<Text<'a>>,
+    theme: &Theme,
+) -> List<'a> {
+    if items.is_empty() {
+        items.push(ListItem::new(empty));
+    }
+    List::new(items)
+        .highlight_style(
+            Style::default()
+                .bg(theme.highlight_bg)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("➜ ")
+        .highlight_spacing(HighlightSpacing::Always)
+}
+
+/// Splits `area` into the panels and the helper line under them.
+pub fn split(area: Rect) -> (Rect, Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(0),    // top: main panels
+            Constraint::Length(1), // bottom: help line
+        ])
+        .split(area);
+    (chunks[0], chunks[1])
+}
+
+/// Draws the helper line, dimmed unless it's asking something.
+pub fn render_help(frame: &mut Frame, area: Rect, line: Line, asking: bool) {
+    let style = if asking {
+        Style::default()
+    } else {
+        Style::default().dim()
+    };
+    let paragraph = Paragraph::new(line)
+        .alignment(Alignment::Center)
+        .style(style);
+    frame.render_widget(paragraph, area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+
+    fn lines(n: usize) -> Vec<Line<'static>> {
+        (0..n).map(|i| Line::from(format!("line {}", i))).collect()
+    }
+
+    fn draw(panes: &mut Panes<usize>, rows: usize) -> Buffer {
+        let theme = Theme::default();
+        let items = (0..rows)
+            .map(|i| ListItem::new(format!("row {}", i)))
+            .collect();
+        let list = list(items, "Nothing here", &theme);
+        let mut terminal = Terminal::new(TestBackend::new(40, 12)).unwrap();
+        terminal
+            .draw(|frame| panes.render(frame, frame.area(), &list, " Rows ", " Row ", &theme))
+            .unwrap();
+        terminal.backend().buffer().clone()
+    }
+
+    fn text(buffer: &Buffer) -> String {
+        buffer.content().iter().map(|cell| cell.symbol()).collect()
+    }
+
+    #[test]
+    fn the_preview_is_only_loaded_for_a_new_row() {
+        let mut panes: Panes<usize> = Panes::new(50);
+        let mut loads = 0;
+        for row in [Some(0), Some(0), Some(1), None, None] {
+            panes.preview(
+                row,
+                |&i| i,
+                |i| {
+                    loads += 1;
+                    lines(i + 1)
+                },
+            );
+        }
+        assert_eq!(loads, 2);
+        assert!(panes.lines.is_empty());
+
+        panes.preview(Some(2), |&i| i, |i| lines(i + 1));
+        panes.scroll = 2;
+        panes.forget();
+        panes.preview(Some(2), |&i| i, |_| lines(1));
+        assert_eq!(panes.lines.len(), 1);
+        assert_eq!(panes.scroll, 0);
+    }
+
+    #[test]
+    fn keys_move_the_focused_panel() {
+        let mut panes: Panes<usize> = Panes::new(50);
+        panes.show(Some(0), lines(30));
+        draw(&mut panes, 20);
+
+        assert!(panes.navigate(Action::NavigateDown, 20));
+        assert_eq!(panes.selected(), Some(1));
+        // Ten rows inside the borders, one kept from the last page
+        assert!(panes.navigate(Action::PageDown, 20));
+        assert_eq!(panes.selected(), Some(10));
+        assert!(panes.navigate(Action::Bottom, 20));
+        assert_eq!(panes.selected(), Some(19));
+        assert!(panes.navigate(Action::NavigateDown, 20));
+        assert_eq!(panes.selected(), Some(19));
+
+        assert!(panes.navigate(Action::SwitchFocus, 20));
+        assert!(panes.navigate(Action::NavigateDown, 20));
+        assert_eq!((panes.selected(), panes.scroll), (Some(19), 1));
+        assert!(panes.navigate(Action::Bottom, 20));
+        assert_eq!(panes.scroll, 20);
+        assert!(panes.navigate(Action::Top, 20));
+        assert_eq!(panes.scroll, 0);
+
+        assert!(!panes.navigate(Action::Quit, 20));
+    }
+
+    #[test]
+    fn only_what_fits_is_drawn() {
+        let mut panes: Panes<usize> = Panes::new(50);
+        panes.show(Some(0), lines(30));
+        panes.scroll = 100;
+        let screen = text(&draw(&mut panes, 0));
+        assert!(screen.contains("Nothing here"));
+        // Scrolled back so the last line is at the bottom
+        assert_eq!(panes.scroll, 20);
+        assert!(screen.contains("line 29"));
+        assert!(!screen.contains("line 19"));
+    }
+}
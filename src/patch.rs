@@ -0,0 +1,188 @@
+pub mod mailbox;
+
+use crate::help;
+use crate::keymap::{Action, Keymap, Scope};
+use crate::panes::{self, Panes};
+use crate::theme::Theme;
+use crate::tui::{TICK, Tui};
+use anyhow::{Result, bail};
+use crossterm::event::{self, Event};
+use git2::{ApplyLocation, Repository};
+use ratatui::{prelude::*, widgets::ListItem};
+use std::path::PathBuf;
+
+/// The patch files in `paths` (a directory meaning every patch in it), with
+/// the selected one's message and diff alongside, to look over before
+/// applying each to the worktree, the index, or both.
+pub fn apply(
+    tui: &mut Tui,
+    repo: &Repository,
+    paths: &[PathBuf],
+    list_width: u16,
+    keymap: &Keymap,
+    theme: &Theme,
+) -> Result<()> {
+    let files = mailbox::files(paths)?;
+    if files.is_empty() {
+        bail!("no patches there");
+    }
+    // Where each patch went, once applied
+    let mut applied: Vec<Option<ApplyLocation>> = vec![None; files.len()];
+    let mut panes: Panes<usize> = Panes::new(list_width);
+
+    let mut message: Option<String> = None;
+    let mut show_help = false;
+
+    // ---------- Main Loop ----------
+    loop {
+        let selected = panes.selected();
+
+        // ---------- Preview ----------
+        panes.preview(
+            selected,
+            |&i| i,
+            |i| match mailbox::preview(&files[i], theme) {
+                Ok(lines) => lines,
+                Err(e) => vec![Line::styled(
+                    format!("Error: {:#}", e),
+                    Style::default().fg(theme.warning),
+                )],
+            },
+        );
+
+        // ---------- Helper line ----------
+        let help_line = match message.clone() {
+            Some(text) => Line::from(text),
+            None => help_line(keymap),
+        };
+
+        let items: Vec<ListItem> = files
+            .iter()
+            .zip(&applied)
+            .map(|(path, applied)| ListItem::new(patch_line(path, *applied, theme)))
+            .collect();
+        let list = panes::list(items, "No patches", theme);
+
+        tui.draw(|frame| {
+            let (main, help) = panes::split(frame.area());
+            panes.render(frame, main, &list, " Patches ", " Patch ", theme);
+            panes::render_help(frame, help, help_line, false);
+
+            if show_help {
+                help::render(frame, keymap, theme);
+            }
+        })?;
+
+        // ---------- Input Handling ----------
+        if !event::poll(TICK)? {
+            continue;
+        }
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        message = None;
+
+        if show_help {
+            if matches!(
+                keymap.resolve(&key, Scope::Apply),
+                Some(Action::Help | Action::Quit)
+            ) {
+                show_help = false;
+            }
+            continue;
+        }
+
+        let location = match keymap.resolve(&key, Scope::Apply) {
+            Some(Action::ApplyPatch) => Some(ApplyLocation::WorkDir),
+            Some(Action::ApplyPatchCached) => Some(ApplyLocation::Index),
+            Some(Action::ApplyPatchIndex) => Some(ApplyLocation::Both),
+            _ => None,
+        };
+        if let (Some(location), Some(i)) = (location, selected) {
+            match mailbox::apply(repo, &files[i], location) {
+                Ok(text) => {
+                    message = Some(text);
+                    applied[i] = Some(location);
+                    // On to the next of a series
+                    panes.select(Some((i + 1).min(files.len() - 1)));
+                }
+                Err(e) => message = Some(format!("Error: {:#}", e)),
+            }
+            continue;
+        }
+
+        match keymap.resolve(&key, Scope::Apply) {
+            Some(Action::Quit) => break,
+
+            Some(Action::Help) => show_help = true,
+
+            Some(Action::Refresh) => panes.forget(),
+
+            Some(action) => {
+                panes.navigate(action, files.len());
+            }
+
+            None => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// `hg apply <patch>...` outside the status view.
+pub fn run(
+    repo: &Repository,
+    paths: &[PathBuf],
+    list_width: u16,
+    keymap: &Keymap,
+    theme: &Theme,
+) -> Result<()> {
+    let mut tui = Tui::new()?;
+    apply(&mut tui, repo, paths, list_width, keymap, theme)
+}
+
+/// `0001-Fix-the-parser.patch  applied to the index`
+fn patch_line(
+    path: &std::path::Path,
+    applied: Option<ApplyLocation>,
+    theme: &Theme,
+) -> Line<'static> {
+    let mut spans = vec![Span::raw(mailbox::name(path))];
+    if let Some(location) = applied {
+        spans.push(Span::styled(
+            match location {
+                ApplyLocation::WorkDir => "  applied",
+                ApplyLocation::Index => "  applied to the index",
+                ApplyLocation::Both => "  applied and staged",
+            },
+            Style::default().fg(theme.staged),
+        ));
+    }
+    Line::from(spans)
+}
+
+fn help_line(keymap: &Keymap) -> Line<'static> {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let mut spans = vec![Span::raw(" ")];
+
+    for (i, (action, label)) in [
+        (Action::ApplyPatch, "apply"),
+        (Action::ApplyPatchCached, "apply to index"),
+        (Action::ApplyPatchIndex, "apply and stage"),
+        (Action::SwitchFocus, "switch focus"),
+        (Action::Help, "help"),
+        (Action::Quit, "quit"),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        if i > 0 {
+            spans.push(Span::raw(" • "));
+        }
+        spans.push(Span::styled(keymap.label(action), bold));
+        spans.push(Span::raw(format!(" {}", label)));
+    }
+
+    Line::from(spans)
+}
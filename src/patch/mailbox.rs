@@ -0,0 +1,193 @@
+use crate::status::diff;
+use crate::theme::Theme;
+use anyhow::{Context, Result, bail};
+use git2::{
+    ApplyLocation, ApplyOptions, Diff, DiffFindOptions, Email, EmailCreateOptions, Oid, Repository,
+};
+use ratatui::{
+    style::{Modifier, Style},
+    text::Line,
+};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The longest a patch's file name gets from its subject, as in git.
+const NAME_LENGTH: usize = 52;
+
+/// Writes each commit, oldest first, as a mailbox patch numbered `[PATCH
+/// n/m]` into `dir`, the way `git format-patch -o <dir>` does. Returns what
+/// to tell the user.
+pub fn export(repo: &Repository, oids: &[Oid], dir: &Path) -> Result<String> {
+    for oid in oids {
+        if repo.find_commit(*oid)?.parent_count() > 1 {
+            bail!("{:.7} is a merge, which can't be exported as a patch", oid);
+        }
+    }
+    fs::create_dir_all(dir).with_context(|| format!("cannot create {}", dir.display()))?;
+
+    for (i, oid) in oids.iter().enumerate() {
+        let commit = repo.find_commit(*oid)?;
+        let parent_tree = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree()?),
+            Err(_) => None,
+        };
+        let mut diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+        diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+
+        let summary = commit.summary().unwrap_or("");
+        let email = Email::from_diff(
+            &diff,
+            i + 1,
+            oids.len(),
+            oid,
+            summary,
+            commit.body().unwrap_or(""),
+            &commit.author(),
+            &mut EmailCreateOptions::new(),
+        )?;
+        let path = dir.join(file_name(i + 1, summary));
+        fs::write(&path, email.as_slice())
+            .with_context(|| format!("cannot write {}", path.display()))?;
+    }
+
+    Ok(format!(
+        "Wrote {} patch{} to {}",
+        oids.len(),
+        if oids.len() == 1 { "" } else { "es" },
+        dir.display()
+    ))
+}
+
+/// `0001-Fix-the-parser.patch`: the subject with everything but letters,
+/// digits, dots and underscores run together as dashes.
+fn file_name(number: usize, subject: &str) -> String {
+    let mut name = String::new();
+    for c in subject.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+            name.push(c);
+        } else if !name.ends_with('-') {
+            name.push('-');
+        }
+    }
+    let mut name: String = name
+        .trim_matches(['-', '.'])
+        .chars()
+        .take(NAME_LENGTH)
+        .collect();
+    while name.ends_with(['-', '.']) {
+        name.pop();
+    }
+    format!("{:04}-{}.patch", number, name)
+}
+
+/// The patch files `paths` names: each file as it is, and every `.patch`,
+/// `.diff` and `.eml` file of a directory, sorted, so a directory written by
+/// [`export`] comes back in order.
+pub fn files(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            files.push(path.clone());
+            continue;
+        }
+        let mut found: Vec<PathBuf> = fs::read_dir(path)
+            .with_context(|| format!("cannot read {}", path.display()))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| {
+                p.is_file()
+                    && p.extension()
+                        .is_some_and(|ext| ext == "patch" || ext == "diff" || ext == "eml")
+            })
+            .collect();
+        found.sort();
+        files.extend(found);
+    }
+    Ok(files)
+}
+
+/// The mail's subject, author and message, when it is one, then the diff
+/// as the diff pane draws it.
+pub fn preview(path: &Path, theme: &Theme) -> Result<Vec<Line<'static>>> {
+    let bytes = fs::read(path).with_context(|| format!("cannot read {}", path.display()))?;
+    let text = String::from_utf8_lossy(&bytes);
+    let mut lines = Vec::new();
+
+    // A mailbox patch: headers, a blank line, the message, then `---`
+    if text.starts_with("From ") {
+        let (headers, rest) = text.split_once("\n\n").unwrap_or((&text, ""));
+        for header in headers.lines().skip(1) {
+            if let Some(subject) = header.strip_prefix("Subject: ") {
+                lines.insert(
+                    0,
+                    Line::styled(
+                        subject.to_string(),
+                        Style::default()
+                            .fg(theme.accent)
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                );
+            } else if let Some(from) = header.strip_prefix("From: ") {
+                lines.push(Line::from(format!("Author: {}", from)));
+            } else if let Some(date) = header.strip_prefix("Date: ") {
+                lines.push(Line::from(format!("Date:   {}", date)));
+            }
+        }
+        let message = match rest.strip_prefix("---\n") {
+            Some(_) => "",
+            None => rest.split("\n---\n").next().unwrap_or(""),
+        };
+        if !message.trim().is_empty() {
+            lines.push(Line::from(""));
+            for line in message.trim_end().lines() {
+                lines.push(Line::from(format!("    {}", line)));
+            }
+        }
+        lines.push(Line::from(""));
+    }
+
+    let diff = Diff::from_buffer(diff_part(&bytes)).context("not a patch git can read")?;
+    lines.extend(diff::render_diff(&diff, theme)?.lines);
+    Ok(lines)
+}
+
+/// Applies the patch to the worktree, the index, or both as `git apply`,
+/// `git apply --cached` and `git apply --index` do; nothing is changed when
+/// any of it doesn't apply.
+pub fn apply(repo: &Repository, path: &Path, location: ApplyLocation) -> Result<String> {
+    let bytes = fs::read(path).with_context(|| format!("cannot read {}", path.display()))?;
+    let diff = Diff::from_buffer(diff_part(&bytes)).context("not a patch git can read")?;
+
+    repo.apply(&diff, location, Some(ApplyOptions::new().check(true)))
+        .with_context(|| format!("{} doesn't apply", name(path)))?;
+    repo.apply(&diff, location, None)?;
+
+    let to = match location {
+        ApplyLocation::WorkDir => "the worktree",
+        ApplyLocation::Index => "the index",
+        ApplyLocation::Both => "the worktree and the index",
+    };
+    Ok(format!("Applied {} to {}", name(path), to))
+}
+
+/// The patch itself, from its first `diff --git` to the `-- ` a mail signs
+/// off with, leaving out the message and diffstat before it.
+fn diff_part(bytes: &[u8]) -> &[u8] {
+    let find = |needle: &[u8]| bytes.windows(needle.len()).position(|w| w == needle);
+    let start = if bytes.starts_with(b"diff --git ") {
+        0
+    } else {
+        find(b"\ndiff --git ").map_or(0, |i| i + 1)
+    };
+    let bytes = &bytes[start..];
+    match bytes.windows(5).rposition(|w| w == b"\n-- \n") {
+        Some(end) => &bytes[..end + 1],
+        None => bytes,
+    }
+}
+
+/// The file's name, for messages and the list.
+pub fn name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
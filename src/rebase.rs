@@ -0,0 +1,298 @@
+pub mod sequencer;
+pub mod stopped;
+pub mod todo;
+
+use crate::help;
+use crate::keymap::{Action, Keymap, Scope};
+use crate::log;
+use crate::panes::{self, Panes};
+use crate::status::message::{InputResult, MessageInput};
+use crate::theme::Theme;
+use crate::tui::{TICK, Tui};
+use anyhow::{Result, bail};
+use crossterm::event::{self, Event, KeyCode};
+use git2::{Oid, Repository};
+use ratatui::{prelude::*, widgets::ListItem};
+use sequencer::{Operation, Progress, Sequencer};
+use todo::{Step, Verb};
+
+/// `git rebase -i <base>` as a list to edit: each commit since `base` gets a
+/// verb, and can be moved up and down, with its diff alongside. Nothing
+/// happens until the rebase is started; a conflict then stops it in
+/// [`stopped::stopped`] until it is continued or aborted. Returns what to
+/// print once the view has closed.
+pub fn rebase(
+    tui: &mut Tui,
+    repo: &Repository,
+    base: &str,
+    list_width: u16,
+    keymap: &Keymap,
+    theme: &Theme,
+) -> Result<Option<String>> {
+    let onto = repo.revparse_single(base)?.peel_to_commit()?;
+    let (mut steps, merges) = todo::load(repo, onto.id())?;
+    if steps.is_empty() {
+        bail!(
+            "nothing to rebase: HEAD has no commits that {} doesn't",
+            base
+        );
+    }
+    let mut panes: Panes<Oid> = Panes::new(list_width);
+
+    let mut message: Option<String> = None;
+    let mut reword: Option<MessageInput> = None;
+    let mut confirm_start = false;
+    let mut show_help = false;
+
+    // ---------- Main Loop ----------
+    loop {
+        let rows = steps.len();
+
+        // ---------- Preview ----------
+        let previewed = panes.selected().and_then(|i| steps.get(i)).map(|s| s.oid);
+        panes.preview(
+            previewed,
+            |&oid| oid,
+            |oid| {
+                log::show_commit(repo, oid, theme)
+                    .map(|diff| diff.lines)
+                    .unwrap_or_else(|e| vec![Line::from(format!("Error: {}", e))])
+            },
+        );
+
+        // ---------- Helper line ----------
+        let help_line = match (confirm_start, message.clone()) {
+            (true, _) => Line::styled(
+                format!(
+                    "Rebase {} onto {:.7}? (y/n)",
+                    count(steps.iter().filter(|s| s.verb != Verb::Drop).count()),
+                    onto.id()
+                ),
+                Style::default()
+                    .fg(theme.warning)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            (false, Some(text)) => Line::from(text),
+            (false, None) => help_line(keymap),
+        };
+
+        let items: Vec<ListItem> = steps.iter().map(|step| step_line(step, theme)).collect();
+        let list = panes::list(items, "Nothing to rebase", theme);
+        let title = match merges {
+            0 => format!(" Rebase onto {:.7} ", onto.id()),
+            _ => format!(
+                " Rebase onto {:.7} · {} merge{} left out ",
+                onto.id(),
+                merges,
+                if merges == 1 { "" } else { "s" }
+            ),
+        };
+
+        tui.draw(|frame| {
+            let (main, help) = panes::split(frame.area());
+            panes.render(frame, main, &list, &title, " Commit ", theme);
+            panes::render_help(frame, help, help_line, confirm_start);
+
+            if let Some(input) = &reword {
+                input.render(frame, theme);
+            }
+
+            if show_help {
+                help::render(frame, keymap, theme);
+            }
+        })?;
+
+        // ---------- Input Handling ----------
+        if !event::poll(TICK)? {
+            continue;
+        }
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        message = None;
+
+        // The question swallows every key until it is answered
+        if confirm_start {
+            match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    let mut sequencer = Sequencer::start(
+                        repo,
+                        Operation::Rebase,
+                        onto.id(),
+                        std::mem::take(&mut steps),
+                    )?;
+                    return match sequencer.run(repo) {
+                        Ok(Progress::Done(text)) => Ok(Some(text)),
+                        Ok(Progress::Conflict(step, _)) => {
+                            stopped::stopped(tui, repo, sequencer, step, list_width, keymap, theme)
+                                .map(Some)
+                        }
+                        Err(e) => {
+                            // Nothing half done is left behind
+                            sequencer.abort(repo)?;
+                            Err(e)
+                        }
+                    };
+                }
+                KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => confirm_start = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        if let Some(input) = &mut reword {
+            match input.handle(&key) {
+                InputResult::Editing => {}
+                InputResult::Cancelled => reword = None,
+                InputResult::Submitted(text) => {
+                    if let Some(step) = panes.selected().and_then(|i| steps.get_mut(i)) {
+                        if text.trim().is_empty() {
+                            message = Some(String::from("Error: the message can't be empty"));
+                        } else {
+                            step.message = format!("{}\n", text.trim_end());
+                            step.summary = text.lines().next().unwrap_or("").to_string();
+                            step.verb = Verb::Reword;
+                        }
+                    }
+                    reword = None;
+                }
+            }
+            continue;
+        }
+
+        if show_help {
+            if matches!(
+                keymap.resolve(&key, Scope::Rebase),
+                Some(Action::Help | Action::Quit)
+            ) {
+                show_help = false;
+            }
+            continue;
+        }
+
+        let selected = panes.selected();
+        match keymap.resolve(&key, Scope::Rebase) {
+            Some(Action::Quit) => return Ok(None),
+
+            Some(Action::Help) => show_help = true,
+
+            Some(
+                action @ (Action::RebasePick
+                | Action::RebaseSquash
+                | Action::RebaseFixup
+                | Action::RebaseDrop),
+            ) => {
+                if let Some(step) = selected.and_then(|i| steps.get_mut(i)) {
+                    step.verb = match action {
+                        Action::RebasePick => Verb::Pick,
+                        Action::RebaseSquash => Verb::Squash,
+                        Action::RebaseFixup => Verb::Fixup,
+                        _ => Verb::Drop,
+                    };
+                }
+            }
+
+            Some(Action::RebaseReword) => {
+                if let Some(step) = selected.and_then(|i| steps.get(i)) {
+                    reword = Some(MessageInput::new(
+                        format!("Reword {:.7}", step.oid),
+                        step.message.trim_end().to_string(),
+                    ));
+                }
+            }
+
+            Some(action @ (Action::MoveUp | Action::MoveDown)) => {
+                if let Some(i) = selected {
+                    let j = if action == Action::MoveUp {
+                        i.checked_sub(1)
+                    } else {
+                        Some(i + 1).filter(|&j| j < steps.len())
+                    };
+                    if let Some(j) = j {
+                        steps.swap(i, j);
+                        panes.select(Some(j));
+                    }
+                }
+            }
+
+            Some(Action::StartRebase) => match todo::check(repo, &steps) {
+                Ok(()) => confirm_start = true,
+                Err(e) => message = Some(format!("Error: {}", e)),
+            },
+
+            Some(action) => {
+                panes.navigate(action, rows);
+            }
+
+            None => {}
+        }
+    }
+}
+
+/// `hg rebase -i <base>`, printing how it went once the view has closed.
+pub fn run(
+    repo: &Repository,
+    base: &str,
+    list_width: u16,
+    keymap: &Keymap,
+    theme: &Theme,
+) -> Result<()> {
+    let mut tui = Tui::new()?;
+    let outcome = rebase(&mut tui, repo, base, list_width, keymap, theme)?;
+    drop(tui);
+    if let Some(text) = outcome {
+        println!("{}", text);
+    }
+    Ok(())
+}
+
+/// `pick   1a2b3c4 summary`, the verb in its color and a dropped commit dimmed.
+fn step_line(step: &Step, theme: &Theme) -> ListItem<'static> {
+    let rest = if step.verb == Verb::Drop {
+        Style::default().dim().add_modifier(Modifier::CROSSED_OUT)
+    } else {
+        Style::default()
+    };
+    ListItem::new(Line::from(vec![
+        Span::styled(
+            format!("{:<7}", step.verb.name()),
+            Style::default().fg(step.verb.color(theme)),
+        ),
+        Span::styled(format!("{:.7}", step.oid), rest.fg(theme.commit_sha)),
+        Span::styled(format!(" {}", step.summary), rest),
+    ]))
+}
+
+/// `1 commit`, `3 commits`.
+fn count(n: usize) -> String {
+    format!("{} commit{}", n, if n == 1 { "" } else { "s" })
+}
+
+fn help_line(keymap: &Keymap) -> Line<'static> {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let mut spans = vec![Span::raw(" ")];
+
+    for (i, (action, label)) in [
+        (Action::RebasePick, "pick"),
+        (Action::RebaseReword, "reword"),
+        (Action::RebaseSquash, "squash"),
+        (Action::RebaseFixup, "fixup"),
+        (Action::RebaseDrop, "drop"),
+        (Action::MoveUp, "move up"),
+        (Action::MoveDown, "move down"),
+        (Action::StartRebase, "start"),
+        (Action::Quit, "quit"),
+    ]
+    .iter()
+    .enumerate()
+    {
+        if i > 0 {
+            spans.push(Span::raw(" • "));
+        }
+        spans.push(Span::styled(keymap.label(*action), bold));
+        spans.push(Span::raw(format!(" {}", label)));
+    }
+
+    Line::from(spans)
+}
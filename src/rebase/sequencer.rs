@@ -0,0 +1,268 @@
+use super::todo::{Step, Verb};
+use anyhow::{Result, bail};
+use git2::{Commit, Oid, Repository, ResetType, RevertOptions, Tree};
+use helper_git_core::status;
+use std::path::PathBuf;
+
+/// What a sequencer is carrying out, for its messages and the reflog.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Operation {
+    Rebase,
+    CherryPick,
+    Revert,
+}
+
+impl Operation {
+    pub fn name(self) -> &'static str {
+        match self {
+            Operation::Rebase => "rebase",
+            Operation::CherryPick => "cherry-pick",
+            Operation::Revert => "revert",
+        }
+    }
+}
+
+/// Where a replay got to.
+pub enum Progress {
+    /// Every step is done and the branch moved; what to tell the user
+    Done(String),
+    /// The step at this index left conflicts in the worktree, in these files
+    Conflict(usize, Vec<PathBuf>),
+}
+
+/// Carries out a todo list like git's sequencer, though without writing it
+/// to disk: steps are replayed in memory, one cherry-pick onto the last
+/// result at a time, and the branch only moves once they're all through.
+/// The worktree is left alone unless a step conflicts; then the worktree is
+/// moved to where the replay got to and the pick is applied there, markers
+/// and all, so the conflicts can be resolved before it goes on.
+pub struct Sequencer {
+    operation: Operation,
+    steps: Vec<Step>,
+    next: usize,
+    original: Oid,
+    /// What HEAD named when the replay started; `None` when it was detached
+    branch: Option<String>,
+    /// The replay's result so far
+    head: Oid,
+    /// Whether HEAD and the worktree were moved to stop on a conflict
+    stopped: bool,
+    replayed: usize,
+    skipped: usize,
+}
+
+impl Sequencer {
+    /// Takes the list as it stands; [`todo::check`](super::todo::check) it
+    /// first. A cherry-pick goes onto HEAD itself.
+    pub fn start(
+        repo: &Repository,
+        operation: Operation,
+        onto: Oid,
+        steps: Vec<Step>,
+    ) -> Result<Self> {
+        let head = repo.head()?;
+        Ok(Sequencer {
+            operation,
+            steps,
+            next: 0,
+            original: head.peel_to_commit()?.id(),
+            branch: head
+                .is_branch()
+                .then(|| head.name().map(String::from))
+                .flatten(),
+            head: onto,
+            stopped: false,
+            replayed: 0,
+            skipped: 0,
+        })
+    }
+
+    /// Replays steps until one conflicts or they're all done.
+    pub fn run(&mut self, repo: &Repository) -> Result<Progress> {
+        while let Some(step) = self.steps.get(self.next) {
+            if step.verb == Verb::Drop {
+                self.next += 1;
+                continue;
+            }
+
+            let commit = repo.find_commit(step.oid)?;
+            let onto = repo.find_commit(self.head)?;
+            let mut index = match step.verb {
+                Verb::Revert { mainline } => repo.revert_commit(&commit, &onto, mainline, None)?,
+                _ => repo.cherrypick_commit(&commit, &onto, 0, None)?,
+            };
+            if index.has_conflicts() {
+                return self.stop(repo, &commit);
+            }
+
+            let tree = repo.find_tree(index.write_tree_to(repo)?)?;
+            self.record(repo, &tree)?;
+            self.next += 1;
+        }
+
+        self.finish(repo)
+    }
+
+    /// Goes on after a conflict: the index, now free of conflicts, becomes
+    /// the stopped step's commit.
+    pub fn resume(&mut self, repo: &Repository) -> Result<Progress> {
+        let mut index = repo.index()?;
+        if index.has_conflicts() {
+            bail!("resolve every conflicted file first");
+        }
+
+        let tree = repo.find_tree(index.write_tree()?)?;
+        self.record(repo, &tree)?;
+        repo.cleanup_state()?;
+        repo.set_head_detached(self.head)?;
+        self.next += 1;
+        self.run(repo)
+    }
+
+    /// Puts the branch, index and worktree back the way they were.
+    pub fn abort(&self, repo: &Repository) -> Result<()> {
+        if !self.stopped {
+            return Ok(());
+        }
+
+        repo.cleanup_state()?;
+        match &self.branch {
+            Some(name) => repo.set_head(name)?,
+            None => repo.set_head_detached(self.original)?,
+        }
+        let original = repo.find_object(self.original, None)?;
+        repo.reset(&original, ResetType::Hard, None)?;
+        Ok(())
+    }
+
+    /// Conflicted files right now, as the index lists them.
+    pub fn conflicts(repo: &Repository) -> Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        for conflict in repo.index()?.conflicts()? {
+            let conflict = conflict?;
+            let entry = conflict.our.or(conflict.their).or(conflict.ancestor);
+            if let Some(entry) = entry {
+                paths.push(status::bytes_path(&entry.path));
+            }
+        }
+        Ok(paths)
+    }
+
+    /// Commits `tree` for the current step, or folds it into the commit
+    /// before for a squash or fixup. A pick that adds nothing any more, its
+    /// changes already being there, drops out, as does a revert with nothing
+    /// left to undo.
+    fn record(&mut self, repo: &Repository, tree: &Tree) -> Result<()> {
+        let step = &self.steps[self.next];
+        let commit = repo.find_commit(step.oid)?;
+        let previous = repo.find_commit(self.head)?;
+        let committer = repo.signature()?;
+
+        self.head = if step.verb.folds() {
+            // Squashed commits keep the first one's author, as in git
+            let message = match step.verb {
+                Verb::Squash => format!(
+                    "{}\n\n{}",
+                    previous.message().unwrap_or("").trim_end(),
+                    step.message
+                ),
+                _ => previous.message().unwrap_or("").to_string(),
+            };
+            let parents: Vec<Commit> = previous.parents().collect();
+            let parents: Vec<&Commit> = parents.iter().collect();
+            repo.commit(
+                None,
+                &previous.author(),
+                &committer,
+                &message,
+                tree,
+                &parents,
+            )?
+        } else if tree.id() == previous.tree_id() {
+            self.skipped += 1;
+            return Ok(());
+        } else if let Verb::Revert { .. } = step.verb {
+            // A revert is the committer's own work, as in git
+            self.replayed += 1;
+            repo.commit(
+                None,
+                &committer,
+                &committer,
+                &step.message,
+                tree,
+                &[&previous],
+            )?
+        } else {
+            self.replayed += 1;
+            repo.commit(
+                None,
+                &commit.author(),
+                &committer,
+                &step.message,
+                tree,
+                &[&previous],
+            )?
+        };
+
+        Ok(())
+    }
+
+    fn stop(&mut self, repo: &Repository, commit: &Commit) -> Result<Progress> {
+        // Safe checkouts only: the worktree was clean when the replay started
+        let onto = repo.find_commit(self.head)?;
+        repo.checkout_tree(onto.as_object(), None)?;
+        repo.set_head_detached(self.head)?;
+        self.stopped = true;
+
+        match self.steps[self.next].verb {
+            Verb::Revert { mainline } => {
+                repo.revert(commit, Some(RevertOptions::new().mainline(mainline)))?
+            }
+            _ => repo.cherrypick(commit, None)?,
+        }
+        Ok(Progress::Conflict(self.next, Self::conflicts(repo)?))
+    }
+
+    fn finish(&mut self, repo: &Repository) -> Result<Progress> {
+        let result = repo.find_commit(self.head)?;
+        repo.checkout_tree(result.as_object(), None)?;
+
+        let message = match self.operation {
+            Operation::Rebase => format!("rebase -i (finish): returning to {:.7}", self.head),
+            Operation::CherryPick => format!("cherry-pick: now at {:.7}", self.head),
+            Operation::Revert => format!("revert: now at {:.7}", self.head),
+        };
+        match &self.branch {
+            Some(name) => {
+                repo.reference(name, self.head, true, &message)?;
+                repo.set_head(name)?;
+            }
+            None => repo.set_head_detached(self.head)?,
+        }
+
+        let mut text = format!(
+            "{} {} commit{}: now at {:.7}",
+            match self.operation {
+                Operation::Rebase => "Rebased",
+                Operation::CherryPick => "Picked",
+                Operation::Revert => "Reverted",
+            },
+            self.replayed,
+            if self.replayed == 1 { "" } else { "s" },
+            self.head
+        );
+        if self.skipped > 0 {
+            text.push_str(&format!(" · {} already applied, dropped", self.skipped));
+        }
+        Ok(Progress::Done(text))
+    }
+
+    /// The todo list being carried out.
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    pub fn operation(&self) -> Operation {
+        self.operation
+    }
+}
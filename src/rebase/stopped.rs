@@ -0,0 +1,198 @@
+use super::sequencer::{Operation, Progress, Sequencer};
+use super::todo::{Step, Verb};
+use crate::help;
+use crate::keymap::{Action, Keymap, Scope};
+use crate::log;
+use crate::panes::{self, Panes};
+use crate::resolve;
+use crate::theme::Theme;
+use crate::tui::{TICK, Tui};
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode};
+use git2::Repository;
+use ratatui::{prelude::*, widgets::ListItem};
+use std::path::PathBuf;
+
+/// A replay stopped on a conflict at `step`: the conflicted files, each
+/// opening in the conflict resolver, beside the commit that conflicted, until
+/// it's continued through to the end or aborted. Returns what to tell the user.
+pub fn stopped(
+    tui: &mut Tui,
+    repo: &Repository,
+    mut sequencer: Sequencer,
+    mut step: usize,
+    list_width: u16,
+    keymap: &Keymap,
+    theme: &Theme,
+) -> Result<String> {
+    let name = sequencer.operation().name();
+    let mut conflicts: Vec<PathBuf> = Sequencer::conflicts(repo)?;
+    let mut panes: Panes<usize> = Panes::new(list_width);
+
+    let mut message: Option<String> = None;
+    let mut confirm_abort = false;
+    let mut show_help = false;
+
+    // ---------- Main Loop ----------
+    loop {
+        let current = &sequencer.steps()[step];
+        panes.preview(
+            Some(current),
+            |_| step,
+            |current| {
+                log::show_commit(repo, current.oid, theme)
+                    .map(|diff| diff.lines)
+                    .unwrap_or_else(|e| vec![Line::from(format!("Error: {}", e))])
+            },
+        );
+
+        // ---------- Helper line ----------
+        let help_line = match (confirm_abort, message.clone()) {
+            (true, _) => Line::styled(
+                format!(
+                    "Abort the {} and put everything back as it was? (y/n)",
+                    name
+                ),
+                Style::default()
+                    .fg(theme.warning)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            (false, Some(text)) => Line::from(text),
+            (false, None) => help_line(keymap),
+        };
+
+        let items: Vec<ListItem> = conflicts
+            .iter()
+            .map(|path| {
+                ListItem::new(Line::styled(
+                    path.to_string_lossy().into_owned(),
+                    Style::default().fg(theme.conflict),
+                ))
+            })
+            .collect();
+        let resolved = Line::styled(
+            format!("All conflicts resolved; continue the {}", name),
+            Style::default().dim(),
+        );
+        let list = panes::list(items, resolved, theme);
+        // Which step of how many, counting only those that do something
+        let kept = |s: &&Step| s.verb != Verb::Drop;
+        let title = format!(
+            " Conflicts in {:.7} {} · {}/{} ",
+            current.oid,
+            current.summary,
+            sequencer.steps()[..=step].iter().filter(kept).count(),
+            sequencer.steps().iter().filter(kept).count()
+        );
+
+        tui.draw(|frame| {
+            let (main, help) = panes::split(frame.area());
+            panes.render(frame, main, &list, &title, " Commit ", theme);
+            panes::render_help(frame, help, help_line, confirm_abort);
+
+            if show_help {
+                help::render(frame, keymap, theme);
+            }
+        })?;
+
+        // ---------- Input Handling ----------
+        if !event::poll(TICK)? {
+            continue;
+        }
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        message = None;
+
+        // The question swallows every key until it is answered
+        if confirm_abort {
+            match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    sequencer.abort(repo)?;
+                    return Ok(format!(
+                        "{} aborted; nothing was changed",
+                        match sequencer.operation() {
+                            Operation::Rebase => "Rebase",
+                            Operation::CherryPick => "Cherry-pick",
+                            Operation::Revert => "Revert",
+                        }
+                    ));
+                }
+                KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => confirm_abort = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        if show_help {
+            if matches!(
+                keymap.resolve(&key, Scope::Rebase),
+                Some(Action::Help | Action::Quit)
+            ) {
+                show_help = false;
+            }
+            continue;
+        }
+
+        let selected = panes.selected();
+        let rows = conflicts.len();
+        match keymap.resolve(&key, Scope::Rebase) {
+            Some(Action::Quit) => confirm_abort = true,
+
+            Some(Action::Help) => show_help = true,
+
+            Some(Action::Select) => {
+                if let Some(path) = selected.and_then(|i| conflicts.get(i)).cloned() {
+                    match resolve::resolve(tui, repo, &path, keymap, theme) {
+                        Ok(resolved) => message = resolved,
+                        Err(e) => message = Some(format!("Error: {}", e)),
+                    }
+                    conflicts = Sequencer::conflicts(repo)?;
+                    panes.select(Some(
+                        selected.unwrap_or(0).min(conflicts.len().saturating_sub(1)),
+                    ));
+                }
+            }
+
+            Some(Action::ContinueRebase) => match sequencer.resume(repo) {
+                Ok(Progress::Done(text)) => return Ok(text),
+                Ok(Progress::Conflict(next, paths)) => {
+                    step = next;
+                    conflicts = paths;
+                    panes.select(Some(0));
+                }
+                Err(e) => message = Some(format!("Error: {}", e)),
+            },
+
+            Some(action) => {
+                panes.navigate(action, rows);
+            }
+
+            None => {}
+        }
+    }
+}
+
+fn help_line(keymap: &Keymap) -> Line<'static> {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let mut spans = vec![Span::raw(" ")];
+
+    for (i, (action, label)) in [
+        (Action::Select, "resolve"),
+        (Action::ContinueRebase, "continue"),
+        (Action::SwitchFocus, "switch focus"),
+        (Action::Quit, "abort"),
+    ]
+    .iter()
+    .enumerate()
+    {
+        if i > 0 {
+            spans.push(Span::raw(" • "));
+        }
+        spans.push(Span::styled(keymap.label(*action), bold));
+        spans.push(Span::raw(format!(" {}", label)));
+    }
+
+    Line::from(spans)
+}
@@ -0,0 +1,113 @@
+use crate::theme::Theme;
+use anyhow::{Result, bail};
+use git2::{Commit, Oid, Repository, Sort, StatusOptions};
+use ratatui::style::Color;
+
+/// What happens to one commit of the todo list, as in `git rebase -i`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Verb {
+    Pick,
+    /// Pick, with the message replaced
+    Reword,
+    /// Fold into the commit before, keeping both messages
+    Squash,
+    /// Fold into the commit before, keeping only its message
+    Fixup,
+    Drop,
+    /// Undo the commit, as `git revert -m <mainline>` does; a mainline of 0
+    /// is for a commit that isn't a merge
+    Revert {
+        mainline: u32,
+    },
+}
+
+impl Verb {
+    pub fn name(self) -> &'static str {
+        match self {
+            Verb::Pick => "pick",
+            Verb::Reword => "reword",
+            Verb::Squash => "squash",
+            Verb::Fixup => "fixup",
+            Verb::Drop => "drop",
+            Verb::Revert { .. } => "revert",
+        }
+    }
+
+    pub fn color(self, theme: &Theme) -> Color {
+        match self {
+            Verb::Pick => theme.staged,
+            Verb::Reword => theme.accent,
+            Verb::Squash | Verb::Fixup => theme.modified,
+            Verb::Drop | Verb::Revert { .. } => theme.untracked,
+        }
+    }
+
+    /// Whether the commit is folded into the one before it.
+    pub fn folds(self) -> bool {
+        matches!(self, Verb::Squash | Verb::Fixup)
+    }
+}
+
+pub struct Step {
+    pub verb: Verb,
+    pub oid: Oid,
+    pub summary: String,
+    /// The commit's message, or the new one once reworded
+    pub message: String,
+}
+
+impl Step {
+    pub fn pick(commit: &Commit) -> Self {
+        Step {
+            verb: Verb::Pick,
+            oid: commit.id(),
+            summary: commit.summary().unwrap_or("").to_string(),
+            message: commit.message().unwrap_or("").to_string(),
+        }
+    }
+}
+
+/// The commits `git rebase -i <base>` would list: those on HEAD but not on
+/// `base`, oldest first. Merges are left out, as git leaves them out without
+/// `--rebase-merges`; the second value is how many were.
+pub fn load(repo: &Repository, base: Oid) -> Result<(Vec<Step>, usize)> {
+    let mut walk = repo.revwalk()?;
+    walk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+    walk.push_head()?;
+    walk.hide(base)?;
+
+    let mut steps = Vec::new();
+    let mut merges = 0;
+    for oid in walk {
+        let commit = repo.find_commit(oid?)?;
+        if commit.parent_count() > 1 {
+            merges += 1;
+            continue;
+        }
+        steps.push(Step::pick(&commit));
+    }
+
+    Ok((steps, merges))
+}
+
+/// Fails when the list can't be carried out as it stands, or when local
+/// changes are in the way.
+pub fn check(repo: &Repository, steps: &[Step]) -> Result<()> {
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(false);
+    if !repo.statuses(Some(&mut opts))?.is_empty() {
+        bail!("commit or stash your changes first");
+    }
+
+    let mut kept = steps.iter().filter(|s| s.verb != Verb::Drop);
+    if let Some(first) = kept.next()
+        && first.verb.folds()
+    {
+        bail!(
+            "cannot {} {:.7}: there is no commit before it to fold into",
+            first.verb.name(),
+            first.oid
+        );
+    }
+    Ok(())
+}
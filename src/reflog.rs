@@ -0,0 +1,257 @@
+mod entry;
+
+use crate::help;
+use crate::keymap::{Action, Keymap, Scope};
+use crate::log;
+use crate::panes::{self, Panes};
+use crate::reset::{ResetAction, ResetPopup};
+use crate::theme::Theme;
+use crate::tui::{TICK, Tui};
+use anyhow::{Result, bail};
+use crossterm::event::{self, Event, KeyCode};
+use entry::ReflogEntry;
+use git2::Repository;
+use ratatui::{prelude::*, widgets::ListItem};
+
+/// The reflog of HEAD or of a branch: every place the ref has been, newest
+/// first, with the commit of the selected entry alongside. `b` steps through
+/// HEAD and the branches. A branch can be created at an entry, or the current
+/// branch reset to it, to get back what a reset, rebase or amend left behind.
+pub fn reflog(
+    tui: &mut Tui,
+    repo: &Repository,
+    refname: Option<&str>,
+    list_width: u16,
+    keymap: &Keymap,
+    theme: &Theme,
+) -> Result<()> {
+    let refs = entry::refs(repo)?;
+    let mut current = match refname {
+        None => 0,
+        Some(name) => match refs
+            .iter()
+            .position(|r| r == name || entry::short_name(r) == name)
+        {
+            Some(i) => i,
+            None => bail!("no reflog for {}", name),
+        },
+    };
+    let mut entries = entry::load(repo, &refs[current])?;
+    let mut panes: Panes<String> = Panes::new(list_width);
+
+    let mut message: Option<String> = None;
+    let mut prompt: Option<String> = None;
+    // The reset being chosen, to the entry it was opened on
+    let mut reset_popup: Option<ResetPopup> = None;
+    let mut show_help = false;
+
+    // ---------- Main Loop ----------
+    loop {
+        let selected = panes.selected().and_then(|i| entries.get(i));
+
+        // ---------- Preview ----------
+        panes.preview(
+            selected,
+            |e| format!("{} {}", e.selector, e.new),
+            |e| entry::show(repo, e, theme),
+        );
+
+        // ---------- Helper line ----------
+        let help_line = match (&prompt, selected, message.clone()) {
+            (Some(name), Some(entry), _) => Line::from(vec![
+                Span::styled(
+                    format!("New branch at {}: ", entry.selector),
+                    Style::default().fg(theme.accent),
+                ),
+                Span::raw(name.clone()),
+                Span::styled("█", Style::default().fg(theme.accent)),
+            ]),
+            (.., Some(text)) => Line::from(text),
+            _ => help_line(keymap),
+        };
+
+        let items: Vec<ListItem> = entries
+            .iter()
+            .map(|e| ListItem::new(entry_line(e, theme)))
+            .collect();
+        let list = panes::list(items, "No entries", theme);
+        let title = format!(" Reflog of {} ", entry::short_name(&refs[current]));
+
+        tui.draw(|frame| {
+            let (main, help) = panes::split(frame.area());
+            panes.render(frame, main, &list, &title, " Entry ", theme);
+            panes::render_help(frame, help, help_line, prompt.is_some());
+
+            if let Some(popup) = &reset_popup {
+                popup.render(frame, theme);
+            }
+            if show_help {
+                help::render(frame, keymap, theme);
+            }
+        })?;
+
+        // ---------- Input Handling ----------
+        if !event::poll(TICK)? {
+            continue;
+        }
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        message = None;
+
+        let selected = panes.selected().and_then(|i| entries.get(i));
+
+        // A pending reset swallows every key until it is done or cancelled
+        if let Some(popup) = &mut reset_popup {
+            match popup.handle(&key) {
+                ResetAction::Waiting => {}
+                ResetAction::Cancelled => reset_popup = None,
+                ResetAction::Reset(mode) => {
+                    message = Some(match popup.reset(repo, mode) {
+                        Ok(text) => text,
+                        Err(e) => format!("Error: {}", e),
+                    });
+                    reset_popup = None;
+                    // The reset is the newest entry now, in HEAD's reflog and the branch's
+                    entries = entry::load(repo, &refs[current])?;
+                    panes.select(Some(0));
+                    panes.forget();
+                }
+            }
+            continue;
+        }
+
+        if let (Some(name), Some(selected)) = (&mut prompt, selected) {
+            match key.code {
+                KeyCode::Esc => prompt = None,
+                KeyCode::Backspace => {
+                    name.pop();
+                }
+                KeyCode::Char(c) => name.push(c),
+                KeyCode::Enter => {
+                    message = Some(match entry::branch_at(repo, name.trim(), selected) {
+                        Ok(text) => text,
+                        Err(e) => format!("Error: {}", e),
+                    });
+                    prompt = None;
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        if show_help {
+            if matches!(
+                keymap.resolve(&key, Scope::Reflog),
+                Some(Action::Help | Action::Quit)
+            ) {
+                show_help = false;
+            }
+            continue;
+        }
+
+        match keymap.resolve(&key, Scope::Reflog) {
+            Some(Action::Quit) => break,
+
+            Some(Action::Help) => show_help = true,
+
+            Some(Action::Refresh) => {
+                entries = entry::load(repo, &refs[current])?;
+                panes.select(Some(
+                    panes
+                        .selected()
+                        .unwrap_or(0)
+                        .min(entries.len().saturating_sub(1)),
+                ));
+                panes.forget();
+            }
+
+            Some(Action::SwitchReflog) => {
+                current = (current + 1) % refs.len();
+                entries = entry::load(repo, &refs[current])?;
+                panes.select(Some(0));
+                panes.forget();
+            }
+
+            Some(Action::BranchAtEntry) if selected.is_some() => prompt = Some(String::new()),
+
+            Some(Action::ResetToEntry) => {
+                if let Some(entry) = selected {
+                    match ResetPopup::new(repo, entry.new, entry.selector.clone()) {
+                        Ok(popup) => reset_popup = Some(popup),
+                        // Garbage collection may have taken the entry's commit
+                        Err(e) => message = Some(format!("Error: {}", e)),
+                    }
+                }
+            }
+
+            Some(action) => {
+                panes.navigate(action, entries.len());
+            }
+
+            None => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// `hg reflog [<ref>]` outside the status view.
+pub fn run(
+    repo: &Repository,
+    refname: Option<&str>,
+    list_width: u16,
+    keymap: &Keymap,
+    theme: &Theme,
+) -> Result<()> {
+    let mut tui = Tui::new()?;
+    reflog(&mut tui, repo, refname, list_width, keymap, theme)
+}
+
+/// `HEAD@{2}  1a2b3c4  3 hours ago  checkout: moving from main to topic`
+fn entry_line(entry: &ReflogEntry, theme: &Theme) -> Line<'static> {
+    Line::from(vec![
+        Span::styled(
+            format!("{:.7}", entry.new),
+            Style::default().fg(theme.commit_sha),
+        ),
+        Span::raw(" "),
+        Span::styled(
+            format!("{:<10}", entry.selector),
+            Style::default().fg(theme.accent),
+        ),
+        Span::raw(" "),
+        Span::styled(
+            log::relative_date(entry.when),
+            Style::default().fg(theme.commit_date),
+        ),
+        Span::raw(" "),
+        Span::raw(entry.message.clone()),
+    ])
+}
+
+fn help_line(keymap: &Keymap) -> Line<'static> {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let mut spans = vec![Span::raw(" ")];
+
+    for (i, (action, label)) in [
+        (Action::SwitchReflog, "next ref"),
+        (Action::BranchAtEntry, "branch here"),
+        (Action::ResetToEntry, "reset here"),
+        (Action::SwitchFocus, "switch focus"),
+        (Action::Help, "help"),
+        (Action::Quit, "quit"),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        if i > 0 {
+            spans.push(Span::raw(" • "));
+        }
+        spans.push(Span::styled(keymap.label(action), bold));
+        spans.push(Span::raw(format!(" {}", label)));
+    }
+
+    Line::from(spans)
+}
@@ -0,0 +1,103 @@
+use crate::log;
+use crate::theme::Theme;
+use anyhow::{Result, bail};
+use git2::{BranchType, Oid, Reference, Repository, Time};
+use ratatui::{style::Style, text::Line};
+
+/// One line of `git reflog`: where the ref moved and why.
+pub struct ReflogEntry {
+    /// `HEAD@{2}`, `main@{0}`
+    pub selector: String,
+    pub old: Oid,
+    pub new: Oid,
+    pub committer: String,
+    pub when: Time,
+    pub message: String,
+}
+
+/// HEAD, then every local branch that has a reflog, as full ref names.
+pub fn refs(repo: &Repository) -> Result<Vec<String>> {
+    let mut refs = vec![String::from("HEAD")];
+    for branch in repo.branches(Some(BranchType::Local))? {
+        let (branch, _) = branch?;
+        if let Some(name) = branch.get().name()
+            && repo.reflog(name).is_ok_and(|log| !log.is_empty())
+        {
+            refs.push(name.to_string());
+        }
+    }
+    Ok(refs)
+}
+
+/// `main` for `refs/heads/main`; `HEAD` stays as it is.
+pub fn short_name(refname: &str) -> &str {
+    refname.strip_prefix("refs/heads/").unwrap_or(refname)
+}
+
+/// The ref's reflog, newest first, as `git reflog show <ref>` lists it.
+pub fn load(repo: &Repository, refname: &str) -> Result<Vec<ReflogEntry>> {
+    let reflog = repo.reflog(refname)?;
+    let short = short_name(refname);
+
+    Ok(reflog
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let committer = entry.committer();
+            ReflogEntry {
+                selector: format!("{}@{{{}}}", short, i),
+                old: entry.id_old(),
+                new: entry.id_new(),
+                committer: committer.to_string(),
+                when: committer.when(),
+                message: entry.message().unwrap_or("").to_string(),
+            }
+        })
+        .collect())
+}
+
+/// The entry's header, then the commit it moved the ref to as `git show`
+/// prints it.
+pub fn show(repo: &Repository, entry: &ReflogEntry, theme: &Theme) -> Vec<Line<'static>> {
+    let mut lines = vec![
+        Line::styled(entry.selector.clone(), Style::default().fg(theme.accent)),
+        Line::from(format!("Reflog: {}", entry.committer)),
+        Line::from(format!("Date:   {}", log::relative_date(entry.when))),
+    ];
+    if !entry.old.is_zero() {
+        lines.push(Line::from(format!(
+            "Moved:  {:.7} → {:.7}",
+            entry.old, entry.new
+        )));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(format!("    {}", entry.message)));
+    lines.push(Line::from(""));
+
+    match log::show_commit(repo, entry.new, theme) {
+        Ok(commit) => lines.extend(commit.lines),
+        // Garbage collection may have taken what the ref once pointed at
+        Err(_) => lines.push(Line::styled(
+            format!("{} no longer exists", entry.new),
+            Style::default().fg(theme.warning),
+        )),
+    }
+    lines
+}
+
+/// Creates local branch `name` at the entry's commit, without switching to
+/// it, which is how a commit lost to a reset or rebase gets a name again.
+pub fn branch_at(repo: &Repository, name: &str, entry: &ReflogEntry) -> Result<String> {
+    let refname = format!("refs/heads/{}", name);
+    if name.is_empty() || !Reference::is_valid_name(&refname) {
+        bail!("\"{}\" isn't a valid branch name", name);
+    }
+    if repo.find_reference(&refname).is_ok() {
+        bail!("a branch named {} already exists", name);
+    }
+    repo.branch(name, &repo.find_commit(entry.new)?, false)?;
+    Ok(format!(
+        "Created {} at {} ({:.7})",
+        name, entry.selector, entry.new
+    ))
+}
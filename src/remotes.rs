@@ -0,0 +1,351 @@
+mod entry;
+
+use crate::help;
+use crate::keymap::{Action, Keymap, Scope};
+use crate::panes::{self, Panes};
+use crate::status::remote::{self, RemoteOp, RemoteTask};
+use crate::theme::Theme;
+use crate::tui::{TICK, Tui};
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode};
+use entry::RemoteInfo;
+use git2::Repository;
+use ratatui::{
+    prelude::*,
+    widgets::{ListItem, ListState},
+};
+
+/// What is being typed on the helper line.
+enum Prompt {
+    /// A new remote's name
+    AddName(String),
+    /// Its URL, the name already given
+    AddUrl {
+        name: String,
+        url: String,
+    },
+    Rename(String),
+    SetUrl(String),
+}
+
+/// Every remote with its URLs, and its remote-tracking branches and what
+/// tracks them alongside. Remotes can be added, renamed, removed, pointed at
+/// a new URL, and pruned of branches that are gone from them.
+pub fn remotes(
+    tui: &mut Tui,
+    repo: &Repository,
+    list_width: u16,
+    keymap: &Keymap,
+    theme: &Theme,
+) -> Result<()> {
+    let mut remotes = entry::load(repo)?;
+    let mut panes: Panes<String> = Panes::new(list_width);
+
+    let mut message: Option<String> = None;
+    let mut prompt: Option<Prompt> = None;
+    let mut confirm_remove: Option<String> = None;
+    let mut task: Option<RemoteTask> = None;
+    let mut show_help = false;
+
+    // ---------- Main Loop ----------
+    loop {
+        if let Some(done) = task.as_ref().and_then(|t| t.poll()) {
+            message = Some(match done {
+                Ok(text) => text,
+                Err(e) => format!("Error: {}", e),
+            });
+            task = None;
+            let name = selected_name(&remotes, &panes.list_state);
+            reload(repo, &mut remotes, &mut panes.list_state, name.as_deref())?;
+            panes.forget();
+        }
+
+        let selected = panes.selected().and_then(|i| remotes.get(i));
+
+        // ---------- Preview ----------
+        panes.preview(selected, |r| r.name.clone(), |r| entry::show(r, theme));
+
+        // ---------- Helper line ----------
+        let help_line = match (&prompt, &confirm_remove, &task, message.clone()) {
+            (Some(current), ..) => {
+                let (label, text) = match (current, selected) {
+                    (Prompt::AddName(name), _) => (String::from("New remote: "), name),
+                    (Prompt::AddUrl { name, url }, _) => (format!("URL of {}: ", name), url),
+                    (Prompt::Rename(name), Some(remote)) => {
+                        (format!("Rename {} to: ", remote.name), name)
+                    }
+                    (Prompt::SetUrl(url), Some(remote)) => {
+                        (format!("New URL of {}: ", remote.name), url)
+                    }
+                    (Prompt::Rename(text) | Prompt::SetUrl(text), None) => (String::new(), text),
+                };
+                Line::from(vec![
+                    Span::styled(label, Style::default().fg(theme.accent)),
+                    Span::raw(text.clone()),
+                    Span::styled("█", Style::default().fg(theme.accent)),
+                ])
+            }
+            (None, Some(name), ..) => Line::styled(
+                format!(
+                    "Remove remote {} and its remote-tracking branches? (y/n)",
+                    name
+                ),
+                Style::default()
+                    .fg(theme.warning)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            (None, None, Some(task), _) => Line::from(task.label()),
+            (None, None, None, Some(text)) => Line::from(text),
+            (None, None, None, None) => help_line(keymap),
+        };
+
+        let width = remotes
+            .iter()
+            .map(|r| r.name.chars().count())
+            .max()
+            .unwrap_or(0);
+        let items: Vec<ListItem> = remotes
+            .iter()
+            .map(|r| ListItem::new(remote_line(r, width, theme)))
+            .collect();
+        let list = panes::list(items, "No remotes yet", theme);
+
+        tui.draw(|frame| {
+            let (main, help) = panes::split(frame.area());
+            panes.render(frame, main, &list, " Remotes ", " Remote ", theme);
+            panes::render_help(
+                frame,
+                help,
+                help_line,
+                prompt.is_some() || confirm_remove.is_some(),
+            );
+
+            if show_help {
+                help::render(frame, keymap, theme);
+            }
+        })?;
+
+        // ---------- Input Handling ----------
+        if !event::poll(TICK)? {
+            continue;
+        }
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        message = None;
+
+        let selected = panes.selected().and_then(|i| remotes.get(i));
+
+        // A pending remove swallows every key until it is answered
+        if let Some(name) = &confirm_remove {
+            match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    message = Some(match entry::remove(repo, name) {
+                        Ok(text) => text,
+                        Err(e) => format!("Error: {}", e),
+                    });
+                    confirm_remove = None;
+                    reload(repo, &mut remotes, &mut panes.list_state, None)?;
+                    panes.forget();
+                }
+                KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => confirm_remove = None,
+                _ => {}
+            }
+            continue;
+        }
+
+        if let Some(current) = &mut prompt {
+            let text = match current {
+                Prompt::AddName(text) | Prompt::Rename(text) | Prompt::SetUrl(text) => text,
+                Prompt::AddUrl { url, .. } => url,
+            };
+            match key.code {
+                KeyCode::Esc => prompt = None,
+                KeyCode::Backspace => {
+                    text.pop();
+                }
+                KeyCode::Char(c) => text.push(c),
+                KeyCode::Enter => {
+                    let done = match (prompt.take(), selected) {
+                        (Some(Prompt::AddName(name)), _) => {
+                            prompt = Some(Prompt::AddUrl {
+                                name: name.trim().to_string(),
+                                url: String::new(),
+                            });
+                            continue;
+                        }
+                        (Some(Prompt::AddUrl { name, url }), _) => {
+                            entry::add(repo, &name, url.trim()).map(|text| (text, name))
+                        }
+                        (Some(Prompt::Rename(new_name)), Some(remote)) => {
+                            let new_name = new_name.trim().to_string();
+                            entry::rename(repo, &remote.name, &new_name)
+                                .map(|text| (text, new_name))
+                        }
+                        (Some(Prompt::SetUrl(url)), Some(remote)) => {
+                            entry::set_url(repo, &remote.name, url.trim())
+                                .map(|text| (text, remote.name.clone()))
+                        }
+                        _ => continue,
+                    };
+                    match done {
+                        Ok((text, name)) => {
+                            message = Some(text);
+                            reload(repo, &mut remotes, &mut panes.list_state, Some(&name))?;
+                            panes.forget();
+                        }
+                        Err(e) => message = Some(format!("Error: {}", e)),
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        if show_help {
+            if matches!(
+                keymap.resolve(&key, Scope::Remotes),
+                Some(Action::Help | Action::Quit)
+            ) {
+                show_help = false;
+            }
+            continue;
+        }
+
+        match keymap.resolve(&key, Scope::Remotes) {
+            Some(Action::Quit) => break,
+
+            Some(Action::Help) => show_help = true,
+
+            Some(Action::Refresh) => {
+                let name = selected.map(|r| r.name.clone());
+                reload(repo, &mut remotes, &mut panes.list_state, name.as_deref())?;
+                panes.forget();
+            }
+
+            Some(Action::AddRemote) => prompt = Some(Prompt::AddName(String::new())),
+
+            Some(Action::RenameRemote) => {
+                if let Some(remote) = selected {
+                    prompt = Some(Prompt::Rename(remote.name.clone()));
+                }
+            }
+
+            Some(Action::SetRemoteUrl) => {
+                if let Some(remote) = selected {
+                    prompt = Some(Prompt::SetUrl(remote.url.clone().unwrap_or_default()));
+                }
+            }
+
+            Some(Action::RemoveRemote) => {
+                if let Some(remote) = selected {
+                    confirm_remove = Some(remote.name.clone());
+                }
+            }
+
+            Some(Action::PruneRemote) => match selected {
+                Some(_) if task.is_some() => {
+                    message = Some(String::from("Error: wait for the last prune to finish"));
+                }
+                Some(remote) => {
+                    let name = remote.name.clone();
+                    task = Some(RemoteTask::spawn_job(
+                        repo,
+                        RemoteOp::Prune,
+                        move |repo, _| remote::prune(repo, &name),
+                    ));
+                }
+                None => {}
+            },
+
+            Some(action) => {
+                panes.navigate(action, remotes.len());
+            }
+
+            None => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// `hg remotes` outside the status view.
+pub fn run(repo: &Repository, list_width: u16, keymap: &Keymap, theme: &Theme) -> Result<()> {
+    let mut tui = Tui::new()?;
+    remotes(&mut tui, repo, list_width, keymap, theme)
+}
+
+fn selected_name(remotes: &[RemoteInfo], list_state: &ListState) -> Option<String> {
+    list_state
+        .selected()
+        .and_then(|i| remotes.get(i))
+        .map(|r| r.name.clone())
+}
+
+/// Reloads the remotes, selecting `name` if it still exists, or else the
+/// same row (clamped).
+fn reload(
+    repo: &Repository,
+    remotes: &mut Vec<RemoteInfo>,
+    list_state: &mut ListState,
+    name: Option<&str>,
+) -> Result<()> {
+    *remotes = entry::load(repo)?;
+
+    let by_name = name.and_then(|name| remotes.iter().position(|r| r.name == name));
+    let row = list_state
+        .selected()
+        .unwrap_or(0)
+        .min(remotes.len().saturating_sub(1));
+    list_state.select(Some(by_name.unwrap_or(row)));
+    Ok(())
+}
+
+/// `origin  git@github.com:me/repo.git  3 branches`
+fn remote_line(remote: &RemoteInfo, width: usize, theme: &Theme) -> Line<'static> {
+    let branches = remote.branches.len();
+    Line::from(vec![
+        Span::styled(
+            format!("{:<width$}", remote.name),
+            Style::default().fg(theme.accent),
+        ),
+        Span::raw(" "),
+        Span::raw(remote.url.clone().unwrap_or_default()),
+        Span::styled(
+            format!(
+                "  {} branch{}",
+                branches,
+                if branches == 1 { "" } else { "es" }
+            ),
+            Style::default().dim(),
+        ),
+    ])
+}
+
+fn help_line(keymap: &Keymap) -> Line<'static> {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let mut spans = vec![Span::raw(" ")];
+
+    for (i, (action, label)) in [
+        (Action::AddRemote, "add"),
+        (Action::RenameRemote, "rename"),
+        (Action::SetRemoteUrl, "set URL"),
+        (Action::RemoveRemote, "remove"),
+        (Action::PruneRemote, "prune"),
+        (Action::SwitchFocus, "switch focus"),
+        (Action::Help, "help"),
+        (Action::Quit, "quit"),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        if i > 0 {
+            spans.push(Span::raw(" • "));
+        }
+        spans.push(Span::styled(keymap.label(action), bold));
+        spans.push(Span::raw(format!(" {}", label)));
+    }
+
+    Line::from(spans)
+}
@@ -0,0 +1,239 @@
+use crate::theme::Theme;
+use anyhow::{Result, bail};
+use git2::{BranchType, Oid, Remote, Repository};
+use ratatui::{
+    style::{Modifier, Style},
+    text::{Line, Span},
+};
+
+/// A local branch that tracks one of the remote's branches, and how far
+/// ahead and behind it is.
+pub struct Tracker {
+    pub local: String,
+    pub ahead: usize,
+    pub behind: usize,
+}
+
+/// One of the remote's branches as the last fetch saw it.
+pub struct TrackingBranch {
+    /// Short name, `origin/main`
+    pub name: String,
+    pub target: Option<Oid>,
+    pub summary: String,
+    pub tracked_by: Vec<Tracker>,
+}
+
+/// One row of the remotes view, like a line of `git remote -v`.
+pub struct RemoteInfo {
+    pub name: String,
+    pub url: Option<String>,
+    /// Set only when it differs from the fetch URL
+    pub push_url: Option<String>,
+    pub refspecs: Vec<String>,
+    pub branches: Vec<TrackingBranch>,
+}
+
+/// Every remote, sorted by name, with its remote-tracking branches.
+pub fn load(repo: &Repository) -> Result<Vec<RemoteInfo>> {
+    let mut remotes = Vec::new();
+
+    for name in repo.remotes()?.iter().flatten() {
+        let remote = repo.find_remote(name)?;
+        remotes.push(RemoteInfo {
+            name: name.to_string(),
+            url: remote.url().map(String::from),
+            push_url: remote
+                .pushurl()
+                .filter(|push| Some(*push) != remote.url())
+                .map(String::from),
+            refspecs: refspecs(&remote),
+            branches: tracking_branches(repo, name)?,
+        });
+    }
+
+    remotes.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(remotes)
+}
+
+fn refspecs(remote: &Remote) -> Vec<String> {
+    remote
+        .refspecs()
+        .filter_map(|spec| spec.str().map(String::from))
+        .collect()
+}
+
+/// The remote-tracking branches under `name/`, each with the local branches
+/// that track it. The remote's symbolic `HEAD` is left out.
+fn tracking_branches(repo: &Repository, name: &str) -> Result<Vec<TrackingBranch>> {
+    let prefix = format!("{}/", name);
+
+    // Which local branch tracks what, worked out once for all of them
+    let mut trackers: Vec<(String, String, Option<Oid>)> = Vec::new();
+    for branch in repo.branches(Some(BranchType::Local))? {
+        let (branch, _) = branch?;
+        let Some(local) = branch.name()?.map(String::from) else {
+            continue;
+        };
+        if let Ok(upstream) = branch.upstream()
+            && let Some(upstream_name) = upstream.name()?
+        {
+            trackers.push((upstream_name.to_string(), local, branch.get().target()));
+        }
+    }
+
+    let mut branches = Vec::new();
+    for branch in repo.branches(Some(BranchType::Remote))? {
+        let (branch, _) = branch?;
+        let Some(short) = branch.name()?.map(String::from) else {
+            continue;
+        };
+        if !short.starts_with(&prefix) || short.ends_with("/HEAD") {
+            continue;
+        }
+
+        let target = branch.get().target();
+        let summary = target
+            .and_then(|oid| repo.find_commit(oid).ok())
+            .and_then(|c| c.summary().map(String::from))
+            .unwrap_or_default();
+        let tracked_by = trackers
+            .iter()
+            .filter(|(upstream, ..)| *upstream == short)
+            .map(|(_, local, local_target)| {
+                let (ahead, behind) = match (local_target, target) {
+                    (Some(local), Some(upstream)) => {
+                        repo.graph_ahead_behind(*local, upstream).unwrap_or((0, 0))
+                    }
+                    _ => (0, 0),
+                };
+                Tracker {
+                    local: local.clone(),
+                    ahead,
+                    behind,
+                }
+            })
+            .collect();
+
+        branches.push(TrackingBranch {
+            name: short,
+            target,
+            summary,
+            tracked_by,
+        });
+    }
+
+    branches.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(branches)
+}
+
+/// URLs, refspecs, then every remote-tracking branch with what tracks it.
+pub fn show(remote: &RemoteInfo, theme: &Theme) -> Vec<Line<'static>> {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let mut lines = vec![
+        Line::styled(
+            format!("remote {}", remote.name),
+            Style::default().fg(theme.accent),
+        ),
+        Line::from(format!(
+            "URL:      {}",
+            remote.url.as_deref().unwrap_or("(none)")
+        )),
+    ];
+    if let Some(push) = &remote.push_url {
+        lines.push(Line::from(format!("Push URL: {}", push)));
+    }
+    for spec in &remote.refspecs {
+        lines.push(Line::from(format!("Refspec:  {}", spec)));
+    }
+    lines.push(Line::from(""));
+
+    if remote.branches.is_empty() {
+        lines.push(Line::styled(
+            "No remote-tracking branches; fetch to get them",
+            Style::default().dim(),
+        ));
+        return lines;
+    }
+
+    lines.push(Line::styled("Remote-tracking branches", bold));
+    let width = remote
+        .branches
+        .iter()
+        .map(|b| b.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    for branch in &remote.branches {
+        let mut spans = vec![
+            Span::raw(format!("  {:<width$} ", branch.name)),
+            Span::styled(
+                branch
+                    .target
+                    .map(|oid| format!("{:.7}", oid))
+                    .unwrap_or_default(),
+                Style::default().fg(theme.commit_sha),
+            ),
+            Span::raw(" "),
+        ];
+        for tracker in &branch.tracked_by {
+            let counts = match (tracker.ahead, tracker.behind) {
+                (0, 0) => String::new(),
+                (ahead, 0) => format!(" ↑{}", ahead),
+                (0, behind) => format!(" ↓{}", behind),
+                (ahead, behind) => format!(" ↑{} ↓{}", ahead, behind),
+            };
+            spans.push(Span::styled(
+                format!("[{}{}] ", tracker.local, counts),
+                Style::default().fg(theme.staged),
+            ));
+        }
+        spans.push(Span::raw(branch.summary.clone()));
+        lines.push(Line::from(spans));
+    }
+
+    lines
+}
+
+pub fn add(repo: &Repository, name: &str, url: &str) -> Result<String> {
+    if url.is_empty() {
+        bail!("the remote needs a URL");
+    }
+    repo.remote(name, url)?;
+    Ok(format!(
+        "Added remote {} ({}); fetch to get its branches",
+        name, url
+    ))
+}
+
+/// Renames the remote along with its remote-tracking branches and the
+/// upstream config of the branches that track them, as `git remote rename`.
+pub fn rename(repo: &Repository, name: &str, new_name: &str) -> Result<String> {
+    if new_name == name {
+        bail!("{} already has that name", name);
+    }
+    let problems = repo.remote_rename(name, new_name)?;
+    // Fetch refspecs that weren't the default can't be renamed for us
+    let problems: Vec<&str> = problems.iter().flatten().collect();
+    if problems.is_empty() {
+        Ok(format!("Renamed remote {} to {}", name, new_name))
+    } else {
+        Ok(format!(
+            "Renamed remote {} to {}; update these refspecs by hand: {}",
+            name,
+            new_name,
+            problems.join(", ")
+        ))
+    }
+}
+
+pub fn remove(repo: &Repository, name: &str) -> Result<String> {
+    repo.remote_delete(name)?;
+    Ok(format!("Removed remote {}", name))
+}
+
+pub fn set_url(repo: &Repository, name: &str, url: &str) -> Result<String> {
+    if url.is_empty() {
+        bail!("the remote needs a URL");
+    }
+    repo.remote_set_url(name, url)?;
+    Ok(format!("{} now fetches from {}", name, url))
+}
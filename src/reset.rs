@@ -0,0 +1,400 @@
+use crate::stash::entry as stash;
+use crate::theme::Theme;
+use crate::tui::{TICK, Tui};
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEvent};
+use git2::{Oid, Repository, ResetType};
+use helper_git_core::status::{ChangeKind, StatusFilter, collect_status};
+use ratatui::{
+    layout::Flex,
+    prelude::*,
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+};
+
+/// How many of the commits leaving the branch are listed by name.
+const LISTED: usize = 5;
+
+pub enum ResetAction {
+    Waiting,
+    Cancelled,
+    /// Reset in this mode, a hard one having been typed out
+    Reset(ResetType),
+}
+
+/// The popup a reset is chosen in, which says before anything moves what
+/// each mode keeps and what it throws away: the commits that leave the
+/// branch, and the staged and unstaged changes. A hard reset has the branch
+/// name typed first, and stashes the changes it would lose unless told not to.
+pub struct ResetPopup {
+    target: Oid,
+    /// How the target was picked: `HEAD@{2}`, `v1.0`, a short hash
+    label: String,
+    summary: String,
+    /// The branch that moves, or `HEAD` when detached
+    branch: String,
+    /// The first few commits on the branch that aren't behind the target,
+    /// newest first, and how many there are
+    leaving: Vec<(Oid, String)>,
+    left: usize,
+    /// How many commits the branch gains, when the target isn't behind it
+    joining: usize,
+    staged: usize,
+    unstaged: usize,
+    untracked: usize,
+    /// Stash the uncommitted changes before a hard reset
+    stash: bool,
+    /// What has been typed to confirm a hard reset, once `h` is pressed
+    typed: Option<String>,
+}
+
+impl ResetPopup {
+    pub fn new(repo: &Repository, target: Oid, label: impl Into<String>) -> Result<Self> {
+        let commit = repo.find_commit(target)?;
+        let branch = match repo.head() {
+            Ok(head) if head.is_branch() => head.shorthand().unwrap_or("HEAD").to_string(),
+            _ => String::from("HEAD"),
+        };
+
+        let (mut leaving, mut left, mut joining) = (Vec::new(), 0, 0);
+        // An unborn HEAD has no commits to leave or to compare against
+        if let Ok(head) = repo.head()
+            && let Some(head) = head.target()
+        {
+            let mut revwalk = repo.revwalk()?;
+            revwalk.push(head)?;
+            revwalk.hide(target)?;
+            for oid in revwalk {
+                let oid = oid?;
+                if leaving.len() < LISTED {
+                    let summary = repo.find_commit(oid)?.summary().unwrap_or("").to_string();
+                    leaving.push((oid, summary));
+                }
+                left += 1;
+            }
+
+            let mut revwalk = repo.revwalk()?;
+            revwalk.push(target)?;
+            revwalk.hide(head)?;
+            joining = revwalk.count();
+        }
+
+        let (mut staged, mut unstaged, mut untracked) = (0, 0, 0);
+        for file in collect_status(repo, false, &StatusFilter::default())? {
+            if file.conflicted || file.staged.is_some() {
+                staged += 1;
+            }
+            match &file.unstaged {
+                Some(change) if change.kind == ChangeKind::Untracked => untracked += 1,
+                Some(_) => unstaged += 1,
+                None => {}
+            }
+        }
+
+        Ok(ResetPopup {
+            target,
+            label: label.into(),
+            summary: commit.summary().unwrap_or("").to_string(),
+            branch,
+            leaving,
+            left,
+            joining,
+            staged,
+            unstaged,
+            untracked,
+            stash: true,
+            typed: None,
+        })
+    }
+
+    pub fn handle(&mut self, key: &KeyEvent) -> ResetAction {
+        // Typing the branch name swallows every key until Enter or Esc
+        if let Some(typed) = &mut self.typed {
+            match key.code {
+                KeyCode::Esc => self.typed = None,
+                KeyCode::Backspace => {
+                    typed.pop();
+                }
+                KeyCode::Enter if typed.trim() == self.branch => {
+                    return ResetAction::Reset(ResetType::Hard);
+                }
+                KeyCode::Char(c) => typed.push(c),
+                _ => {}
+            }
+            return ResetAction::Waiting;
+        }
+
+        match key.code {
+            KeyCode::Char('s') => return ResetAction::Reset(ResetType::Soft),
+            KeyCode::Char('m') => return ResetAction::Reset(ResetType::Mixed),
+            KeyCode::Char('h') => self.typed = Some(String::new()),
+            KeyCode::Char('a') => self.stash = !self.stash,
+            KeyCode::Esc | KeyCode::Char('q') => return ResetAction::Cancelled,
+            _ => {}
+        }
+        ResetAction::Waiting
+    }
+
+    /// Moves the branch (or a detached HEAD) to the target as
+    /// `git reset --<mode>` does, after stashing what a hard reset would
+    /// throw away when `stash` is set.
+    pub fn reset(&self, repo: &Repository, mode: ResetType) -> Result<String> {
+        let stashed = mode == ResetType::Hard && self.stash && self.staged + self.unstaged > 0;
+        if stashed {
+            stash::save(
+                repo,
+                &format!("hg: before reset to {:.7}", self.target),
+                false,
+            )?;
+        }
+
+        let commit = repo.find_commit(self.target)?;
+        repo.reset(commit.as_object(), mode, None)?;
+        let hash = format!("{:.7}", self.target);
+        let to = if self.label == hash {
+            hash
+        } else {
+            format!("{} {}", self.label, hash)
+        };
+        Ok(format!(
+            "Reset {} ({}) to {}{}",
+            self.branch,
+            mode_name(mode),
+            to,
+            if stashed {
+                "; the uncommitted changes are in stash@{0}"
+            } else {
+                ""
+            }
+        ))
+    }
+
+    /// What a soft, a mixed and a hard reset would each do.
+    fn effects(&self) -> [String; 3] {
+        let changes = self.staged + self.unstaged;
+        let (soft, mixed) = if self.left > 0 {
+            (
+                "keeps every change; the commits' changes come back staged",
+                "keeps the worktree; the commits' and the staged changes come back unstaged",
+            )
+        } else if self.joining > 0 {
+            (
+                "keeps the index and worktree, which then undo the commits that join",
+                "keeps the worktree, which then undoes the commits that join, unstaged",
+            )
+        } else {
+            (
+                "keeps the index and worktree as they are",
+                "keeps the worktree; the staged changes come back unstaged",
+            )
+        };
+        let lost = match (self.left > 0, changes) {
+            (false, 0) => String::from("has nothing to throw away"),
+            (true, 0) => String::from("throws away the commits' changes"),
+            (true, _) if self.stash => format!(
+                "throws away the commits' changes; {} uncommitted change{} go to a stash first",
+                changes,
+                plural(changes)
+            ),
+            (false, _) if self.stash => format!(
+                "{} uncommitted change{} go to a stash, then the worktree is clean",
+                changes,
+                plural(changes)
+            ),
+            (true, _) => format!(
+                "throws away the commits' changes and {} uncommitted change{}",
+                changes,
+                plural(changes)
+            ),
+            (false, _) => format!(
+                "throws away {} uncommitted change{}",
+                changes,
+                plural(changes)
+            ),
+        };
+        [soft.to_string(), mixed.to_string(), lost]
+    }
+
+    pub fn render(&self, frame: &mut Frame, theme: &Theme) {
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let dim = Style::default().dim();
+        let warning = Style::default().fg(theme.warning);
+
+        let mut lines = vec![
+            Line::from(vec![
+                Span::raw(format!("{} → ", self.branch)),
+                Span::styled(
+                    format!("{:.7}", self.target),
+                    Style::default().fg(theme.commit_sha),
+                ),
+                Span::raw(format!(" {}", self.summary)),
+            ]),
+            Line::raw(""),
+        ];
+
+        // ---------- Commits ----------
+        match (self.left, self.joining) {
+            (0, 0) => lines.push(Line::styled(
+                format!("{} is already there; only changes are reset", self.branch),
+                dim,
+            )),
+            (left, joining) => {
+                if left > 0 {
+                    lines.push(Line::raw(format!(
+                        "{} commit{} leave{} {}, still reachable from the reflog:",
+                        left,
+                        plural(left),
+                        if left == 1 { "s" } else { "" },
+                        self.branch
+                    )));
+                    for (oid, summary) in &self.leaving {
+                        lines.push(Line::from(vec![
+                            Span::raw("  "),
+                            Span::styled(
+                                format!("{:.7}", oid),
+                                Style::default().fg(theme.commit_sha),
+                            ),
+                            Span::raw(format!(" {}", summary)),
+                        ]));
+                    }
+                    if left > self.leaving.len() {
+                        lines.push(Line::styled(
+                            format!("  … and {} more", left - self.leaving.len()),
+                            dim,
+                        ));
+                    }
+                }
+                if joining > 0 {
+                    lines.push(Line::raw(format!(
+                        "{} commit{} join{} {} from {}",
+                        joining,
+                        plural(joining),
+                        if joining == 1 { "s" } else { "" },
+                        self.branch,
+                        self.label
+                    )));
+                }
+            }
+        }
+
+        // ---------- Changes ----------
+        lines.push(Line::raw(if self.staged + self.unstaged == 0 {
+            String::from("No uncommitted changes")
+        } else {
+            format!(
+                "Uncommitted: {} staged, {} unstaged",
+                self.staged, self.unstaged
+            )
+        }));
+        if self.untracked > 0 {
+            lines.push(Line::styled(
+                format!(
+                    "{} untracked file{}, which every mode leaves alone",
+                    self.untracked,
+                    plural(self.untracked)
+                ),
+                dim,
+            ));
+        }
+        lines.push(Line::raw(""));
+
+        // ---------- Modes ----------
+        let [soft, mixed, hard] = self.effects();
+        for (key, name, effect, style) in [
+            ("s", "soft ", soft, Style::default()),
+            ("m", "mixed", mixed, Style::default()),
+            ("h", "hard ", hard, warning),
+        ] {
+            lines.push(Line::from(vec![
+                Span::styled(format!("{} ", key), bold),
+                Span::styled(format!("{}  ", name), bold),
+                Span::styled(effect, style),
+            ]));
+        }
+        lines.push(Line::from(vec![
+            Span::styled("a ", bold),
+            Span::raw(format!(
+                "safety stash before a hard reset: {}",
+                if self.stash { "on" } else { "off" }
+            )),
+        ]));
+
+        // ---------- Typed confirmation ----------
+        let bottom = match &self.typed {
+            Some(typed) => {
+                lines.push(Line::raw(""));
+                lines.push(Line::from(vec![
+                    Span::styled(
+                        format!("Type {} to reset hard: ", self.branch),
+                        warning.add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw(typed.clone()),
+                    Span::styled("█", Style::default().fg(theme.accent)),
+                ]));
+                " Enter reset • Esc back "
+            }
+            None => " s soft • m mixed • h hard • a safety stash • Esc cancel ",
+        };
+
+        let [area] = Layout::horizontal([Constraint::Percentage(70)])
+            .flex(Flex::Center)
+            .areas(frame.area());
+        let block = Block::default()
+            .title(format!(" Reset {} to {} ", self.branch, self.label))
+            .title_bottom(bottom)
+            .borders(Borders::ALL)
+            .border_style(warning);
+        let paragraph = Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false });
+        // Tall enough for the wrapped lines, borders included
+        let height = paragraph.line_count(area.width) as u16;
+        let [area] = Layout::vertical([Constraint::Length(height)])
+            .flex(Flex::Center)
+            .areas(area);
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(paragraph, area);
+    }
+}
+
+fn mode_name(mode: ResetType) -> &'static str {
+    match mode {
+        ResetType::Soft => "soft",
+        ResetType::Mixed => "mixed",
+        ResetType::Hard => "hard",
+    }
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 { "" } else { "s" }
+}
+
+/// `hg reset <rev>`: the reset popup on its own, for the current branch and
+/// `rev`. What the reset did is printed once the terminal is back.
+pub fn run(repo: &Repository, rev: &str, theme: &Theme) -> Result<()> {
+    let target = repo.revparse_single(rev)?.peel_to_commit()?.id();
+    let mut popup = ResetPopup::new(repo, target, rev)?;
+
+    let mut tui = Tui::new()?;
+    let done = loop {
+        tui.draw(|frame| popup.render(frame, theme))?;
+
+        if !event::poll(TICK)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        match popup.handle(&key) {
+            ResetAction::Waiting => {}
+            ResetAction::Cancelled => break None,
+            ResetAction::Reset(mode) => break Some(popup.reset(repo, mode)),
+        }
+    };
+    drop(tui);
+
+    if let Some(result) = done {
+        println!("{}", result?);
+    }
+    Ok(())
+}
@@ -0,0 +1,486 @@
+use crate::keymap::{Action, Keymap, Scope};
+use crate::theme::Theme;
+use crate::tui::{TICK, Tui};
+use anyhow::{Result, anyhow, bail};
+use crossterm::event::{self, Event};
+use git2::{MergeFileOptions, Repository};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Paragraph},
+};
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Pick {
+    Ours,
+    Theirs,
+    /// Ours followed by theirs
+    Both,
+}
+
+/// One `<<<<<<< … >>>>>>>` block. Lines keep their endings so writing the
+/// file back changes nothing but the conflicts.
+struct Conflict {
+    ours_label: String,
+    theirs_label: String,
+    ours: Vec<String>,
+    /// Only known when the markers are diff3 style or the index has a base
+    base: Option<Vec<String>>,
+    theirs: Vec<String>,
+    pick: Option<Pick>,
+}
+
+impl Conflict {
+    fn resolved(&self) -> Vec<String> {
+        match self.pick {
+            Some(Pick::Ours) => self.ours.clone(),
+            Some(Pick::Theirs) => self.theirs.clone(),
+            Some(Pick::Both) => self.ours.iter().chain(&self.theirs).cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+enum Segment {
+    Text(Vec<String>),
+    Conflict(Conflict),
+}
+
+/// Full-screen resolver for the conflicted file `path`: each conflict shows
+/// ours, base and theirs side by side and the user picks a side; writing
+/// saves the result and stages it, which marks the file resolved. Returns
+/// what to report back in the status view.
+pub fn resolve(
+    tui: &mut Tui,
+    repo: &Repository,
+    path: &Path,
+    keymap: &Keymap,
+    theme: &Theme,
+) -> Result<Option<String>> {
+    let mut segments = load(repo, path)?;
+    let conflicts = segments
+        .iter()
+        .filter(|s| matches!(s, Segment::Conflict(_)))
+        .count();
+    if conflicts == 0 {
+        bail!(
+            "no conflict markers left in {}; stage it to mark it resolved",
+            path.display()
+        );
+    }
+
+    let mut current: usize = 0;
+    let mut scroll: usize = 0;
+    let mut height: usize = 0;
+    let mut message: Option<String> = None;
+
+    loop {
+        let conflict = nth_conflict(&segments, current);
+        let result = result_lines(&segments, current, theme);
+        let result_top = result
+            .iter()
+            .position(|(_, here)| *here)
+            .unwrap_or(0)
+            .saturating_sub(3);
+
+        tui.draw(|frame| {
+            let area = frame.area();
+            let [sides, bottom, help] = Layout::vertical([
+                Constraint::Percentage(50),
+                Constraint::Min(0),
+                Constraint::Length(1),
+            ])
+            .areas(area);
+            let [ours_area, base_area, theirs_area] =
+                Layout::horizontal([Constraint::Ratio(1, 3); 3]).areas(sides);
+
+            height = sides.height.saturating_sub(2) as usize;
+
+            // ---------- Ours / base / theirs ----------
+            let pane = |title: String, lines: Option<&Vec<String>>, picked: bool| {
+                let body: Vec<Line> = match lines {
+                    Some(lines) => lines
+                        .iter()
+                        .skip(scroll)
+                        .map(|l| Line::raw(l.trim_end_matches(['\r', '\n']).to_string()))
+                        .collect(),
+                    None => vec![Line::styled("(not recorded)", Style::default().dim())],
+                };
+                let block = Block::default()
+                    .title(title)
+                    .borders(Borders::ALL)
+                    .border_style(if picked {
+                        theme.focus()
+                    } else {
+                        Style::default()
+                    });
+                Paragraph::new(body).block(block)
+            };
+
+            if let Some(conflict) = conflict {
+                let picked =
+                    |pick: Pick| conflict.pick == Some(pick) || conflict.pick == Some(Pick::Both);
+                frame.render_widget(
+                    pane(
+                        format!(" Ours · {} ", conflict.ours_label),
+                        Some(&conflict.ours),
+                        picked(Pick::Ours),
+                    ),
+                    ours_area,
+                );
+                frame.render_widget(
+                    pane(String::from(" Base "), conflict.base.as_ref(), false),
+                    base_area,
+                );
+                frame.render_widget(
+                    pane(
+                        format!(" Theirs · {} ", conflict.theirs_label),
+                        Some(&conflict.theirs),
+                        picked(Pick::Theirs),
+                    ),
+                    theirs_area,
+                );
+            }
+
+            // ---------- Result ----------
+            let unresolved = (0..conflicts)
+                .filter(|&i| nth_conflict(&segments, i).is_some_and(|c| c.pick.is_none()))
+                .count();
+            let block = Block::default()
+                .title(format!(
+                    " {} · conflict {}/{} · {} unresolved ",
+                    path.display(),
+                    current + 1,
+                    conflicts,
+                    unresolved
+                ))
+                .borders(Borders::ALL);
+            let lines: Vec<Line> = result.iter().map(|(line, _)| line.clone()).collect();
+            frame.render_widget(
+                Paragraph::new(lines)
+                    .block(block)
+                    .scroll((result_top.try_into().unwrap_or(u16::MAX), 0)),
+                bottom,
+            );
+
+            // ---------- Helper Line ----------
+            let help_line = match &message {
+                Some(text) => Line::from(text.clone()),
+                None => help_line(keymap),
+            };
+            frame.render_widget(
+                Paragraph::new(help_line)
+                    .alignment(Alignment::Center)
+                    .style(Style::default().dim()),
+                help,
+            );
+        })?;
+
+        if !event::poll(TICK)? {
+            continue;
+        }
+
+        // Anything but a key, a resize included, just redraws at the current size
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        message = None;
+
+        let longest = conflict.map_or(0, |c| {
+            c.ours
+                .len()
+                .max(c.theirs.len())
+                .max(c.base.as_ref().map_or(0, Vec::len))
+        });
+        let page = height.saturating_sub(1).max(1);
+
+        match keymap.resolve(&key, Scope::Resolve) {
+            Some(Action::Quit) => return Ok(None),
+
+            Some(action @ (Action::PickOurs | Action::PickTheirs | Action::PickBoth)) => {
+                if let Some(conflict) = nth_conflict_mut(&mut segments, current) {
+                    conflict.pick = Some(match action {
+                        Action::PickOurs => Pick::Ours,
+                        Action::PickTheirs => Pick::Theirs,
+                        _ => Pick::Both,
+                    });
+                }
+                // Straight on to the next one still open
+                if let Some(next) = (current + 1..conflicts)
+                    .find(|&i| nth_conflict(&segments, i).is_some_and(|c| c.pick.is_none()))
+                {
+                    current = next;
+                    scroll = 0;
+                }
+            }
+
+            Some(Action::ClearPick) => {
+                if let Some(conflict) = nth_conflict_mut(&mut segments, current) {
+                    conflict.pick = None;
+                }
+            }
+
+            Some(Action::WriteResolution) => match write(repo, path, &segments) {
+                Ok(()) => return Ok(Some(format!("Resolved {}", path.display()))),
+                Err(e) => message = Some(format!("Error: {}", e)),
+            },
+
+            Some(Action::NavigateUp) => {
+                current = current.saturating_sub(1);
+                scroll = 0;
+            }
+            Some(Action::NavigateDown) => {
+                current = (current + 1).min(conflicts - 1);
+                scroll = 0;
+            }
+            Some(Action::PageUp) => scroll = scroll.saturating_sub(page),
+            Some(Action::PageDown) => {
+                scroll = (scroll + page).min(longest.saturating_sub(height));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Splits the worktree file at its conflict markers, filling in each
+/// conflict's base from the index when the markers don't carry it.
+fn load(repo: &Repository, path: &Path) -> Result<Vec<Segment>> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("cannot resolve: repository has no working directory"))?;
+    let content = fs::read(workdir.join(path))?;
+    if content.iter().take(8000).any(|&b| b == 0) {
+        bail!(
+            "{} is binary; pick a side with git checkout --ours/--theirs",
+            path.display()
+        );
+    }
+
+    let mut segments = parse(&String::from_utf8_lossy(&content))?;
+
+    // Plain markers leave the base out, so redo the merge in diff3 style for it
+    if let Some(bases) = index_bases(repo, path) {
+        let conflicts = segments.iter_mut().filter_map(|s| match s {
+            Segment::Conflict(c) => Some(c),
+            Segment::Text(_) => None,
+        });
+        let conflicts: Vec<&mut Conflict> = conflicts.collect();
+        if conflicts.len() == bases.len() {
+            for (conflict, base) in conflicts.into_iter().zip(bases) {
+                if conflict.base.is_none() {
+                    conflict.base = base;
+                }
+            }
+        }
+    }
+
+    Ok(segments)
+}
+
+fn parse(content: &str) -> Result<Vec<Segment>> {
+    enum Part {
+        Text,
+        Ours,
+        Base,
+        Theirs,
+    }
+
+    let mut segments = Vec::new();
+    let mut text = Vec::new();
+    let mut conflict: Option<Conflict> = None;
+    let mut part = Part::Text;
+
+    for line in content.split_inclusive('\n') {
+        let label = |marker: &str| line[marker.len()..].trim().to_string();
+
+        match part {
+            Part::Text if line.starts_with("<<<<<<<") => {
+                if !text.is_empty() {
+                    segments.push(Segment::Text(std::mem::take(&mut text)));
+                }
+                conflict = Some(Conflict {
+                    ours_label: label("<<<<<<<"),
+                    theirs_label: String::new(),
+                    ours: Vec::new(),
+                    base: None,
+                    theirs: Vec::new(),
+                    pick: None,
+                });
+                part = Part::Ours;
+            }
+            Part::Ours if line.starts_with("|||||||") => {
+                if let Some(c) = &mut conflict {
+                    c.base = Some(Vec::new());
+                }
+                part = Part::Base;
+            }
+            Part::Ours | Part::Base if line.starts_with("=======") => part = Part::Theirs,
+            Part::Theirs if line.starts_with(">>>>>>>") => {
+                if let Some(mut c) = conflict.take() {
+                    c.theirs_label = label(">>>>>>>");
+                    segments.push(Segment::Conflict(c));
+                }
+                part = Part::Text;
+            }
+            Part::Text => text.push(line.to_string()),
+            Part::Ours => conflict
+                .iter_mut()
+                .for_each(|c| c.ours.push(line.to_string())),
+            Part::Base => conflict
+                .iter_mut()
+                .filter_map(|c| c.base.as_mut())
+                .for_each(|b| b.push(line.to_string())),
+            Part::Theirs => conflict
+                .iter_mut()
+                .for_each(|c| c.theirs.push(line.to_string())),
+        }
+    }
+
+    if conflict.is_some() {
+        bail!("a conflict marker is never closed");
+    }
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+    Ok(segments)
+}
+
+/// The base side of every conflict, in order, from a diff3-style re-merge of
+/// the index stages. `None` when the index doesn't hold all three.
+fn index_bases(repo: &Repository, path: &Path) -> Option<Vec<Option<Vec<String>>>> {
+    let index = repo.index().ok()?;
+    let ancestor = index.get_path(path, 1)?;
+    let ours = index.get_path(path, 2)?;
+    let theirs = index.get_path(path, 3)?;
+
+    let mut opts = MergeFileOptions::new();
+    opts.style_diff3(true);
+    let merged = repo
+        .merge_file_from_index(&ancestor, &ours, &theirs, Some(&mut opts))
+        .ok()?;
+
+    let segments = parse(&String::from_utf8_lossy(merged.content())).ok()?;
+    Some(
+        segments
+            .into_iter()
+            .filter_map(|s| match s {
+                Segment::Conflict(c) => Some(c.base),
+                Segment::Text(_) => None,
+            })
+            .collect(),
+    )
+}
+
+/// Writes the picked sides over the worktree file and stages it, which is
+/// how git marks a conflict resolved.
+fn write(repo: &Repository, path: &Path, segments: &[Segment]) -> Result<()> {
+    let mut content = String::new();
+    for segment in segments {
+        match segment {
+            Segment::Text(lines) => content.extend(lines.iter().map(String::as_str)),
+            Segment::Conflict(c) if c.pick.is_none() => {
+                bail!("pick a side for every conflict first")
+            }
+            Segment::Conflict(c) => content.extend(c.resolved()),
+        }
+    }
+
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("cannot resolve: repository has no working directory"))?;
+    fs::write(workdir.join(path), content)?;
+
+    let mut index = repo.index()?;
+    index.add_path(path)?;
+    index.write()?;
+    Ok(())
+}
+
+fn nth_conflict(segments: &[Segment], n: usize) -> Option<&Conflict> {
+    segments
+        .iter()
+        .filter_map(|s| match s {
+            Segment::Conflict(c) => Some(c),
+            Segment::Text(_) => None,
+        })
+        .nth(n)
+}
+
+fn nth_conflict_mut(segments: &mut [Segment], n: usize) -> Option<&mut Conflict> {
+    segments
+        .iter_mut()
+        .filter_map(|s| match s {
+            Segment::Conflict(c) => Some(c),
+            Segment::Text(_) => None,
+        })
+        .nth(n)
+}
+
+/// The file as it would be written, with unpicked conflicts standing in as a
+/// marker line. Each line is paired with whether it belongs to conflict
+/// `current`, for scrolling to it.
+fn result_lines(segments: &[Segment], current: usize, theme: &Theme) -> Vec<(Line<'static>, bool)> {
+    let mut lines = Vec::new();
+    let mut n = 0;
+
+    for segment in segments {
+        match segment {
+            Segment::Text(text) => lines.extend(text.iter().map(|l| {
+                (
+                    Line::raw(l.trim_end_matches(['\r', '\n']).to_string()),
+                    false,
+                )
+            })),
+            Segment::Conflict(conflict) => {
+                let here = n == current;
+                let style = if here {
+                    Style::default().bg(theme.hunk_bg)
+                } else {
+                    Style::default()
+                };
+
+                if conflict.pick.is_none() {
+                    lines.push((
+                        Line::styled(
+                            format!("<<<<<<< unresolved conflict {}", n + 1),
+                            style.fg(theme.conflict).add_modifier(Modifier::BOLD),
+                        ),
+                        here,
+                    ));
+                } else {
+                    let resolved = conflict.resolved();
+                    if resolved.is_empty() {
+                        lines.push((Line::styled("(nothing)", style.dim()), here));
+                    }
+                    lines.extend(resolved.iter().map(|l| {
+                        (
+                            Line::styled(
+                                l.trim_end_matches(['\r', '\n']).to_string(),
+                                style.fg(theme.addition),
+                            ),
+                            here,
+                        )
+                    }));
+                }
+                n += 1;
+            }
+        }
+    }
+
+    lines
+}
+
+fn help_line(keymap: &Keymap) -> Line<'static> {
+    Line::from(format!(
+        " {} {} conflict • {} ours • {} theirs • {} both • {} undo • {} write & stage • {} cancel",
+        keymap.label(Action::NavigateUp),
+        keymap.label(Action::NavigateDown),
+        keymap.label(Action::PickOurs),
+        keymap.label(Action::PickTheirs),
+        keymap.label(Action::PickBoth),
+        keymap.label(Action::ClearPick),
+        keymap.label(Action::WriteResolution),
+        keymap.label(Action::Quit),
+    ))
+}
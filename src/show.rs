@@ -0,0 +1,153 @@
+use crate::help;
+use crate::keymap::{Action, Keymap, Scope};
+use crate::log;
+use crate::theme::Theme;
+use crate::tui::{TICK, Tui};
+use anyhow::{Result, anyhow};
+use crossterm::event::{self, Event};
+use git2::Repository;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Paragraph},
+};
+
+/// `hg show <rev>`: header, stat and patch of any commit `rev` names, be it
+/// a branch, a tag, `HEAD~2` or a short hash.
+pub fn show(repo: &Repository, rev: &str, keymap: &Keymap, theme: &Theme) -> Result<()> {
+    // Resolved before the screen is taken over, so a typo reads as a plain error
+    let commit = repo
+        .revparse_single(rev)
+        .map_err(|e| anyhow!("unknown revision \"{}\": {}", rev, e.message()))?
+        .peel_to_commit()
+        .map_err(|_| anyhow!("\"{}\" does not name a commit", rev))?;
+    let commit_diff = log::show_commit_stat(repo, commit.id(), theme)?;
+
+    let mut tui = Tui::new()?;
+
+    let mut scroll: usize = 0;
+    let mut hscroll: u16 = 0;
+    let mut height: usize = 0;
+    let mut width: usize = 0;
+    let longest = commit_diff.lines.iter().map(Line::width).max().unwrap_or(0);
+    let mut show_help = false;
+
+    loop {
+        tui.draw(|frame| {
+            let area = frame.area();
+            let [main, help] =
+                Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(area);
+
+            height = main.height.saturating_sub(2) as usize;
+            width = main.width.saturating_sub(2) as usize;
+            scroll = scroll.min(commit_diff.lines.len().saturating_sub(height));
+
+            let block = Block::default()
+                .title(format!(" {} · {:.7} ", rev, commit.id()))
+                .borders(Borders::ALL)
+                .border_style(theme.focus());
+            // Only the lines on screen are copied into the paragraph
+            let end = (scroll + height).min(commit_diff.lines.len());
+            frame.render_widget(
+                Paragraph::new(commit_diff.lines[scroll..end].to_vec())
+                    .block(block)
+                    .scroll((0, hscroll)),
+                main,
+            );
+
+            let files = if commit_diff.files.len() > 1 {
+                format!(
+                    "{} {} file • ",
+                    keymap.label(Action::NextFile),
+                    keymap.label(Action::PrevFile)
+                )
+            } else {
+                String::new()
+            };
+            let help_line = Line::from(format!(
+                " {} {} scroll • {} {} hunk • {}{} {} scroll sideways • {} quit",
+                keymap.label(Action::NavigateUp),
+                keymap.label(Action::NavigateDown),
+                keymap.label(Action::NextHunk),
+                keymap.label(Action::PrevHunk),
+                files,
+                keymap.label(Action::ScrollLeft),
+                keymap.label(Action::ScrollRight),
+                keymap.label(Action::Quit),
+            ));
+            frame.render_widget(
+                Paragraph::new(help_line)
+                    .alignment(Alignment::Center)
+                    .style(Style::default().dim()),
+                help,
+            );
+
+            if show_help {
+                help::render(frame, keymap, theme);
+            }
+        })?;
+
+        if !event::poll(TICK)? {
+            continue;
+        }
+
+        // Anything but a key, a resize included, just redraws at the current size
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+
+        if show_help {
+            if matches!(
+                keymap.resolve(&key, Scope::Diff),
+                Some(Action::Help | Action::Quit)
+            ) {
+                show_help = false;
+            }
+            continue;
+        }
+
+        let bottom = commit_diff.lines.len().saturating_sub(height);
+        let page = height.saturating_sub(1).max(1);
+        match keymap.resolve(&key, Scope::Diff) {
+            Some(Action::Quit) => break,
+            Some(Action::Help) => show_help = true,
+            Some(Action::NavigateUp) => scroll = scroll.saturating_sub(1),
+            Some(Action::NavigateDown) => scroll = (scroll + 1).min(bottom),
+            Some(Action::PageUp) => scroll = scroll.saturating_sub(page),
+            Some(Action::PageDown) => scroll = (scroll + page).min(bottom),
+            Some(Action::Top) => scroll = 0,
+            Some(Action::Bottom) => scroll = bottom,
+            Some(Action::ScrollLeft) => hscroll = hscroll.saturating_sub(1),
+            Some(Action::ScrollRight) => {
+                let widest = longest.saturating_sub(width).try_into().unwrap_or(u16::MAX);
+                hscroll = hscroll.saturating_add(1).min(widest);
+            }
+
+            // Hunk starts below or above the top row, so the same key walks on
+            Some(Action::NextHunk) => {
+                if let Some(hunk) = commit_diff.hunks.iter().find(|h| h.start > scroll) {
+                    scroll = hunk.start.min(bottom);
+                }
+            }
+            Some(Action::PrevHunk) => {
+                if let Some(hunk) = commit_diff.hunks.iter().rev().find(|h| h.start < scroll) {
+                    scroll = hunk.start;
+                }
+            }
+            Some(Action::NextFile) => {
+                if let Some((start, _)) = commit_diff.files.iter().find(|(s, _)| *s > scroll) {
+                    scroll = (*start).min(bottom);
+                }
+            }
+            Some(Action::PrevFile) => {
+                if let Some((start, _)) = commit_diff.files.iter().rev().find(|(s, _)| *s < scroll)
+                {
+                    scroll = *start;
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,245 @@
+use anyhow::{Context, Result, anyhow, bail};
+use git2::{Config, Oid, Repository};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+
+/// What checking a commit's signature found.
+pub struct Verification {
+    /// A good signature from a known key
+    pub good: bool,
+    /// What to show for it, like `git log --show-signature` sums it up
+    pub text: String,
+}
+
+/// Signs `payload` the way git does for `gpg.format`: an armored detached
+/// OpenPGP signature from gpg, or an SSH signature from `ssh-keygen -Y sign`,
+/// with `user.signingKey` (for gpg, the committer's identity when unset).
+/// Either program may ask for a passphrase, so run this with the terminal
+/// handed back.
+pub fn sign(repo: &Repository, payload: &str) -> Result<String> {
+    let config = repo.config()?;
+    let key = config.get_string("user.signingkey").ok();
+    let format = config
+        .get_string("gpg.format")
+        .unwrap_or_else(|_| String::from("openpgp"));
+
+    let (program, args) = match format.as_str() {
+        "openpgp" => {
+            let program = gpg_program(&config);
+            let key = match key {
+                Some(key) => key,
+                None => repo
+                    .signature()
+                    .map(|s| s.to_string())
+                    .context("set user.signingKey, or user.name and user.email")?,
+            };
+            (program, vec![String::from("-bsau"), key])
+        }
+        "ssh" => {
+            let program = ssh_program(&config);
+            let key =
+                key.ok_or_else(|| anyhow!("gpg.format is ssh, but user.signingKey is unset"))?;
+            if key.starts_with("key::") || key.starts_with("ssh-") {
+                bail!("user.signingKey has to be the path of a key file to sign with ssh-keygen");
+            }
+            let path = match key.strip_prefix("~/") {
+                Some(rest) => home()?.join(rest),
+                None => PathBuf::from(&key),
+            };
+            let mut args = ["-Y", "sign", "-n", "git", "-f"].map(String::from).to_vec();
+            args.push(path.to_string_lossy().into_owned());
+            (program, args)
+        }
+        other => bail!("signing with gpg.format \"{}\" isn't supported", other),
+    };
+
+    let output = run(&program, &args, payload.as_bytes(), Stdio::inherit())?;
+    let signature = String::from_utf8_lossy(&output.stdout).into_owned();
+    if !output.status.success() || signature.trim().is_empty() {
+        bail!("\"{}\" failed to sign ({})", program, output.status);
+    }
+    Ok(signature)
+}
+
+/// Checks the signature of commit `oid`, if it has one, with gpg or with
+/// `ssh-keygen -Y verify` against `gpg.ssh.allowedSignersFile`, as `git
+/// verify-commit` does.
+pub fn verify(repo: &Repository, oid: Oid) -> Result<Option<Verification>> {
+    let Ok((signature, data)) = repo.extract_signature(&oid, None) else {
+        return Ok(None);
+    };
+    let signature = String::from_utf8_lossy(&signature).into_owned();
+    let config = repo.config()?;
+
+    // The signature itself says what made it, whatever gpg.format is now
+    let file = SignatureFile::new(oid, &signature)?;
+    let verification = if signature.starts_with("-----BEGIN SSH SIGNATURE-----") {
+        verify_ssh(&config, file.path(), &data)?
+    } else if signature.starts_with("-----BEGIN PGP SIGNATURE-----") {
+        verify_gpg(&config, file.path(), &data)?
+    } else {
+        Verification {
+            good: false,
+            text: String::from("Signed, in a format that can't be checked here"),
+        }
+    };
+    Ok(Some(verification))
+}
+
+fn verify_gpg(config: &Config, signature: &Path, data: &[u8]) -> Result<Verification> {
+    let program = gpg_program(config);
+    let args = [
+        "--status-fd=1",
+        "--keyid-format=long",
+        "--verify",
+        &signature.to_string_lossy(),
+        "-",
+    ]
+    .map(String::from);
+    let output = run(&program, &args, data, Stdio::null())?;
+
+    // gpg's machine-readable verdict, as git reads it
+    let status = String::from_utf8_lossy(&output.stdout);
+    for line in status.lines() {
+        let Some(line) = line.strip_prefix("[GNUPG:] ") else {
+            continue;
+        };
+        let (word, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let (key, who) = rest.split_once(' ').unwrap_or((rest, ""));
+        let (good, text) = match word {
+            "GOODSIG" => (true, format!("Good signature from {} (key {})", who, key)),
+            "BADSIG" => (false, format!("BAD signature from {} (key {})", who, key)),
+            "EXPSIG" => (
+                false,
+                format!("Expired signature from {} (key {})", who, key),
+            ),
+            "EXPKEYSIG" => (
+                false,
+                format!("Signed by {} with a key that has expired ({})", who, key),
+            ),
+            "REVKEYSIG" => (
+                false,
+                format!("Signed by {} with a revoked key ({})", who, key),
+            ),
+            "ERRSIG" => (
+                false,
+                format!("Can't check the signature: no public key {}", key),
+            ),
+            _ => continue,
+        };
+        return Ok(Verification { good, text });
+    }
+    Ok(Verification {
+        good: false,
+        text: format!("\"{}\" couldn't check the signature", program),
+    })
+}
+
+fn verify_ssh(config: &Config, signature: &Path, data: &[u8]) -> Result<Verification> {
+    let program = ssh_program(config);
+    let signature = signature.to_string_lossy().into_owned();
+
+    let Ok(allowed) = config.get_path("gpg.ssh.allowedsignersfile") else {
+        let args = ["-Y", "check-novalidate", "-n", "git", "-s", &signature].map(String::from);
+        let output = run(&program, &args, data, Stdio::null())?;
+        return Ok(Verification {
+            good: false,
+            text: if output.status.success() {
+                String::from(
+                    "Good SSH signature, but gpg.ssh.allowedSignersFile is unset to say whose",
+                )
+            } else {
+                String::from("BAD SSH signature")
+            },
+        });
+    };
+    let allowed = allowed.to_string_lossy().into_owned();
+
+    let args = ["-Y", "find-principals", "-f", &allowed, "-s", &signature].map(String::from);
+    let found = run(&program, &args, &[], Stdio::null())?;
+    let principals = String::from_utf8_lossy(&found.stdout);
+    let Some(principal) = principals.lines().next().filter(|_| found.status.success()) else {
+        return Ok(Verification {
+            good: false,
+            text: String::from("SSH signature by a key that isn't in the allowed signers"),
+        });
+    };
+
+    let args = [
+        "-Y", "verify", "-n", "git", "-f", &allowed, "-I", principal, "-s", &signature,
+    ]
+    .map(String::from);
+    let output = run(&program, &args, data, Stdio::null())?;
+    Ok(if output.status.success() {
+        Verification {
+            good: true,
+            text: format!("Good SSH signature from {}", principal),
+        }
+    } else {
+        Verification {
+            good: false,
+            text: format!("BAD SSH signature claiming to be from {}", principal),
+        }
+    })
+}
+
+fn gpg_program(config: &Config) -> String {
+    config
+        .get_string("gpg.openpgp.program")
+        .or_else(|_| config.get_string("gpg.program"))
+        .unwrap_or_else(|_| String::from("gpg"))
+}
+
+fn ssh_program(config: &Config) -> String {
+    config
+        .get_string("gpg.ssh.program")
+        .unwrap_or_else(|_| String::from("ssh-keygen"))
+}
+
+/// Runs `program` with `input` on its stdin, collecting what it prints.
+fn run(program: &str, args: &[String], input: &[u8], stderr: Stdio) -> Result<process::Output> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(stderr)
+        .spawn()
+        .with_context(|| format!("cannot run \"{}\"", program))?;
+    child
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow!("cannot write to \"{}\"", program))?
+        .write_all(input)?;
+    Ok(child.wait_with_output()?)
+}
+
+/// The signature written out for the verifying program, which reads it from
+/// a file, removed again once checked.
+struct SignatureFile(PathBuf);
+
+impl SignatureFile {
+    fn new(oid: Oid, signature: &str) -> Result<Self> {
+        let path =
+            std::env::temp_dir().join(format!("helper-git-{}-{:.12}.sig", process::id(), oid));
+        fs::write(&path, signature)?;
+        Ok(SignatureFile(path))
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for SignatureFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn home() -> Result<PathBuf> {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .ok_or_else(|| anyhow!("HOME is unset"))
+}
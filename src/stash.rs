@@ -0,0 +1,252 @@
+pub mod entry;
+
+use crate::help;
+use crate::keymap::{Action, Keymap, Scope};
+use crate::panes::{self, Panes};
+use crate::theme::Theme;
+use crate::tui::{TICK, Tui};
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode};
+use entry::StashEntry;
+use git2::Repository;
+use ratatui::{
+    prelude::*,
+    widgets::{ListItem, ListState},
+};
+
+/// A stash message being typed on the helper line.
+struct SaveInput {
+    message: String,
+    include_untracked: bool,
+}
+
+pub fn stash(
+    tui: &mut Tui,
+    repo: &Repository,
+    list_width: u16,
+    keymap: &Keymap,
+    theme: &Theme,
+) -> Result<()> {
+    let mut stashes = entry::load(repo)?;
+    let mut panes: Panes<git2::Oid> = Panes::new(list_width);
+
+    let mut message: Option<String> = None;
+    let mut save_input: Option<SaveInput> = None;
+    let mut confirm_drop = false;
+    let mut show_help = false;
+
+    // ---------- Main Loop ----------
+    loop {
+        let selected = panes.selected().and_then(|i| stashes.get(i));
+
+        // ---------- Preview ----------
+        panes.preview(
+            selected,
+            |s| s.id,
+            |stash| {
+                entry::show(repo, stash, theme)
+                    .unwrap_or_else(|e| vec![Line::from(format!("Error: {}", e))])
+            },
+        );
+
+        // ---------- Helper line ----------
+        let help_line = match (&save_input, message.clone()) {
+            (Some(input), _) => Line::from(vec![
+                Span::styled(
+                    if input.include_untracked {
+                        "Stash with untracked files, message: "
+                    } else {
+                        "Stash message: "
+                    },
+                    Style::default().fg(theme.accent),
+                ),
+                Span::raw(input.message.clone()),
+                Span::styled("█", Style::default().fg(theme.accent)),
+            ]),
+            _ if confirm_drop => Line::styled(
+                selected.map_or(String::new(), |s| {
+                    format!("Drop {} ({})? (y/n)", s.name(), s.message)
+                }),
+                Style::default()
+                    .fg(theme.warning)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            (None, Some(text)) => Line::from(text),
+            (None, None) => help_line(keymap),
+        };
+
+        let items: Vec<ListItem> = stashes
+            .iter()
+            .map(|s| {
+                ListItem::new(Line::from(vec![
+                    Span::styled(s.name(), Style::default().fg(theme.commit_sha)),
+                    Span::raw(" "),
+                    Span::raw(s.message.clone()),
+                ]))
+            })
+            .collect();
+        let list = panes::list(items, "No stashes", theme);
+
+        tui.draw(|frame| {
+            let (main, help) = panes::split(frame.area());
+            panes.render(frame, main, &list, " Stashes ", " Changes ", theme);
+            panes::render_help(frame, help, help_line, save_input.is_some() || confirm_drop);
+
+            if show_help {
+                help::render(frame, keymap, theme);
+            }
+        })?;
+
+        // ---------- Input Handling ----------
+        if !event::poll(TICK)? {
+            continue;
+        }
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        message = None;
+
+        let selected = panes.selected().and_then(|i| stashes.get(i));
+
+        // A pending drop swallows every key until it is answered
+        if confirm_drop {
+            match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    if let Some(stash) = selected
+                        && let Err(e) = entry::drop(repo, stash)
+                    {
+                        message = Some(format!("Error: {}", e));
+                    }
+                    confirm_drop = false;
+                    reload(repo, &mut stashes, &mut panes.list_state)?;
+                }
+                KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                    confirm_drop = false;
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        if let Some(input) = &mut save_input {
+            match key.code {
+                KeyCode::Esc => save_input = None,
+                KeyCode::Backspace => {
+                    input.message.pop();
+                }
+                KeyCode::Char(c) => input.message.push(c),
+                KeyCode::Enter => {
+                    let result = entry::save(repo, input.message.trim(), input.include_untracked);
+                    save_input = None;
+                    match result {
+                        Ok(oid) => {
+                            message = Some(format!("Saved stash@{{0}}: {:.7}", oid));
+                            reload(repo, &mut stashes, &mut panes.list_state)?;
+                            panes.select(Some(0));
+                        }
+                        Err(e) => message = Some(format!("Error: {}", e)),
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        if show_help {
+            if matches!(
+                keymap.resolve(&key, Scope::Stash),
+                Some(Action::Help | Action::Quit)
+            ) {
+                show_help = false;
+            }
+            continue;
+        }
+
+        match keymap.resolve(&key, Scope::Stash) {
+            Some(Action::Quit) => break,
+
+            Some(Action::Help) => show_help = true,
+
+            Some(Action::Refresh) => reload(repo, &mut stashes, &mut panes.list_state)?,
+
+            Some(action @ (Action::StashSave | Action::StashUntracked)) => {
+                save_input = Some(SaveInput {
+                    message: String::new(),
+                    include_untracked: action == Action::StashUntracked,
+                });
+            }
+
+            Some(action @ (Action::StashApply | Action::StashPop)) => {
+                if let Some(stash) = selected {
+                    let name = stash.name();
+                    let result = if action == Action::StashPop {
+                        entry::pop(repo, stash).map(|()| format!("Popped {}", name))
+                    } else {
+                        entry::apply(repo, stash).map(|()| format!("Applied {}", name))
+                    };
+                    message = Some(result.unwrap_or_else(|e| format!("Error: {}", e)));
+                    reload(repo, &mut stashes, &mut panes.list_state)?;
+                }
+            }
+
+            Some(Action::StashDrop) if selected.is_some() => confirm_drop = true,
+
+            Some(action) => {
+                panes.navigate(action, stashes.len());
+            }
+
+            None => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// `hg stash` outside the status view.
+pub fn run(repo: &Repository, list_width: u16, keymap: &Keymap, theme: &Theme) -> Result<()> {
+    let mut tui = Tui::new()?;
+    stash(&mut tui, repo, list_width, keymap, theme)
+}
+
+/// Reloads the stash list, keeping the selected row (clamped). Stash indices
+/// shift as entries come and go, so the row is all there is to keep.
+fn reload(
+    repo: &Repository,
+    stashes: &mut Vec<StashEntry>,
+    list_state: &mut ListState,
+) -> Result<()> {
+    *stashes = entry::load(repo)?;
+    let row = list_state
+        .selected()
+        .unwrap_or(0)
+        .min(stashes.len().saturating_sub(1));
+    list_state.select(Some(row));
+    Ok(())
+}
+
+fn help_line(keymap: &Keymap) -> Line<'static> {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let mut spans = vec![Span::raw(" ")];
+
+    for (i, (action, label)) in [
+        (Action::StashSave, "stash"),
+        (Action::StashUntracked, "stash with untracked"),
+        (Action::StashApply, "apply"),
+        (Action::StashPop, "pop"),
+        (Action::StashDrop, "drop"),
+        (Action::SwitchFocus, "switch focus"),
+        (Action::Quit, "quit"),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        if i > 0 {
+            spans.push(Span::raw(" • "));
+        }
+        spans.push(Span::styled(keymap.label(action), bold));
+        spans.push(Span::raw(format!(" {}", label)));
+    }
+
+    Line::from(spans)
+}
@@ -0,0 +1,115 @@
+use crate::status::diff;
+use crate::theme::Theme;
+use anyhow::{Result, anyhow};
+use git2::{ErrorCode, Oid, Repository, StashFlags};
+use ratatui::{
+    style::Style,
+    text::{Line, Span},
+};
+
+/// One entry of `git stash list`.
+pub struct StashEntry {
+    pub index: usize,
+    /// `WIP on main: 1a2b3c4 summary`, or `On main: <message>`
+    pub message: String,
+    pub id: Oid,
+}
+
+impl StashEntry {
+    pub fn name(&self) -> String {
+        format!("stash@{{{}}}", self.index)
+    }
+}
+
+// The stash calls need `&mut Repository`, so each one opens its own handle.
+
+pub fn load(repo: &Repository) -> Result<Vec<StashEntry>> {
+    let mut repo = Repository::open(repo.path())?;
+    let mut entries = Vec::new();
+
+    repo.stash_foreach(|index, message, id| {
+        entries.push(StashEntry {
+            index,
+            message: message.to_string(),
+            id: *id,
+        });
+        true
+    })?;
+
+    Ok(entries)
+}
+
+/// Stashes the index and worktree changes like `git stash push`, and resets
+/// both to HEAD. Untracked files go too when `include_untracked` is set.
+pub fn save(repo: &Repository, message: &str, include_untracked: bool) -> Result<Oid> {
+    let mut repo = Repository::open(repo.path())?;
+    let signature = repo.signature()?;
+    let flags = if include_untracked {
+        StashFlags::INCLUDE_UNTRACKED
+    } else {
+        StashFlags::DEFAULT
+    };
+    let message = Some(message).filter(|m| !m.is_empty());
+
+    repo.stash_save2(&signature, message, Some(flags))
+        .map_err(|e| match e.code() {
+            ErrorCode::NotFound => anyhow!("no local changes to stash"),
+            _ => e.into(),
+        })
+}
+
+/// Applies `entry` onto the worktree, keeping it in the stash list.
+pub fn apply(repo: &Repository, entry: &StashEntry) -> Result<()> {
+    let mut repo = Repository::open(repo.path())?;
+    repo.stash_apply(entry.index, None)
+        .map_err(|e| apply_error(e, entry))
+}
+
+/// Applies `entry` and drops it, unless applying it failed.
+pub fn pop(repo: &Repository, entry: &StashEntry) -> Result<()> {
+    let mut repo = Repository::open(repo.path())?;
+    repo.stash_pop(entry.index, None)
+        .map_err(|e| apply_error(e, entry))
+}
+
+pub fn drop(repo: &Repository, entry: &StashEntry) -> Result<()> {
+    let mut repo = Repository::open(repo.path())?;
+    repo.stash_drop(entry.index)?;
+    Ok(())
+}
+
+fn apply_error(e: git2::Error, entry: &StashEntry) -> anyhow::Error {
+    match e.code() {
+        ErrorCode::Conflict | ErrorCode::MergeConflict => anyhow!(
+            "{} touches files with local changes; commit or stash them first",
+            entry.name()
+        ),
+        _ => e.into(),
+    }
+}
+
+/// The changes `entry` saved, like `git stash show -p --include-untracked`.
+pub fn show(repo: &Repository, entry: &StashEntry, theme: &Theme) -> Result<Vec<Line<'static>>> {
+    let mut lines: Vec<Line<'static>> = Vec::new();
+    let commit = repo.find_commit(entry.id)?;
+
+    lines.push(Line::from(Span::styled(
+        format!("{} {}", entry.name(), entry.id),
+        Style::default().fg(theme.commit_sha),
+    )));
+    lines.push(Line::from(format!("    {}", entry.message)));
+    lines.push(Line::from(""));
+
+    // The stash commit holds the worktree; its first parent is HEAD at the time
+    let base = commit.parent(0)?.tree()?;
+    let diff = repo.diff_tree_to_tree(Some(&base), Some(&commit.tree()?), None)?;
+    lines.extend(diff::render_diff(&diff, theme)?.lines);
+
+    // Untracked files, when stashed, sit in a third parent of their own
+    if let Ok(untracked) = commit.parent(2) {
+        let diff = repo.diff_tree_to_tree(None, Some(&untracked.tree()?), None)?;
+        lines.extend(diff::render_diff(&diff, theme)?.lines);
+    }
+
+    Ok(lines)
+}
@@ -1,224 +1,606 @@
-mod diff;
-
-use anyhow::Result;
-use crossterm::{
-    ExecutableCommand,
-    event::{self, Event, KeyCode},
-    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
-};
-use git2::{Repository, Status, StatusOptions};
-use ratatui::{
-    prelude::*,
-    widgets::{Block, Borders, HighlightSpacing, List, ListItem, ListState, Paragraph},
+mod app;
+mod commit;
+mod compose;
+pub mod diff;
+mod discard;
+mod editor;
+#[cfg(feature = "syntax")]
+mod highlight;
+mod hooks;
+mod ignore;
+mod list;
+mod loader;
+pub mod message;
+mod palette;
+pub mod remote;
+mod search;
+mod stage;
+mod submodule;
+mod tool;
+mod undo;
+mod watcher;
+mod words;
+
+use crate::bisect;
+use crate::blame;
+use crate::branches;
+use crate::clean;
+use crate::history;
+use crate::json;
+use crate::keymap::Keymap;
+use crate::reflog;
+use crate::remotes;
+use crate::resolve;
+use crate::stash;
+use crate::tags;
+use crate::theme::Theme;
+use crate::tui::{self, Tui};
+use crate::worktree;
+use anyhow::{Result, anyhow, bail};
+use app::{App, Command};
+use crossterm::event;
+use git2::Repository;
+use helper_git_core::diff::{DiffSettings, file_diff};
+use helper_git_core::repo;
+use helper_git_core::status::{
+    Change, ChangeKind, FileEntry, Section, StatusFilter, collect_status, collect_status_collapsed,
 };
-use std::io::stdout;
-
-#[derive(PartialEq)]
-enum Focus {
-    Left,
-    Right,
+use list::{Row, StatusList};
+use ratatui::prelude::*;
+use serde::Serialize;
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// The filter for `hg status <pathspec>...`. Pathspecs are taken relative
+/// to the current directory, as git takes them, or to the top of the
+/// worktree with `:/` magic; `:!` (or `:^`) ones say what to leave out,
+/// `:(glob)` ones glob within a directory and `:(literal)` ones not at all.
+/// A path outside the worktree is an error, as it is to git.
+pub fn filter(
+    repo: &Repository,
+    pathspecs: &[String],
+    only: Option<Section>,
+) -> Result<StatusFilter> {
+    let workdir = repo
+        .workdir()
+        .unwrap_or_else(|| repo.path())
+        .canonicalize()?;
+    let cwd = std::env::current_dir()?.canonicalize()?;
+    let prefix = cwd.strip_prefix(&workdir).unwrap_or(Path::new(""));
+    filter_in(&workdir, prefix, pathspecs, only)
 }
 
-pub fn status(repo: &Repository) -> Result<()> {
-    enable_raw_mode()?;
-    stdout().execute(EnterAlternateScreen)?;
-
-    let backend = CrosstermBackend::new(stdout());
-    let mut terminal = Terminal::new(backend)?;
-
-    // ---------- Load Git Status ----------
-    let mut opts = StatusOptions::new();
-    opts.include_untracked(true);
-    opts.recurse_untracked_dirs(true);
+/// [`filter`] from `prefix`, the current directory within `workdir`.
+fn filter_in(
+    workdir: &Path,
+    prefix: &Path,
+    pathspecs: &[String],
+    only: Option<Section>,
+) -> Result<StatusFilter> {
+    let mut filter = StatusFilter {
+        only,
+        ..StatusFilter::default()
+    };
+    let mut everything = false;
+    for pathspec in pathspecs {
+        let (magic, pattern) = Magic::parse(pathspec)?;
+        let pattern = Path::new(pattern);
+        let outside = || {
+            anyhow!(
+                "fatal: {}: '{}' is outside repository at '{}'",
+                pathspec,
+                pathspec,
+                workdir.display()
+            )
+        };
+        let full = if pattern.is_absolute() {
+            pattern
+                .strip_prefix(workdir)
+                .map_err(|_| outside())?
+                .to_path_buf()
+        } else if magic.top {
+            pattern.to_path_buf()
+        } else {
+            prefix.join(pattern)
+        };
+        let mut path = PathBuf::new();
+        for part in full.components() {
+            match part {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    if !path.pop() {
+                        return Err(outside());
+                    }
+                }
+                part => path.push(part),
+            }
+        }
 
-    let statuses = repo.statuses(Some(&mut opts))?;
+        let mut path = path.to_string_lossy().into_owned();
+        if magic.literal {
+            path = path.chars().fold(String::new(), |mut escaped, c| {
+                if matches!(c, '*' | '?' | '[' | '\\') {
+                    escaped.push('\\');
+                }
+                escaped.push(c);
+                escaped
+            });
+        } else if path.starts_with('!') && !magic.glob {
+            // Which libgit2 would take to mean leave it out
+            path.insert(0, '\\');
+        }
+        let glob = magic.glob && !magic.literal;
+        match (magic.exclude, glob, path.is_empty()) {
+            // The whole worktree
+            (true, _, true) => filter.excludes.push(String::from("*")),
+            (true, true, false) => filter.exclude_globs.push(path),
+            (true, false, false) => filter.excludes.push(path),
+            // Matches everything the others would
+            (false, _, true) => everything = true,
+            (false, true, false) => filter.globs.push(path),
+            (false, false, false) => filter.pathspecs.push(path),
+        }
+    }
+    if everything {
+        filter.pathspecs.clear();
+        filter.globs.clear();
+    }
+    Ok(filter)
+}
 
-    let mut items: Vec<ListItem> = Vec::new();
-    let mut files: Vec<String> = Vec::new();
+/// The pathspec magic [`filter`] knows, from the short form (`:/`, `:!`,
+/// `:^`) or the long one (`:(top,exclude,glob,literal)`).
+#[derive(Default)]
+struct Magic {
+    top: bool,
+    exclude: bool,
+    glob: bool,
+    literal: bool,
+}
 
-    for entry in statuses.iter() {
-        let path = match entry.path() {
-            Some(p) => p.to_string(),
-            None => continue,
+impl Magic {
+    /// The magic at the start of `pathspec`, and the pattern after it.
+    fn parse(pathspec: &str) -> Result<(Magic, &str)> {
+        let mut magic = Magic::default();
+        let Some(rest) = pathspec.strip_prefix(':') else {
+            return Ok((magic, pathspec));
         };
 
-        let (label, color) = match entry.status() {
-            s if s.contains(Status::WT_NEW) => ("New", Color::Red),
-            s if s.contains(Status::WT_MODIFIED) => ("Modified", Color::Yellow),
-            s if s.contains(Status::INDEX_NEW)
-                || s.contains(Status::WT_RENAMED)
-                || s.contains(Status::INDEX_MODIFIED) =>
-            {
-                ("Added", Color::Green)
+        if let Some(long) = rest.strip_prefix('(') {
+            let (words, pattern) = long.split_once(')').ok_or_else(|| {
+                anyhow!(
+                    "fatal: missing ')' at the end of pathspec magic in '{}'",
+                    pathspec
+                )
+            })?;
+            for word in words.split(',').map(str::trim).filter(|w| !w.is_empty()) {
+                match word {
+                    "top" => magic.top = true,
+                    "exclude" => magic.exclude = true,
+                    "glob" => magic.glob = true,
+                    "literal" => magic.literal = true,
+                    _ => bail!(
+                        "fatal: pathspec magic '{}' in '{}' isn't supported by hg status",
+                        word,
+                        pathspec
+                    ),
+                }
             }
-            _ => continue,
-        };
+            return Ok((magic, pattern));
+        }
 
-        files.push(path.clone());
+        for (i, c) in rest.char_indices() {
+            match c {
+                '/' => magic.top = true,
+                '!' | '^' => magic.exclude = true,
+                // Ends the magic, so the pattern can start with one of those
+                ':' => return Ok((magic, &rest[i + 1..])),
+                _ => return Ok((magic, &rest[i..])),
+            }
+        }
+        Ok((magic, ""))
+    }
+}
+
+/// Prints every changed file as a `git status --porcelain` style line, for
+/// scripts and pipes.
+pub fn porcelain(repo: &Repository, filter: &StatusFilter, ignored: bool) -> Result<()> {
+    let mut out = io::stdout().lock();
+    let mut files = collect_status_collapsed(repo, ignored, filter)?;
+    // git lists the tracked changes first, then `??`, then `!!`
+    files.sort_by_key(|file| match file.unstaged.as_ref().map(|c| c.kind) {
+        Some(ChangeKind::Untracked) => 1,
+        Some(ChangeKind::Ignored) => 2,
+        _ => 0,
+    });
+
+    for file in files {
+        match writeln!(out, "{}", file.porcelain()) {
+            // Piped into `head` or similar, which stopped reading
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            result => result?,
+        }
+    }
 
-        let line = Line::from(vec![
-            Span::styled(
-                label,
-                Style::default().fg(color).add_modifier(Modifier::BOLD),
-            ),
-            Span::raw(" | "),
-            Span::raw(path),
-        ]);
+    Ok(())
+}
 
-        items.push(ListItem::new(line));
+/// Prints the status as one JSON object, for editor plugins and scripts: the
+/// head, then every changed file with each side of its status, where it was
+/// renamed from and how many lines its diff adds and deletes.
+pub fn json(
+    repo: &Repository,
+    settings: DiffSettings,
+    filter: &StatusFilter,
+    ignored: bool,
+) -> Result<()> {
+    let mut files = Vec::new();
+
+    for file in collect_status(repo, ignored, filter)? {
+        files.push(JsonFile {
+            path: file.current_path().to_path_buf(),
+            staged: json_side(repo, file.staged.as_ref(), Section::Index, settings)?,
+            unstaged: json_side(repo, file.unstaged.as_ref(), Section::Worktree, settings)?,
+            conflicted: file.conflicted,
+            submodule: file.submodule.map(|state| JsonSubmodule {
+                new_commits: state.new_commits,
+                modified_content: state.modified_content,
+                untracked_content: state.untracked_content,
+            }),
+        });
     }
 
-    if items.is_empty() {
-        items.push(ListItem::new("Working tree clean"));
+    let status = JsonStatus {
+        head: repo::head_summary(repo),
+        files,
+    };
+    match writeln!(io::stdout().lock(), "{}", serde_json::to_string(&status)?) {
+        // Piped into `head` or similar, which stopped reading
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
     }
+}
 
-    let mut list_state = ListState::default();
-    list_state.select(Some(0));
+/// What [`json`] prints; fields go out in the order they're declared.
+#[derive(Serialize)]
+struct JsonStatus {
+    head: String,
+    files: Vec<JsonFile>,
+}
 
-    // ---------- UI State ----------
-    let mut focus = Focus::Left;
-    let mut diff_scroll: u16 = 0;
-    let mut current_diff: Vec<Line<'static>> = Vec::new();
-    let mut last_selected: Option<usize> = None;
+#[derive(Serialize)]
+struct JsonFile {
+    #[serde(serialize_with = "json::path")]
+    path: PathBuf,
+    staged: Option<JsonSide>,
+    unstaged: Option<JsonSide>,
+    conflicted: bool,
+    submodule: Option<JsonSubmodule>,
+}
 
-    // ---------- Main Loop ----------
-    loop {
-        // Recalculate diff only if selection changed
-        if let Some(selected) = list_state.selected() {
-            if Some(selected) != last_selected {
-                if let Some(path) = files.get(selected) {
-                    current_diff = diff::show_file_diff(repo, path)
-                        .unwrap_or_else(|e| vec![Line::from(format!("Error: {}", e))]);
-                }
-                diff_scroll = 0;
-                last_selected = Some(selected);
-            }
-        }
+/// One side of a file's status, with the stats of the diff behind it.
+#[derive(Serialize)]
+struct JsonSide {
+    status: &'static str,
+    #[serde(serialize_with = "json::path")]
+    path: PathBuf,
+    rename: Option<JsonRename>,
+    additions: usize,
+    deletions: usize,
+}
 
-        // ---------- Helper line ----------
-        let help_line = Line::from(vec![
-            Span::raw(" ↑↓ / j k "),
-            Span::styled("navigate", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" • "),
-            Span::styled("Enter", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" actions "),
-            Span::raw(" • "),
-            Span::styled("Tab", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" switch focus "),
-            Span::raw(" • "),
-            Span::styled("q", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" quit"),
-        ]);
-
-        terminal.draw(|frame| {
-            let area = frame.area();
-
-            // ---------- Reserve bottom line for helper ----------
-            let outer_chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([
-                    Constraint::Min(0),    // top: main panels
-                    Constraint::Length(1), // bottom: help line
-                ])
-                .split(area);
-
-            // ---------- Horizontal panels ----------
-            let chunks = Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
-                .split(outer_chunks[0]); // top section
-
-            // ---------- Left Panel ----------
-            let left_block = Block::default()
-                .title(" Git Status ")
-                .borders(Borders::ALL)
-                .border_style(if focus == Focus::Left {
-                    Style::default().fg(Color::Yellow)
-                } else {
-                    Style::default()
-                });
-
-            let list = List::new(items.clone())
-                .block(left_block)
-                .highlight_style(
-                    Style::default()
-                        .bg(Color::DarkGray)
-                        .add_modifier(Modifier::BOLD),
-                )
-                .highlight_symbol("➜ ")
-                .highlight_spacing(HighlightSpacing::Always);
+#[derive(Serialize)]
+struct JsonRename {
+    #[serde(serialize_with = "json::path")]
+    from: PathBuf,
+    similarity: Option<u16>,
+}
+
+#[derive(Serialize)]
+struct JsonSubmodule {
+    new_commits: bool,
+    modified_content: bool,
+    untracked_content: bool,
+}
+
+/// `change` as a [`JsonSide`]; `None`, which prints as `null`, when that
+/// side has nothing.
+fn json_side(
+    repo: &Repository,
+    change: Option<&Change>,
+    section: Section,
+    settings: DiffSettings,
+) -> Result<Option<JsonSide>> {
+    let Some(change) = change else {
+        return Ok(None);
+    };
+
+    let entry = FileEntry {
+        path: change.path.clone(),
+        section,
+        rename: change.rename.clone(),
+    };
+    let stats = file_diff(repo, &entry, settings, false)?.stats()?;
+    Ok(Some(JsonSide {
+        status: change.kind.name(),
+        path: change.path.clone(),
+        rename: change.rename.as_ref().map(|rename| JsonRename {
+            from: rename.from.clone(),
+            similarity: rename.similarity,
+        }),
+        additions: stats.insertions(),
+        deletions: stats.deletions(),
+    }))
+}
 
-            frame.render_stateful_widget(list, chunks[0], &mut list_state);
+/// Prints the status list as the UI shows it and then each file's patch, for
+/// when there is no screen to draw on; in color when `color` is set.
+pub fn text(
+    repo: &Repository,
+    settings: DiffSettings,
+    theme: &Theme,
+    color: bool,
+    filter: &StatusFilter,
+    ignored: bool,
+) -> Result<()> {
+    match write_text(repo, settings, theme, color, filter, ignored) {
+        // Piped into `head` or similar, which stopped reading
+        Err(e)
+            if e.downcast_ref::<io::Error>()
+                .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) =>
+        {
+            Ok(())
+        }
+        result => result,
+    }
+}
 
-            // ---------- Right Panel ----------
-            let right_block = Block::default()
-                .title(" Diff ")
-                .borders(Borders::ALL)
-                .border_style(if focus == Focus::Right {
-                    Style::default().fg(Color::Yellow)
-                } else {
-                    Style::default()
-                });
+fn write_text(
+    repo: &Repository,
+    settings: DiffSettings,
+    theme: &Theme,
+    color: bool,
+    filter: &StatusFilter,
+    ignored: bool,
+) -> Result<()> {
+    let mut out = io::stdout().lock();
+    let list = StatusList::load(repo, theme, ignored, filter)?;
+
+    let title = Line::styled(
+        format!("{} ({})", list.summary, list.counts),
+        Style::default().add_modifier(Modifier::BOLD),
+    );
+    writeln!(out, "{}", tui::ansi(&title, color))?;
+    for line in &list.lines {
+        writeln!(out, "{}", tui::ansi(line, color))?;
+    }
 
-            let paragraph = Paragraph::new(current_diff.clone())
-                .block(right_block)
-                .scroll((diff_scroll, 0));
+    for row in &list.rows {
+        let Row::File(entry) = row else {
+            continue;
+        };
+        writeln!(out)?;
+        for line in &diff::patch_lines(repo, entry, settings, theme)? {
+            writeln!(out, "{}", tui::ansi(line, color))?;
+        }
+    }
 
-            frame.render_widget(paragraph, chunks[1]);
+    Ok(())
+}
 
-            // ---------- Helper Line ----------
-            let help_paragraph = Paragraph::new(help_line)
-                .alignment(Alignment::Center)
-                .style(Style::default().dim());
+pub fn status(
+    repo: &Repository,
+    settings: DiffSettings,
+    list_width: u16,
+    keymap: &Keymap,
+    theme: &Theme,
+    filter: StatusFilter,
+    show_ignored: bool,
+) -> Result<()> {
+    let mut tui = Tui::new()?;
+    let app = App::new(
+        repo,
+        settings,
+        list_width,
+        keymap,
+        theme,
+        filter,
+        show_ignored,
+    )?;
+    run(&mut tui, app, repo, settings, list_width, keymap, theme)
+}
 
-            frame.render_widget(help_paragraph, outer_chunks[1]);
-        })?;
+/// `app` on a terminal that's already set up, so a submodule's status can
+/// run inside its parent's and hand the screen back afterwards.
+fn run(
+    tui: &mut Tui,
+    mut app: App,
+    repo: &Repository,
+    settings: DiffSettings,
+    list_width: u16,
+    keymap: &Keymap,
+    theme: &Theme,
+) -> Result<()> {
+    // ---------- Main Loop ----------
+    loop {
+        // The mouse is this view's alone; the views it opens leave it to the terminal
+        tui.capture_mouse(true)?;
+        let finished = app.tick()?;
+        tui.draw(|frame| app.render(frame))?;
+        tui.show_pictures(theme.images, &app.pictures())?;
 
         // ---------- Input Handling ----------
-        if let Event::Key(key) = event::read()? {
-            match key.code {
-                KeyCode::Char('q') | KeyCode::Esc => break,
-
-                KeyCode::Tab => {
-                    focus = if focus == Focus::Left {
-                        Focus::Right
-                    } else {
-                        Focus::Left
-                    };
+        // Everything that needs the terminal itself comes back as a command,
+        // from a key or from background work that just finished
+        let command = match finished {
+            Command::None => {
+                if !event::poll(app.poll_timeout())? {
+                    continue;
                 }
-
-                KeyCode::Up | KeyCode::Char('k') => match focus {
-                    Focus::Left => {
-                        if let Some(i) = list_state.selected() {
-                            if i > 0 {
-                                list_state.select(Some(i - 1));
-                            }
-                        }
-                    }
-                    Focus::Right => {
-                        diff_scroll = diff_scroll.saturating_sub(1);
-                    }
-                },
-
-                KeyCode::Down | KeyCode::Char('j') => match focus {
-                    Focus::Left => {
-                        if let Some(i) = list_state.selected() {
-                            if i < items.len().saturating_sub(1) {
-                                list_state.select(Some(i + 1));
-                            }
-                        }
-                    }
-                    Focus::Right => {
-                        diff_scroll = diff_scroll.saturating_add(1);
-                    }
-                },
-
-                _ => {}
+                app.update(&event::read()?)?
+            }
+            command => command,
+        };
+        if !matches!(command, Command::None | Command::Quit) {
+            tui.capture_mouse(false)?;
+        }
+        match command {
+            Command::None => {}
+            Command::Quit => break,
+            Command::Edit(path, line) => {
+                if let Err(e) = tui.suspend(|| editor::open(&path, line))? {
+                    app.message = Some(format!("Error: {}", e));
+                }
+                app.refresh()?;
+            }
+            Command::Resolve(path) => {
+                match resolve::resolve(tui, repo, &path, keymap, theme) {
+                    Ok(resolved) => app.message = resolved,
+                    Err(e) => app.message = Some(format!("Error: {}", e)),
+                }
+                app.refresh()?;
+            }
+            Command::Tool(entry) => {
+                match tui.suspend(|| tool::open(repo, &entry))? {
+                    Ok(message) => app.message = message,
+                    Err(e) => app.message = Some(format!("Error: {}", e)),
+                }
+                app.refresh()?;
+            }
+            Command::SignedCommit(mode, text) => {
+                let before = undo::head(repo);
+                app.message = Some(
+                    tui.suspend(|| mode.submit(repo, &text, true))?
+                        .unwrap_or_else(|e| format!("Error: {}", e)),
+                );
+                app.record_commit(mode, before);
+                app.refresh()?;
+            }
+            Command::Blame(path) => blame::blame(tui, repo, &path, keymap, theme)?,
+            Command::History(path) => {
+                history::history(tui, repo, &path, list_width, keymap, theme)?
+            }
+            Command::Branches => {
+                branches::branches(tui, repo, keymap, theme)?;
+                app.refresh()?;
+            }
+            Command::Tags => {
+                tags::tags(tui, repo, list_width, keymap, theme)?;
+                app.refresh()?;
+            }
+            Command::Remotes => {
+                remotes::remotes(tui, repo, list_width, keymap, theme)?;
+                app.refresh()?;
+            }
+            Command::Bisect => {
+                bisect::bisect(tui, repo, &[], list_width, keymap, theme)?;
+                app.refresh()?;
+            }
+            Command::Reflog => {
+                reflog::reflog(tui, repo, None, list_width, keymap, theme)?;
+                app.refresh()?;
+            }
+            Command::Worktrees => {
+                if let Some(dir) = worktree::worktree(tui, repo, list_width, keymap, theme)? {
+                    nested(tui, &dir, &mut app, settings, list_width, keymap, theme);
+                }
+                app.refresh()?;
+            }
+            Command::Stash => {
+                stash::stash(tui, repo, list_width, keymap, theme)?;
+                app.refresh()?;
+            }
+            Command::Clean => {
+                clean::clean(tui, repo, list_width, keymap, theme, app.show_ignored())?;
+                app.refresh()?;
+            }
+            Command::Submodule(path) => {
+                let dir = repo.workdir().unwrap_or_else(|| repo.path()).join(&path);
+                nested(tui, &dir, &mut app, settings, list_width, keymap, theme);
+                // Whatever was committed in there moved the gitlink out here
+                app.refresh()?;
             }
         }
     }
 
-    disable_raw_mode()?;
-    stdout().execute(LeaveAlternateScreen)?;
     Ok(())
 }
+
+/// A status view for the repository at `dir`, a submodule or another
+/// worktree, run inside `app`'s; anything going wrong is shown in `app`.
+fn nested(
+    tui: &mut Tui,
+    dir: &Path,
+    app: &mut App,
+    settings: DiffSettings,
+    list_width: u16,
+    keymap: &Keymap,
+    theme: &Theme,
+) {
+    let opened = Repository::open(dir).map_err(anyhow::Error::from);
+    let result = opened.and_then(|inner| {
+        let theme = &Theme {
+            whitespace: theme.whitespace.for_repo(&inner),
+            ..theme.clone()
+        };
+        let filter = StatusFilter::default();
+        let show_ignored = app.show_ignored();
+        let inner_app = App::new(
+            &inner,
+            settings,
+            list_width,
+            keymap,
+            theme,
+            filter,
+            show_ignored,
+        )?;
+        run(tui, inner_app, &inner, settings, list_width, keymap, theme)
+    });
+    if let Err(e) = result {
+        app.message = Some(format!("Error: {}", e));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(prefix: &str, pathspecs: &[&str]) -> Result<StatusFilter> {
+        let pathspecs: Vec<String> = pathspecs.iter().map(|p| p.to_string()).collect();
+        filter_in(Path::new("/repo"), Path::new(prefix), &pathspecs, None)
+    }
+
+    #[test]
+    fn pathspecs_are_taken_from_the_current_directory() {
+        let found = filter("sub", &["x", "../y", ":/z", "/repo/top"]).unwrap();
+        assert_eq!(found.pathspecs, ["sub/x", "y", "z", "top"]);
+        assert!(filter("sub", &["x", ".."]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn magic_is_read_before_the_path() {
+        let found = filter(
+            "sub",
+            &[":!x", ":^/y", ":(exclude,glob)*.rs", ":(glob)**/a"],
+        )
+        .unwrap();
+        assert_eq!(found.excludes, ["sub/x", "y"]);
+        assert_eq!(found.exclude_globs, ["sub/*.rs"]);
+        assert_eq!(found.globs, ["sub/**/a"]);
+        // The `:` ends the magic, so the `!` is the name's
+        let found = filter("", &[":(literal)a*[b]", ":/:!c"]).unwrap();
+        assert_eq!(found.pathspecs, ["a\\*\\[b]", "\\!c"]);
+        assert!(found.excludes.is_empty());
+        assert!(filter("", &[":(icase)a"]).is_err());
+    }
+
+    #[test]
+    fn paths_outside_the_worktree_are_refused() {
+        let error = filter("sub", &["../../x"]).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "fatal: ../../x: '../../x' is outside repository at '/repo'"
+        );
+        assert!(filter("", &["/elsewhere/x"]).is_err());
+    }
+}
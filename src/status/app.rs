@@ -0,0 +1,2542 @@
+use super::commit::{self, CommitMode};
+use super::compose::{BoxResult, CommitBox};
+use super::diff::{self, Column, FileDiff, More, SplitDiff};
+use super::discard::{self, Discarded};
+use super::editor;
+use super::hooks::{self, HookAction, HookPopup};
+use super::ignore::{self, IgnorePrompt, IgnoreResult};
+use super::list::StatusList;
+use super::loader::{DiffLoader, StatusLoader};
+use super::palette::{Palette, PaletteResult};
+use super::remote::{self, RemoteOp, RemoteTask};
+use super::search::DiffSearch;
+use super::stage;
+use super::undo::{self, UndoStack};
+use super::watcher::Watcher;
+use crate::clipboard::Clipboard;
+use crate::help;
+use crate::image::Picture;
+use crate::keymap::{Action, Keymap, Scope};
+use crate::theme::Theme;
+use crate::tui::TICK;
+use anyhow::{Result, anyhow};
+use crossterm::event::{Event, KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind};
+use git2::Repository;
+use helper_git_core::diff::{DiffSettings, DiffText};
+use helper_git_core::lfs;
+use helper_git_core::repo::{RepoSummary, Upstream};
+use helper_git_core::status::{FileEntry, Section, StatusFilter};
+use ratatui::{
+    prelude::*,
+    widgets::{
+        Block, Borders, LineGauge, ListState, Paragraph, Scrollbar, ScrollbarOrientation,
+        ScrollbarState, Wrap,
+    },
+};
+use std::cell::{Ref, RefCell};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+#[derive(PartialEq)]
+enum Focus {
+    Left,
+    Right,
+}
+
+/// What the event loop has to do after [`App::update`], beyond redrawing.
+/// These are the steps that need the terminal itself.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    None,
+    Quit,
+    /// Hand the terminal to `$EDITOR` for this file, at this line if known,
+    /// then refresh
+    Edit(PathBuf, Option<u32>),
+    /// Open the conflict resolver on this path, then refresh
+    Resolve(PathBuf),
+    Blame(PathBuf),
+    /// Run the configured diff or merge tool on this entry, then refresh
+    Tool(FileEntry),
+    History(PathBuf),
+    /// Open the branch or stash list, then refresh
+    Branches,
+    /// Open the tag list, then refresh
+    Tags,
+    /// Open the remote list, then refresh
+    Remotes,
+    /// Open the worktree list, then refresh, or run a status view in the
+    /// worktree picked there
+    Worktrees,
+    /// Open the reflog of HEAD, then refresh
+    Reflog,
+    /// Open the bisect in progress, or start one, then refresh
+    Bisect,
+    Stash,
+    /// Open the list of untracked files to delete, then refresh
+    Clean,
+    /// Run a status view inside the submodule at this path, then refresh
+    Submodule(PathBuf),
+    /// Make a signed commit with this message, handing the terminal to gpg
+    /// or ssh-keygen in case it asks for a passphrase, then refresh
+    SignedCommit(CommitMode, String),
+}
+
+/// Everything the status view knows between two frames. [`App::render`]
+/// draws it and [`App::update`] applies one input event to it, so neither
+/// needs a real terminal.
+pub struct App<'r> {
+    repo: &'r Repository,
+    keymap: &'r Keymap,
+    theme: &'r Theme,
+    settings: DiffSettings,
+    // Percentage of the width the list takes; dragging the divider changes it
+    list_width: u16,
+    dragging_divider: bool,
+
+    list: StatusList,
+    // What `hg status` was narrowed to on the command line
+    filter: StatusFilter,
+    list_state: ListState,
+    focus: Focus,
+    pub message: Option<String>,
+
+    diff_scroll: u16,
+    diff_height: u16,
+    diff_hscroll: u16,
+    diff_width: u16,
+    // Screen rows the diff takes, which is more than its line count when wrapped
+    diff_rows: usize,
+    wrap: bool,
+    // Side by side; diffs without a split form still show unified
+    split: bool,
+    current_diff: FileDiff,
+    // Where `current_diff` lands on screen, worked out once per diff and layout
+    layout: RefCell<DiffLayout>,
+    selected_hunk: usize,
+    // In line mode: the diff line under the cursor, always one of `current_diff.changes`
+    line_cursor: Option<usize>,
+    // The entry whose diff is shown; rows shift on refresh, so compare entries, not indices
+    last_selected: Option<FileEntry>,
+    diff_stale: bool,
+    // Lines of a whole new or deleted file to show; `load_more` raises it
+    preview_limit: usize,
+    line_numbers: bool,
+    loader: DiffLoader,
+    status_loader: StatusLoader,
+    // Set while a status load is running; the list keeps showing the last one
+    pending_status: bool,
+    started: Instant,
+    // `None` when the platform refused a watch; `r` still refreshes by hand
+    watcher: Option<Watcher>,
+    // Set while a diff is computing; `true` when it's a reload of the shown file
+    pending_reload: Option<bool>,
+    // What to discard and the prompt for it; `true` once saving it to the stash failed
+    confirm_discard: Option<(Vec<FileEntry>, String, bool)>,
+    editing_filter: bool,
+    // The `/` search of the diff pane, kept from file to file until Esc
+    search: Option<DiffSearch>,
+    show_help: bool,
+    commit_box: Option<CommitBox>,
+    // The commit hooks running, or the one that failed
+    hooks: Option<HookPopup>,
+    palette: Option<Palette>,
+    // What `u` can take back, newest last
+    undo: UndoStack,
+    ignore_prompt: Option<IgnorePrompt>,
+    remote_task: Option<RemoteTask>,
+    clipboard: Clipboard,
+    // Panel areas from the last draw, for mapping mouse events
+    list_area: Rect,
+    diff_area: Rect,
+    // Where the last draw left room for the old and new image of a preview
+    pictures: Vec<(Column, Rect)>,
+}
+
+impl<'r> App<'r> {
+    pub fn new(
+        repo: &'r Repository,
+        settings: DiffSettings,
+        list_width: u16,
+        keymap: &'r Keymap,
+        theme: &'r Theme,
+        filter: StatusFilter,
+        show_ignored: bool,
+    ) -> Result<Self> {
+        let mut app = App {
+            repo,
+            keymap,
+            theme,
+            settings,
+            list_width,
+            dragging_divider: false,
+            list: StatusList::loading(repo, theme),
+            filter: filter.clone(),
+            list_state: ListState::default(),
+            focus: Focus::Left,
+            message: None,
+            diff_scroll: 0,
+            diff_height: 0,
+            diff_hscroll: 0,
+            diff_width: 0,
+            diff_rows: 0,
+            wrap: false,
+            split: false,
+            current_diff: FileDiff::message(String::new()),
+            layout: RefCell::default(),
+            selected_hunk: 0,
+            line_cursor: None,
+            last_selected: None,
+            diff_stale: true,
+            preview_limit: diff::PREVIEW_LINES,
+            line_numbers: true,
+            loader: DiffLoader::spawn(repo, theme.clone())?,
+            status_loader: StatusLoader::spawn(repo, theme.clone(), filter)?,
+            pending_status: false,
+            started: Instant::now(),
+            watcher: Watcher::spawn(repo).ok(),
+            pending_reload: None,
+            confirm_discard: None,
+            editing_filter: false,
+            search: None,
+            show_help: false,
+            commit_box: None,
+            hooks: None,
+            palette: None,
+            undo: UndoStack::default(),
+            ignore_prompt: None,
+            remote_task: None,
+            clipboard: Clipboard::default(),
+            list_area: Rect::default(),
+            diff_area: Rect::default(),
+            pictures: Vec::new(),
+        };
+
+        app.list.show_ignored = show_ignored;
+        app.refresh()?;
+        Ok(app)
+    }
+
+    /// Whether ignored files are listed, which a submodule's view starts out with too.
+    pub fn show_ignored(&self) -> bool {
+        self.list.show_ignored
+    }
+
+    /// How long the loop may wait for input: short while a status, a diff or
+    /// a remote operation is in flight, so results show up without a keypress.
+    pub fn poll_timeout(&self) -> Duration {
+        if self.pending_status
+            || self.pending_reload.is_some()
+            || self.remote_task.is_some()
+            || self.hooks.is_some()
+        {
+            Duration::from_millis(50)
+        } else {
+            TICK
+        }
+    }
+
+    /// Picks up finished background work and asks for the selected file's
+    /// diff when the selection moved. Runs before every draw; what's finished
+    /// may need the terminal, like a signed commit after its hooks.
+    pub fn tick(&mut self) -> Result<Command> {
+        if self.focus == Focus::Left {
+            self.line_cursor = None;
+        }
+
+        let mut command = Command::None;
+        if let Some(popup) = &mut self.hooks
+            && let Some(message) = popup.poll()
+        {
+            let (mode, sign) = (popup.mode, popup.sign);
+            self.hooks = None;
+            command = self.make_commit(mode, sign, &message)?;
+        }
+
+        if let Some(task) = &self.remote_task
+            && let Some(result) = task.poll()
+        {
+            self.message = Some(result.unwrap_or_else(|e| format!("Error: {}", e)));
+            self.remote_task = None;
+            // Picks up new ahead/behind counts and whatever a pull changed
+            self.refresh()?;
+        }
+
+        if self.pending_status
+            && let Some(result) = self.status_loader.recv_timeout(Duration::ZERO)
+        {
+            self.apply_status(result)?;
+        }
+
+        // Something changed on disk: an editor saved, a checkout ran elsewhere
+        if let Some(watcher) = &mut self.watcher
+            && let Some(written) = watcher.changed()
+        {
+            self.request_status(Some(written))?;
+        }
+
+        // Recalculate diff only if selection changed
+        let selected = self.selected().cloned();
+        if selected != self.last_selected || self.diff_stale {
+            let reload = selected == self.last_selected;
+            if !reload {
+                self.preview_limit = diff::PREVIEW_LINES;
+            }
+
+            match &selected {
+                Some(entry) => {
+                    self.loader.request(
+                        entry.clone(),
+                        self.settings,
+                        self.preview_limit,
+                        self.line_numbers,
+                    );
+                    self.pending_reload = Some(reload);
+                }
+                None => {
+                    self.set_diff(FileDiff::message(String::new()));
+                    self.pending_reload = None;
+                }
+            }
+
+            if !reload {
+                self.selected_hunk = 0;
+                self.diff_scroll = 0;
+                self.diff_hscroll = 0;
+                self.line_cursor = None;
+            }
+            self.last_selected = selected;
+            self.diff_stale = false;
+
+            // Give quick diffs a moment to land so small files don't flash a placeholder
+            if let Some(result) = self.loader.recv_timeout(Duration::from_millis(30)) {
+                self.apply_loaded_diff(result, reload);
+                self.pending_reload = None;
+            } else if !reload {
+                self.set_diff(FileDiff::message(String::from("Loading diff…")));
+            }
+        } else if let Some(reload) = self.pending_reload
+            && let Some(result) = self.loader.recv_timeout(Duration::ZERO)
+        {
+            self.apply_loaded_diff(result, reload);
+            self.pending_reload = None;
+        }
+
+        Ok(command)
+    }
+
+    pub fn render(&mut self, frame: &mut Frame) {
+        let theme = self.theme;
+        let area = frame.area();
+
+        // ---------- Helper line ----------
+        let help_line = match (&self.confirm_discard, self.message.clone()) {
+            (Some((_, prompt, _)), _) => Line::styled(
+                prompt.clone(),
+                Style::default()
+                    .fg(theme.warning)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            _ if self.editing_filter => Line::from(vec![
+                Span::styled("/", Style::default().fg(theme.accent)),
+                Span::raw(self.list.filter.clone()),
+                Span::styled("█", Style::default().fg(theme.accent)),
+            ]),
+            _ if let Some(search) = self.search.as_ref().filter(|s| s.editing) => Line::from(vec![
+                Span::styled("/", Style::default().fg(theme.accent)),
+                Span::raw(search.query.clone()),
+                Span::styled("█", Style::default().fg(theme.accent)),
+                Span::styled(format!("  {}", search.counter()), Style::default().dim()),
+            ]),
+            (None, Some(text)) => Line::from(text),
+            (None, None) => help_line(
+                &self.focus,
+                self.line_cursor.is_some(),
+                self.list.tree,
+                self.current_diff.files.len() > 1,
+                self.keymap,
+            ),
+        };
+        // A running fetch, pull or push takes the helper line for its progress
+        let gauge = match (&self.remote_task, &self.confirm_discard, &self.message) {
+            (Some(task), None, None) if !self.editing_filter && !self.searching() => Some(
+                LineGauge::default()
+                    .ratio(task.transfer().ratio())
+                    .label(task.label())
+                    .filled_style(Style::default().fg(theme.accent))
+                    .unfilled_style(Style::default().dim()),
+            ),
+            _ => None,
+        };
+
+        // ---------- Reserve top line for the header, bottom line for helper ----------
+        let outer_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1), // top: repository summary
+                Constraint::Min(0),    // middle: main panels
+                Constraint::Length(1), // bottom: help line
+            ])
+            .split(area);
+        frame.render_widget(
+            Paragraph::new(header_line(&self.list.summary, self.keymap, theme)),
+            outer_chunks[0],
+        );
+
+        // ---------- Horizontal panels ----------
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Percentage(self.list_width),
+                Constraint::Percentage(100 - self.list_width),
+            ])
+            .split(outer_chunks[1]); // main section
+
+        // ---------- Left Panel ----------
+        let mut title = format!(" Git Status ({}) ", self.list.counts);
+        for pathspec in &self.filter.pathspecs {
+            title.push_str(&format!("· {} ", pathspec));
+        }
+        for glob in &self.filter.globs {
+            title.push_str(&format!("· :(glob){} ", glob));
+        }
+        for exclude in &self.filter.excludes {
+            title.push_str(&format!("· :!{} ", exclude));
+        }
+        for glob in &self.filter.exclude_globs {
+            title.push_str(&format!("· :(exclude,glob){} ", glob));
+        }
+        match self.filter.only {
+            Some(Section::Index) => title.push_str("· staged only "),
+            Some(Section::Worktree) => title.push_str("· unstaged only "),
+            Some(Section::Conflicted) | None => {}
+        }
+        if self.list.show_ignored {
+            title.push_str("· +ignored ");
+        }
+        let marked = self.list.marked_count();
+        if marked > 0 {
+            title.push_str(&format!("· {} marked ", marked));
+        }
+        if !self.list.filter.is_empty() {
+            title.push_str(&format!("· /{} ", self.list.filter));
+            if self.list.fuzzy {
+                title.push_str("(fuzzy) ");
+            }
+        }
+        if self.pending_status {
+            let frame = self.started.elapsed().as_millis() / 100;
+            title.push_str(&format!("{} ", SPINNER[frame as usize % SPINNER.len()]));
+        }
+
+        let left_block = Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .border_style(if self.focus == Focus::Left {
+                theme.focus()
+            } else {
+                Style::default()
+            });
+
+        // The list is built with the status, so drawing it only borrows it
+        let list_inner = left_block.inner(chunks[0]);
+        frame.render_widget(left_block, chunks[0]);
+        frame.render_stateful_widget(&self.list.widget, list_inner, &mut self.list_state);
+        self.list_area = chunks[0];
+
+        // ---------- Right Panel ----------
+        let mut title = String::from(" Diff ");
+        if self.split_view().is_some() {
+            title.push_str("· split ");
+        } else if self.wrap {
+            title.push_str("· wrap ");
+        }
+        for (on, flag) in [
+            (self.settings.ignore_all_space, "-w"),
+            (self.settings.ignore_space_change, "-b"),
+            (self.settings.ignore_blank_lines, "--ignore-blank-lines"),
+        ] {
+            if on {
+                title.push_str(&format!("· {} ", flag));
+            }
+        }
+        if let Some(search) = &self.search
+            && !search.query.is_empty()
+        {
+            title.push_str(&format!("· /{} {} ", search.query, search.counter()));
+        }
+
+        let right_block = Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .border_style(if self.focus == Focus::Right {
+                theme.focus()
+            } else {
+                Style::default()
+            });
+
+        // Re-clamp on every draw so a resize never leaves the diff scrolled past its end
+        let diff_inner = chunks[1].inner(Margin {
+            vertical: 1,
+            horizontal: 0,
+        });
+        let text_area = right_block.inner(chunks[1]);
+        // Each column keeps one cell for the divider or the scrollbar
+        let [old_column, new_column] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(text_area);
+        self.diff_height = diff_inner.height;
+        self.diff_width = if self.split_view().is_some() {
+            old_column.width.saturating_sub(1)
+        } else {
+            diff_inner.width.saturating_sub(2)
+        };
+        let rows = self.layout().rows;
+        self.diff_rows = rows;
+        let width = self.wrap_width().is_none().then(|| self.content_width());
+        (self.diff_scroll, self.diff_hscroll) = clamp_offsets(
+            (self.diff_scroll, self.diff_hscroll),
+            (self.diff_rows, width),
+            (self.diff_height, self.diff_width),
+        );
+
+        let hunk = self.current_diff.hunks.get(self.selected_hunk);
+        let hunk_bg = |line: &mut Line| line.style = line.style.bg(theme.hunk_bg);
+        // Reversed rather than a background, which highlighted code would paint over
+        let reversed = |line: &mut Line| line.style = line.style.add_modifier(Modifier::REVERSED);
+
+        if let Some(split) = self.split_view() {
+            // Only the rows on screen are copied out and styled
+            let top = (self.diff_scroll as usize).min(split.rows.len());
+            let end = (top + self.diff_height as usize).min(split.rows.len());
+            let mut rows = split.rows[top..end].to_vec();
+            if let Some(search) = &self.search {
+                let gutter = self.gutter();
+                // The current match is in one column, or both for a context line
+                let current = search.line().map(|line| (split.positions[line], line));
+                for (row, (old, new)) in (top..).zip(&mut rows) {
+                    let on = |columns: [Column; 2]| match current {
+                        Some(((r, column), line)) if r == row && columns.contains(&column) => {
+                            search.current_on(line)
+                        }
+                        _ => None,
+                    };
+                    search.highlight(old, gutter, on([Column::Old, Column::Both]), theme);
+                    search.highlight(new, gutter, on([Column::New, Column::Both]), theme);
+                }
+            }
+            if let Some(hunk) = hunk {
+                let first = split.positions[hunk.start].0;
+                let last = hunk
+                    .clone()
+                    .map(|l| split.positions[l].0)
+                    .max()
+                    .unwrap_or(first);
+                for (row, (old, new)) in (top..).zip(&mut rows) {
+                    if (first..=last).contains(&row) {
+                        hunk_bg(old);
+                        hunk_bg(new);
+                    }
+                }
+            }
+            if let Some(cursor) = self.line_cursor
+                && let (row, column) = split.positions[cursor]
+                && (top..end).contains(&row)
+            {
+                let (old, new) = &mut rows[row - top];
+                match column {
+                    Column::Old => reversed(old),
+                    Column::New => reversed(new),
+                    Column::Both => {
+                        reversed(old);
+                        reversed(new);
+                    }
+                }
+            }
+
+            // Both columns share one scroll offset, so their rows stay aligned
+            let (old, new): (Vec<_>, Vec<_>) = rows.into_iter().unzip();
+            let scroll = (0, self.diff_hscroll);
+            frame.render_widget(right_block, chunks[1]);
+            frame.render_widget(
+                Paragraph::new(old)
+                    .block(Block::default().borders(Borders::RIGHT))
+                    .scroll(scroll),
+                old_column,
+            );
+            frame.render_widget(Paragraph::new(new).scroll(scroll), new_column);
+        } else {
+            // Likewise only the lines on screen; a wrapped line can start above
+            // the panel, so the paragraph scrolls past the part that's out of view
+            let all = &self.current_diff.lines;
+            let (top, end, scroll) = {
+                let layout = self.layout();
+                let scroll = self.diff_scroll as usize;
+                let top = layout.line_at(scroll).min(all.len());
+                let end = layout
+                    .line_at(scroll + self.diff_height as usize)
+                    .saturating_add(1)
+                    .min(all.len());
+                let offset = scroll.saturating_sub(layout.starts.get(top).copied().unwrap_or(0));
+                (top, end, offset as u16)
+            };
+            let mut lines = all[top..end].to_vec();
+            let gutter = self.gutter();
+            for (i, line) in (top..).zip(&mut lines) {
+                if let Some(search) = &self.search {
+                    search.highlight(line, gutter, search.current_on(i), theme);
+                }
+                if hunk.is_some_and(|h| h.contains(&i)) {
+                    hunk_bg(line);
+                }
+                if self.line_cursor == Some(i) {
+                    reversed(line);
+                }
+            }
+
+            let mut paragraph = Paragraph::new(lines)
+                .block(right_block)
+                .scroll((scroll, self.diff_hscroll));
+            if self.wrap {
+                paragraph = paragraph.wrap(Wrap { trim: false });
+            }
+
+            frame.render_widget(paragraph, chunks[1]);
+        }
+        self.diff_area = chunks[1];
+
+        // ---------- Image preview ----------
+        // Below the diff's text, old and new side by side; the terminal draws
+        // the images into the space once the frame is out, so popups go without
+        self.pictures.clear();
+        let covered = self.show_help
+            || self.commit_box.is_some()
+            || self.hooks.is_some()
+            || self.palette.is_some()
+            || self.ignore_prompt.is_some();
+        if let Some(preview) = &self.current_diff.image
+            && !covered
+        {
+            // A blank row under the text
+            let shown = self.diff_rows.saturating_sub(self.diff_scroll as usize) + 1;
+            let top = text_area
+                .y
+                .saturating_add(shown.min(u16::MAX as usize) as u16);
+            if text_area.bottom() > top.saturating_add(2) {
+                let area = Rect {
+                    y: top,
+                    height: text_area.bottom() - top,
+                    ..text_area
+                };
+                let sides: Vec<_> = [(Column::Old, &preview.old), (Column::New, &preview.new)]
+                    .into_iter()
+                    .filter_map(|(column, picture)| Some((column, picture.as_ref()?)))
+                    .collect();
+                let columns = Layout::horizontal(vec![Constraint::Fill(1); sides.len()])
+                    .spacing(1)
+                    .split(area);
+                for ((column, picture), &area) in sides.into_iter().zip(columns.iter()) {
+                    let label = format!(
+                        "{} · {}×{}",
+                        if column == Column::Old { "old" } else { "new" },
+                        picture.width,
+                        picture.height
+                    );
+                    frame.render_widget(
+                        Paragraph::new(label).style(Style::default().dim()),
+                        Rect { height: 1, ..area },
+                    );
+                    self.pictures.push((
+                        column,
+                        Rect {
+                            y: area.y + 1,
+                            height: area.height - 1,
+                            ..area
+                        },
+                    ));
+                }
+            }
+        }
+
+        // ---------- Diff Scrollbar ----------
+        let max_scroll = max_scroll(self.diff_rows, self.diff_height);
+
+        let mut scrollbar_state = ScrollbarState::new(max_scroll as usize + 1)
+            .viewport_content_length(self.diff_height as usize)
+            .position(self.diff_scroll as usize);
+
+        frame.render_stateful_widget(
+            Scrollbar::new(ScrollbarOrientation::VerticalRight),
+            diff_inner,
+            &mut scrollbar_state,
+        );
+
+        // ---------- Helper Line ----------
+        let help_paragraph = Paragraph::new(help_line)
+            .alignment(Alignment::Center)
+            .style(
+                if self.confirm_discard.is_some() || self.editing_filter || self.searching() {
+                    Style::default()
+                } else {
+                    Style::default().dim()
+                },
+            );
+
+        match gauge {
+            Some(gauge) => frame.render_widget(gauge, outer_chunks[2]),
+            None => frame.render_widget(help_paragraph, outer_chunks[2]),
+        }
+
+        if let Some(commit_box) = &mut self.commit_box {
+            commit_box.render(frame, theme);
+        }
+
+        if let Some(popup) = &mut self.hooks {
+            popup.render(frame, theme);
+        }
+
+        if let Some(palette) = &mut self.palette {
+            palette.render(frame, self.keymap, theme);
+        }
+
+        if let Some(prompt) = &mut self.ignore_prompt {
+            prompt.render(frame, theme);
+        }
+
+        if self.show_help {
+            help::render(frame, self.keymap, theme);
+        }
+    }
+
+    /// The images of the shown diff's preview, each with the area the last
+    /// draw left for it.
+    pub fn pictures(&self) -> Vec<(&Picture, Rect)> {
+        let Some(preview) = &self.current_diff.image else {
+            return Vec::new();
+        };
+        self.pictures
+            .iter()
+            .filter_map(|&(column, area)| {
+                let picture = match column {
+                    Column::Old => preview.old.as_ref(),
+                    _ => preview.new.as_ref(),
+                };
+                Some((picture?, area))
+            })
+            .collect()
+    }
+
+    /// Applies one input event. Resizes need nothing here: the next draw lays
+    /// the panels out for the new size and re-clamps the scroll.
+    pub fn update(&mut self, event: &Event) -> Result<Command> {
+        match event {
+            Event::Mouse(mouse) => {
+                self.handle_mouse(mouse);
+                Ok(Command::None)
+            }
+            Event::Key(key) => self.handle_key(key),
+            _ => Ok(Command::None),
+        }
+    }
+
+    fn handle_mouse(&mut self, mouse: &MouseEvent) {
+        if self.confirm_discard.is_some()
+            || self.editing_filter
+            || self.searching()
+            || self.show_help
+            || self.commit_box.is_some()
+            || self.hooks.is_some()
+            || self.palette.is_some()
+            || self.ignore_prompt.is_some()
+        {
+            return;
+        }
+
+        let position = Position::new(mouse.column, mouse.row);
+        let over_list = self.list_area.contains(position);
+        let over_diff = self.diff_area.contains(position);
+        // The two borders where the panels meet
+        let on_divider = (over_list && mouse.column == self.list_area.right() - 1)
+            || (over_diff && mouse.column == self.diff_area.x);
+
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) if on_divider => self.dragging_divider = true,
+            MouseEventKind::Drag(MouseButton::Left) if self.dragging_divider => {
+                let total = self.list_area.width + self.diff_area.width;
+                let column = mouse.column.saturating_sub(self.list_area.x) + 1;
+                if total > 0 {
+                    self.list_width = (column as u32 * 100 / total as u32).clamp(10, 90) as u16;
+                }
+            }
+            MouseEventKind::Up(MouseButton::Left) => self.dragging_divider = false,
+            MouseEventKind::Down(MouseButton::Left) if over_list => {
+                self.focus = Focus::Left;
+                // Rows start below the top border, shifted by the list's scroll offset
+                let row = (mouse.row - self.list_area.y).checked_sub(1);
+                if let Some(row) = row.map(|r| r as usize + self.list_state.offset())
+                    && self.list.selectable(row)
+                {
+                    self.list_state.select(Some(row));
+                }
+            }
+            MouseEventKind::Down(MouseButton::Left) if over_diff => self.focus = Focus::Right,
+            MouseEventKind::ScrollUp if over_list => {
+                if let Some(i) = self.list_state.selected().and_then(|i| self.list.prev(i)) {
+                    self.list_state.select(Some(i));
+                }
+            }
+            MouseEventKind::ScrollDown if over_list => {
+                if let Some(i) = self.list_state.selected().and_then(|i| self.list.next(i)) {
+                    self.list_state.select(Some(i));
+                }
+            }
+            MouseEventKind::ScrollUp if over_diff => {
+                self.diff_scroll = self.diff_scroll.saturating_sub(3);
+            }
+            MouseEventKind::ScrollDown if over_diff => {
+                self.diff_scroll = clamp_scroll(
+                    self.diff_scroll.saturating_add(3),
+                    self.diff_rows,
+                    self.diff_height,
+                );
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_key(&mut self, key: &KeyEvent) -> Result<Command> {
+        let repo = self.repo;
+        self.message = None;
+
+        // A pending discard swallows every key until it is answered
+        if let Some((entries, _, unsaved)) = &self.confirm_discard {
+            match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    let (entries, unsaved) = (entries.clone(), *unsaved);
+                    self.confirm_discard = None;
+                    let result = if unsaved {
+                        discard::discard_unsaved(repo, &entries)
+                    } else {
+                        match discard::discard(repo, &entries) {
+                            Ok(Discarded::Saved(text, stash)) => {
+                                let what = format!("discarding {}", discard::describe(&entries));
+                                self.undo.stash(what, stash, &entries);
+                                Ok(text)
+                            }
+                            // Nothing was touched; going on for good is asked again
+                            Ok(Discarded::NotSaved(e)) => {
+                                let prompt = format!(
+                                    "Can't save it to the stash first ({}); discard it for good? (y/n)",
+                                    e
+                                );
+                                self.confirm_discard = Some((entries, prompt, true));
+                                return Ok(Command::None);
+                            }
+                            Err(e) => Err(e),
+                        }
+                    };
+                    self.message = Some(result.unwrap_or_else(|e| format!("Error: {}", e)));
+                    self.list.clear_marks();
+                    self.refresh()?;
+                }
+                KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                    self.confirm_discard = None;
+                }
+                _ => {}
+            }
+            return Ok(Command::None);
+        }
+
+        if let Some(popup) = &mut self.hooks {
+            match popup.handle(key) {
+                HookAction::Waiting => {}
+                HookAction::Retry => popup.retry(repo),
+                // Back to the message, so it isn't lost
+                HookAction::Cancelled => {
+                    let mut commit_box = CommitBox::new(repo, popup.mode, popup.message());
+                    commit_box.sign = popup.sign;
+                    self.commit_box = Some(commit_box);
+                    self.hooks = None;
+                }
+                HookAction::Commit(message) => {
+                    let (mode, sign) = (popup.mode, popup.sign);
+                    self.hooks = None;
+                    return self.make_commit(mode, sign, &message);
+                }
+            }
+            return Ok(Command::None);
+        }
+
+        if let Some(commit_box) = &mut self.commit_box {
+            match commit_box.handle(key) {
+                BoxResult::Editing => {}
+                BoxResult::Cancelled => self.commit_box = None,
+                BoxResult::Submitted(text) => match commit_box.message(&text) {
+                    Err(e) => self.message = Some(format!("Error: {}", e)),
+                    // The hooks run first, as `git commit` runs them
+                    Ok(text) if !hooks::installed(repo).is_empty() => {
+                        let popup = HookPopup::new(repo, commit_box.mode, commit_box.sign, text);
+                        self.hooks = Some(popup);
+                        self.commit_box = None;
+                    }
+                    Ok(text) => {
+                        let (mode, sign) = (commit_box.mode, commit_box.sign);
+                        self.commit_box = None;
+                        return self.make_commit(mode, sign, &text);
+                    }
+                },
+            }
+            return Ok(Command::None);
+        }
+
+        if let Some(palette) = &mut self.palette {
+            return match palette.handle(key) {
+                PaletteResult::Open => Ok(Command::None),
+                PaletteResult::Closed => {
+                    self.palette = None;
+                    Ok(Command::None)
+                }
+                PaletteResult::Run(action) => {
+                    self.palette = None;
+                    self.perform(action)
+                }
+                PaletteResult::Undo(count) => {
+                    self.palette = None;
+                    self.undo_steps(count)?;
+                    Ok(Command::None)
+                }
+            };
+        }
+
+        if let Some(prompt) = &mut self.ignore_prompt {
+            match prompt.handle(key) {
+                IgnoreResult::Open => {}
+                IgnoreResult::Closed => self.ignore_prompt = None,
+                IgnoreResult::Chosen(pattern) => {
+                    self.message = Some(
+                        ignore::append(repo, &pattern).unwrap_or_else(|e| format!("Error: {}", e)),
+                    );
+                    self.ignore_prompt = None;
+                    self.refresh()?;
+                }
+            }
+            return Ok(Command::None);
+        }
+
+        if self.editing_filter {
+            match key.code {
+                KeyCode::Esc => {
+                    self.editing_filter = false;
+                    self.apply_filter(String::new());
+                }
+                KeyCode::Enter => self.editing_filter = false,
+                KeyCode::Backspace => {
+                    let mut filter = self.list.filter.clone();
+                    filter.pop();
+                    self.apply_filter(filter);
+                }
+                KeyCode::Char(c) => {
+                    let filter = format!("{}{}", self.list.filter, c);
+                    self.apply_filter(filter);
+                }
+                _ => {}
+            }
+            return Ok(Command::None);
+        }
+
+        if let Some(search) = self.search.as_mut().filter(|s| s.editing) {
+            match key.code {
+                KeyCode::Esc => self.search = None,
+                KeyCode::Enter => search.editing = false,
+                KeyCode::Backspace => {
+                    search.query.pop();
+                    self.find_matches();
+                }
+                KeyCode::Char(c) => {
+                    search.query.push(c);
+                    self.find_matches();
+                }
+                _ => {}
+            }
+            return Ok(Command::None);
+        }
+
+        // The help popup only closes; everything else is ignored while it's up
+        if self.show_help {
+            if key.code == KeyCode::Esc
+                || self.keymap.resolve(key, Scope::Global) == Some(Action::Help)
+            {
+                self.show_help = false;
+            }
+            return Ok(Command::None);
+        }
+
+        let scope = match self.focus {
+            Focus::Left => Scope::List,
+            Focus::Right => Scope::Diff,
+        };
+
+        let Some(action) = self.keymap.resolve(key, scope) else {
+            return Ok(Command::None);
+        };
+
+        // Esc drops a diff search, an active filter, then the marks, before it quits
+        if action == Action::Quit && key.code == KeyCode::Esc {
+            if self.search.is_some() {
+                self.search = None;
+                return Ok(Command::None);
+            }
+            if !self.list.filter.is_empty() {
+                self.apply_filter(String::new());
+                return Ok(Command::None);
+            }
+            if !self.list.marked().is_empty() {
+                self.list.clear_marks();
+                return Ok(Command::None);
+            }
+        }
+
+        self.perform(action)
+    }
+
+    /// Does what `action` means in the status view, whether its key was
+    /// pressed or it was picked from the command palette. Each scope of the
+    /// keymap has its own handler.
+    fn perform(&mut self, action: Action) -> Result<Command> {
+        match action.scope() {
+            Scope::List => self.perform_list(action),
+            Scope::Diff => self.perform_diff(action),
+            _ => self.perform_global(action),
+        }
+    }
+
+    /// What works the same from either panel, bar moving around.
+    fn perform_global(&mut self, action: Action) -> Result<Command> {
+        let repo = self.repo;
+
+        match action {
+            Action::Quit if self.line_cursor.is_some() => self.line_cursor = None,
+
+            Action::Quit => return Ok(Command::Quit),
+
+            Action::Help => self.show_help = true,
+
+            Action::Palette => self.palette = Some(Palette::new(self.undo.recent(RECENT))),
+
+            Action::Undo => self.undo_steps(1)?,
+
+            Action::Branches => return Ok(Command::Branches),
+            Action::Tags => return Ok(Command::Tags),
+            Action::Remotes => return Ok(Command::Remotes),
+            Action::Worktrees => return Ok(Command::Worktrees),
+            Action::Reflog => return Ok(Command::Reflog),
+            Action::Bisect => return Ok(Command::Bisect),
+            Action::Stashes => return Ok(Command::Stash),
+            Action::Clean => return Ok(Command::Clean),
+
+            Action::Filter => {
+                self.editing_filter = true;
+                self.focus = Focus::Left;
+            }
+
+            Action::Edit => {
+                if let Some(entry) = self.selected() {
+                    match editor::path(repo, entry) {
+                        Ok(path) => return Ok(Command::Edit(path, self.cursor_line())),
+                        Err(e) => self.message = Some(format!("Error: {}", e)),
+                    }
+                }
+            }
+
+            action @ (Action::CopyPath | Action::CopyAbsolutePath) => {
+                let Some(entry) = self.selected() else {
+                    return Ok(Command::None);
+                };
+
+                let path = match repo.workdir() {
+                    Some(workdir) if action == Action::CopyAbsolutePath => {
+                        workdir.join(&entry.path).display().to_string()
+                    }
+                    _ => entry.path.display().to_string(),
+                };
+
+                self.message = Some(match self.clipboard.copy(&path) {
+                    Ok(()) => format!("Copied: {}", path),
+                    Err(e) => format!("Error: {}", e),
+                });
+            }
+
+            action @ (Action::StageAll | Action::UnstageAll) => {
+                let (section, nothing) = if action == Action::StageAll {
+                    (Section::Worktree, "Nothing to stage")
+                } else {
+                    (Section::Index, "Nothing to unstage")
+                };
+
+                if !self.list.has_section(section) {
+                    self.message = Some(String::from(nothing));
+                    return Ok(Command::None);
+                }
+
+                let before = undo::index_tree(repo);
+                let result = if action == Action::StageAll {
+                    stage::stage_all(repo)
+                } else {
+                    stage::unstage_all(repo)
+                };
+                match result {
+                    Ok(()) if action == Action::StageAll => {
+                        self.undo.index(repo, "staging everything", before)
+                    }
+                    Ok(()) => self.undo.index(repo, "unstaging everything", before),
+                    Err(e) => self.message = Some(format!("Error: {}", e)),
+                }
+
+                self.refresh()?;
+            }
+
+            Action::Commit => {
+                // A merge can be concluded with nothing new staged
+                if !self.list.has_section(Section::Index)
+                    && repo.state() != git2::RepositoryState::Merge
+                {
+                    self.message = Some(String::from("Nothing staged to commit"));
+                    return Ok(Command::None);
+                }
+                self.commit_box = Some(CommitBox::new(repo, CommitMode::New, String::new()));
+            }
+
+            Action::Amend => match commit::head_message(repo) {
+                Ok(text) => self.commit_box = Some(CommitBox::new(repo, CommitMode::Amend, text)),
+                Err(e) => self.message = Some(format!("Error: {}", e)),
+            },
+
+            action @ (Action::Fetch | Action::Pull | Action::Push) => {
+                if self.remote_task.is_some() {
+                    self.message = Some(String::from("Wait for the running remote operation"));
+                    return Ok(Command::None);
+                }
+
+                let op = match action {
+                    Action::Fetch => RemoteOp::Fetch,
+                    Action::Pull => RemoteOp::Pull,
+                    _ => RemoteOp::Push,
+                };
+                self.remote_task = Some(RemoteTask::spawn(repo, op));
+            }
+
+            Action::FetchLfs => {
+                if self.remote_task.is_some() {
+                    self.message = Some(String::from("Wait for the running remote operation"));
+                    return Ok(Command::None);
+                }
+                let Some(entry) = self.selected() else {
+                    return Ok(Command::None);
+                };
+                if self.current_diff.lfs.is_empty() {
+                    self.message = Some(String::from("No LFS content to download"));
+                    return Ok(Command::None);
+                }
+
+                let path = entry.path.clone();
+                let pointers = self.current_diff.lfs.clone();
+                self.remote_task = Some(RemoteTask::spawn_job(
+                    repo,
+                    RemoteOp::Fetch,
+                    move |repo, _| {
+                        let count = lfs::fetch(repo, &path, &pointers)?;
+                        Ok(format!(
+                            "Downloaded {} LFS object{} of {}",
+                            count,
+                            if count == 1 { "" } else { "s" },
+                            path.display()
+                        ))
+                    },
+                ));
+            }
+
+            Action::SetUpstream => {
+                self.message = Some(match remote::set_upstream(repo) {
+                    Ok(message) => message,
+                    Err(e) => format!("Error: {}", e),
+                });
+                self.refresh()?;
+            }
+
+            Action::DiffTool => {
+                if let Some(entry) = self.selected() {
+                    return Ok(Command::Tool(entry.clone()));
+                }
+            }
+
+            Action::Blame => {
+                if let Some(entry) = self.selected() {
+                    return Ok(Command::Blame(entry.path.clone()));
+                }
+            }
+
+            Action::History => {
+                if let Some(entry) = self.selected() {
+                    return Ok(Command::History(entry.path.clone()));
+                }
+            }
+
+            Action::Refresh => self.refresh()?,
+
+            // Only folders of the tree do something on Enter
+            Action::Select => {
+                if let Some(i) = self.list_state.selected() {
+                    self.list.toggle_folder(i);
+                }
+            }
+
+            Action::ToggleIgnored => {
+                self.list.show_ignored = !self.list.show_ignored;
+                self.refresh()?;
+            }
+
+            Action::SwitchFocus => {
+                self.focus = if self.focus == Focus::Left {
+                    Focus::Right
+                } else {
+                    Focus::Left
+                };
+            }
+
+            action @ (Action::NavigateUp
+            | Action::NavigateDown
+            | Action::PageUp
+            | Action::PageDown
+            | Action::Top
+            | Action::Bottom) => self.navigate(action),
+
+            _ => {}
+        }
+
+        Ok(Command::None)
+    }
+
+    /// What the file list does to the selected file, or the marked ones.
+    fn perform_list(&mut self, action: Action) -> Result<Command> {
+        let repo = self.repo;
+
+        match action {
+            Action::Discard => {
+                let entries = self.targets();
+                let prompt = match entries.as_slice() {
+                    [] => return Ok(Command::None),
+                    [entry] => discard::prompt(repo, entry),
+                    _ => discard::prompt_many(repo, &entries),
+                };
+                match prompt {
+                    Ok(prompt) => self.confirm_discard = Some((entries, prompt, false)),
+                    Err(e) => self.message = Some(format!("Error: {}", e)),
+                }
+            }
+
+            Action::StashFiles => {
+                let entries = self.targets();
+                if entries.is_empty() {
+                    return Ok(Command::None);
+                }
+                self.message = Some(match discard::stash(repo, &entries) {
+                    Ok((text, stash)) => {
+                        let what = format!("stashing {}", discard::describe(&entries));
+                        self.undo.stash(what, stash, &entries);
+                        text
+                    }
+                    Err(e) => format!("Error: {}", e),
+                });
+                self.list.clear_marks();
+                self.refresh()?;
+            }
+
+            Action::OpenSubmodule => {
+                let Some(entry) = self.selected() else {
+                    return Ok(Command::None);
+                };
+                // Submodules are looked up by name, which git only writes as UTF-8
+                let submodule = entry
+                    .path
+                    .to_str()
+                    .and_then(|p| repo.find_submodule(p).ok());
+                return Ok(match submodule {
+                    Some(_) => Command::Submodule(entry.path.clone()),
+                    None => {
+                        self.message = Some(String::from("Not a submodule"));
+                        Command::None
+                    }
+                });
+            }
+
+            Action::Ignore => {
+                if let Some(entry) = self.selected() {
+                    match IgnorePrompt::new(repo, entry) {
+                        Ok(prompt) => self.ignore_prompt = Some(prompt),
+                        Err(e) => self.message = Some(format!("Error: {}", e)),
+                    }
+                }
+            }
+
+            Action::Mark => {
+                if let Some(i) = self.list_state.selected() {
+                    self.list.toggle_mark(i);
+                }
+            }
+
+            Action::Resolve => {
+                let Some(entry) = self.selected() else {
+                    return Ok(Command::None);
+                };
+                if entry.section != Section::Conflicted {
+                    self.message = Some(String::from("Only conflicted files need resolving"));
+                    return Ok(Command::None);
+                }
+                return Ok(Command::Resolve(entry.path.clone()));
+            }
+
+            Action::ToggleTree => {
+                let old_index = self.list_state.selected().unwrap_or(0);
+                let previous = self.selected().cloned();
+                self.list.set_tree(!self.list.tree);
+                self.list_state
+                    .select(self.list.reselect(previous.as_ref(), old_index));
+            }
+
+            Action::StageFile => {
+                let entries = self.targets();
+                if entries.is_empty() {
+                    return Ok(Command::None);
+                }
+
+                // Carries on past a file that fails, so one bad path doesn't hold up the rest
+                let before = undo::index_tree(repo);
+                let failed: Vec<String> = entries
+                    .iter()
+                    .filter_map(|entry| stage::toggle_file(repo, entry).err())
+                    .map(|e| e.to_string())
+                    .collect();
+                if let Some(first) = failed.first() {
+                    self.message = Some(format!("Error: {}", first));
+                } else if entries.len() > 1 {
+                    self.message = Some(format!("Staged/unstaged {} files", entries.len()));
+                }
+                let what = match entries.as_slice() {
+                    [entry] => format!("{} {}", staging(entry), entry.path.display()),
+                    _ => format!("staging/unstaging {} files", entries.len()),
+                };
+                self.undo.index(repo, what, before);
+
+                self.list.clear_marks();
+                self.refresh()?;
+            }
+
+            _ => {}
+        }
+
+        Ok(Command::None)
+    }
+
+    /// How the diff is shown and moved through, and staging part of it.
+    fn perform_diff(&mut self, action: Action) -> Result<Command> {
+        let repo = self.repo;
+
+        match action {
+            Action::LineMode if self.line_cursor.is_some() => self.line_cursor = None,
+
+            action @ (Action::CopyHunk | Action::CopyDiff) => {
+                let Some(entry) = self.selected().cloned() else {
+                    return Ok(Command::None);
+                };
+                let hunk = (action == Action::CopyHunk).then_some(self.selected_hunk);
+
+                self.message = Some(
+                    match DiffText::load(repo, &entry, self.settings)
+                        .and_then(|diff| diff.patch(hunk).ok_or_else(|| anyhow!("no hunk to copy")))
+                        .and_then(|text| self.clipboard.copy(&text))
+                    {
+                        Ok(()) if hunk.is_some() => String::from("Copied the hunk"),
+                        Ok(()) => format!("Copied the diff of {}", entry.path.display()),
+                        Err(e) => format!("Error: {}", e),
+                    },
+                );
+            }
+
+            Action::ToggleWrap => self.wrap = !self.wrap,
+
+            Action::ToggleLineNumbers => {
+                self.line_numbers = !self.line_numbers;
+                self.diff_stale = true;
+            }
+
+            Action::LoadMore => {
+                if self.current_diff.more.is_some() {
+                    self.preview_limit *= 4;
+                    self.diff_stale = true;
+                } else {
+                    self.message = Some(String::from("Nothing more to load"));
+                }
+            }
+
+            Action::ToggleSplit => {
+                self.split = !self.split;
+                // Rows differ between the layouts, so the selected hunk or line keeps its place
+                let line = self.line_cursor.or(self
+                    .current_diff
+                    .hunks
+                    .get(self.selected_hunk)
+                    .map(|h| h.start));
+                self.diff_scroll = line.map_or(0, |line| self.row_of(line) as u16);
+            }
+
+            Action::ScrollLeft => {
+                self.diff_hscroll = self.diff_hscroll.saturating_sub(1);
+            }
+
+            Action::ScrollRight => {
+                self.diff_hscroll = clamp_scroll(
+                    self.diff_hscroll.saturating_add(1),
+                    self.content_width(),
+                    self.diff_width,
+                );
+            }
+
+            Action::SearchDiff => {
+                self.search = Some(DiffSearch::new());
+                self.focus = Focus::Right;
+            }
+
+            // Before there's a search to step through, they step through hunks
+            action @ (Action::NextMatch | Action::PrevMatch) => match &mut self.search {
+                Some(search) => {
+                    search.step(action == Action::NextMatch);
+                    if search.line().is_none() {
+                        self.message = Some(format!("No matches for \"{}\"", search.query));
+                    }
+                    self.show_match();
+                }
+                None if action == Action::NextMatch => return self.perform(Action::NextHunk),
+                None => return self.perform(Action::PrevHunk),
+            },
+
+            action @ (Action::NextHunk | Action::PrevHunk) => {
+                let last = self.current_diff.hunks.len().saturating_sub(1);
+                self.selected_hunk = if action == Action::NextHunk {
+                    (self.selected_hunk + 1).min(last)
+                } else {
+                    self.selected_hunk.saturating_sub(1)
+                };
+
+                if let Some(hunk) = self.current_diff.hunks.get(self.selected_hunk) {
+                    let row = self.row_of(hunk.start);
+                    self.diff_scroll = clamp_scroll(row as u16, self.diff_rows, self.diff_height);
+
+                    if self.line_cursor.is_some() {
+                        self.line_cursor = self
+                            .current_diff
+                            .changes
+                            .iter()
+                            .find(|c| hunk.contains(c))
+                            .copied();
+                    }
+                }
+            }
+
+            // File starts below or above the top line, so the same key walks on
+            action @ (Action::NextFile | Action::PrevFile) => {
+                let top = self.top_line();
+                let files = &self.current_diff.files;
+                let start = if action == Action::NextFile {
+                    files.iter().find(|(start, _)| *start > top)
+                } else {
+                    files.iter().rev().find(|(start, _)| *start < top)
+                };
+                let Some(&(start, _)) = start else {
+                    return Ok(Command::None);
+                };
+
+                let hunks = &self.current_diff.hunks;
+                if let Some(hunk) = hunks.iter().position(|h| h.start > start) {
+                    self.selected_hunk = hunk;
+                    if self.line_cursor.is_some() {
+                        let hunk = &hunks[hunk];
+                        self.line_cursor = self
+                            .current_diff
+                            .changes
+                            .iter()
+                            .find(|c| hunk.contains(c))
+                            .copied();
+                    }
+                }
+                let row = self.row_of(start);
+                self.diff_scroll = clamp_scroll(row as u16, self.diff_rows, self.diff_height);
+            }
+
+            Action::LineMode => {
+                if self.pending_reload.is_some() {
+                    self.message = Some(String::from("Diff is still loading"));
+                    return Ok(Command::None);
+                }
+
+                if self.settings.ignores_whitespace() {
+                    self.message = Some(String::from(
+                        "Turn the whitespace options off to stage part of a file",
+                    ));
+                    return Ok(Command::None);
+                }
+
+                let hunk_start = self
+                    .current_diff
+                    .hunks
+                    .get(self.selected_hunk)
+                    .map_or(0, |h| h.start);
+                let Some(&first) = self.current_diff.changes.iter().find(|&&c| c > hunk_start)
+                else {
+                    self.message = Some(String::from("No single lines to stage here"));
+                    return Ok(Command::None);
+                };
+
+                self.line_cursor = Some(first);
+                let row = self.row_of(first);
+                self.diff_scroll = scroll_into_view(row, self.diff_scroll, self.diff_height);
+            }
+
+            Action::Stage if self.line_cursor.is_some() => {
+                let Some(entry) = self.selected().cloned() else {
+                    return Ok(Command::None);
+                };
+                let Some((hunk, line)) = self.line_cursor.and_then(|cursor| {
+                    let hunk = hunk_of(&self.current_diff, cursor)?;
+                    // Line 0 of a hunk range is its `@@` header
+                    Some((hunk, cursor - self.current_diff.hunks[hunk].start - 1))
+                }) else {
+                    return Ok(Command::None);
+                };
+
+                if self.pending_reload.is_some() {
+                    self.message = Some(String::from("Diff is still loading"));
+                    return Ok(Command::None);
+                }
+
+                let before = undo::index_tree(repo);
+                match stage::toggle_line(repo, &entry, self.settings, hunk, line) {
+                    Ok(()) => {
+                        let what =
+                            format!("{} a line of {}", staging(&entry), entry.path.display());
+                        self.undo.index(repo, what, before);
+                    }
+                    Err(e) => self.message = Some(format!("Error: {}", e)),
+                }
+
+                self.refresh()?;
+            }
+
+            Action::Stage => {
+                let Some(entry) = self.selected().cloned() else {
+                    return Ok(Command::None);
+                };
+
+                if self.pending_reload.is_some() {
+                    self.message = Some(String::from("Diff is still loading"));
+                    return Ok(Command::None);
+                }
+
+                if self.selected_hunk >= self.current_diff.hunks.len() {
+                    self.message = Some(String::from("No hunk to stage"));
+                    return Ok(Command::None);
+                }
+
+                // Hunks of a diff that skips whitespace would not apply to the real file
+                if self.settings.ignores_whitespace() {
+                    self.message = Some(String::from(
+                        "Turn the whitespace options off to stage part of a file",
+                    ));
+                    return Ok(Command::None);
+                }
+
+                let before = undo::index_tree(repo);
+                match stage::toggle_hunk(repo, &entry, self.settings, self.selected_hunk) {
+                    Ok(()) => {
+                        let what =
+                            format!("{} a hunk of {}", staging(&entry), entry.path.display());
+                        self.undo.index(repo, what, before);
+                    }
+                    Err(e) => self.message = Some(format!("Error: {}", e)),
+                }
+
+                self.refresh()?;
+            }
+
+            action @ (Action::MoreContext | Action::LessContext) => {
+                let context = &mut self.settings.context_lines;
+                *context = if action == Action::MoreContext {
+                    context.saturating_add(1)
+                } else {
+                    context.saturating_sub(1)
+                };
+                self.message = Some(format!("Context lines: {}", context));
+                self.diff_stale = true;
+            }
+
+            action @ (Action::IgnoreAllSpace
+            | Action::IgnoreSpaceChange
+            | Action::IgnoreBlankLines) => {
+                let (option, name) = match action {
+                    Action::IgnoreAllSpace => {
+                        (&mut self.settings.ignore_all_space, "Ignore all whitespace")
+                    }
+                    Action::IgnoreSpaceChange => (
+                        &mut self.settings.ignore_space_change,
+                        "Ignore whitespace changes",
+                    ),
+                    _ => (&mut self.settings.ignore_blank_lines, "Ignore blank lines"),
+                };
+                *option = !*option;
+                self.message = Some(format!("{}: {}", name, if *option { "on" } else { "off" }));
+                // Line mode points into the old diff's lines
+                self.line_cursor = None;
+                self.diff_stale = true;
+            }
+
+            _ => {}
+        }
+
+        Ok(Command::None)
+    }
+
+    /// Moves the line-mode cursor, or else around the focused panel.
+    fn navigate(&mut self, action: Action) {
+        match action {
+            action @ (Action::NavigateUp | Action::NavigateDown) if self.line_cursor.is_some() => {
+                let Some(cursor) = self.line_cursor else {
+                    return;
+                };
+                let changes = &self.current_diff.changes;
+                let next = if action == Action::NavigateDown {
+                    changes.iter().find(|&&c| c > cursor)
+                } else {
+                    changes.iter().rev().find(|&&c| c < cursor)
+                };
+
+                if let Some(&next) = next {
+                    self.line_cursor = Some(next);
+                    if let Some(hunk) = hunk_of(&self.current_diff, next) {
+                        self.selected_hunk = hunk;
+                    }
+                    let row = self.row_of(next);
+                    self.diff_scroll = scroll_into_view(row, self.diff_scroll, self.diff_height);
+                }
+            }
+
+            Action::NavigateUp => match self.focus {
+                Focus::Left => {
+                    if let Some(i) = self.list_state.selected().and_then(|i| self.list.prev(i)) {
+                        self.list_state.select(Some(i));
+                    }
+                }
+                Focus::Right => {
+                    self.diff_scroll = self.diff_scroll.saturating_sub(1);
+                }
+            },
+
+            action @ (Action::PageUp | Action::PageDown) => {
+                let forward = action == Action::PageDown;
+                match self.focus {
+                    Focus::Left => {
+                        // Inside the borders, less one row so the page overlaps slightly
+                        let page = (self.list_area.height.saturating_sub(3) as usize).max(1);
+                        if let Some(i) = self.list_state.selected() {
+                            self.list_state
+                                .select(Some(self.list.step(i, page, forward)));
+                        }
+                    }
+                    Focus::Right => {
+                        let page = self.diff_height.saturating_sub(1).max(1);
+                        self.diff_scroll = if forward {
+                            clamp_scroll(
+                                self.diff_scroll.saturating_add(page),
+                                self.diff_rows,
+                                self.diff_height,
+                            )
+                        } else {
+                            self.diff_scroll.saturating_sub(page)
+                        };
+                    }
+                }
+            }
+
+            Action::Top => match self.focus {
+                Focus::Left => self.list_state.select(self.list.first()),
+                Focus::Right => self.diff_scroll = 0,
+            },
+
+            Action::Bottom => match self.focus {
+                Focus::Left => self.list_state.select(self.list.last()),
+                Focus::Right => {
+                    self.diff_scroll = max_scroll(self.diff_rows, self.diff_height);
+                }
+            },
+
+            Action::NavigateDown => match self.focus {
+                Focus::Left => {
+                    if let Some(i) = self.list_state.selected().and_then(|i| self.list.next(i)) {
+                        self.list_state.select(Some(i));
+                    }
+                }
+                Focus::Right => {
+                    self.diff_scroll = clamp_scroll(
+                        self.diff_scroll.saturating_add(1),
+                        self.diff_rows,
+                        self.diff_height,
+                    );
+                }
+            },
+
+            _ => {}
+        }
+    }
+
+    /// The line of the file the diff is at, to open the editor there: the
+    /// line-mode cursor, else the first change of the selected hunk. A
+    /// deleted line has no number of its own, so the line after it stands in.
+    fn cursor_line(&self) -> Option<u32> {
+        let diff = &self.current_diff;
+        let at = self.line_cursor.or_else(|| {
+            let hunk = diff.hunks.get(self.selected_hunk)?;
+            diff.changes.iter().find(|c| hunk.contains(c)).copied()
+        })?;
+
+        diff.numbers
+            .iter()
+            .skip(at)
+            .find_map(|&(_, new)| new)
+            .or_else(|| diff.numbers.iter().take(at).rev().find_map(|&(_, new)| new))
+    }
+
+    /// What a batch action applies to: the marked files, or else the
+    /// selected one.
+    fn targets(&self) -> Vec<FileEntry> {
+        let marked = self.list.marked();
+        if marked.is_empty() {
+            self.selected().cloned().into_iter().collect()
+        } else {
+            marked
+        }
+    }
+
+    /// Commits `message` once the box and any hooks are done with it; a
+    /// signed commit goes back as a command, to hand the terminal over.
+    fn make_commit(&mut self, mode: CommitMode, sign: bool, message: &str) -> Result<Command> {
+        if sign {
+            return Ok(Command::SignedCommit(mode, message.to_string()));
+        }
+        let before = undo::head(self.repo);
+        self.message = Some(
+            mode.submit(self.repo, message, false)
+                .unwrap_or_else(|e| format!("Error: {}", e)),
+        );
+        self.record_commit(mode, before);
+        self.refresh()?;
+        Ok(Command::None)
+    }
+
+    /// Puts the commit or amend that moved HEAD from `before` on the undo
+    /// stack, when it did move it.
+    pub fn record_commit(&mut self, mode: CommitMode, before: Option<git2::Oid>) {
+        let what = match mode {
+            CommitMode::New => "committing",
+            CommitMode::Amend => "amending HEAD into",
+        };
+        self.undo.commit(self.repo, what, before);
+    }
+
+    /// Takes back the last `count` steps on the undo stack, newest first,
+    /// stopping at one that can't be undone.
+    fn undo_steps(&mut self, count: usize) -> Result<()> {
+        let mut undone = Vec::new();
+        for _ in 0..count {
+            match self.undo.undo(self.repo) {
+                Ok(text) => undone.push(text),
+                Err(e) => {
+                    undone.push(format!("Error: {}", e));
+                    break;
+                }
+            }
+        }
+        self.message = Some(undone.join(" · "));
+        self.refresh()
+    }
+
+    pub fn selected(&self) -> Option<&FileEntry> {
+        self.list_state.selected().and_then(|i| self.list.entry(i))
+    }
+
+    /// Has the status list reloaded in the background; see [`App::apply_status`].
+    /// Quick loads still land before the next frame, so small repos never
+    /// show the spinner.
+    pub fn refresh(&mut self) -> Result<()> {
+        self.request_status(None)
+    }
+
+    /// [`App::refresh`], rescanning only `written` paths when there are some
+    /// and nothing else has changed since the last load.
+    fn request_status(&mut self, written: Option<Vec<PathBuf>>) -> Result<()> {
+        self.status_loader.request(self.list.show_ignored, written);
+        self.pending_status = true;
+
+        if let Some(result) = self.status_loader.recv_timeout(Duration::from_millis(30)) {
+            self.apply_status(result)?;
+        }
+
+        Ok(())
+    }
+
+    /// Swaps in a freshly loaded list, keeping the selection on the same file
+    /// when it is still listed, and has the shown diff reloaded.
+    fn apply_status(&mut self, result: Result<StatusList>) -> Result<()> {
+        let old_index = self.list_state.selected().unwrap_or(0);
+        let previous = self.selected().cloned();
+
+        let mut list = result?;
+        list.take_view(&mut self.list);
+        self.list = list;
+        self.list_state
+            .select(self.list.reselect(previous.as_ref(), old_index));
+        self.pending_status = false;
+        self.diff_stale = true;
+
+        Ok(())
+    }
+
+    /// Narrows the list to `filter`, keeping the selection on the same file if it
+    /// still matches.
+    fn apply_filter(&mut self, filter: String) {
+        let old_index = self.list_state.selected().unwrap_or(0);
+        let previous = self.selected().cloned();
+
+        self.list.set_filter(filter);
+        self.list_state
+            .select(self.list.reselect(previous.as_ref(), old_index));
+    }
+
+    fn apply_loaded_diff(&mut self, result: Result<FileDiff>, reload: bool) {
+        let mut diff = result.unwrap_or_else(|e| FileDiff::message(format!("Error: {}", e)));
+        if let Some(more) = diff.more {
+            let more = match more {
+                More::Bytes(bytes) => format!("{} more", diff::human_size(bytes)),
+                More::Lines(lines) => format!("{} more lines", thousands(lines)),
+            };
+            let line = Line::styled(
+                format!(
+                    "… {}; {} loads more",
+                    more,
+                    self.keymap.label(Action::LoadMore)
+                ),
+                Style::default().dim(),
+            );
+            diff.lines.push(line.clone());
+            diff.numbers.push((None, None));
+            if let Some(split) = &mut diff.split {
+                split.positions.push((split.rows.len(), Column::Both));
+                split.rows.push((line, Line::default()));
+            }
+        }
+        if !diff.lfs.is_empty() {
+            diff.lines.push(Line::styled(
+                format!(
+                    "{} downloads the content from the LFS server",
+                    self.keymap.label(Action::FetchLfs)
+                ),
+                Style::default().dim(),
+            ));
+            diff.numbers.push((None, None));
+        }
+        // Another file changed, not this one, so nothing on screen should move
+        if reload && diff.lines == self.current_diff.lines {
+            return;
+        }
+        self.set_diff(diff);
+
+        // A staged line leaves this side of the diff, so the cursor moves on to the next change
+        if reload && let Some(cursor) = self.line_cursor {
+            let changes = &self.current_diff.changes;
+            self.line_cursor = changes
+                .iter()
+                .find(|&&c| c >= cursor)
+                .or(changes.last())
+                .copied();
+
+            if let Some(cursor) = self.line_cursor {
+                self.selected_hunk = hunk_of(&self.current_diff, cursor).unwrap_or(0);
+                // Near the top, which keeps it on screen whatever the panel height
+                let hunk_top = self.row_of(self.current_diff.hunks[self.selected_hunk].start);
+                let row = self.row_of(cursor);
+                self.diff_scroll = row.saturating_sub(5).max(hunk_top) as u16;
+                return;
+            }
+        }
+
+        if reload {
+            // Same file reloaded (e.g. after staging a hunk): stay near the old cursor
+            self.selected_hunk = self
+                .selected_hunk
+                .min(self.current_diff.hunks.len().saturating_sub(1));
+            self.diff_scroll = self
+                .current_diff
+                .hunks
+                .get(self.selected_hunk)
+                .map_or(0, |h| self.row_of(h.start) as u16);
+        }
+    }
+
+    /// The side-by-side layout, when it's switched on and the diff has one.
+    fn split_view(&self) -> Option<&SplitDiff> {
+        self.current_diff.split.as_ref().filter(|_| self.split)
+    }
+
+    /// Wrapping is for the unified layout only; split columns scroll sideways.
+    fn wrap_width(&self) -> Option<u16> {
+        (self.wrap && self.split_view().is_none()).then_some(self.diff_width)
+    }
+
+    fn set_diff(&mut self, diff: FileDiff) {
+        self.current_diff = diff;
+        self.layout.take();
+        let gutter = self.gutter();
+        if let Some(search) = &mut self.search {
+            search.find(&self.current_diff.lines, gutter, 0);
+        }
+    }
+
+    fn searching(&self) -> bool {
+        self.search.as_ref().is_some_and(|s| s.editing)
+    }
+
+    /// Whether the diff's lines start with a line number gutter, which
+    /// searching skips.
+    fn gutter(&self) -> bool {
+        self.line_numbers
+            && self
+                .current_diff
+                .numbers
+                .iter()
+                .any(|&(old, new)| old.is_some() || new.is_some())
+    }
+
+    /// Searches the diff again for the query as it's typed, from what's at
+    /// the top of the pane, and brings the first match there into view.
+    fn find_matches(&mut self) {
+        let from = self.top_line();
+        let gutter = self.gutter();
+        if let Some(search) = &mut self.search {
+            search.find(&self.current_diff.lines, gutter, from);
+        }
+        self.show_match();
+    }
+
+    /// Scrolls the current match into view, sideways too when lines aren't
+    /// wrapped.
+    fn show_match(&mut self) {
+        let Some(search) = &self.search else {
+            return;
+        };
+        let Some(line) = search.line() else {
+            return;
+        };
+        let column = search.column(&self.current_diff.lines[line], self.gutter());
+
+        let mut row = self.row_of(line);
+        match self.wrap_width() {
+            // The match can be on a later row of a wrapped line
+            Some(width) => row += column / (width as usize).max(1),
+            None => {
+                let width = self.diff_width as usize;
+                let hscroll = self.diff_hscroll as usize;
+                if column < hscroll || column >= hscroll + width {
+                    self.diff_hscroll = clamp_scroll(
+                        column.saturating_sub(width / 4) as u16,
+                        self.content_width(),
+                        self.diff_width,
+                    );
+                }
+            }
+        }
+        self.diff_scroll = scroll_into_view(row, self.diff_scroll, self.diff_height);
+    }
+
+    /// `current_diff` laid out the way it's shown, worked out again only when
+    /// the layout or the wrap width has changed since last time.
+    fn layout(&self) -> Ref<'_, DiffLayout> {
+        let key = (self.split_view().is_some(), self.wrap_width());
+        if self.layout.borrow().key != Some(key) {
+            let layout = DiffLayout::new(&self.current_diff, self.split_view(), self.wrap_width());
+            *self.layout.borrow_mut() = DiffLayout {
+                key: Some(key),
+                ..layout
+            };
+        }
+        self.layout.borrow()
+    }
+
+    /// The diff line at the top of the pane, in whichever layout is shown.
+    fn top_line(&self) -> usize {
+        let scroll = self.diff_scroll as usize;
+        match self.split_view() {
+            Some(split) => split
+                .positions
+                .iter()
+                .position(|&(row, _)| row >= scroll)
+                .unwrap_or(0),
+            None => self.layout().line_at(scroll),
+        }
+    }
+
+    /// Screen row diff line `line` starts on, in whichever layout is shown.
+    fn row_of(&self, line: usize) -> usize {
+        match self.split_view() {
+            Some(split) => split.positions[line].0,
+            None => self.layout().starts[line],
+        }
+    }
+
+    /// Widest line in the shown layout, for clamping the sideways scroll.
+    fn content_width(&self) -> usize {
+        self.layout().width
+    }
+}
+
+/// Where a diff's lines land on screen in one layout: the row each unified
+/// line starts on, how many rows there are and how wide the widest is.
+#[derive(Default)]
+struct DiffLayout {
+    /// Split view and wrap width it was worked out for; `None` until it is
+    key: Option<(bool, Option<u16>)>,
+    /// One per unified line; empty in the split view, whose rows say it already
+    starts: Vec<usize>,
+    rows: usize,
+    width: usize,
+}
+
+impl DiffLayout {
+    fn new(diff: &FileDiff, split: Option<&SplitDiff>, wrap_width: Option<u16>) -> Self {
+        if let Some(split) = split {
+            return DiffLayout {
+                key: None,
+                starts: Vec::new(),
+                rows: split.rows.len(),
+                width: split
+                    .rows
+                    .iter()
+                    .map(|(old, new)| old.width().max(new.width()))
+                    .max()
+                    .unwrap_or(0),
+            };
+        }
+
+        let mut starts = Vec::with_capacity(diff.lines.len());
+        let mut rows = 0;
+        for line in &diff.lines {
+            starts.push(rows);
+            rows += match wrap_width {
+                Some(width) => Paragraph::new(line.clone())
+                    .wrap(Wrap { trim: false })
+                    .line_count(width),
+                None => 1,
+            };
+        }
+        DiffLayout {
+            key: None,
+            starts,
+            rows,
+            width: longest_line(&diff.lines),
+        }
+    }
+
+    /// The unified line that screen row `row` belongs to.
+    fn line_at(&self, row: usize) -> usize {
+        self.starts
+            .partition_point(|&start| start <= row)
+            .saturating_sub(1)
+    }
+}
+
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+/// How many of the undo stack's steps the command palette lists.
+const RECENT: usize = 5;
+
+/// `staging` or `unstaging`, as toggling `entry` does.
+fn staging(entry: &FileEntry) -> &'static str {
+    if entry.section == Section::Index {
+        "unstaging"
+    } else {
+        "staging"
+    }
+}
+
+/// The hunk whose lines include `line`.
+fn hunk_of(diff: &FileDiff, line: usize) -> Option<usize> {
+    diff.hunks.iter().position(|h| h.contains(&line))
+}
+
+/// Scroll offset that brings `row` on screen, moving as little as possible.
+fn scroll_into_view(row: usize, scroll: u16, height: u16) -> u16 {
+    let row = row.try_into().unwrap_or(u16::MAX);
+    if row < scroll {
+        row
+    } else if row >= scroll.saturating_add(height) {
+        row - height.saturating_sub(1)
+    } else {
+        scroll
+    }
+}
+
+/// The repository name, where HEAD is and how far from its upstream, the
+/// stash count and the last commit's summary, across the top.
+fn header_line(summary: &RepoSummary, keymap: &Keymap, theme: &Theme) -> Line<'static> {
+    let separator = || Span::styled(" · ", Style::default().dim());
+    let mut spans = vec![
+        Span::styled(
+            format!(" {}", summary.name),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        separator(),
+    ];
+
+    if summary.detached {
+        spans.push(Span::styled(
+            format!("detached at {}", summary.head),
+            Style::default().fg(theme.warning),
+        ));
+    } else {
+        spans.push(Span::styled(
+            summary.head.clone(),
+            Style::default().fg(theme.accent),
+        ));
+    }
+    if summary.unborn {
+        spans.extend([
+            separator(),
+            Span::styled("no commits yet", Style::default().dim()),
+        ]);
+    }
+    match &summary.upstream {
+        None => {}
+        Some(Upstream::Unset) => spans.push(Span::styled(
+            format!(
+                " (no upstream; {} sets one)",
+                keymap.label(Action::SetUpstream)
+            ),
+            Style::default().fg(theme.warning),
+        )),
+        Some(Upstream::Gone(name)) => spans.push(Span::styled(
+            format!(" → {} (gone)", name),
+            Style::default().fg(theme.warning),
+        )),
+        Some(Upstream::Tracking {
+            name,
+            ahead,
+            behind,
+        }) => spans.push(Span::raw(format!(" → {} ↑{} ↓{}", name, ahead, behind))),
+    }
+
+    match summary.stashes {
+        0 => {}
+        1 => spans.extend([separator(), Span::raw("1 stash")]),
+        n => spans.extend([separator(), Span::raw(format!("{} stashes", n))]),
+    }
+    if let Some(last) = &summary.last_commit {
+        spans.extend([
+            separator(),
+            Span::styled(last.clone(), Style::default().dim()),
+        ]);
+    }
+
+    Line::from(spans)
+}
+
+fn help_line(
+    focus: &Focus,
+    line_mode: bool,
+    tree: bool,
+    files: bool,
+    keymap: &Keymap,
+) -> Line<'static> {
+    let label = |actions: &[Action]| {
+        actions
+            .iter()
+            .map(|a| keymap.label(*a))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let mut entries = vec![(
+        label(&[Action::NavigateUp, Action::NavigateDown]),
+        "navigate",
+    )];
+
+    if line_mode {
+        entries.extend([
+            (label(&[Action::Stage]), "stage/unstage line"),
+            (label(&[Action::LineMode]), "hunks"),
+        ]);
+    } else if *focus == Focus::Right {
+        entries.extend([(label(&[Action::NextHunk, Action::PrevHunk]), "hunk")]);
+        if files {
+            entries.push((label(&[Action::NextFile, Action::PrevFile]), "file"));
+        }
+        entries.extend([
+            (label(&[Action::SearchDiff]), "search"),
+            (label(&[Action::Stage]), "stage/unstage hunk"),
+            (label(&[Action::LineMode]), "lines"),
+            (
+                label(&[Action::MoreContext, Action::LessContext]),
+                "context",
+            ),
+            (label(&[Action::ToggleWrap]), "wrap"),
+            (label(&[Action::ToggleSplit]), "split"),
+        ]);
+    } else {
+        entries.extend([
+            (
+                label(&[Action::Select]),
+                if tree { "fold/unfold" } else { "actions" },
+            ),
+            (label(&[Action::StageFile]), "stage/unstage"),
+            (label(&[Action::Discard]), "discard"),
+            (label(&[Action::Mark]), "mark"),
+            (label(&[Action::Filter]), "filter"),
+            (label(&[Action::ToggleTree]), "tree"),
+        ]);
+    }
+
+    entries.extend([
+        (label(&[Action::SwitchFocus]), "switch focus"),
+        (label(&[Action::Refresh]), "refresh"),
+        (label(&[Action::Palette]), "commands"),
+        (label(&[Action::Help]), "help"),
+        (label(&[Action::Quit]), "quit"),
+    ]);
+
+    let mut spans = vec![Span::raw(" ")];
+    for (i, (keys, text)) in entries.into_iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::raw(" • "));
+        }
+        spans.push(Span::styled(
+            keys,
+            Style::default().add_modifier(Modifier::BOLD),
+        ));
+        spans.push(Span::raw(format!(" {}", text)));
+    }
+
+    Line::from(spans)
+}
+
+/// Largest scroll offset that still keeps the last line of the content at the
+/// bottom of a viewport `height` rows tall.
+fn max_scroll(content_len: usize, height: u16) -> u16 {
+    content_len
+        .saturating_sub(height as usize)
+        .try_into()
+        .unwrap_or(u16::MAX)
+}
+
+/// `scroll` pulled back to at most [`max_scroll`], as it's redone whenever
+/// the content or the viewport changes size.
+fn clamp_scroll(scroll: u16, content_len: usize, height: u16) -> u16 {
+    scroll.min(max_scroll(content_len, height))
+}
+
+/// The diff's down and sideways scroll offsets clamped to `rows` rows
+/// `width` wide in a `height` by `view_width` panel, as every draw redoes
+/// them in case the terminal was resized. Wrapped lines (no `width`) never
+/// scroll sideways.
+fn clamp_offsets(
+    (scroll, hscroll): (u16, u16),
+    (rows, width): (usize, Option<usize>),
+    (height, view_width): (u16, u16),
+) -> (u16, u16) {
+    let hscroll = width.map_or(0, |width| clamp_scroll(hscroll, width, view_width));
+    (clamp_scroll(scroll, rows, height), hscroll)
+}
+
+/// `12345` as `12,345`.
+fn thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut text = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            text.push(',');
+        }
+        text.push(digit);
+    }
+    text
+}
+
+fn longest_line(lines: &[Line]) -> usize {
+    lines.iter().map(Line::width).max().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+    use helper_git_core::testing::{commit, repo, write};
+    use ratatui::backend::TestBackend;
+
+    fn key(code: KeyCode) -> Event {
+        Event::Key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    /// Two committed files with a change each, `a.txt` then `b.txt`.
+    fn changed_repo() -> (tempfile::TempDir, Repository) {
+        let (dir, repo) = repo();
+        write(&repo, "a.txt", "one\ntwo\n");
+        write(&repo, "b.txt", "three\n");
+        commit(&repo, "first");
+        write(&repo, "a.txt", "one\nchanged\n");
+        write(&repo, "b.txt", "three\nfour\n");
+        (dir, repo)
+    }
+
+    fn app<'r>(repo: &'r Repository, keymap: &'r Keymap, theme: &'r Theme) -> App<'r> {
+        let settings = DiffSettings {
+            context_lines: 3,
+            ..Default::default()
+        };
+        let mut app = App::new(
+            repo,
+            settings,
+            40,
+            keymap,
+            theme,
+            StatusFilter::default(),
+            false,
+        )
+        .unwrap();
+        settle(&mut app);
+        app
+    }
+
+    /// Ticks until the list and the selected file's diff have loaded.
+    fn settle(app: &mut App) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            app.tick().unwrap();
+            let loaded = !app.pending_status
+                && app.pending_reload.is_none()
+                && !app.diff_stale
+                && app.last_selected.as_ref() == app.selected();
+            if loaded {
+                return;
+            }
+            assert!(Instant::now() < deadline, "the status view never loaded");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn screen(app: &mut App, width: u16, height: u16) -> Vec<String> {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(|frame| app.render(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        buffer
+            .content
+            .chunks(width as usize)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn keys_become_commands_for_the_event_loop() {
+        let (_dir, repo) = changed_repo();
+        let (keymap, theme) = (Keymap::default(), Theme::dark());
+        let mut app = app(&repo, &keymap, &theme);
+
+        let command = app.update(&key(KeyCode::Char('e'))).unwrap();
+        assert!(matches!(&command, Command::Edit(path, Some(2)) if path.ends_with("a.txt")));
+        let command = app.update(&key(KeyCode::Char('b'))).unwrap();
+        assert_eq!(command, Command::Blame(PathBuf::from("a.txt")));
+        assert_eq!(app.update(&key(KeyCode::Char('q'))).unwrap(), Command::Quit);
+    }
+
+    #[test]
+    fn tab_moves_the_keys_to_the_other_panel() {
+        let (_dir, repo) = changed_repo();
+        let (keymap, theme) = (Keymap::default(), Theme::dark());
+        let mut app = app(&repo, &keymap, &theme);
+        let selected = |app: &App| app.selected().map(|e| e.path.clone());
+
+        app.update(&key(KeyCode::Char('j'))).unwrap();
+        assert_eq!(selected(&app), Some(PathBuf::from("b.txt")));
+
+        app.update(&key(KeyCode::Tab)).unwrap();
+        assert!(app.focus == Focus::Right);
+        // Down scrolls the diff now, and the diff fits, so nothing moves
+        app.update(&key(KeyCode::Char('j'))).unwrap();
+        app.update(&key(KeyCode::Char('k'))).unwrap();
+        assert_eq!(selected(&app), Some(PathBuf::from("b.txt")));
+
+        app.update(&key(KeyCode::Tab)).unwrap();
+        assert!(app.focus == Focus::Left);
+        app.update(&key(KeyCode::Char('k'))).unwrap();
+        assert_eq!(selected(&app), Some(PathBuf::from("a.txt")));
+    }
+
+    #[test]
+    fn a_key_means_what_the_focused_panel_binds_it_to() {
+        let (_dir, repo) = changed_repo();
+        let (keymap, theme) = (Keymap::default(), Theme::dark());
+        let v = KeyEvent::new(KeyCode::Char('v'), KeyModifiers::NONE);
+        assert!(keymap.resolve(&v, Scope::List) == Some(Action::Mark));
+        assert!(keymap.resolve(&v, Scope::Diff) == Some(Action::LineMode));
+        // Global keys work from both
+        let q = KeyEvent::new(KeyCode::Char('q'), KeyModifiers::NONE);
+        assert!(keymap.resolve(&q, Scope::List) == Some(Action::Quit));
+        assert!(keymap.resolve(&q, Scope::Diff) == Some(Action::Quit));
+
+        let mut app = app(&repo, &keymap, &theme);
+        app.update(&Event::Key(v)).unwrap();
+        assert_eq!(app.list.marked_count(), 1);
+        assert_eq!(app.line_cursor, None);
+
+        app.update(&key(KeyCode::Tab)).unwrap();
+        app.update(&Event::Key(v)).unwrap();
+        assert_eq!(app.list.marked_count(), 1);
+        assert!(app.line_cursor.is_some());
+        // Out of line mode first, then out of the view
+        assert_eq!(app.update(&Event::Key(q)).unwrap(), Command::None);
+        assert_eq!(app.line_cursor, None);
+        assert_eq!(app.update(&Event::Key(q)).unwrap(), Command::Quit);
+    }
+
+    #[test]
+    #[ignore = "times frames by the clock; run it with --ignored on an idle machine"]
+    fn big_status_and_diff_draw_within_a_frame_budget() {
+        let (_dir, repo) = repo();
+        for i in 0..2000 {
+            write(&repo, &format!("dir{}/file{}.txt", i % 40, i), "old\n");
+        }
+        let lines: String = (0..5000).map(|i| format!("line {}\n", i)).collect();
+        // Sorts first, so its diff is the one shown
+        write(&repo, "aaa.txt", &lines);
+        commit(&repo, "first");
+        for i in 0..2000 {
+            write(&repo, &format!("dir{}/file{}.txt", i % 40, i), "new\n");
+        }
+        write(&repo, "aaa.txt", &lines.replace("line", "changed"));
+
+        let (keymap, theme) = (Keymap::default(), Theme::dark());
+        let mut app = app(&repo, &keymap, &theme);
+        assert_eq!(app.selected().unwrap().path, PathBuf::from("aaa.txt"));
+        assert!(app.current_diff.lines.len() > 1000);
+        app.update(&key(KeyCode::Tab)).unwrap();
+
+        let mut terminal = Terminal::new(TestBackend::new(200, 60)).unwrap();
+        // The first frame lays the diff out; the ones after scroll through it
+        terminal.draw(|frame| app.render(frame)).unwrap();
+        let started = Instant::now();
+        for _ in 0..50 {
+            app.update(&key(KeyCode::PageDown)).unwrap();
+            terminal.draw(|frame| app.render(frame)).unwrap();
+        }
+        let frame = started.elapsed() / 50;
+        // Room for a slow machine: a debug build takes about a fifth of this
+        assert!(
+            frame < Duration::from_millis(50),
+            "{:?} a frame is too slow to keep up with the keys",
+            frame
+        );
+    }
+
+    #[test]
+    fn a_discard_that_cannot_be_saved_is_asked_again() {
+        // Before the first commit there's nothing to stash on
+        let (dir, repo) = repo();
+        write(&repo, "new.txt", "keep me\n");
+        let path = dir.path().join("new.txt");
+        let (keymap, theme) = (Keymap::default(), Theme::dark());
+        let mut app = app(&repo, &keymap, &theme);
+
+        app.update(&key(KeyCode::Char('d'))).unwrap();
+        app.update(&key(KeyCode::Char('y'))).unwrap();
+        assert!(matches!(app.confirm_discard, Some((_, _, true))));
+        assert!(path.exists());
+
+        app.update(&key(KeyCode::Char('y'))).unwrap();
+        assert!(app.confirm_discard.is_none());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn list_and_diff_are_drawn() {
+        let (_dir, repo) = changed_repo();
+        let (keymap, theme) = (Keymap::default(), Theme::dark());
+        let mut app = app(&repo, &keymap, &theme);
+
+        let screen = screen(&mut app, 100, 20);
+        let shows = |text: &str| screen.iter().any(|row| row.contains(text));
+        assert!(shows("a.txt"), "{:#?}", screen);
+        assert!(shows("b.txt"), "{:#?}", screen);
+        assert!(shows("two"), "{:#?}", screen);
+        assert!(shows("changed"), "{:#?}", screen);
+        // b.txt's diff only shows once it's selected
+        assert!(!shows("four"), "{:#?}", screen);
+
+        app.update(&key(KeyCode::Char('j'))).unwrap();
+        settle(&mut app);
+        let screen = self::screen(&mut app, 100, 20);
+        assert!(
+            screen.iter().any(|row| row.contains("four")),
+            "{:#?}",
+            screen
+        );
+    }
+
+    #[test]
+    fn content_shorter_than_the_viewport_never_scrolls() {
+        assert_eq!(max_scroll(5, 10), 0);
+        assert_eq!(max_scroll(10, 10), 0);
+        assert_eq!(clamp_scroll(3, 5, 10), 0);
+        assert_eq!(clamp_scroll(7, 0, 10), 0);
+    }
+
+    #[test]
+    fn scroll_is_clamped_again_on_a_resize() {
+        // Scrolled to the end of 100 rows in a 20-row panel
+        let scroll = clamp_scroll(u16::MAX, 100, 20);
+        assert_eq!(scroll, 80);
+        // Taller: the last row comes up to the new bottom
+        assert_eq!(clamp_scroll(scroll, 100, 30), 70);
+        // Shorter: the top row stays where it was
+        assert_eq!(clamp_scroll(scroll, 100, 10), 80);
+        // So tall the whole diff fits
+        assert_eq!(clamp_scroll(scroll, 100, 200), 0);
+    }
+
+    #[test]
+    fn both_offsets_are_clamped_to_a_resized_panel() {
+        // 100 rows of 300 columns, scrolled to the far end of an 80x20 panel
+        let offsets = clamp_offsets((80, 220), (100, Some(300)), (20, 80));
+        assert_eq!(offsets, (80, 220));
+        assert_eq!(
+            clamp_offsets(offsets, (100, Some(300)), (40, 120)),
+            (60, 180)
+        );
+        // Wrapping puts everything back at the left edge
+        assert_eq!(clamp_offsets(offsets, (100, None), (20, 80)), (80, 0));
+        // A shorter file picked after a long one
+        assert_eq!(clamp_offsets(offsets, (5, Some(10)), (20, 80)), (0, 0));
+    }
+
+    #[test]
+    fn offsets_stop_at_the_largest_a_u16_holds() {
+        assert_eq!(max_scroll(usize::MAX, 20), u16::MAX);
+    }
+
+    #[test]
+    fn scrolling_a_row_into_view_moves_as_little_as_possible() {
+        assert_eq!(scroll_into_view(5, 0, 10), 0);
+        assert_eq!(scroll_into_view(12, 0, 10), 3);
+        assert_eq!(scroll_into_view(2, 5, 10), 2);
+    }
+}
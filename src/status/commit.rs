@@ -0,0 +1,244 @@
+use crate::sign;
+use anyhow::{Result, anyhow, bail};
+use git2::{Commit, Oid, Repository, RepositoryState, Signature, Tree};
+use std::fs;
+
+/// What the commit message box will do once it's submitted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CommitMode {
+    New,
+    Amend,
+}
+
+impl CommitMode {
+    /// Makes the commit, signed when `sign` is set (which may run gpg or
+    /// ssh-keygen for a passphrase), and says what was done.
+    pub fn submit(self, repo: &Repository, message: &str, sign: bool) -> Result<String> {
+        Ok(match self {
+            CommitMode::New => format!("Committed: {:.7}", commit(repo, message, sign)?),
+            CommitMode::Amend => format!("Amended HEAD: {:.7}", amend(repo, message, sign)?),
+        })
+    }
+}
+
+/// Whether commits are signed unless the commit box says otherwise, as
+/// `commit.gpgSign` makes `git commit` do.
+pub fn sign_by_default(repo: &Repository) -> bool {
+    repo.config()
+        .and_then(|c| c.get_bool("commit.gpgsign"))
+        .unwrap_or(false)
+}
+
+/// Commits the current index with `message` on top of HEAD, like
+/// `git commit`. Finishes a merge in progress, with the merged commits as
+/// extra parents.
+pub fn commit(repo: &Repository, message: &str, sign: bool) -> Result<Oid> {
+    let message = clean_message(message)?;
+
+    let mut index = repo.index()?;
+    if index.has_conflicts() {
+        bail!("fix the conflicts and stage the result before committing");
+    }
+    let tree = repo.find_tree(index.write_tree()?)?;
+
+    // An unborn branch gets a root commit
+    let head = repo.head().ok().map(|h| h.peel_to_commit()).transpose()?;
+    let mut parents: Vec<_> = head.iter().cloned().collect();
+
+    let merging = repo.state() == RepositoryState::Merge;
+    if merging {
+        // git2's `mergehead_foreach` wants the repository mutably, so read the file it reads
+        let merge_head = fs::read_to_string(repo.path().join("MERGE_HEAD"))?;
+        for line in merge_head.lines().filter(|l| !l.is_empty()) {
+            parents.push(repo.find_commit(Oid::from_str(line.trim())?)?);
+        }
+    }
+
+    // A merge may legitimately change nothing; anything else needs staged changes
+    let unchanged = match &head {
+        Some(head) => head.tree_id() == tree.id(),
+        None => index.is_empty(),
+    };
+    if unchanged && !merging {
+        bail!("nothing staged to commit");
+    }
+
+    let signature = repo.signature()?;
+    let parents: Vec<_> = parents.iter().collect();
+    let oid = if sign {
+        let kind = match (&head, merging) {
+            (None, _) => "commit (initial)",
+            (_, true) => "commit (merge)",
+            _ => "commit",
+        };
+        signed_commit(
+            repo, kind, &signature, &signature, &message, &tree, &parents,
+        )?
+    } else {
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            &message,
+            &tree,
+            &parents,
+        )?
+    };
+
+    if merging {
+        repo.cleanup_state()?;
+    }
+
+    Ok(oid)
+}
+
+/// Message of the HEAD commit, to pre-fill an amend.
+pub fn head_message(repo: &Repository) -> Result<String> {
+    let head = amendable_head(repo)?;
+    Ok(head.message().unwrap_or("").trim_end().to_string())
+}
+
+/// Replaces HEAD with a commit of the current index and `message`. The
+/// original author and parents are kept, so an amended merge stays a merge;
+/// the committer becomes the current user.
+pub fn amend(repo: &Repository, message: &str, sign: bool) -> Result<Oid> {
+    let message = clean_message(message)?;
+
+    let head = amendable_head(repo)?;
+
+    let mut index = repo.index()?;
+    if index.has_conflicts() {
+        bail!("fix the conflicts and stage the result before amending");
+    }
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let committer = repo.signature()?;
+
+    if sign {
+        let parents: Vec<_> = head.parents().collect();
+        let parents: Vec<_> = parents.iter().collect();
+        return signed_commit(
+            repo,
+            "commit (amend)",
+            &head.author(),
+            &committer,
+            &message,
+            &tree,
+            &parents,
+        );
+    }
+
+    let oid = head.amend(
+        Some("HEAD"),
+        None,
+        Some(&committer),
+        None,
+        Some(&message),
+        Some(&tree),
+    )?;
+
+    Ok(oid)
+}
+
+/// Writes the commit with a signature of it in its `gpgsig` header, then
+/// moves HEAD's branch (or a detached HEAD) onto it as `git commit -S` does.
+fn signed_commit(
+    repo: &Repository,
+    kind: &str,
+    author: &Signature,
+    committer: &Signature,
+    message: &str,
+    tree: &Tree,
+    parents: &[&Commit],
+) -> Result<Oid> {
+    let buffer = repo.commit_create_buffer(author, committer, message, tree, parents)?;
+    let buffer = buffer
+        .as_str()
+        .ok_or_else(|| anyhow!("the commit isn't valid UTF-8, so it can't be signed"))?;
+    let signature = sign::sign(repo, buffer)?;
+    let oid = repo.commit_signed(buffer, &signature, None)?;
+
+    let log = format!("{}: {}", kind, message.lines().next().unwrap_or(""));
+    let head = repo.find_reference("HEAD")?;
+    // An unborn branch is created here, as `git commit` creates it
+    let target = head.symbolic_target().unwrap_or("HEAD").to_string();
+    repo.reference(&target, oid, true, &log)?;
+    Ok(oid)
+}
+
+/// HEAD, unless there is nothing to amend or amending would lose something.
+fn amendable_head(repo: &Repository) -> Result<git2::Commit<'_>> {
+    let head = repo
+        .head()
+        .map_err(|_| anyhow!("nothing to amend: no commits yet"))?
+        .peel_to_commit()?;
+
+    // Amending now would drop the merge being made, as git warns
+    if repo.state() == RepositoryState::Merge {
+        bail!("in the middle of a merge; commit it instead of amending");
+    }
+
+    Ok(head)
+}
+
+/// Trailing whitespace trimmed and a final newline added, as git stores it.
+fn clean_message(message: &str) -> Result<String> {
+    let message = message.trim_end();
+    if message.trim().is_empty() {
+        bail!("aborting commit due to empty commit message");
+    }
+    Ok(format!("{}\n", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Time;
+    use helper_git_core::testing::{commit as commit_all, repo, stage, write};
+
+    #[test]
+    fn amend_keeps_the_parent_and_author() {
+        let (_dir, repo) = repo();
+        write(&repo, "a.txt", "a\n");
+        let parent = commit_all(&repo, "first");
+
+        // Someone else's commit from long ago, to amend as the test user
+        write(&repo, "b.txt", "b\n");
+        stage(&repo, "b.txt");
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let original =
+            Signature::new("Original", "original@example.com", &Time::new(1, 0)).unwrap();
+        let parent_commit = repo.find_commit(parent).unwrap();
+        let old = repo
+            .commit(
+                Some("HEAD"),
+                &original,
+                &original,
+                "second",
+                &tree,
+                &[&parent_commit],
+            )
+            .unwrap();
+
+        write(&repo, "c.txt", "c\n");
+        stage(&repo, "c.txt");
+        let new = amend(&repo, "second, amended", false).unwrap();
+
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.id(), new);
+        assert_ne!(new, old);
+        assert_eq!(head.parent_ids().collect::<Vec<_>>(), [parent]);
+        assert_eq!(head.message(), Some("second, amended\n"));
+        assert!(head.tree().unwrap().get_name("c.txt").is_some());
+
+        let author = head.author();
+        assert_eq!(author.name(), Some("Original"));
+        assert_eq!(author.email(), Some("original@example.com"));
+        assert_eq!(author.when(), original.when());
+        let committer = head.committer();
+        assert_eq!(committer.name(), Some("Test"));
+        assert_eq!(committer.email(), Some("test@example.com"));
+        assert_ne!(committer.when(), original.when());
+    }
+}
@@ -0,0 +1,638 @@
+use super::commit::{self, CommitMode};
+use super::message::{InputResult, MessageInput};
+use super::palette;
+use crate::theme::Theme;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use git2::{Repository, Sort};
+use ratatui::{
+    layout::Flex,
+    prelude::*,
+    widgets::{Block, Borders, Clear, HighlightSpacing, List, ListItem, ListState, Paragraph},
+};
+use std::fs;
+
+/// The commit types of the Conventional Commits spec and the Angular
+/// convention it grew from, with what each is for.
+const TYPES: &[(&str, &str)] = &[
+    ("feat", "a new feature"),
+    ("fix", "a bug fix"),
+    ("docs", "documentation only"),
+    ("style", "formatting, no change in meaning"),
+    ("refactor", "neither fixes a bug nor adds a feature"),
+    ("perf", "makes it faster"),
+    ("test", "adds or corrects tests"),
+    ("build", "the build system or dependencies"),
+    ("ci", "the CI configuration"),
+    ("chore", "anything else that leaves src and tests be"),
+    ("revert", "reverts an earlier commit"),
+];
+
+/// Past this many characters the header gets a warning, and past
+/// [`HEADER_LIMIT`] it's too long for most tools to show whole.
+const HEADER_WARN: usize = 50;
+const HEADER_LIMIT: usize = 72;
+
+/// The column the body is wrapped at, as git's own guidelines have it.
+const BODY_WIDTH: usize = 72;
+
+/// How far back the co-author picker looks for people to offer.
+const RECENT_COMMITS: usize = 1000;
+
+pub enum BoxResult {
+    Editing,
+    Cancelled,
+    Submitted(String),
+}
+
+/// The commit message box, with whether the commit is to be signed, the
+/// `commit.template` it started from, the guided mode over it, and the
+/// trailers it can add.
+pub struct CommitBox {
+    pub mode: CommitMode,
+    pub sign: bool,
+    input: MessageInput,
+    template: Option<String>,
+    guide: Option<Guide>,
+    /// `Name <email>` of the user, for `Signed-off-by:`
+    user: Option<String>,
+    /// Whoever authored the recent commits, most recent first
+    authors: Vec<String>,
+    picker: Option<AuthorPicker>,
+}
+
+impl CommitBox {
+    /// An empty box for a new commit starts from `commit.template` when one
+    /// is set; an amend starts from HEAD's message.
+    pub fn new(repo: &Repository, mode: CommitMode, text: String) -> Self {
+        let template = match mode {
+            CommitMode::New => template(repo),
+            CommitMode::Amend => None,
+        };
+        let text = match &template {
+            Some(template) if text.is_empty() => template.clone(),
+            _ => text,
+        };
+        let user = repo.signature().ok().map(|s| s.to_string());
+        let mut commit_box = CommitBox {
+            mode,
+            sign: commit::sign_by_default(repo),
+            input: MessageInput::new("", text),
+            template,
+            guide: None,
+            authors: recent_authors(repo, user.as_deref()),
+            user,
+            picker: None,
+        };
+        commit_box.retitle();
+        commit_box
+    }
+
+    pub fn handle(&mut self, key: &KeyEvent) -> BoxResult {
+        if let Some(guide) = &mut self.guide {
+            match guide.handle(key) {
+                GuideResult::Editing => {}
+                GuideResult::Cancelled => self.guide = None,
+                GuideResult::Done(text) => {
+                    self.input.text = text;
+                    self.guide = None;
+                }
+            }
+            return BoxResult::Editing;
+        }
+
+        if let Some(picker) = &mut self.picker {
+            if let Some(picked) = picker.handle(key) {
+                if let Some(author) = picked {
+                    self.input.text = add_trailer(&self.input.text, "Co-authored-by", &author);
+                }
+                self.picker = None;
+            }
+            return BoxResult::Editing;
+        }
+
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            match key.code {
+                KeyCode::Char('s') => {
+                    self.sign = !self.sign;
+                    self.retitle();
+                    return BoxResult::Editing;
+                }
+                KeyCode::Char('g') => {
+                    self.guide = Some(Guide::new());
+                    return BoxResult::Editing;
+                }
+                KeyCode::Char('o') => {
+                    if let Some(user) = &self.user {
+                        self.input.text = add_trailer(&self.input.text, "Signed-off-by", user);
+                    }
+                    return BoxResult::Editing;
+                }
+                KeyCode::Char('a') => {
+                    self.picker = Some(AuthorPicker::new(self.authors.clone()));
+                    return BoxResult::Editing;
+                }
+                _ => {}
+            }
+        }
+
+        match self.input.handle(key) {
+            InputResult::Editing => BoxResult::Editing,
+            InputResult::Cancelled => BoxResult::Cancelled,
+            InputResult::Submitted(text) => BoxResult::Submitted(text),
+        }
+    }
+
+    /// The message to commit: with the template's `#` lines dropped, as git
+    /// drops them, and refused when the template went in untouched.
+    pub fn message(&self, text: &str) -> Result<String, String> {
+        let Some(template) = &self.template else {
+            return Ok(text.to_string());
+        };
+        let strip = |text: &str| {
+            text.lines()
+                .filter(|line| !line.starts_with('#'))
+                .collect::<Vec<_>>()
+                .join("\n")
+                .trim()
+                .to_string()
+        };
+        let message = strip(text);
+        if message == strip(template) && !message.is_empty() {
+            return Err(String::from(
+                "the template wasn't edited, so nothing was committed",
+            ));
+        }
+        Ok(message)
+    }
+
+    pub fn render(&mut self, frame: &mut Frame, theme: &Theme) {
+        match &self.guide {
+            Some(guide) => guide.render(frame, theme),
+            None => self.input.render(frame, theme),
+        }
+        if let Some(picker) = &mut self.picker {
+            picker.render(frame, theme);
+        }
+    }
+
+    fn retitle(&mut self) {
+        let verb = match self.mode {
+            CommitMode::New => "Commit",
+            CommitMode::Amend => "Amend commit",
+        };
+        self.input.title = if self.sign {
+            format!("{}, signed", verb)
+        } else {
+            String::from(verb)
+        };
+        self.input.hints = vec![
+            String::from(if self.sign {
+                "Ctrl-s don't sign"
+            } else {
+                "Ctrl-s sign"
+            }),
+            String::from("Ctrl-g guided"),
+            String::from("Ctrl-o sign off"),
+            String::from("Ctrl-a co-author"),
+        ];
+    }
+}
+
+/// What `commit.template` names, read, if it's set.
+fn template(repo: &Repository) -> Option<String> {
+    let path = repo.config().ok()?.get_path("commit.template").ok()?;
+    // A relative path is taken from the top of the worktree, as git takes it
+    let path = match repo.workdir() {
+        Some(dir) if path.is_relative() => dir.join(path),
+        _ => path,
+    };
+    fs::read_to_string(path).ok()
+}
+
+/// `Name <email>` of everyone who authored one of the last
+/// [`RECENT_COMMITS`] commits, once each, most recent first, leaving out
+/// `user`.
+fn recent_authors(repo: &Repository, user: Option<&str>) -> Vec<String> {
+    let mut authors: Vec<String> = Vec::new();
+    let Ok(mut walk) = repo.revwalk() else {
+        return authors;
+    };
+    if walk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME).is_err() || walk.push_head().is_err() {
+        return authors;
+    }
+    for oid in walk.take(RECENT_COMMITS).flatten() {
+        let Ok(commit) = repo.find_commit(oid) else {
+            continue;
+        };
+        let author = commit.author().to_string();
+        if Some(author.as_str()) != user && !authors.contains(&author) {
+            authors.push(author);
+        }
+    }
+    authors
+}
+
+/// `text` with `key: value` as a trailer: on the end of the trailers already
+/// there, or after a blank line when there are none, and not again when it's
+/// there already. An empty message is left be: trailers go after one.
+fn add_trailer(text: &str, key: &str, value: &str) -> String {
+    let trailer = format!("{}: {}", key, value);
+    let text = text.trim_end();
+    if text.trim().is_empty() || text.lines().any(|line| line.trim() == trailer) {
+        return text.to_string();
+    }
+
+    // The last paragraph is trailers when every line of it is `Token: value`
+    let last = text.rsplit("\n\n").next().unwrap_or("");
+    let is_trailers = text.contains("\n\n")
+        && last.lines().all(|line| {
+            line.split_once(": ").is_some_and(|(token, _)| {
+                !token.is_empty() && token.chars().all(|c| c.is_alphanumeric() || c == '-')
+            })
+        });
+    if is_trailers {
+        format!("{}\n{}", text, trailer)
+    } else {
+        format!("{}\n\n{}", text, trailer)
+    }
+}
+
+/// Recent authors to pick a co-author from, narrowed down as a name is
+/// typed.
+struct AuthorPicker {
+    authors: Vec<String>,
+    query: String,
+    matches: Vec<usize>,
+    state: ListState,
+}
+
+impl AuthorPicker {
+    fn new(authors: Vec<String>) -> Self {
+        let mut picker = AuthorPicker {
+            authors,
+            query: String::new(),
+            matches: Vec::new(),
+            state: ListState::default(),
+        };
+        picker.search();
+        picker
+    }
+
+    /// `Some` once it's closed: with the author picked, or `None` when
+    /// nobody was.
+    fn handle(&mut self, key: &KeyEvent) -> Option<Option<String>> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Some(None),
+            KeyCode::Enter => {
+                let picked = self.state.selected().and_then(|i| self.matches.get(i));
+                return picked.map(|&i| Some(self.authors[i].clone()));
+            }
+            KeyCode::Up => self.state.select_previous(),
+            KeyCode::Char('p') if ctrl => self.state.select_previous(),
+            KeyCode::Down => self.state.select_next(),
+            KeyCode::Char('n') if ctrl => self.state.select_next(),
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.search();
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.search();
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Best match first; with nothing typed, the most recent first.
+    fn search(&mut self) {
+        let mut scored: Vec<(i32, usize)> = self
+            .authors
+            .iter()
+            .enumerate()
+            .filter_map(|(i, author)| palette::score(&self.query, author).map(|s| (s, i)))
+            .collect();
+        scored.sort_by_key(|(score, _)| -score);
+        self.matches = scored.into_iter().map(|(_, i)| i).collect();
+        self.state.select(if self.matches.is_empty() {
+            None
+        } else {
+            Some(0)
+        });
+    }
+
+    fn render(&mut self, frame: &mut Frame, theme: &Theme) {
+        let [area] = Layout::horizontal([Constraint::Percentage(50)])
+            .flex(Flex::Center)
+            .areas(frame.area());
+        let [area] = Layout::vertical([Constraint::Length(12)])
+            .flex(Flex::Center)
+            .areas(area);
+
+        let block = Block::default()
+            .title(" Co-authored-by ")
+            .title_bottom(" Enter add • ↑/↓ choose • Esc close ")
+            .borders(Borders::ALL)
+            .border_style(theme.focus());
+        let inner = block.inner(area);
+        let [input, list] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(inner);
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(block, area);
+        frame.render_widget(
+            Paragraph::new(Line::from(vec![
+                Span::styled("> ", Style::default().fg(theme.accent)),
+                Span::raw(self.query.clone()),
+                Span::styled("█", Style::default().fg(theme.accent)),
+            ])),
+            input,
+        );
+
+        if self.matches.is_empty() {
+            let text = if self.authors.is_empty() {
+                "Nobody else has committed here lately"
+            } else {
+                "Nobody matches"
+            };
+            frame.render_widget(
+                Paragraph::new(Line::styled(text, Style::default().dim())),
+                list,
+            );
+            return;
+        }
+
+        let items: Vec<ListItem> = self
+            .matches
+            .iter()
+            .map(|&i| ListItem::new(self.authors[i].clone()))
+            .collect();
+        frame.render_stateful_widget(
+            List::new(items)
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+                .highlight_symbol("> ")
+                .highlight_spacing(HighlightSpacing::Always),
+            list,
+            &mut self.state,
+        );
+    }
+}
+
+enum GuideResult {
+    Editing,
+    Cancelled,
+    Done(String),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    Type,
+    Scope,
+    Subject,
+    Body,
+}
+
+/// Builds a Conventional Commits message, `type(scope)!: subject`, a field
+/// at a time: the type picked from [`TYPES`], the header measured as it's
+/// typed, and the body wrapped at [`BODY_WIDTH`]. What it makes goes back
+/// into the message box to look over before committing.
+struct Guide {
+    field: Field,
+    kind: usize,
+    scope: String,
+    breaking: bool,
+    subject: String,
+    body: String,
+}
+
+impl Guide {
+    fn new() -> Self {
+        Guide {
+            field: Field::Type,
+            kind: 0,
+            scope: String::new(),
+            breaking: false,
+            subject: String::new(),
+            body: String::new(),
+        }
+    }
+
+    fn handle(&mut self, key: &KeyEvent) -> GuideResult {
+        let fields = [Field::Type, Field::Scope, Field::Subject, Field::Body];
+        let at = fields.iter().position(|f| *f == self.field).unwrap_or(0);
+
+        match key.code {
+            KeyCode::Esc => return GuideResult::Cancelled,
+            KeyCode::Tab => self.field = fields[(at + 1) % fields.len()],
+            KeyCode::BackTab => self.field = fields[(at + fields.len() - 1) % fields.len()],
+            KeyCode::Char('b') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.breaking = !self.breaking
+            }
+            KeyCode::Enter
+                if self.field == Field::Body
+                    && (key.modifiers.contains(KeyModifiers::ALT)
+                        || key.modifiers.contains(KeyModifiers::SHIFT)) =>
+            {
+                self.body.push('\n')
+            }
+            KeyCode::Char('j')
+                if self.field == Field::Body && key.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                self.body.push('\n')
+            }
+            KeyCode::Enter if self.field != Field::Body => self.field = fields[at + 1],
+            // Done, once there's a subject to make a header of
+            KeyCode::Enter if self.subject.trim().is_empty() => self.field = Field::Subject,
+            KeyCode::Enter => return GuideResult::Done(self.message()),
+            KeyCode::Up | KeyCode::Left if self.field == Field::Type => {
+                self.kind = (self.kind + TYPES.len() - 1) % TYPES.len()
+            }
+            KeyCode::Down | KeyCode::Right if self.field == Field::Type => {
+                self.kind = (self.kind + 1) % TYPES.len()
+            }
+            KeyCode::Backspace => {
+                if let Some(text) = self.text_mut() {
+                    text.pop();
+                }
+            }
+            // Typing a type's first letters picks it
+            KeyCode::Char(c) if self.field == Field::Type => {
+                if let Some(i) = TYPES.iter().position(|(name, _)| name.starts_with(c)) {
+                    self.kind = i;
+                }
+            }
+            KeyCode::Char(c) => {
+                if let Some(text) = self.text_mut() {
+                    text.push(c);
+                }
+            }
+            _ => {}
+        }
+        GuideResult::Editing
+    }
+
+    fn text_mut(&mut self) -> Option<&mut String> {
+        match self.field {
+            Field::Type => None,
+            Field::Scope => Some(&mut self.scope),
+            Field::Subject => Some(&mut self.subject),
+            Field::Body => Some(&mut self.body),
+        }
+    }
+
+    /// `feat(parser)!: accept trailing commas`
+    fn header(&self) -> String {
+        let scope = self.scope.trim();
+        format!(
+            "{}{}{}: {}",
+            TYPES[self.kind].0,
+            if scope.is_empty() {
+                String::new()
+            } else {
+                format!("({})", scope)
+            },
+            if self.breaking { "!" } else { "" },
+            self.subject.trim()
+        )
+    }
+
+    fn message(&self) -> String {
+        let body = wrap(self.body.trim(), BODY_WIDTH);
+        if body.is_empty() {
+            self.header()
+        } else {
+            format!("{}\n\n{}", self.header(), body)
+        }
+    }
+
+    fn render(&self, frame: &mut Frame, theme: &Theme) {
+        let [area] = Layout::horizontal([Constraint::Percentage(70)])
+            .flex(Flex::Center)
+            .areas(frame.area());
+        let [area] = Layout::vertical([Constraint::Length(14)])
+            .flex(Flex::Center)
+            .areas(area);
+
+        let accent = Style::default().fg(theme.accent);
+        let label = |field: Field, name: &'static str| {
+            if field == self.field {
+                Span::styled(name, accent.add_modifier(Modifier::BOLD))
+            } else {
+                Span::styled(name, Style::default().dim())
+            }
+        };
+        let cursor = |field: Field| {
+            if field == self.field {
+                Span::styled("█", accent)
+            } else {
+                Span::raw("")
+            }
+        };
+
+        let (kind, about) = TYPES[self.kind];
+        let mut type_line = vec![label(Field::Type, "Type     ")];
+        type_line.push(Span::styled(
+            format!("‹ {} ›", kind),
+            Style::default().add_modifier(Modifier::BOLD),
+        ));
+        type_line.push(Span::styled(format!("  {}", about), Style::default().dim()));
+        if self.breaking {
+            type_line.push(Span::styled(
+                "  breaking change",
+                Style::default().fg(theme.warning),
+            ));
+        }
+
+        // The header counted as it will be committed
+        let length = self.header().chars().count();
+        let count_style = match length {
+            n if n > HEADER_LIMIT => Style::default()
+                .fg(theme.warning)
+                .add_modifier(Modifier::BOLD),
+            n if n > HEADER_WARN => Style::default().fg(theme.warning),
+            _ => Style::default().dim(),
+        };
+        let count = match length {
+            n if n > HEADER_LIMIT => format!("  {}/{} too long", n, HEADER_WARN),
+            n => format!("  {}/{}", n, HEADER_WARN),
+        };
+
+        let mut lines = vec![
+            Line::from(type_line),
+            Line::from(vec![
+                label(Field::Scope, "Scope    "),
+                Span::raw(self.scope.clone()),
+                cursor(Field::Scope),
+            ]),
+            Line::from(vec![
+                label(Field::Subject, "Subject  "),
+                Span::raw(self.subject.clone()),
+                cursor(Field::Subject),
+                Span::styled(count, count_style),
+            ]),
+            Line::from(vec![label(Field::Body, "Body")]),
+        ];
+        let body: Vec<&str> = self.body.split('\n').collect();
+        for (i, line) in body.iter().enumerate() {
+            let mut spans = vec![Span::raw("  "), Span::raw(line.to_string())];
+            if i == body.len() - 1 {
+                spans.push(cursor(Field::Body));
+            }
+            lines.push(Line::from(spans));
+        }
+        lines.push(Line::from(""));
+        lines.push(Line::styled(self.header(), accent));
+
+        let block = Block::default()
+            .title(" Conventional commit ")
+            .title_bottom(
+                " Tab field • ←/→ type • Ctrl-b breaking • Alt-Enter new line • Enter done • Esc back ",
+            )
+            .borders(Borders::ALL)
+            .border_style(theme.focus());
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+}
+
+/// `text` with each paragraph's lines run together and broken again at
+/// `width`; list items and indented lines are left as they are.
+fn wrap(text: &str, width: usize) -> String {
+    let mut out: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let flush = |current: &mut String, out: &mut Vec<String>| {
+        if !current.is_empty() {
+            out.push(std::mem::take(current));
+        }
+    };
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() {
+            flush(&mut current, &mut out);
+            out.push(String::new());
+            continue;
+        }
+        // A list item or an indented line starts a line of its own
+        if line.starts_with([' ', '\t']) || trimmed.starts_with(['-', '*']) {
+            flush(&mut current, &mut out);
+            if line.starts_with([' ', '\t']) {
+                out.push(line.to_string());
+                continue;
+            }
+        }
+        for word in trimmed.split_whitespace() {
+            if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > width {
+                flush(&mut current, &mut out);
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(word);
+        }
+    }
+    flush(&mut current, &mut out);
+    out.join("\n")
+}
@@ -1,69 +1,938 @@
-use anyhow::Result;
-use git2::{DiffFormat, DiffOptions, Repository, Status};
+#[cfg(feature = "syntax")]
+use super::highlight::Highlighter;
+use super::submodule;
+use super::words;
+use crate::image::Preview;
+use crate::theme::Theme;
+use crate::whitespace;
+use anyhow::{Result, anyhow};
+use git2::{Diff, DiffFormat, DiffStatsFormat, Patch, Repository, Status};
+use helper_git_core::diff::{DiffSettings, DiffText, file_contents, file_diff};
+use helper_git_core::lfs::{self, Pointer};
+use helper_git_core::status::{FileEntry, Section};
 use ratatui::{
-    style::{Color, Style},
+    style::{Modifier, Style},
     text::{Line, Span},
 };
-use std::fs;
-use std::path::Path;
+use std::fs::{self, File};
+use std::io::Read;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
-pub fn show_file_diff(repo: &Repository, path: &str) -> Result<Vec<Line<'static>>> {
-    let mut lines: Vec<Line<'static>> = Vec::new();
+/// Rendered diff plus the line range each hunk occupies, so the panel can
+/// move a cursor between hunks and stage them one at a time.
+#[derive(Default)]
+pub struct FileDiff {
+    pub lines: Vec<Line<'static>>,
+    pub hunks: Vec<Range<usize>>,
+    /// The `+` and `-` lines, which can be staged one at a time; empty when
+    /// the file can only be staged whole
+    pub changes: Vec<usize>,
+    /// Where each file's header starts, with the file's new path; filled in
+    /// for multi-file diffs only
+    pub files: Vec<(usize, PathBuf)>,
+    /// The same diff side by side; `None` for anything that isn't a patch,
+    /// like a conflict or a whole untracked file
+    pub split: Option<SplitDiff>,
+    /// Old and new line number of each line, where it has them; empty when
+    /// there are none at all
+    pub numbers: Vec<(Option<u32>, Option<u32>)>,
+    /// What was left out of a preview that got too long
+    pub more: Option<More>,
+    /// The file as an image, old and new, where the terminal can draw it
+    pub image: Option<Preview>,
+    /// The Git LFS objects the file points to that aren't downloaded yet
+    pub lfs: Vec<Pointer>,
+}
 
-    // ---------- Check file status ----------
-    let status = repo.status_file(Path::new(path))?;
+/// The rest of a cut-off preview: bytes of a whole new or deleted file, or
+/// lines of a diff.
+#[derive(Clone, Copy, PartialEq)]
+pub enum More {
+    Bytes(u64),
+    Lines(usize),
+}
 
-    // ---------- If untracked (WT_NEW) ----------
-    if status.contains(Status::WT_NEW) {
-        let full_path = repo.workdir().unwrap().join(path);
+/// Which column of the split view a line of the unified diff lands in.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Column {
+    Both,
+    Old,
+    New,
+}
 
-        let content = fs::read_to_string(full_path)?;
+/// A diff as two aligned columns, old on the left and new on the right, with
+/// each run of deletions paired up against the additions that replaced it.
+pub struct SplitDiff {
+    pub rows: Vec<(Line<'static>, Line<'static>)>,
+    /// Row and column of each unified line, so hunks and the line cursor carry over
+    pub positions: Vec<(usize, Column)>,
+}
 
-        lines.push(Line::from(Span::styled(
-            format!("New file: {}\n", path),
-            Style::default().fg(Color::Blue),
+impl FileDiff {
+    pub fn message(text: String) -> Self {
+        FileDiff {
+            lines: vec![Line::from(text)],
+            ..Default::default()
+        }
+    }
+
+    /// Takes the line number gutters off again. Whenever any line has a
+    /// number, every line and split row starts with its gutter.
+    pub fn hide_numbers(&mut self) {
+        if !self
+            .numbers
+            .iter()
+            .any(|&(old, new)| old.is_some() || new.is_some())
+        {
+            return;
+        }
+        for line in self.lines.iter_mut().take(self.numbers.len()) {
+            line.spans.remove(0);
+        }
+        if let Some(split) = &mut self.split {
+            for (old, new) in &mut split.rows {
+                old.spans.remove(0);
+                new.spans.remove(0);
+            }
+        }
+    }
+}
+
+/// Lines of a diff or a whole new or deleted file shown at first; each
+/// `load_more` shows four times as many.
+pub const PREVIEW_LINES: usize = 2000;
+
+/// The biggest Git LFS object diffed against the other side.
+const LFS_DIFF_LIMIT: u64 = 8 << 20;
+
+/// Bytes read per preview line at most, so a file of a few enormous lines
+/// still stops somewhere.
+const PREVIEW_LINE_BYTES: usize = 512;
+
+/// Diff for one side of a status entry: index against the worktree for
+/// unstaged changes, HEAD against the index for staged ones. These are the
+/// same diffs `stage` applies hunks from, so hunk indices line up. Up to
+/// `limit` lines are shown.
+pub fn show_file_diff(
+    repo: &Repository,
+    entry: &FileEntry,
+    settings: DiffSettings,
+    limit: usize,
+    theme: &Theme,
+) -> Result<FileDiff> {
+    let path = &entry.path;
+
+    // ---------- If an ignored directory ----------
+    // These are listed collapsed, with a trailing slash, and have no status of their own
+    if entry.is_directory() {
+        return Ok(FileDiff::message(format!(
+            "Ignored directory: {}",
+            path.display()
         )));
+    }
 
-        for line in content.lines() {
-            lines.push(Line::from(Span::styled(
-                format!("+{}\n", line),
-                Style::default().fg(Color::Green),
-            )));
+    // ---------- Check file status ----------
+    let status = repo.status_file(path)?;
+
+    // ---------- If unmerged ----------
+    if entry.section == Section::Conflicted {
+        return conflict_lines(repo, entry, theme);
+    }
+
+    // ---------- If a submodule ----------
+    // Submodules are looked up by name, which git only writes as UTF-8
+    if let Some(sm) = path.to_str().and_then(|p| repo.find_submodule(p).ok()) {
+        return submodule::summary(repo, &sm, entry, theme);
+    }
+
+    // ---------- If kept in Git LFS ----------
+    // The pointers themselves say little; what they point to is shown instead
+    if lfs::tracked(repo, path) {
+        let [old, new] = file_contents(repo, entry, LFS_DIFF_LIMIT)?;
+        let pointed =
+            |side: &Option<Vec<u8>>| side.as_deref().is_some_and(|c| Pointer::parse(c).is_some());
+        if pointed(&old) || pointed(&new) {
+            return lfs_lines(repo, entry, [old, new], limit, theme);
         }
+    }
+
+    // ---------- If untracked (WT_NEW) or ignored ----------
+    // The new side of a worktree rename is untracked too, but has a real diff
+    if entry.section == Section::Worktree
+        && entry.rename.is_none()
+        && status.intersects(Status::WT_NEW | Status::IGNORED)
+    {
+        let workdir = repo
+            .workdir()
+            .ok_or_else(|| anyhow!("cannot show diff: repository has no working directory"))?;
+        let file = File::open(workdir.join(path))?;
+        let size = file.metadata()?.len();
+
+        // Never more than the preview can show, however big the file is
+        let mut content = Vec::new();
+        file.take((limit * PREVIEW_LINE_BYTES) as u64)
+            .read_to_end(&mut content)?;
+
+        return Ok(whole_file_lines(
+            if status.contains(Status::IGNORED) {
+                format!("Ignored file: {}", path.display())
+            } else {
+                format!("New file: {}", path.display())
+            },
+            path,
+            &content,
+            size,
+            limit,
+            '+',
+            theme,
+        ));
+    }
 
-        return Ok(lines);
+    // ---------- If deleted (no longer on disk) ----------
+    let deleted = entry.rename.is_none()
+        && match entry.section {
+            Section::Index => status.contains(Status::INDEX_DELETED),
+            Section::Worktree | Section::Conflicted => status.contains(Status::WT_DELETED),
+        };
+    if deleted {
+        let (content, size) = deleted_content(repo, entry, limit * PREVIEW_LINE_BYTES)?;
+
+        return Ok(whole_file_lines(
+            format!("Deleted file: {}", path.display()),
+            path,
+            &content,
+            size,
+            limit,
+            '-',
+            theme,
+        ));
     }
 
     // ---------- Otherwise normal diff ----------
-    let mut opts = DiffOptions::new();
-    opts.pathspec(path);
-    opts.include_untracked(true);
-    opts.recurse_untracked_dirs(true);
-
-    let head = repo.head().ok();
-    let tree = head.and_then(|h| h.peel_to_tree().ok());
-
-    let diff = repo.diff_tree_to_workdir_with_index(tree.as_ref(), Some(&mut opts))?;
-
-    diff.print(DiffFormat::Patch, |_delta, _hunk, line| {
-        let content = std::str::from_utf8(line.content())
-            .unwrap_or("")
-            .to_string();
-
-        let span = match line.origin() {
-            '+' => Span::styled(content, Style::default().fg(Color::Green)),
-            '-' => Span::styled(content, Style::default().fg(Color::Red)),
-            'F' => Span::styled(content, Style::default().fg(Color::Blue)),
-            _ => Span::raw(content),
+    let diff = file_diff(repo, entry, settings, false)?;
+
+    let mut file_diff = render_diff_upto(&diff, limit, true, theme)?;
+
+    if file_diff.lines.is_empty() {
+        file_diff.lines.push(Line::from("No changes"));
+    }
+
+    Ok(file_diff)
+}
+
+/// `entry`'s diff as `git diff` prints it, in the diff colors: with the `+`
+/// and `-` in front of each line, it reads the same once the colors are gone.
+pub fn patch_lines(
+    repo: &Repository,
+    entry: &FileEntry,
+    settings: DiffSettings,
+    theme: &Theme,
+) -> Result<Vec<Line<'static>>> {
+    // Unmerged files have no patch, just their markers
+    if entry.section == Section::Conflicted {
+        return Ok(conflict_lines(repo, entry, theme)?.lines);
+    }
+
+    let lines = DiffText::load(repo, entry, settings)?
+        .lines
+        .into_iter()
+        .flat_map(|line| {
+            let content = line.content.trim_end_matches('\n');
+            match line.origin {
+                origin @ ('+' | '-' | ' ') => {
+                    vec![plain_line(origin, &format!("{}{}", origin, content), theme)]
+                }
+                'F' => content
+                    .lines()
+                    .map(|l| Line::styled(l.to_string(), Style::default().fg(theme.file_header)))
+                    .collect(),
+                _ => vec![Line::raw(content.to_string())],
+            }
+        })
+        .collect();
+
+    Ok(lines)
+}
+
+/// A conflicted file: which index stages exist, then the worktree content
+/// with the conflict markers picked out.
+fn conflict_lines(repo: &Repository, entry: &FileEntry, theme: &Theme) -> Result<FileDiff> {
+    let path = &entry.path;
+    let index = repo.index()?;
+
+    let stages: Vec<String> = [(1, "base"), (2, "ours"), (3, "theirs")]
+        .iter()
+        .map(|&(stage, name)| {
+            let mark = if index.get_path(path, stage).is_some() {
+                "✓"
+            } else {
+                "—"
+            };
+            format!("{} {}", name, mark)
+        })
+        .collect();
+
+    let mut lines: Vec<Line<'static>> = vec![
+        Line::styled(
+            format!("Conflicted file: {}", entry.path.display()),
+            Style::default().fg(theme.file_header),
+        ),
+        Line::from(format!("Stages: {}", stages.join("  "))),
+    ];
+
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("cannot show diff: repository has no working directory"))?;
+
+    let content = match fs::read(workdir.join(path)) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            lines.push(Line::from("Deleted in the working tree"));
+            return Ok(FileDiff {
+                lines,
+                ..Default::default()
+            });
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    if is_binary(&content) {
+        lines.push(binary_line(&content, content.len() as u64));
+        return Ok(FileDiff {
+            lines,
+            ..Default::default()
+        });
+    }
+
+    let mut numbers = vec![(None, None); lines.len()];
+    for (i, line) in String::from_utf8_lossy(&content).lines().enumerate() {
+        let marker = ["<<<<<<<", "|||||||", "=======", ">>>>>>>"]
+            .iter()
+            .any(|m| line.starts_with(m));
+
+        lines.push(if marker {
+            Line::styled(
+                line.to_string(),
+                Style::default()
+                    .fg(theme.conflict)
+                    .add_modifier(Modifier::BOLD),
+            )
+        } else {
+            Line::raw(line.to_string())
+        });
+        numbers.push((None, Some(i as u32 + 1)));
+    }
+    add_gutter(&mut lines, &numbers, theme);
+
+    // Resolving means editing the file and staging all of it, not picking hunks
+    Ok(FileDiff {
+        lines,
+        numbers,
+        ..Default::default()
+    })
+}
+
+/// A file kept in Git LFS: the object each side points to, then the diff of
+/// their contents where both have been downloaded. The contents are shown,
+/// not staged from, so the file is staged whole.
+fn lfs_lines(
+    repo: &Repository,
+    entry: &FileEntry,
+    sides: [Option<Vec<u8>>; 2],
+    limit: usize,
+    theme: &Theme,
+) -> Result<FileDiff> {
+    let mut lines = vec![Line::styled(
+        format!("LFS object: {}", entry.path.display()),
+        Style::default().fg(theme.file_header),
+    )];
+    let mut missing = Vec::new();
+    let mut contents = Vec::new();
+    let [old, new] = sides;
+    for (side, prefix, color) in [(old, '-', theme.deletion), (new, '+', theme.addition)] {
+        let Some(content) = side else {
+            // New or deleted, so there's nothing on this side
+            contents.push(Some(Vec::new()));
+            continue;
+        };
+        let style = Style::default().fg(color);
+        let Some(pointer) = Pointer::parse(&content) else {
+            lines.push(Line::styled(
+                format!(
+                    "{} {}, not stored in LFS",
+                    prefix,
+                    human_size(content.len() as u64)
+                ),
+                style,
+            ));
+            contents.push(Some(content));
+            continue;
+        };
+
+        let mut line = Line::styled(
+            format!(
+                "{} sha256:{}…  {}",
+                prefix,
+                &pointer.oid[..12],
+                human_size(pointer.size)
+            ),
+            style,
+        );
+        if pointer.fetched(repo) {
+            contents.push(
+                (pointer.size <= LFS_DIFF_LIMIT)
+                    .then(|| fs::read(pointer.object_path(repo)).ok())
+                    .flatten(),
+            );
+        } else {
+            line.spans
+                .push(Span::styled("  not downloaded", Style::default().dim()));
+            contents.push(None);
+            missing.push(pointer);
+        }
+        lines.push(line);
+    }
+
+    let mut numbers = vec![(None, None); lines.len()];
+    if let [Some(old), Some(new)] = &contents[..] {
+        lines.push(Line::default());
+        numbers.push((None, None));
+
+        let path = entry.path.to_str();
+        let mut patch =
+            Patch::from_buffers(old, path.map(Path::new), new, path.map(Path::new), None)?;
+        let diff = Diff::from_buffer(&patch.to_buf()?)?;
+        let content = render_diff_upto(&diff, limit, false, theme)?;
+        // The header lines get a gutter too, blank, when the content has one
+        if content
+            .numbers
+            .iter()
+            .any(|&(old, new)| old.is_some() || new.is_some())
+            && let Some(gutter) = content.lines.first().and_then(|l| l.spans.first())
+        {
+            let blank = " ".repeat(gutter.content.chars().count() - 2) + "│ ";
+            for line in &mut lines {
+                line.spans.insert(
+                    0,
+                    Span::styled(blank.clone(), Style::default().fg(theme.gutter)),
+                );
+            }
+        }
+        lines.extend(content.lines);
+        numbers.extend(content.numbers);
+    }
+
+    Ok(FileDiff {
+        lines,
+        numbers,
+        lfs: missing,
+        ..Default::default()
+    })
+}
+
+/// A file that only exists on one side, every line an addition or deletion.
+/// `content` is all of it or just its start, out of `size` bytes; at most
+/// `limit` lines are shown, and whatever is left over is counted in `more`.
+fn whole_file_lines(
+    header: String,
+    path: &Path,
+    content: &[u8],
+    size: u64,
+    limit: usize,
+    prefix: char,
+    theme: &Theme,
+) -> FileDiff {
+    let mut lines: Vec<Line<'static>> = Vec::new();
+
+    lines.push(Line::from(Span::styled(
+        header,
+        Style::default().fg(theme.file_header),
+    )));
+
+    if is_binary(content) {
+        lines.push(binary_line(content, size));
+        return FileDiff {
+            lines,
+            ..Default::default()
+        };
+    }
+
+    // Whole lines only: a preview cut off by the byte cap drops its last, partial one
+    let mut shown = content;
+    if (content.len() as u64) < size
+        && let Some(end) = content.iter().rposition(|&b| b == b'\n')
+    {
+        shown = &content[..=end];
+    }
+    if let Some((end, _)) = shown
+        .iter()
+        .enumerate()
+        .filter(|(_, b)| **b == b'\n')
+        .nth(limit.saturating_sub(1))
+    {
+        shown = &shown[..=end];
+    }
+    let more = size.saturating_sub(shown.len() as u64);
+
+    let tab_width = theme.whitespace.tab_width(path);
+    let (color, trailing) = match prefix {
+        '+' => (theme.addition, whitespace::trailing_style(theme)),
+        _ => (theme.deletion, None),
+    };
+    let mut numbers = vec![(None, None)];
+    for (i, line) in String::from_utf8_lossy(shown).lines().enumerate() {
+        let style = Style::default().fg(color);
+        // Tab stops count from after the prefix
+        let mut line = Line::from(Span::styled(line.to_string(), style));
+        whitespace::show_line(&mut line, tab_width, theme.whitespace.markers, trailing);
+        line.spans
+            .insert(0, Span::styled(prefix.to_string(), style));
+        lines.push(line);
+
+        let lineno = Some(i as u32 + 1);
+        numbers.push(if prefix == '-' {
+            (lineno, None)
+        } else {
+            (None, lineno)
+        });
+    }
+    add_gutter(&mut lines, &numbers, theme);
+
+    // The whole file is the one hunk git would produce for it
+    let mut hunks = Vec::new();
+    if lines.len() > 1 {
+        hunks.push(1..lines.len());
+    }
+
+    // A file that exists on one side only is staged whole
+    FileDiff {
+        lines,
+        hunks,
+        numbers,
+        more: (more > 0).then_some(More::Bytes(more)),
+        ..Default::default()
+    }
+}
+
+/// `Binary file — 4.0 MiB, PNG image` for a binary file of `size` bytes that
+/// starts with `content`.
+fn binary_line(content: &[u8], size: u64) -> Line<'static> {
+    match file_kind(content) {
+        Some(kind) => Line::from(format!("Binary file — {}, {}", human_size(size), kind)),
+        None => Line::from(format!("Binary file — {}", human_size(size))),
+    }
+}
+
+/// What kind of binary file `content` starts like, from its magic number.
+fn file_kind(content: &[u8]) -> Option<&'static str> {
+    #[rustfmt::skip]
+    const MAGIC: &[(&[u8], &str)] = &[
+        (b"\x89PNG", "PNG image"),
+        (b"\xFF\xD8\xFF", "JPEG image"),
+        (b"GIF8", "GIF image"),
+        (b"%PDF", "PDF document"),
+        (b"PK\x03\x04", "zip archive"),
+        (b"\x1F\x8B", "gzip archive"),
+        (b"BZh", "bzip2 archive"),
+        (b"\xFD7zXZ\x00", "xz archive"),
+        (b"7z\xBC\xAF\x27\x1C", "7-zip archive"),
+        (b"\x7FELF", "ELF executable"),
+        (b"MZ", "Windows executable"),
+        (b"\xCA\xFE\xBA\xBE", "Java class or Mach-O binary"),
+        (b"\x00asm", "WebAssembly module"),
+        (b"SQLite format 3\x00", "SQLite database"),
+        (b"OggS", "Ogg media"),
+        (b"fLaC", "FLAC audio"),
+        (b"ID3", "MP3 audio"),
+    ];
+
+    // RIFF wraps several formats; the real one follows the length
+    if content.starts_with(b"RIFF") && content.len() >= 12 {
+        return match &content[8..12] {
+            b"WEBP" => Some("WebP image"),
+            b"WAVE" => Some("WAV audio"),
+            b"AVI " => Some("AVI video"),
+            _ => None,
+        };
+    }
+    MAGIC
+        .iter()
+        .find(|(magic, _)| content.starts_with(magic))
+        .map(|(_, kind)| *kind)
+}
+
+/// `512 bytes`, `12.3 KiB`, `4.0 MiB`.
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} bytes", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Same heuristic git uses: a NUL byte in the first 8000 bytes means binary.
+fn is_binary(content: &[u8]) -> bool {
+    content.iter().take(8000).any(|&b| b == 0)
+}
+
+/// Last known content of a deleted file: the HEAD blob for a staged deletion,
+/// the index blob for one that is only gone from the worktree. At most `cap`
+/// bytes of it are read, and its whole size comes with them.
+fn deleted_content(repo: &Repository, entry: &FileEntry, cap: usize) -> Result<(Vec<u8>, u64)> {
+    let path = &entry.path;
+
+    let id = match entry.section {
+        Section::Index => repo.head()?.peel_to_tree()?.get_path(path)?.id(),
+        Section::Worktree | Section::Conflicted => {
+            repo.index()?
+                .get_path(path, 0)
+                .ok_or_else(|| anyhow!("cannot show diff: {} not found in index", path.display()))?
+                .id
+        }
+    };
+
+    // The header has the size without loading the blob
+    let odb = repo.odb()?;
+    let (size, _) = odb.read_header(id)?;
+    if size > cap
+        && let Ok((reader, ..)) = odb.reader(id)
+    {
+        // Loose objects stream, so a huge one is never read whole
+        let mut content = Vec::new();
+        reader.take(cap as u64).read_to_end(&mut content)?;
+        return Ok((content, size as u64));
+    }
+
+    // Packed ones only come whole; at least only the preview is copied
+    let blob = repo.find_blob(id)?;
+    let content = blob.content();
+    Ok((content[..content.len().min(cap)].to_vec(), size as u64))
+}
+
+pub fn render_diff(diff: &Diff, theme: &Theme) -> Result<FileDiff> {
+    render_diff_upto(diff, usize::MAX, false, theme)
+}
+
+/// A `git diff --stat` summary of `diff`: a row with a histogram bar per
+/// file, the totals, then a blank line to set it off from the patch.
+pub fn stat_lines(diff: &Diff, theme: &Theme) -> Result<Vec<Line<'static>>> {
+    let summary = diff
+        .stats()?
+        .to_buf(DiffStatsFormat::FULL | DiffStatsFormat::INCLUDE_SUMMARY, 80)?;
+    let mut lines: Vec<Line<'static>> = summary
+        .as_str()
+        .unwrap_or("")
+        .lines()
+        .map(|line| stat_line(line, theme))
+        .collect();
+    lines.push(Line::from(""));
+    Ok(lines)
+}
+
+/// ` src/main.rs | 12 +++++---`, with the bar in the add/delete colors.
+fn stat_line(line: &str, theme: &Theme) -> Line<'static> {
+    let Some((name, bar)) = line.split_once('|') else {
+        return Line::raw(line.to_string());
+    };
+
+    let mut spans = vec![Span::raw(format!("{}|", name))];
+    let count = bar.trim_end_matches(['+', '-']);
+    spans.push(Span::raw(count.to_string()));
+    let marks = &bar[count.len()..];
+    let plus = marks.find('-').unwrap_or(marks.len());
+    spans.push(Span::styled(
+        marks[..plus].to_string(),
+        Style::default().fg(theme.addition),
+    ));
+    spans.push(Span::styled(
+        marks[plus..].to_string(),
+        Style::default().fg(theme.deletion),
+    ));
+
+    Line::from(spans)
+}
+
+/// [`render_diff`], cut off after `limit` lines, and with [`stat_lines`]
+/// on top when `stat` is set. The hunk the cut goes through isn't one any
+/// more, since staging it would take lines nobody saw.
+fn render_diff_upto(diff: &Diff, limit: usize, stat: bool, theme: &Theme) -> Result<FileDiff> {
+    let mut lines: Vec<Line<'static>> = if stat && diff.deltas().len() > 0 {
+        stat_lines(diff, theme)?
+    } else {
+        Vec::new()
+    };
+    let mut hunks: Vec<Range<usize>> = Vec::new();
+    let mut hunk_start: Option<usize> = None;
+    // The summary is header lines, without numbers
+    let mut numbers: Vec<(Option<u32>, Option<u32>)> = vec![(None, None); lines.len()];
+    let mut changes: Vec<usize> = Vec::new();
+    let mut files: Vec<(usize, PathBuf)> = Vec::new();
+    let mut origins: Vec<char> = vec!['F'; lines.len()];
+    let mut left_out = 0;
+    // Set per file from its extension; `None` keeps the plain colors
+    #[cfg(feature = "syntax")]
+    let mut highlighter: Option<Highlighter> = None;
+
+    diff.print(DiffFormat::Patch, |delta, _hunk, line| {
+        // Past the limit lines are only counted, not formatted
+        if lines.len() >= limit {
+            if let Some(start) = hunk_start.take() {
+                if matches!(line.origin(), 'H' | 'F') {
+                    // It ended right at the cut, whole
+                    hunks.push(start..lines.len());
+                } else {
+                    changes.retain(|&c| c < start);
+                }
+            }
+            left_out += match line.origin() {
+                'F' => line.content().iter().filter(|&&b| b == b'\n').count(),
+                _ => 1,
+            };
+            return true;
+        }
+
+        let content = String::from_utf8_lossy(line.content()).into_owned();
+
+        // A hunk runs until the next hunk or file header
+        if matches!(line.origin(), 'H' | 'F')
+            && let Some(start) = hunk_start.take()
+        {
+            hunks.push(start..lines.len());
+        }
+        if line.origin() == 'H' {
+            hunk_start = Some(lines.len());
+        }
+
+        match line.origin() {
+            'B' => lines.push(Line::from(format!(
+                "Binary file — {} → {} bytes",
+                delta.old_file().size(),
+                delta.new_file().size()
+            ))),
+            origin @ ('+' | '-' | ' ') => {
+                let text = content.trim_end_matches('\n');
+                if origin != ' ' {
+                    changes.push(lines.len());
+                }
+
+                #[cfg(feature = "syntax")]
+                let code = match highlighter.as_mut() {
+                    Some(highlighter) => highlighter.line(origin, text, theme),
+                    None => plain_line(origin, text, theme),
+                };
+                #[cfg(not(feature = "syntax"))]
+                let code = plain_line(origin, text, theme);
+
+                lines.push(code);
+            }
+            // File headers (diff --git, index, ---, +++) arrive as one chunk
+            'F' => {
+                if let Some(path) = delta.new_file().path() {
+                    files.push((lines.len(), path.to_path_buf()));
+                }
+
+                #[cfg(feature = "syntax")]
+                {
+                    highlighter = delta
+                        .new_file()
+                        .path()
+                        .and_then(|path| Highlighter::for_path(path, theme));
+                }
+
+                lines.extend(
+                    content.lines().map(|l| {
+                        Line::styled(l.to_string(), Style::default().fg(theme.file_header))
+                    }),
+                );
+            }
+            _ => lines.push(Line::raw(content.trim_end_matches('\n').to_string())),
+        }
+
+        // `+` lines only carry a new number, `-` only an old one, context both
+        let line_numbers = match line.origin() {
+            '+' | '-' | ' ' => (line.old_lineno(), line.new_lineno()),
+            _ => (None, None),
         };
+        numbers.resize(lines.len(), line_numbers);
+        origins.resize(lines.len(), line.origin());
 
-        lines.push(Line::from(span));
         true
     })?;
 
-    if lines.is_empty() {
-        lines.push(Line::from("No changes"));
+    if let Some(start) = hunk_start {
+        hunks.push(start..lines.len());
     }
 
-    Ok(lines)
+    words::emphasize(&mut lines, &origins, theme);
+    whitespace::show(&mut lines, &origins, &files, theme);
+    let split = split_diff(&lines, &origins, &numbers, theme);
+    add_gutter(&mut lines, &numbers, theme);
+
+    Ok(FileDiff {
+        lines,
+        hunks,
+        changes,
+        files,
+        split,
+        numbers,
+        more: (left_out > 0).then_some(More::Lines(left_out)),
+        ..Default::default()
+    })
+}
+
+/// Lays `lines` out side by side, each column with a gutter of its own line
+/// numbers. Context and header lines show in both columns.
+fn split_diff(
+    lines: &[Line<'static>],
+    origins: &[char],
+    numbers: &[(Option<u32>, Option<u32>)],
+    theme: &Theme,
+) -> Option<SplitDiff> {
+    let largest = numbers
+        .iter()
+        .flat_map(|&(old, new)| [old, new])
+        .flatten()
+        .max()?;
+    let width = largest.to_string().len();
+
+    let numbered = |n: Option<u32>, line: &Line<'static>| {
+        let gutter = match n {
+            Some(n) => format!("{:>width$} │ ", n),
+            None => format!("{} │ ", " ".repeat(width)),
+        };
+        let mut line = line.clone();
+        line.spans
+            .insert(0, Span::styled(gutter, Style::default().fg(theme.gutter)));
+        line
+    };
+
+    let mut rows = Vec::new();
+    let mut positions = vec![(0, Column::Both); lines.len()];
+    let mut i = 0;
+    while i < lines.len() {
+        if !matches!(origins[i], '+' | '-') {
+            positions[i] = (rows.len(), Column::Both);
+            rows.push((
+                numbered(numbers[i].0, &lines[i]),
+                numbered(numbers[i].1, &lines[i]),
+            ));
+            i += 1;
+            continue;
+        }
+
+        // git prints a change as its deletions, then the additions replacing them
+        let run = |from: usize, origin: char| {
+            (from..lines.len())
+                .take_while(|&j| origins[j] == origin)
+                .collect::<Vec<_>>()
+        };
+        let deleted = run(i, '-');
+        let added = run(i + deleted.len(), '+');
+
+        for k in 0..deleted.len().max(added.len()) {
+            let old = match deleted.get(k) {
+                Some(&j) => {
+                    positions[j] = (rows.len(), Column::Old);
+                    numbered(numbers[j].0, &lines[j])
+                }
+                None => numbered(None, &Line::default()),
+            };
+            let new = match added.get(k) {
+                Some(&j) => {
+                    positions[j] = (rows.len(), Column::New);
+                    numbered(numbers[j].1, &lines[j])
+                }
+                None => numbered(None, &Line::default()),
+            };
+            rows.push((old, new));
+        }
+        i += deleted.len() + added.len();
+    }
+
+    Some(SplitDiff { rows, positions })
+}
+
+/// A diff line in the add/delete colors, for files without a known grammar.
+/// Without color, additions are bold and deletions dim.
+fn plain_line(origin: char, text: &str, theme: &Theme) -> Line<'static> {
+    let (style, modifier) = match origin {
+        '+' => (Style::default().fg(theme.addition), Modifier::BOLD),
+        '-' => (Style::default().fg(theme.deletion), Modifier::DIM),
+        _ => return Line::raw(text.to_string()),
+    };
+
+    if theme.monochrome {
+        Line::styled(text.to_string(), style.add_modifier(modifier))
+    } else {
+        Line::styled(text.to_string(), style)
+    }
+}
+
+/// Prepends a dim `old new │` gutter, wide enough for the largest number, to
+/// every line.
+fn add_gutter(lines: &mut [Line<'static>], numbers: &[(Option<u32>, Option<u32>)], theme: &Theme) {
+    let largest = numbers
+        .iter()
+        .flat_map(|&(old, new)| [old, new])
+        .flatten()
+        .max();
+
+    let Some(largest) = largest else {
+        return;
+    };
+    let width = largest.to_string().len();
+
+    let format = |n: Option<u32>| match n {
+        Some(n) => format!("{:>width$}", n),
+        None => " ".repeat(width),
+    };
+
+    for (line, &(old, new)) in lines.iter_mut().zip(numbers) {
+        let gutter = format!("{} {} │ ", format(old), format(new));
+        line.spans
+            .insert(0, Span::styled(gutter, Style::default().fg(theme.gutter)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helper_git_core::testing::{commit, repo, write};
+    use std::fs;
+
+    fn entry(path: &str, section: Section) -> FileEntry {
+        FileEntry {
+            path: PathBuf::from(path),
+            section,
+            rename: None,
+        }
+    }
+
+    #[test]
+    fn deleted_file_preview_stops_at_the_cap() {
+        let (dir, repo) = repo();
+        let line = format!("{}\n", "x".repeat(99));
+        write(&repo, "big.txt", &line.repeat(10_000));
+        commit(&repo, "big");
+        fs::remove_file(dir.path().join("big.txt")).unwrap();
+
+        let entry = entry("big.txt", Section::Worktree);
+        let (content, size) = deleted_content(&repo, &entry, 1000).unwrap();
+        assert_eq!(content.len(), 1000);
+        assert_eq!(size, 1_000_000);
+
+        let shown = show_file_diff(&repo, &entry, DiffSettings::default(), 5, &Theme::dark());
+        let shown = shown.unwrap();
+        // The header, then the five lines asked for
+        assert_eq!(shown.lines.len(), 6);
+        assert!(shown.more == Some(More::Bytes(1_000_000 - 5 * 100)));
+    }
 }
@@ -0,0 +1,358 @@
+use anyhow::{Result, anyhow, bail};
+use git2::{
+    Commit, FileMode, Oid, Repository, Signature, Status,
+    build::{CheckoutBuilder, TreeUpdateBuilder},
+};
+use helper_git_core::status::FileEntry;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Confirmation text for discarding `entry`, spelling out when staged changes
+/// go too.
+pub fn prompt(repo: &Repository, entry: &FileEntry) -> Result<String> {
+    if entry.is_directory() {
+        bail!(
+            "{} is an ignored directory; delete it by hand",
+            entry.path.display()
+        );
+    }
+    let status = repo.status_file(&entry.path)?;
+
+    let prompt = if entry.rename.is_some() {
+        bail!(
+            "{} is a rename; discard isn't supported for renames yet",
+            entry.path.display()
+        );
+    } else if status.contains(Status::CONFLICTED) {
+        bail!(
+            "{} has a merge conflict; resolve it or abort the merge instead",
+            entry.path.display()
+        );
+    } else if status.contains(Status::WT_NEW) {
+        format!("Delete untracked file {}? (y/n)", entry.path.display())
+    } else if status.contains(Status::IGNORED) {
+        format!("Delete ignored file {}? (y/n)", entry.path.display())
+    } else if status.contains(Status::INDEX_NEW) {
+        bail!(
+            "{} is newly added; unstage it before discarding",
+            entry.path.display()
+        );
+    } else if status.intersects(
+        Status::INDEX_MODIFIED
+            | Status::INDEX_DELETED
+            | Status::INDEX_RENAMED
+            | Status::INDEX_TYPECHANGE,
+    ) {
+        format!(
+            "Discard ALL changes to {}, including staged ones? (y/n)",
+            entry.path.display()
+        )
+    } else {
+        format!("Discard changes to {}? (y/n)", entry.path.display())
+    };
+
+    Ok(prompt)
+}
+
+/// Confirmation text for discarding several marked files at once; fails
+/// like [`prompt`] on the first one that can't be discarded.
+pub fn prompt_many(repo: &Repository, entries: &[FileEntry]) -> Result<String> {
+    let mut untracked = 0;
+    for entry in entries {
+        prompt(repo, entry)?;
+        if repo
+            .status_file(&entry.path)?
+            .intersects(Status::WT_NEW | Status::IGNORED)
+        {
+            untracked += 1;
+        }
+    }
+
+    Ok(match untracked {
+        0 => format!("Discard changes to {} files? (y/n)", entries.len()),
+        _ => format!(
+            "Discard changes to {} files, deleting {} untracked? (y/n)",
+            entries.len(),
+            untracked
+        ),
+    })
+}
+
+/// What came of [`discard`].
+pub enum Discarded {
+    /// Thrown away after saving it as this stash commit, with what to tell
+    /// the user
+    Saved(String, Oid),
+    /// Nothing was thrown away, since it couldn't be saved first
+    NotSaved(anyhow::Error),
+}
+
+/// Throws away local changes to `entries`: untracked and ignored files are
+/// deleted, tracked ones are checked out from HEAD (index and worktree).
+/// What is thrown away is saved as one stash first, so a slip can be undone
+/// by applying it, and when that fails nothing is touched; only
+/// [`discard_unsaved`] goes ahead without the stash.
+pub fn discard(repo: &Repository, entries: &[FileEntry]) -> Result<Discarded> {
+    let files = statuses(repo, entries)?;
+    let what = describe(entries);
+    let stash = match backup(repo, &files, &format!("discarded {}", what)) {
+        Ok(stash) => stash,
+        Err(e) => return Ok(Discarded::NotSaved(e)),
+    };
+
+    revert(repo, &files)?;
+    Ok(Discarded::Saved(
+        format!("Discarded {} · saved to the stash", what),
+        stash,
+    ))
+}
+
+/// [`discard`] with nothing saved to undo it from, once the user has said
+/// to go ahead without the stash.
+pub fn discard_unsaved(repo: &Repository, entries: &[FileEntry]) -> Result<String> {
+    let files = statuses(repo, entries)?;
+    revert(repo, &files)?;
+    Ok(format!("Discarded {} · not saved", describe(entries)))
+}
+
+/// Like `git stash push --include-untracked -- <paths>`: saves `entries` as a
+/// stash entry and then reverts them. Unlike [`discard`], nothing is reverted
+/// when the stash can't be written. Returns the stash commit too.
+pub fn stash(repo: &Repository, entries: &[FileEntry]) -> Result<(String, Oid)> {
+    for entry in entries {
+        prompt(repo, entry)?;
+    }
+    let files = statuses(repo, entries)?;
+    let what = describe(entries);
+
+    let stash = backup(repo, &files, &format!("stashed {}", what))?;
+    revert(repo, &files)?;
+
+    Ok((format!("Stashed {}", what), stash))
+}
+
+/// Puts `paths` back as the stash commit `stash`, made by [`discard`] or
+/// [`stash`], saved them, index and worktree, and drops the stash entry at
+/// `at`. Unlike `git stash pop`, what else is staged is left alone.
+pub fn restore(repo: &Repository, stash: Oid, at: usize, paths: &[PathBuf]) -> Result<()> {
+    let stash = repo.find_commit(stash)?;
+    let worktree = stash.tree()?;
+    let untracked = stash.parent(2).and_then(|c| c.tree()).ok();
+
+    // The index side first, as `git reset <index commit> -- <paths>` sets it
+    let index_commit = stash.parent(1)?;
+    repo.reset_default(Some(index_commit.as_object()), paths)?;
+
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("repository has no working directory"))?;
+    for path in paths {
+        let tree = [Some(&worktree), untracked.as_ref()]
+            .into_iter()
+            .flatten()
+            .find(|tree| tree.get_path(path).is_ok());
+        match tree {
+            Some(tree) => {
+                let mut checkout = CheckoutBuilder::new();
+                checkout.force().update_index(false).path(path);
+                repo.checkout_tree(tree.as_object(), Some(&mut checkout))?;
+            }
+            // It had been deleted from the worktree
+            None => match fs::remove_file(workdir.join(path)) {
+                Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            },
+        }
+    }
+
+    // Dropping needs the repository mutably
+    Repository::open(repo.path())?.stash_drop(at)?;
+    Ok(())
+}
+
+fn statuses<'e>(repo: &Repository, entries: &'e [FileEntry]) -> Result<Vec<(&'e Path, Status)>> {
+    entries
+        .iter()
+        .map(|entry| Ok((entry.path.as_path(), repo.status_file(&entry.path)?)))
+        .collect()
+}
+
+/// `a.txt` for one file, `3 files` for more.
+pub fn describe(entries: &[FileEntry]) -> String {
+    match entries {
+        [entry] => entry.path.display().to_string(),
+        _ => format!("{} files", entries.len()),
+    }
+}
+
+fn revert(repo: &Repository, files: &[(&Path, Status)]) -> Result<()> {
+    for &(path, status) in files {
+        if status.intersects(Status::WT_NEW | Status::IGNORED) {
+            let workdir = repo
+                .workdir()
+                .ok_or_else(|| anyhow!("cannot discard: repository has no working directory"))?;
+            fs::remove_file(workdir.join(path))?;
+        } else {
+            let mut checkout = CheckoutBuilder::new();
+            checkout.force().path(path);
+            repo.checkout_head(Some(&mut checkout))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Records the current state of `files` as a stash entry shaped like the ones
+/// `git stash push --include-untracked -- <paths>` makes, without touching
+/// the worktree, and returns its commit. libgit2's own path-limited stash
+/// resets far more than the given files, so the commits are put together by
+/// hand.
+fn backup(repo: &Repository, files: &[(&Path, Status)], what: &str) -> Result<Oid> {
+    let head = repo.head()?;
+    let branch = head.shorthand().unwrap_or("HEAD").to_string();
+    let head = head.peel_to_commit()?;
+    // Discarding shouldn't hinge on user.name being set
+    let signature = repo
+        .signature()
+        .or_else(|_| Signature::now("helper-git", "helper-git@localhost"))?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("repository has no working directory"))?;
+
+    // Like git's own path-limited stash, both trees start from the whole index
+    let mut index = repo.index()?;
+    let index_tree = repo.find_tree(index.write_tree()?)?;
+    let summary = format!("{:.7} {}", head.id(), head.summary().unwrap_or(""));
+    let index_commit = repo.commit(
+        None,
+        &signature,
+        &signature,
+        &format!("index on {}: {}", branch, summary),
+        &index_tree,
+        &[&head],
+    )?;
+    let mut parents = vec![head.clone(), repo.find_commit(index_commit)?];
+
+    // A stash keeps untracked files in a third parent of their own
+    let mut worktree = TreeUpdateBuilder::new();
+    let mut untracked = TreeUpdateBuilder::new();
+    let mut any_untracked = false;
+    for &(path, status) in files {
+        // The file as it is now; `None` when it's gone from the worktree
+        let full = workdir.join(path);
+        let current = match fs::symlink_metadata(&full) {
+            Ok(meta) if meta.file_type().is_symlink() => {
+                let target = fs::read_link(&full)?;
+                Some((
+                    repo.blob(target.to_string_lossy().as_bytes())?,
+                    FileMode::Link,
+                ))
+            }
+            Ok(_) => {
+                let mode = index
+                    .get_path(path, 0)
+                    .map_or(FileMode::Blob, |e| file_mode(e.mode));
+                Some((repo.blob_path(&full)?, mode))
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+
+        match current {
+            Some((blob, mode)) if status.intersects(Status::WT_NEW | Status::IGNORED) => {
+                untracked.upsert(path, blob, mode);
+                any_untracked = true;
+            }
+            Some((blob, mode)) => {
+                worktree.upsert(path, blob, mode);
+            }
+            None => {
+                worktree.remove(path);
+            }
+        }
+    }
+
+    if any_untracked {
+        let empty = repo.find_tree(repo.treebuilder(None)?.write()?)?;
+        let tree = untracked.create_updated(repo, &empty)?;
+        let untracked = repo.commit(
+            None,
+            &signature,
+            &signature,
+            &format!("untracked files on {}: {}", branch, summary),
+            &repo.find_tree(tree)?,
+            &[],
+        )?;
+        parents.push(repo.find_commit(untracked)?);
+    }
+    let tree = repo.find_tree(worktree.create_updated(repo, &index_tree)?)?;
+
+    let message = format!("On {}: {}", branch, what);
+    let parents: Vec<&Commit> = parents.iter().collect();
+    let stash = repo.commit(None, &signature, &signature, &message, &tree, &parents)?;
+
+    // Stash entries are the reflog of refs/stash, which isn't always kept
+    let before = repo.reflog("refs/stash").map_or(0, |log| log.len());
+    repo.reference("refs/stash", stash, true, &message)?;
+    let mut reflog = repo.reflog("refs/stash")?;
+    if reflog.len() == before {
+        reflog.append(stash, &signature, Some(&message))?;
+        reflog.write()?;
+    }
+
+    Ok(stash)
+}
+
+fn file_mode(mode: u32) -> FileMode {
+    match mode {
+        0o100755 => FileMode::BlobExecutable,
+        0o120000 => FileMode::Link,
+        0o160000 => FileMode::Commit,
+        _ => FileMode::Blob,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helper_git_core::status::Section;
+    use helper_git_core::testing::{commit, repo, write};
+
+    fn entry(path: &str) -> FileEntry {
+        FileEntry {
+            path: PathBuf::from(path),
+            section: Section::Worktree,
+            rename: None,
+        }
+    }
+
+    #[test]
+    fn discard_is_saved_to_the_stash_first() {
+        let (dir, repo) = repo();
+        write(&repo, "a.txt", "a\n");
+        commit(&repo, "first");
+        write(&repo, "a.txt", "changed\n");
+
+        let Discarded::Saved(_, stash) = discard(&repo, &[entry("a.txt")]).unwrap() else {
+            panic!("not saved");
+        };
+        assert_eq!(fs::read_to_string(dir.path().join("a.txt")).unwrap(), "a\n");
+        assert_eq!(repo.refname_to_id("refs/stash").unwrap(), stash);
+    }
+
+    #[test]
+    fn nothing_is_discarded_when_it_cannot_be_saved() {
+        // No commit yet, so no stash can be made
+        let (dir, repo) = repo();
+        write(&repo, "new.txt", "keep me\n");
+        let path = dir.path().join("new.txt");
+
+        let discarded = discard(&repo, &[entry("new.txt")]).unwrap();
+        assert!(matches!(discarded, Discarded::NotSaved(_)));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "keep me\n");
+
+        discard_unsaved(&repo, &[entry("new.txt")]).unwrap();
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,72 @@
+use anyhow::{Context, Result, anyhow, bail};
+use git2::Repository;
+use helper_git_core::status::FileEntry;
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Absolute path of `entry`, or an error if there is nothing on disk to edit.
+pub fn path(repo: &Repository, entry: &FileEntry) -> Result<PathBuf> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("cannot edit: repository has no working directory"))?;
+    let path = workdir.join(&entry.path);
+
+    if !path.is_file() {
+        bail!("cannot edit {}: not on disk", entry.path.display());
+    }
+
+    Ok(path)
+}
+
+/// Runs `$EDITOR` on `path` and waits for it, with the cursor on `line` when
+/// given. `$EDITOR` may carry arguments (`code --wait`), so it is split on
+/// whitespace like git does for simple values.
+pub fn open(path: &Path, line: Option<u32>) -> Result<()> {
+    let editor = env::var("EDITOR")
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| String::from(default_editor()));
+
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or(default_editor());
+
+    let status = Command::new(program)
+        .args(words)
+        .args(line_args(program, path, line))
+        .status()
+        .with_context(|| format!("cannot run editor \"{}\"", editor))?;
+
+    if !status.success() {
+        bail!("editor \"{}\" exited with {}", editor, status);
+    }
+
+    Ok(())
+}
+
+/// The path, and how to tell `program` which line to start on. Most editors
+/// take vi's `+N`; the GUI ones that would open a file named `+N` want
+/// `path:N` instead.
+fn line_args(program: &str, path: &Path, line: Option<u32>) -> Vec<OsString> {
+    let Some(line) = line else {
+        return vec![path.into()];
+    };
+
+    let name = Path::new(program)
+        .file_stem()
+        .and_then(|n| n.to_str())
+        .unwrap_or(program);
+    let mut at_line = path.as_os_str().to_owned();
+    at_line.push(format!(":{}", line));
+
+    match name {
+        "code" | "code-insiders" | "codium" | "cursor" => vec!["--goto".into(), at_line],
+        "subl" | "zed" => vec![at_line],
+        _ => vec![format!("+{}", line).into(), path.into()],
+    }
+}
+
+fn default_editor() -> &'static str {
+    if cfg!(windows) { "notepad" } else { "vi" }
+}
@@ -0,0 +1,87 @@
+use crate::theme::Theme;
+use ratatui::{
+    style::{Color, Style},
+    text::{Line, Span},
+};
+use std::path::Path;
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::SyntaxSet;
+
+// Loading the bundled grammars takes a while, so it happens once, on first use
+fn syntaxes() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn themes() -> &'static ThemeSet {
+    static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+    THEMES.get_or_init(ThemeSet::load_defaults)
+}
+
+/// Highlights the lines of one file's diff. The old and new side each keep
+/// their own parse state, since a `-` line only continues the old file and a
+/// `+` line only the new one.
+pub struct Highlighter {
+    old: HighlightLines<'static>,
+    new: HighlightLines<'static>,
+}
+
+impl Highlighter {
+    /// `None` when the extension has no known grammar, or highlighting is
+    /// turned off, so it stays plain.
+    pub fn for_path(path: &Path, theme: &Theme) -> Option<Self> {
+        let colors = themes().themes.get(theme.syntax?)?;
+        let extension = path.extension()?.to_str()?;
+        let syntax = syntaxes().find_syntax_by_extension(extension)?;
+        // Plain text would only trade the add/delete colors for a flat one
+        if syntax.name == syntaxes().find_syntax_plain_text().name {
+            return None;
+        }
+
+        Some(Highlighter {
+            old: HighlightLines::new(syntax, colors),
+            new: HighlightLines::new(syntax, colors),
+        })
+    }
+
+    /// `text` split into colored spans, on a tinted background for `+` and `-`
+    /// lines so additions and deletions still stand out.
+    pub fn line(&mut self, origin: char, text: &str, theme: &Theme) -> Line<'static> {
+        let background = match origin {
+            '+' => Some(theme.addition_bg),
+            '-' => Some(theme.deletion_bg),
+            _ => None,
+        };
+
+        // The grammars expect each line to end in a newline
+        let input = format!("{}\n", text);
+        let regions = match origin {
+            '+' => self.new.highlight_line(&input, syntaxes()),
+            '-' => self.old.highlight_line(&input, syntaxes()),
+            _ => {
+                // Context lines exist on both sides
+                let _ = self.old.highlight_line(&input, syntaxes());
+                self.new.highlight_line(&input, syntaxes())
+            }
+        };
+
+        let spans = match regions {
+            Ok(regions) => regions
+                .into_iter()
+                .map(|(style, piece)| {
+                    let fg = style.foreground;
+                    let mut style = Style::default().fg(theme.fit(Color::Rgb(fg.r, fg.g, fg.b)));
+                    if let Some(bg) = background {
+                        style = style.bg(bg);
+                    }
+                    Span::styled(piece.trim_end_matches('\n').to_string(), style)
+                })
+                .collect(),
+            Err(_) => vec![Span::raw(text.to_string())],
+        };
+
+        Line::from(spans)
+    }
+}
@@ -0,0 +1,294 @@
+use super::commit::CommitMode;
+use crate::theme::Theme;
+use anyhow::{Context, Result, anyhow};
+use crossterm::event::{KeyCode, KeyEvent};
+use git2::Repository;
+use ratatui::{
+    layout::Flex,
+    prelude::*,
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// The hooks `git commit` runs before committing, in order. libgit2 runs
+/// none of them, so they're run here.
+const HOOKS: &[&str] = &["pre-commit", "commit-msg"];
+
+/// How the hooks came out.
+pub enum Outcome {
+    /// Every hook passed; the message as `commit-msg` left it
+    Passed(String),
+    /// This hook failed, with its exit code when it had one
+    Failed(&'static str, Option<i32>),
+}
+
+/// The hooks in [`HOOKS`] that are set up: executables in `core.hooksPath`,
+/// or `.git/hooks` when that's unset.
+pub fn installed(repo: &Repository) -> Vec<(&'static str, PathBuf)> {
+    let dir = match repo.config().and_then(|c| c.get_path("core.hookspath")) {
+        // A relative path is taken from the top of the worktree, as git takes it
+        Ok(dir) if dir.is_relative() => match repo.workdir() {
+            Some(workdir) => workdir.join(dir),
+            None => repo.path().join(dir),
+        },
+        Ok(dir) => dir,
+        Err(_) => repo.path().join("hooks"),
+    };
+    HOOKS
+        .iter()
+        .map(|name| (*name, dir.join(name)))
+        .filter(|(_, path)| executable(path))
+        .collect()
+}
+
+#[cfg(unix)]
+fn executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// The hooks running on their own thread, with what they print collected
+/// line by line as it comes.
+pub struct HookRun {
+    output: Arc<Mutex<Vec<String>>>,
+    result: Receiver<Result<Outcome>>,
+}
+
+impl HookRun {
+    /// Runs `hooks` one after the other as `git commit` does: `pre-commit`
+    /// with nothing, `commit-msg` with the message in `.git/COMMIT_EDITMSG`
+    /// to check or rewrite. Each runs at the top of the worktree.
+    pub fn spawn(repo: &Repository, hooks: Vec<(&'static str, PathBuf)>, message: String) -> Self {
+        let git_dir = repo.path().to_path_buf();
+        let workdir = repo.workdir().unwrap_or(repo.path()).to_path_buf();
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let lines = Arc::clone(&output);
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            let result = (|| {
+                let message_file = git_dir.join("COMMIT_EDITMSG");
+                // Ending in a newline, as git writes it, so a hook can append a line
+                fs::write(&message_file, format!("{}\n", message.trim_end()))?;
+
+                for (name, path) in hooks {
+                    push(&lines, format!("── {} ──", name));
+                    let mut command = Command::new(&path);
+                    command
+                        .current_dir(&workdir)
+                        .env("GIT_INDEX_FILE", git_dir.join("index"))
+                        .stdin(Stdio::null());
+                    if name == "commit-msg" {
+                        command.arg(&message_file);
+                    }
+                    let code = run(command, &path, &lines)?;
+                    if code != Some(0) {
+                        return Ok(Outcome::Failed(name, code));
+                    }
+                }
+
+                Ok(Outcome::Passed(fs::read_to_string(&message_file)?))
+            })();
+            let _ = tx.send(result);
+        });
+
+        HookRun { output, result: rx }
+    }
+
+    pub fn output(&self) -> Vec<String> {
+        self.output.lock().map(|o| o.clone()).unwrap_or_default()
+    }
+
+    /// The outcome once the hooks have finished.
+    pub fn poll(&self) -> Option<Result<Outcome>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(anyhow!("the hook thread stopped"))),
+        }
+    }
+}
+
+fn push(lines: &Mutex<Vec<String>>, line: String) {
+    if let Ok(mut lines) = lines.lock() {
+        lines.push(line);
+    }
+}
+
+/// Runs the hook with its stdout and stderr both read into `lines` as they
+/// come, and its exit code once it's done.
+fn run(mut command: Command, path: &Path, lines: &Arc<Mutex<Vec<String>>>) -> Result<Option<i32>> {
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("cannot run {}", path.display()))?;
+
+    let readers: Vec<_> = [
+        child
+            .stdout
+            .take()
+            .map(|o| Box::new(o) as Box<dyn Read + Send>),
+        child
+            .stderr
+            .take()
+            .map(|e| Box::new(e) as Box<dyn Read + Send>),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|stream| {
+        let lines = Arc::clone(lines);
+        thread::spawn(move || {
+            for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                push(&lines, line);
+            }
+        })
+    })
+    .collect();
+    for reader in readers {
+        let _ = reader.join();
+    }
+
+    Ok(child.wait()?.code())
+}
+
+pub enum HookAction {
+    Waiting,
+    Cancelled,
+    /// Run the hooks again
+    Retry,
+    /// Commit this message, the hooks passed or bypassed
+    Commit(String),
+}
+
+/// The popup the hooks' output streams into while they run, which stays up
+/// when one fails to offer running them again or committing without them,
+/// as `git commit --no-verify` does.
+pub struct HookPopup {
+    pub mode: CommitMode,
+    pub sign: bool,
+    message: String,
+    run: HookRun,
+    /// The hook that failed and how, once one has
+    failed: Option<String>,
+    scroll: u16,
+    height: u16,
+}
+
+impl HookPopup {
+    pub fn new(repo: &Repository, mode: CommitMode, sign: bool, message: String) -> Self {
+        HookPopup {
+            mode,
+            sign,
+            run: HookRun::spawn(repo, installed(repo), message.clone()),
+            message,
+            failed: None,
+            scroll: 0,
+            height: 0,
+        }
+    }
+
+    /// The message as it was submitted, before any hook touched it.
+    pub fn message(&self) -> String {
+        self.message.clone()
+    }
+
+    pub fn retry(&mut self, repo: &Repository) {
+        self.run = HookRun::spawn(repo, installed(repo), self.message.clone());
+        self.failed = None;
+        self.scroll = 0;
+    }
+
+    /// What the hooks came to, once they've finished: the message to commit
+    /// when they passed. A failure keeps the popup up.
+    pub fn poll(&mut self) -> Option<String> {
+        if self.failed.is_some() {
+            return None;
+        }
+        match self.run.poll()? {
+            Ok(Outcome::Passed(message)) => return Some(message),
+            Ok(Outcome::Failed(name, Some(code))) => {
+                self.failed = Some(format!("{} failed with exit code {}", name, code))
+            }
+            Ok(Outcome::Failed(name, None)) => {
+                self.failed = Some(format!("{} was stopped by a signal", name))
+            }
+            Err(e) => self.failed = Some(format!("{:#}", e)),
+        }
+        // The end of the output, where the reason usually is
+        self.scroll = u16::MAX;
+        None
+    }
+
+    pub fn handle(&mut self, key: &KeyEvent) -> HookAction {
+        let page = self.height.saturating_sub(1).max(1);
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.scroll = self.scroll.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.scroll = self.scroll.saturating_add(1),
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(page),
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_add(page),
+            KeyCode::Esc | KeyCode::Char('q') if self.failed.is_some() => {
+                return HookAction::Cancelled;
+            }
+            KeyCode::Char('r') if self.failed.is_some() => return HookAction::Retry,
+            KeyCode::Char('b') if self.failed.is_some() => {
+                return HookAction::Commit(self.message.clone());
+            }
+            _ => {}
+        }
+        HookAction::Waiting
+    }
+
+    pub fn render(&mut self, frame: &mut Frame, theme: &Theme) {
+        let [area] = Layout::horizontal([Constraint::Percentage(80)])
+            .flex(Flex::Center)
+            .areas(frame.area());
+        let [area] = Layout::vertical([Constraint::Percentage(70)])
+            .flex(Flex::Center)
+            .areas(area);
+
+        let (title, bottom, border) = match &self.failed {
+            Some(reason) => (
+                format!(" {} ", reason),
+                " r retry • b commit anyway (--no-verify) • ↑/↓ scroll • Esc cancel ",
+                Style::default().fg(theme.warning),
+            ),
+            None => (
+                String::from(" Running hooks… "),
+                " ↑/↓ scroll ",
+                theme.focus(),
+            ),
+        };
+        let block = Block::default()
+            .title(title)
+            .title_bottom(bottom)
+            .borders(Borders::ALL)
+            .border_style(border);
+
+        let lines: Vec<Line> = self.run.output().into_iter().map(Line::raw).collect();
+        self.height = area.height.saturating_sub(2);
+        let bottom = lines.len().saturating_sub(self.height as usize);
+        // Follows the output while it runs, unless scrolled back
+        if self.failed.is_none() && self.scroll as usize >= bottom.saturating_sub(1) {
+            self.scroll = bottom as u16;
+        }
+        self.scroll = self.scroll.min(bottom as u16);
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(
+            Paragraph::new(lines).block(block).scroll((self.scroll, 0)),
+            area,
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use crate::theme::Theme;
+use anyhow::{Result, anyhow, bail};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use git2::{Repository, Status};
+use helper_git_core::status::{FileEntry, Section};
+use ratatui::{
+    layout::Flex,
+    prelude::*,
+    widgets::{Block, Borders, Clear, HighlightSpacing, List, ListItem, ListState},
+};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+pub enum IgnoreResult {
+    Open,
+    Closed,
+    Chosen(String),
+}
+
+/// The popup that picks a `.gitignore` pattern for an untracked entry, the
+/// narrowest first.
+pub struct IgnorePrompt {
+    path: String,
+    patterns: Vec<(String, &'static str)>,
+    state: ListState,
+}
+
+impl IgnorePrompt {
+    /// Fails for entries a pattern would do nothing for: tracked files and
+    /// ones that are already ignored.
+    pub fn new(repo: &Repository, entry: &FileEntry) -> Result<Self> {
+        let path = entry
+            .path
+            .to_str()
+            .ok_or_else(|| anyhow!("{} isn't valid UTF-8", entry.path.display()))?;
+        let trimmed = path.trim_end_matches('/');
+        if entry.section != Section::Worktree || repo.status_should_ignore(Path::new(trimmed))? {
+            bail!("{} isn't untracked", path);
+        }
+        // A whole directory has no status of its own
+        if !entry.is_directory() && !repo.status_file(&entry.path)?.contains(Status::WT_NEW) {
+            bail!("{} isn't untracked", path);
+        }
+
+        let mut patterns = vec![(
+            format!("/{}", escape(path)),
+            if entry.is_directory() {
+                "this directory"
+            } else {
+                "this file"
+            },
+        )];
+        let name = Path::new(trimmed).file_name().and_then(|n| n.to_str());
+        if !entry.is_directory()
+            && let Some((stem, extension)) = name.and_then(|n| n.rsplit_once('.'))
+            && !stem.is_empty()
+        {
+            patterns.push((
+                format!("*.{}", escape(extension)),
+                "every file with its extension",
+            ));
+        }
+        if let Some((parent, _)) = trimmed.rsplit_once('/') {
+            patterns.push((format!("/{}/", escape(parent)), "the directory it's in"));
+        }
+
+        Ok(IgnorePrompt {
+            path: path.to_string(),
+            patterns,
+            state: ListState::default().with_selected(Some(0)),
+        })
+    }
+
+    pub fn handle(&mut self, key: &KeyEvent) -> IgnoreResult {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => return IgnoreResult::Closed,
+            KeyCode::Enter => {
+                if let Some((pattern, _)) = self.state.selected().and_then(|i| self.patterns.get(i))
+                {
+                    return IgnoreResult::Chosen(pattern.clone());
+                }
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.state.select_previous(),
+            KeyCode::Char('p') if ctrl => self.state.select_previous(),
+            KeyCode::Down | KeyCode::Char('j') => self.state.select_next(),
+            KeyCode::Char('n') if ctrl => self.state.select_next(),
+            _ => {}
+        }
+        IgnoreResult::Open
+    }
+
+    pub fn render(&mut self, frame: &mut Frame, theme: &Theme) {
+        let [area] = Layout::horizontal([Constraint::Percentage(60)])
+            .flex(Flex::Center)
+            .areas(frame.area());
+        let [area] = Layout::vertical([Constraint::Length(self.patterns.len() as u16 + 2)])
+            .flex(Flex::Center)
+            .areas(area);
+
+        let block = Block::default()
+            .title(format!(" Ignore {} ", self.path))
+            .title_bottom(" Enter add to .gitignore • ↑/↓ choose • Esc cancel ")
+            .borders(Borders::ALL)
+            .border_style(theme.focus());
+
+        let items: Vec<ListItem> = self
+            .patterns
+            .iter()
+            .map(|(pattern, description)| {
+                ListItem::new(Line::from(vec![
+                    Span::raw(pattern.clone()),
+                    Span::raw("  "),
+                    Span::styled(*description, Style::default().dim()),
+                ]))
+            })
+            .collect();
+
+        frame.render_widget(Clear, area);
+        frame.render_stateful_widget(
+            List::new(items)
+                .block(block)
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+                .highlight_symbol("> ")
+                .highlight_spacing(HighlightSpacing::Always),
+            area,
+            &mut self.state,
+        );
+    }
+}
+
+/// Adds `pattern` as a line of its own at the end of the worktree's top-level
+/// `.gitignore`, creating it if need be. Returns what to tell the user.
+pub fn append(repo: &Repository, pattern: &str) -> Result<String> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("repository has no working directory"))?;
+    let file = workdir.join(".gitignore");
+    let mut text = match fs::read_to_string(&file) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+
+    if text.lines().any(|line| line == pattern) {
+        return Ok(format!("{} is already in .gitignore", pattern));
+    }
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text.push_str(pattern);
+    text.push('\n');
+    fs::write(&file, text)?;
+
+    Ok(format!("Added {} to .gitignore", pattern))
+}
+
+/// `text` with the characters gitignore reads as wildcards, and trailing
+/// spaces it would trim, backslash-escaped.
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '?' | '[') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    let kept = escaped.trim_end_matches(' ').len();
+    if kept < escaped.len() {
+        let trailing = escaped.len() - kept;
+        escaped.truncate(kept);
+        escaped.push_str(&"\\ ".repeat(trailing));
+    }
+    escaped
+}
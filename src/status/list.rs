@@ -0,0 +1,672 @@
+use crate::theme::Theme;
+use anyhow::Result;
+use git2::Repository;
+use helper_git_core::lfs;
+use helper_git_core::repo::RepoSummary;
+use helper_git_core::status::{
+    ChangeKind, FileEntry, Rename, Section, StatusEntry, StatusFilter, collect_status, line_counts,
+};
+use ratatui::prelude::*;
+use ratatui::widgets::{HighlightSpacing, List};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// A directory row of the tree layout, which says where it sits so it stays
+/// collapsed across reloads.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Folder {
+    /// With a trailing `/`, like `src/status/`; as shown, so lossy where the
+    /// path isn't UTF-8
+    pub path: String,
+    pub section: Section,
+    pub tracked: bool,
+}
+
+#[derive(Clone, PartialEq)]
+pub enum Row {
+    /// Section headings and placeholders, which navigation skips
+    Heading,
+    File(FileEntry),
+    Folder(Folder),
+}
+
+impl Row {
+    fn selectable(&self) -> bool {
+        !matches!(self, Row::Heading)
+    }
+}
+
+/// The left panel rows, laid out flat or as a directory tree.
+pub struct StatusList {
+    pub lines: Vec<Line<'static>>,
+    /// `lines` as the list widget, built along with them so drawing a frame
+    /// only borrows it
+    pub widget: List<'static>,
+    /// Background of the selected row
+    highlight: Color,
+    pub rows: Vec<Row>,
+    /// Branch, upstream distance, stashes and last commit, for the header
+    pub summary: RepoSummary,
+    /// File counts per kind of change, e.g. `3 staged, 2 untracked`
+    pub counts: String,
+    /// Case-insensitive path substring; empty shows everything
+    pub filter: String,
+    /// Set when no path contains the filter, so it matched fuzzily instead:
+    /// its letters in order, with anything in between
+    pub fuzzy: bool,
+    /// Whether ignored files are listed, toggled at runtime
+    pub show_ignored: bool,
+    /// Files under their directories instead of one path per row
+    pub tree: bool,
+    collapsed: HashSet<Folder>,
+    /// Files picked for a batch stage, discard or stash, by path and section
+    marked: HashSet<(PathBuf, Section)>,
+    entries: Vec<Labelled>,
+}
+
+/// An entry with its label and color, whether git tracks the file (the
+/// untracked ones get a heading of their own under the worktree changes),
+/// what follows the path, like a submodule's `(new commits)`, and the lines
+/// it adds and removes, as `+12 -3` in the diff colors, or an `LFS` badge for
+/// a file kept in Git LFS.
+type Labelled = (
+    FileEntry,
+    &'static str,
+    Color,
+    bool,
+    Option<String>,
+    Vec<Span<'static>>,
+);
+
+impl StatusList {
+    pub fn load(
+        repo: &Repository,
+        theme: &Theme,
+        show_ignored: bool,
+        filter: &StatusFilter,
+    ) -> Result<Self> {
+        let status = collect_status(repo, show_ignored, filter)?;
+        Ok(Self::from_status(repo, theme, show_ignored, status))
+    }
+
+    /// The list for a status already collected, e.g. by a [`StatusCache`].
+    ///
+    /// [`StatusCache`]: helper_git_core::status::StatusCache
+    pub fn from_status(
+        repo: &Repository,
+        theme: &Theme,
+        show_ignored: bool,
+        status: Vec<StatusEntry>,
+    ) -> Self {
+        let mut conflicted: Vec<Labelled> = Vec::new();
+        let mut staged: Vec<Labelled> = Vec::new();
+        let mut unstaged: Vec<Labelled> = Vec::new();
+        let mut ignored: Vec<Labelled> = Vec::new();
+        let mut untracked = 0;
+        let line_counts = line_counts(repo, &status).unwrap_or_default();
+        // A file in Git LFS gets a badge instead, its pointer's counts saying nothing
+        let counted = |path: &PathBuf, section| match line_counts.get(&(path.clone(), section)) {
+            _ if lfs::tracked(repo, path) => vec![Span::styled(
+                "  LFS",
+                Style::default()
+                    .fg(theme.accent)
+                    .add_modifier(Modifier::BOLD),
+            )],
+            Some(&(added, removed)) => count_spans(added, removed, theme),
+            None => Vec::new(),
+        };
+
+        for file in status {
+            if file.conflicted {
+                let entry = FileEntry {
+                    path: file.path,
+                    section: Section::Conflicted,
+                    rename: None,
+                };
+                conflicted.push((entry, "Conflicted", theme.conflict, true, None, Vec::new()));
+                continue;
+            }
+
+            // A file staged and then edited again lands in both sections
+            if let Some(change) = file.staged {
+                let (label, color) = index_label(change.kind, theme);
+                let label = if file.submodule.is_some() {
+                    "Submodule"
+                } else {
+                    label
+                };
+                let counts = counted(&change.path, Section::Index);
+                let entry = FileEntry {
+                    path: change.path,
+                    section: Section::Index,
+                    rename: change.rename,
+                };
+                staged.push((entry, label, color, true, None, counts));
+            }
+            if let Some(change) = file.unstaged {
+                let (label, color) = worktree_label(change.kind, theme);
+                // A changed gitlink says nothing useful as Modified/Added
+                let label = if file.submodule.is_some() {
+                    "Submodule"
+                } else {
+                    label
+                };
+                // Like git status: `sub (new commits, untracked content)`
+                let note = file
+                    .submodule
+                    .map(|state| state.describe())
+                    .filter(|text| !text.is_empty());
+                let counts = counted(&change.path, Section::Worktree);
+                let entry = FileEntry {
+                    path: change.path,
+                    section: Section::Worktree,
+                    rename: change.rename,
+                };
+                match change.kind {
+                    ChangeKind::Ignored => ignored.push((entry, label, color, false, note, counts)),
+                    ChangeKind::Untracked => {
+                        untracked += 1;
+                        unstaged.push((entry, label, color, false, note, counts));
+                    }
+                    _ => unstaged.push((entry, label, color, true, note, counts)),
+                }
+            }
+        }
+
+        let counts = [
+            (conflicted.len(), "conflicted"),
+            (staged.len(), "staged"),
+            (unstaged.len() - untracked, "modified"),
+            (untracked, "untracked"),
+            (ignored.len(), "ignored"),
+        ]
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, kind)| format!("{} {}", count, kind))
+        .collect::<Vec<_>>();
+
+        conflicted.extend(staged);
+        conflicted.extend(unstaged);
+        // Listed last in the Untracked section, so they don't bury real changes
+        conflicted.extend(ignored);
+
+        let mut list = StatusList {
+            lines: Vec::new(),
+            widget: List::default(),
+            highlight: theme.highlight_bg,
+            rows: Vec::new(),
+            summary: RepoSummary::load(repo),
+            counts: if counts.is_empty() {
+                String::from("clean")
+            } else {
+                counts.join(", ")
+            },
+            filter: String::new(),
+            fuzzy: false,
+            show_ignored,
+            tree: false,
+            collapsed: HashSet::new(),
+            marked: HashSet::new(),
+            entries: conflicted,
+        };
+        list.build();
+
+        list
+    }
+
+    /// Stand-in shown until the first status load comes back.
+    pub fn loading(repo: &Repository, theme: &Theme) -> Self {
+        let mut list = StatusList {
+            lines: Vec::new(),
+            widget: List::default(),
+            highlight: theme.highlight_bg,
+            rows: vec![Row::Heading],
+            summary: RepoSummary::load(repo),
+            counts: String::from("…"),
+            filter: String::new(),
+            fuzzy: false,
+            show_ignored: false,
+            tree: false,
+            collapsed: HashSet::new(),
+            marked: HashSet::new(),
+            entries: Vec::new(),
+        };
+        list.set_lines(vec![Line::raw("Loading status…")]);
+        list
+    }
+
+    pub fn set_filter(&mut self, filter: String) {
+        self.filter = filter;
+        self.build();
+    }
+
+    pub fn set_tree(&mut self, tree: bool) {
+        self.tree = tree;
+        self.build();
+    }
+
+    /// Carries the filter, layout, collapsed folders and marks over from the
+    /// list this one replaces. Marks on files that are gone are dropped.
+    pub fn take_view(&mut self, old: &mut StatusList) {
+        self.filter = std::mem::take(&mut old.filter);
+        self.tree = old.tree;
+        self.collapsed = std::mem::take(&mut old.collapsed);
+        self.marked = std::mem::take(&mut old.marked);
+        let entries = &self.entries;
+        self.marked.retain(|(path, section)| {
+            entries
+                .iter()
+                .any(|(file, ..)| file.path == *path && file.section == *section)
+        });
+        self.build();
+    }
+
+    /// Marks the file at `index`, or every file in the folder there; when
+    /// they are all marked already, unmarks them instead.
+    pub fn toggle_mark(&mut self, index: usize) {
+        let keys: Vec<(PathBuf, Section)> = match self.rows.get(index) {
+            Some(Row::File(entry)) => vec![(entry.path.clone(), entry.section)],
+            Some(Row::Folder(folder)) => self
+                .entries
+                .iter()
+                .filter(|(file, _, _, tracked, ..)| {
+                    file.section == folder.section
+                        && *tracked == folder.tracked
+                        && file.path.to_string_lossy().starts_with(&folder.path)
+                })
+                .map(|(file, ..)| (file.path.clone(), file.section))
+                .collect(),
+            _ => return,
+        };
+
+        if keys.iter().all(|key| self.marked.contains(key)) {
+            for key in &keys {
+                self.marked.remove(key);
+            }
+        } else {
+            self.marked.extend(keys);
+        }
+        self.build();
+    }
+
+    /// How many files are marked, without collecting them.
+    pub fn marked_count(&self) -> usize {
+        self.marked.len()
+    }
+
+    /// The marked files, in list order.
+    pub fn marked(&self) -> Vec<FileEntry> {
+        self.entries
+            .iter()
+            .filter(|(file, ..)| self.marked.contains(&(file.path.clone(), file.section)))
+            .map(|(file, ..)| file.clone())
+            .collect()
+    }
+
+    pub fn clear_marks(&mut self) {
+        self.marked.clear();
+        self.build();
+    }
+
+    /// Folds the folder at `index` away, or opens it again.
+    pub fn toggle_folder(&mut self, index: usize) {
+        let Some(Row::Folder(folder)) = self.rows.get(index) else {
+            return;
+        };
+        if !self.collapsed.remove(folder) {
+            self.collapsed.insert(folder.clone());
+        }
+        self.build();
+    }
+
+    fn build(&mut self) {
+        let mut lines: Vec<Line<'static>> = Vec::new();
+        self.rows.clear();
+
+        let filter = self.filter.to_lowercase();
+        let matching = |fuzzy: bool| -> Vec<Labelled> {
+            let matches = |path: &Path| {
+                let path = path.to_string_lossy().to_lowercase();
+                if fuzzy {
+                    let mut rest = path.chars();
+                    filter.chars().all(|c| rest.any(|p| p == c))
+                } else {
+                    path.contains(&filter)
+                }
+            };
+            self.entries
+                .iter()
+                .filter(|(file, ..)| {
+                    matches(&file.path) || file.rename.as_ref().is_some_and(|r| matches(&r.from))
+                })
+                .cloned()
+                .collect()
+        };
+
+        let mut entries = matching(false);
+        self.fuzzy = entries.is_empty() && !filter.is_empty();
+        if self.fuzzy {
+            entries = matching(true);
+        }
+
+        for (title, section, tracked) in [
+            (" Conflicts ", Section::Conflicted, true),
+            (" Staged ", Section::Index, true),
+            (" Unstaged ", Section::Worktree, true),
+            (" Untracked ", Section::Worktree, false),
+        ] {
+            let files: Vec<_> = entries
+                .iter()
+                .filter(|(file, _, _, is_tracked, ..)| {
+                    file.section == section && *is_tracked == tracked
+                })
+                .collect();
+
+            if files.is_empty() {
+                continue;
+            }
+
+            lines.push(Line::from(Span::styled(
+                title,
+                Style::default()
+                    .add_modifier(Modifier::BOLD)
+                    .add_modifier(Modifier::UNDERLINED),
+            )));
+            self.rows.push(Row::Heading);
+
+            if self.tree {
+                let mut rows = Vec::new();
+                Dir::new(&files).push_rows("", 0, section, tracked, &self.collapsed, &mut rows);
+                for (line, row) in rows {
+                    lines.push(line);
+                    self.rows.push(row);
+                }
+            } else {
+                for labelled in files {
+                    lines.push(file_line(labelled, 0, &labelled.0.path.to_string_lossy()));
+                    self.rows.push(Row::File(labelled.0.clone()));
+                }
+            }
+        }
+
+        // A column for the marks, only while anything is marked
+        if !self.marked.is_empty() {
+            for (line, row) in lines.iter_mut().zip(&self.rows) {
+                let mark = match row {
+                    Row::Heading => continue,
+                    Row::File(file) if self.marked.contains(&(file.path.clone(), file.section)) => {
+                        Span::styled("● ", Style::default().add_modifier(Modifier::BOLD))
+                    }
+                    _ => Span::raw("  "),
+                };
+                line.spans.insert(0, mark);
+            }
+        }
+
+        if lines.is_empty() {
+            let placeholder = if self.entries.is_empty() {
+                "Working tree clean"
+            } else {
+                "No files match the filter"
+            };
+            lines.push(Line::raw(placeholder));
+            self.rows.push(Row::Heading);
+        }
+
+        self.set_lines(lines);
+    }
+
+    fn set_lines(&mut self, lines: Vec<Line<'static>>) {
+        self.widget = List::new(lines.clone())
+            .highlight_style(
+                Style::default()
+                    .bg(self.highlight)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .highlight_symbol("➜ ")
+            .highlight_spacing(HighlightSpacing::Always);
+        self.lines = lines;
+    }
+
+    /// Whether any file (filtered out or not) is listed under `section`.
+    pub fn has_section(&self, section: Section) -> bool {
+        self.entries
+            .iter()
+            .any(|(file, ..)| file.section == section)
+    }
+
+    pub fn entry(&self, index: usize) -> Option<&FileEntry> {
+        match self.rows.get(index) {
+            Some(Row::File(entry)) => Some(entry),
+            _ => None,
+        }
+    }
+
+    /// Whether the row at `index` is a file or folder, not a heading.
+    pub fn selectable(&self, index: usize) -> bool {
+        self.rows.get(index).is_some_and(Row::selectable)
+    }
+
+    pub fn first(&self) -> Option<usize> {
+        self.rows.iter().position(Row::selectable)
+    }
+
+    pub fn last(&self) -> Option<usize> {
+        self.rows.iter().rposition(Row::selectable)
+    }
+
+    /// The selectable row up to `count` steps away from `from`, stopping at
+    /// the first or last one.
+    pub fn step(&self, from: usize, count: usize, forward: bool) -> usize {
+        let mut index = from;
+        for _ in 0..count {
+            let step = if forward {
+                self.next(index)
+            } else {
+                self.prev(index)
+            };
+            match step {
+                Some(i) => index = i,
+                None => break,
+            }
+        }
+        index
+    }
+
+    /// Next selectable row after `from`, if any.
+    pub fn next(&self, from: usize) -> Option<usize> {
+        (from + 1..self.rows.len()).find(|&i| self.rows[i].selectable())
+    }
+
+    /// Previous selectable row before `from`, if any.
+    pub fn prev(&self, from: usize) -> Option<usize> {
+        (0..from.min(self.rows.len()))
+            .rev()
+            .find(|&i| self.rows[i].selectable())
+    }
+
+    /// Where `previous` ended up after a reload: the same path in the same
+    /// section, else the same path anywhere, else the selectable row nearest
+    /// to the old index.
+    pub fn reselect(&self, previous: Option<&FileEntry>, old_index: usize) -> Option<usize> {
+        if let Some(previous) = previous {
+            if let Some(i) = self
+                .rows
+                .iter()
+                .position(|r| matches!(r, Row::File(e) if e == previous))
+            {
+                return Some(i);
+            }
+            if let Some(i) = self
+                .rows
+                .iter()
+                .position(|r| matches!(r, Row::File(e) if e.path == previous.path))
+            {
+                return Some(i);
+            }
+        }
+
+        let clamped = old_index.min(self.rows.len().saturating_sub(1));
+        if self.selectable(clamped) {
+            return Some(clamped);
+        }
+        self.next(clamped).or_else(|| self.prev(clamped))
+    }
+}
+
+/// A file's row: its label, then `shown` (the path, or just the name in the
+/// tree) with where it was renamed from, indented `depth` levels.
+fn file_line(
+    (file, label, color, _, note, counts): &Labelled,
+    depth: usize,
+    shown: &str,
+) -> Line<'static> {
+    let mut line = Line::from(vec![
+        Span::raw("  ".repeat(depth)),
+        Span::styled(
+            *label,
+            Style::default().fg(*color).add_modifier(Modifier::BOLD),
+        ),
+        Span::raw(" | "),
+        Span::raw(match &file.rename {
+            Some(Rename {
+                from,
+                similarity: Some(percent),
+            }) => format!("{} → {} ({}%)", from.display(), shown, percent),
+            Some(Rename { from, .. }) => format!("{} → {}", from.display(), shown),
+            None => shown.to_string(),
+        }),
+    ]);
+    if let Some(note) = note {
+        line.spans
+            .push(Span::styled(format!(" ({})", note), Style::default().dim()));
+    }
+    line.spans.extend(counts.iter().cloned());
+    line
+}
+
+/// `  +12 -3`, leaving out a side with nothing on it.
+fn count_spans(added: usize, removed: usize, theme: &Theme) -> Vec<Span<'static>> {
+    let mut spans = vec![Span::raw(" ")];
+    if added > 0 {
+        spans.push(Span::styled(
+            format!(" +{}", added),
+            Style::default().fg(theme.addition),
+        ));
+    }
+    if removed > 0 {
+        spans.push(Span::styled(
+            format!(" -{}", removed),
+            Style::default().fg(theme.deletion),
+        ));
+    }
+    spans
+}
+
+/// The files of one section under their directories, for the tree layout.
+#[derive(Default)]
+struct Dir<'a> {
+    dirs: BTreeMap<String, Dir<'a>>,
+    files: Vec<(&'a Labelled, String)>,
+    // Files anywhere below
+    count: usize,
+}
+
+impl<'a> Dir<'a> {
+    fn new(files: &[&'a Labelled]) -> Self {
+        let mut root = Dir::default();
+
+        for &labelled in files {
+            let path = labelled.0.path.to_string_lossy();
+            // An untracked directory is listed as `dir/`, and shown as one file
+            let (parents, name) = match path.trim_end_matches('/').rsplit_once('/') {
+                Some((parents, _)) => (Some(parents), &path[parents.len() + 1..]),
+                None => (None, &*path),
+            };
+
+            let mut dir = &mut root;
+            dir.count += 1;
+            for part in parents.into_iter().flat_map(|p| p.split('/')) {
+                dir = dir.dirs.entry(part.to_string()).or_default();
+                dir.count += 1;
+            }
+            dir.files.push((labelled, name.to_string()));
+        }
+
+        root
+    }
+
+    /// Rows for the folders and then the files in here, below `prefix`.
+    fn push_rows(
+        &self,
+        prefix: &str,
+        depth: usize,
+        section: Section,
+        tracked: bool,
+        collapsed: &HashSet<Folder>,
+        rows: &mut Vec<(Line<'static>, Row)>,
+    ) {
+        for (name, dir) in &self.dirs {
+            let mut name = name.to_string();
+            let mut dir = dir;
+            // A folder holding nothing but one other folder shares its row
+            while dir.files.is_empty() && dir.dirs.len() == 1 {
+                let (inner, inner_dir) = dir.dirs.iter().next().expect("one folder");
+                name = format!("{}/{}", name, inner);
+                dir = inner_dir;
+            }
+
+            let folder = Folder {
+                path: format!("{}{}/", prefix, name),
+                section,
+                tracked,
+            };
+            let open = !collapsed.contains(&folder);
+            let line = Line::from(vec![
+                Span::raw("  ".repeat(depth)),
+                Span::raw(if open { "▾ " } else { "▸ " }),
+                Span::styled(
+                    format!("{}/", name),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(format!(" ({})", dir.count), Style::default().dim()),
+            ]);
+            let path = folder.path.clone();
+            rows.push((line, Row::Folder(folder)));
+
+            if open {
+                dir.push_rows(&path, depth + 1, section, tracked, collapsed, rows);
+            }
+        }
+
+        for (labelled, name) in &self.files {
+            rows.push((
+                file_line(labelled, depth, name),
+                Row::File(labelled.0.clone()),
+            ));
+        }
+    }
+}
+
+fn index_label(kind: ChangeKind, theme: &Theme) -> (&'static str, Color) {
+    let label = match kind {
+        ChangeKind::Renamed => "Renamed",
+        ChangeKind::Added | ChangeKind::Untracked => "Added",
+        ChangeKind::Modified => "Staged",
+        ChangeKind::Deleted => "Removed",
+        ChangeKind::TypeChange => "Typechange",
+        ChangeKind::Ignored => "Ignored",
+    };
+    (label, theme.staged)
+}
+
+fn worktree_label(kind: ChangeKind, theme: &Theme) -> (&'static str, Color) {
+    match kind {
+        ChangeKind::Renamed => ("Renamed", theme.modified),
+        ChangeKind::Added | ChangeKind::Untracked => ("New", theme.untracked),
+        ChangeKind::Modified => ("Modified", theme.modified),
+        ChangeKind::Deleted => ("Deleted", theme.untracked),
+        ChangeKind::TypeChange => ("Typechange", theme.modified),
+        ChangeKind::Ignored => ("Ignored", theme.ignored),
+    }
+}
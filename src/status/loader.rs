@@ -0,0 +1,165 @@
+use super::diff::{self, FileDiff};
+use super::list::StatusList;
+use crate::image;
+use crate::theme::Theme;
+use anyhow::Result;
+use git2::Repository;
+use helper_git_core::diff::DiffSettings;
+use helper_git_core::status::{FileEntry, StatusCache, StatusFilter};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+/// Computes diffs on a background thread so moving across huge files never
+/// blocks the UI. Only the latest request matters: older ones are skipped by
+/// the worker and their results dropped here.
+pub struct DiffLoader {
+    requests: Sender<DiffRequest>,
+    results: Receiver<(u64, Result<FileDiff>)>,
+    generation: u64,
+}
+
+/// Generation, entry, diff settings, preview line limit and whether to
+/// number the lines.
+type DiffRequest = (u64, FileEntry, DiffSettings, usize, bool);
+
+impl DiffLoader {
+    pub fn spawn(repo: &Repository, theme: Theme) -> Result<Self> {
+        // git2 repositories can't be shared across threads, so the worker opens its own
+        let repo = Repository::open(repo.path())?;
+
+        let (request_tx, request_rx) = mpsc::channel::<DiffRequest>();
+        let (result_tx, result_rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            while let Ok(mut request) = request_rx.recv() {
+                // Skip straight to the newest request if several queued up
+                while let Ok(newer) = request_rx.try_recv() {
+                    request = newer;
+                }
+
+                let (generation, entry, settings, limit, line_numbers) = request;
+                let mut result = diff::show_file_diff(&repo, &entry, settings, limit, &theme);
+                if !line_numbers && let Ok(diff) = &mut result {
+                    diff.hide_numbers();
+                }
+                // Where the terminal draws images, a changed one is shown too
+                if let (Some(graphics), Ok(diff)) = (theme.images, &mut result) {
+                    diff.image = image::preview(&repo, &entry, graphics);
+                }
+
+                if result_tx.send((generation, result)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(DiffLoader {
+            requests: request_tx,
+            results: result_rx,
+            generation: 0,
+        })
+    }
+
+    /// Has `entry`'s diff computed, with whole files shown up to `limit` lines
+    /// and a line number gutter unless `line_numbers` is off.
+    pub fn request(
+        &mut self,
+        entry: FileEntry,
+        settings: DiffSettings,
+        limit: usize,
+        line_numbers: bool,
+    ) {
+        self.generation += 1;
+        let _ = self
+            .requests
+            .send((self.generation, entry, settings, limit, line_numbers));
+    }
+
+    /// Waits up to `timeout` for the result of the latest request.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Result<FileDiff>> {
+        loop {
+            match self.results.recv_timeout(timeout) {
+                Ok((generation, result)) if generation == self.generation => return Some(result),
+                Ok(_) => continue,
+                Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => {
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// Runs `git status` on a background thread the same way, so scanning a
+/// huge worktree never freezes the UI either. The thread keeps the last
+/// result in a [`StatusCache`], so a refresh for a few written paths only
+/// rescans those.
+pub struct StatusLoader {
+    requests: Sender<StatusRequest>,
+    results: Receiver<(u64, Result<StatusList>)>,
+    generation: u64,
+}
+
+/// Generation, whether to list ignored files, and the paths written since
+/// the last request; `None` rescans everything.
+type StatusRequest = (u64, bool, Option<Vec<PathBuf>>);
+
+impl StatusLoader {
+    pub fn spawn(repo: &Repository, theme: Theme, filter: StatusFilter) -> Result<Self> {
+        let repo = Repository::open(repo.path())?;
+
+        let (request_tx, request_rx) = mpsc::channel::<StatusRequest>();
+        let (result_tx, result_rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            let mut cache = StatusCache::default();
+            while let Ok(mut request) = request_rx.recv() {
+                // Skipped requests still wrote their paths, so the newest one takes them over
+                while let Ok(mut newer) = request_rx.try_recv() {
+                    if let (Some(paths), Some(older)) = (&mut newer.2, request.2) {
+                        paths.extend(older);
+                    } else {
+                        newer.2 = None;
+                    }
+                    request = newer;
+                }
+
+                let (generation, show_ignored, changed) = request;
+                let result = cache
+                    .status(&repo, show_ignored, &filter, changed.as_deref())
+                    .map(|status| StatusList::from_status(&repo, &theme, show_ignored, status));
+
+                if result_tx.send((generation, result)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(StatusLoader {
+            requests: request_tx,
+            results: result_rx,
+            generation: 0,
+        })
+    }
+
+    /// Asks for the status again: of `changed` (worktree-relative) paths
+    /// only, on top of the last one, or of everything when that's `None`.
+    pub fn request(&mut self, show_ignored: bool, changed: Option<Vec<PathBuf>>) {
+        self.generation += 1;
+        let _ = self.requests.send((self.generation, show_ignored, changed));
+    }
+
+    /// Waits up to `timeout` for the result of the latest request.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Result<StatusList>> {
+        loop {
+            match self.results.recv_timeout(timeout) {
+                Ok((generation, result)) if generation == self.generation => return Some(result),
+                Ok(_) => continue,
+                Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => {
+                    return None;
+                }
+            }
+        }
+    }
+}
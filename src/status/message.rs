@@ -0,0 +1,91 @@
+use crate::theme::Theme;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    layout::Flex,
+    prelude::*,
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+};
+
+pub enum InputResult {
+    Editing,
+    Cancelled,
+    Submitted(String),
+}
+
+/// A multi-line text box in a popup, for commit messages. Typing always
+/// appends; Enter submits, Alt-Enter or Ctrl-j starts a new line.
+pub struct MessageInput {
+    pub title: String,
+    pub text: String,
+    /// More keys, like `Ctrl-s sign`, for the right of the top border
+    pub hints: Vec<String>,
+}
+
+impl MessageInput {
+    pub fn new(title: impl Into<String>, text: String) -> Self {
+        MessageInput {
+            title: title.into(),
+            text,
+            hints: Vec::new(),
+        }
+    }
+
+    pub fn handle(&mut self, key: &KeyEvent) -> InputResult {
+        match key.code {
+            KeyCode::Esc => return InputResult::Cancelled,
+            KeyCode::Enter if key.modifiers.contains(KeyModifiers::ALT) => self.text.push('\n'),
+            KeyCode::Char('j') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.text.push('\n')
+            }
+            KeyCode::Enter => return InputResult::Submitted(self.text.clone()),
+            KeyCode::Backspace => {
+                self.text.pop();
+            }
+            KeyCode::Char(c) => self.text.push(c),
+            _ => {}
+        }
+        InputResult::Editing
+    }
+
+    fn hints_title(&self) -> String {
+        if self.hints.is_empty() {
+            String::new()
+        } else {
+            format!(" {} ", self.hints.join(" • "))
+        }
+    }
+
+    pub fn render(&self, frame: &mut Frame, theme: &Theme) {
+        let [area] = Layout::horizontal([Constraint::Percentage(70)])
+            .flex(Flex::Center)
+            .areas(frame.area());
+        let [area] = Layout::vertical([Constraint::Length(12)])
+            .flex(Flex::Center)
+            .areas(area);
+
+        let mut lines: Vec<Line> = self.text.split('\n').map(Line::raw).collect();
+        if let Some(last) = lines.last_mut() {
+            last.push_span(Span::styled("█", Style::default().fg(theme.accent)));
+        }
+
+        // Keep the cursor line in view once the message outgrows the box
+        let visible = area.height.saturating_sub(2) as usize;
+        let scroll = lines.len().saturating_sub(visible) as u16;
+
+        let block = Block::default()
+            .title(format!(" {} ", self.title))
+            .title(Line::from(self.hints_title()).right_aligned())
+            .title_bottom(" Enter confirm • Alt-Enter new line • Esc cancel ")
+            .borders(Borders::ALL)
+            .border_style(theme.focus());
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(
+            Paragraph::new(lines)
+                .block(block)
+                .wrap(Wrap { trim: false })
+                .scroll((scroll, 0)),
+            area,
+        );
+    }
+}
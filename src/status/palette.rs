@@ -0,0 +1,238 @@
+use crate::keymap::{Action, Keymap, Scope};
+use crate::theme::Theme;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    layout::Flex,
+    prelude::*,
+    widgets::{Block, Borders, Clear, HighlightSpacing, List, ListItem, ListState, Paragraph},
+};
+
+/// Moving around needs no palette, and the palette can't open itself.
+const SKIPPED: &[Action] = &[
+    Action::Palette,
+    Action::NavigateUp,
+    Action::NavigateDown,
+    Action::PageUp,
+    Action::PageDown,
+    Action::Top,
+    Action::Bottom,
+    Action::ScrollLeft,
+    Action::ScrollRight,
+    Action::Select,
+];
+
+pub enum PaletteResult {
+    Open,
+    Closed,
+    Run(Action),
+    /// Undo this many steps, newest first
+    Undo(usize),
+}
+
+/// One line of the palette.
+#[derive(Clone, Copy)]
+enum Entry {
+    Action(Action),
+    /// The undo stack's step this far from the top, counting from 0
+    Undo(usize),
+}
+
+/// The `:` popup: the status view's actions, narrowed down as a name is typed,
+/// after the last few steps on the undo stack; picking one of those undoes
+/// it and every step after it. Letters only ever type, so the arrows and
+/// Ctrl-p/n choose.
+pub struct Palette {
+    query: String,
+    /// What the undo stack's newest steps did, newest first
+    recent: Vec<String>,
+    matches: Vec<Entry>,
+    state: ListState,
+}
+
+impl Palette {
+    pub fn new(recent: Vec<String>) -> Self {
+        let mut palette = Palette {
+            query: String::new(),
+            recent,
+            matches: Vec::new(),
+            state: ListState::default(),
+        };
+        palette.search();
+        palette
+    }
+
+    pub fn handle(&mut self, key: &KeyEvent) -> PaletteResult {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return PaletteResult::Closed,
+            KeyCode::Enter => {
+                return match self.state.selected().and_then(|i| self.matches.get(i)) {
+                    Some(Entry::Action(action)) => PaletteResult::Run(*action),
+                    Some(Entry::Undo(i)) => PaletteResult::Undo(i + 1),
+                    None => PaletteResult::Open,
+                };
+            }
+            KeyCode::Up => self.state.select_previous(),
+            KeyCode::Char('p') if ctrl => self.state.select_previous(),
+            KeyCode::Down => self.state.select_next(),
+            KeyCode::Char('n') if ctrl => self.state.select_next(),
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.search();
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.search();
+            }
+            _ => {}
+        }
+        PaletteResult::Open
+    }
+
+    /// Best match first; with nothing typed, the undo steps and then every
+    /// action in help order.
+    fn search(&mut self) {
+        let undo = self.recent.iter().enumerate().filter_map(|(i, what)| {
+            score(&self.query, &format!("undo {}", what)).map(|score| (score, Entry::Undo(i)))
+        });
+        let mut scored: Vec<(i32, Entry)> = Action::ALL
+            .iter()
+            .filter(|(action, _, scope, ..)| {
+                matches!(scope, Scope::Global | Scope::List | Scope::Diff)
+                    && !SKIPPED.contains(action)
+            })
+            .filter_map(|(action, name, _, description, _)| {
+                // The config name first, so a match on it ranks above one deep in a description
+                let text = format!("{} {}", name.replace('_', " "), description);
+                score(&self.query, &text).map(|score| (score, Entry::Action(*action)))
+            })
+            .collect();
+        scored.splice(0..0, undo);
+        // Stable, so equal scores keep the help order
+        scored.sort_by_key(|(score, _)| -score);
+
+        self.matches = scored.into_iter().map(|(_, entry)| entry).collect();
+        self.state.select(if self.matches.is_empty() {
+            None
+        } else {
+            Some(0)
+        });
+    }
+
+    pub fn render(&mut self, frame: &mut Frame, keymap: &Keymap, theme: &Theme) {
+        let [area] = Layout::horizontal([Constraint::Percentage(60)])
+            .flex(Flex::Center)
+            .areas(frame.area());
+        let [area] = Layout::vertical([Constraint::Length(16)])
+            .flex(Flex::Center)
+            .areas(area);
+
+        let block = Block::default()
+            .title(" Commands ")
+            .title_bottom(" Enter run • ↑/↓ choose • Esc close ")
+            .borders(Borders::ALL)
+            .border_style(theme.focus());
+        let inner = block.inner(area);
+        let [input, list] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(inner);
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(block, area);
+        frame.render_widget(
+            Paragraph::new(Line::from(vec![
+                Span::styled(": ", Style::default().fg(theme.accent)),
+                Span::raw(self.query.clone()),
+                Span::styled("█", Style::default().fg(theme.accent)),
+            ])),
+            input,
+        );
+
+        if self.matches.is_empty() {
+            frame.render_widget(
+                Paragraph::new(Line::styled("No matching command", Style::default().dim())),
+                list,
+            );
+            return;
+        }
+
+        let items: Vec<ListItem> = self
+            .matches
+            .iter()
+            .map(|entry| match *entry {
+                Entry::Action(action) => ListItem::new(Line::from(vec![
+                    Span::raw(action.description()),
+                    Span::raw("  "),
+                    Span::styled(keymap.label(action), Style::default().dim()),
+                ])),
+                Entry::Undo(i) => ListItem::new(Line::from(vec![
+                    Span::styled("undo ", Style::default().fg(theme.accent)),
+                    Span::raw(self.recent[i].clone()),
+                    Span::raw("  "),
+                    Span::styled(
+                        match i {
+                            0 => keymap.label(Action::Undo),
+                            _ => format!("{} steps", i + 1),
+                        },
+                        Style::default().dim(),
+                    ),
+                ])),
+            })
+            .collect();
+        frame.render_stateful_widget(
+            List::new(items)
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+                .highlight_symbol("> ")
+                .highlight_spacing(HighlightSpacing::Always),
+            list,
+            &mut self.state,
+        );
+    }
+}
+
+/// How well `query` matches `text`, case-insensitively, or `None` when it
+/// doesn't. Every word of the query has to turn up somewhere, its letters in
+/// order though not necessarily next to each other; word starts score best,
+/// the very first word most.
+pub fn score(query: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut total = 0;
+
+    for word in query.to_lowercase().split_whitespace() {
+        let word: Vec<char> = word.chars().collect();
+        let mut best = None;
+
+        // Try every place the word's first letter could start
+        for start in (0..text.len()).filter(|&i| text[i] == word[0]) {
+            let mut points = match start {
+                0 => 12,
+                _ if text[start - 1] == ' ' => 8,
+                _ => 2,
+            };
+            let mut at = start;
+            let mut found = true;
+            for &c in &word[1..] {
+                match (at + 1..text.len()).find(|&i| text[i] == c) {
+                    Some(i) => {
+                        points += if i == at + 1 {
+                            3
+                        } else {
+                            1 - (i - at) as i32 / 4
+                        };
+                        at = i;
+                    }
+                    None => {
+                        found = false;
+                        break;
+                    }
+                }
+            }
+            if found {
+                best = best.max(Some(points));
+            }
+        }
+
+        total += best?;
+    }
+
+    Some(total)
+}
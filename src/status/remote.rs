@@ -0,0 +1,485 @@
+use anyhow::{Context, Result, anyhow, bail};
+use git2::{
+    AnnotatedCommit, AutotagOption, BranchType, Cred, CredentialType, Direction, ErrorCode,
+    FetchOptions, PushOptions, Remote, RemoteCallbacks, Repository, StatusOptions,
+    build::CheckoutBuilder,
+};
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum RemoteOp {
+    Fetch,
+    Pull,
+    Push,
+    Prune,
+}
+
+impl RemoteOp {
+    /// Shown in the help line while the operation runs.
+    pub fn progress(self) -> &'static str {
+        match self {
+            RemoteOp::Fetch => "Fetching…",
+            RemoteOp::Pull => "Pulling…",
+            RemoteOp::Push => "Pushing…",
+            RemoteOp::Prune => "Pruning…",
+        }
+    }
+}
+
+/// Objects sent or received so far, as the transfer callbacks last reported.
+#[derive(Clone, Copy, Default)]
+pub struct Transfer {
+    pub done: usize,
+    pub total: usize,
+    pub bytes: usize,
+}
+
+impl Transfer {
+    pub fn ratio(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            (self.done as f64 / self.total as f64).min(1.0)
+        }
+    }
+}
+
+/// A fetch, pull or push running on its own thread, since network operations
+/// can take as long as the remote likes.
+pub struct RemoteTask {
+    pub op: RemoteOp,
+    result: Receiver<Result<String>>,
+    transfer: Arc<Mutex<Transfer>>,
+}
+
+impl RemoteTask {
+    pub fn spawn(repo: &Repository, op: RemoteOp) -> Self {
+        Self::spawn_job(repo, op, move |repo, progress| run(repo, op, progress))
+    }
+
+    /// Runs `job` on its own thread with a repository of its own; `op` only
+    /// labels it.
+    pub fn spawn_job(
+        repo: &Repository,
+        op: RemoteOp,
+        job: impl FnOnce(&Repository, &Mutex<Transfer>) -> Result<String> + Send + 'static,
+    ) -> Self {
+        let path: PathBuf = repo.path().to_path_buf();
+        let (tx, rx) = mpsc::channel();
+        let transfer = Arc::new(Mutex::new(Transfer::default()));
+        let progress = Arc::clone(&transfer);
+
+        thread::spawn(move || {
+            let result = Repository::open(&path)
+                .map_err(Into::into)
+                .and_then(|repo| job(&repo, &progress));
+            let _ = tx.send(result);
+        });
+
+        RemoteTask {
+            op,
+            result: rx,
+            transfer,
+        }
+    }
+
+    pub fn transfer(&self) -> Transfer {
+        self.transfer.lock().map(|t| *t).unwrap_or_default()
+    }
+
+    /// `Fetching… 120/450 objects, 1.3 MiB`, or just the operation until the
+    /// remote has said how much there is.
+    pub fn label(&self) -> String {
+        let transfer = self.transfer();
+        if transfer.total == 0 {
+            return self.op.progress().to_string();
+        }
+        format!(
+            "{} {}/{} objects, {:.1} MiB",
+            self.op.progress(),
+            transfer.done,
+            transfer.total,
+            transfer.bytes as f64 / (1024.0 * 1024.0)
+        )
+    }
+
+    /// The outcome once the operation has finished.
+    pub fn poll(&self) -> Option<Result<String>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(anyhow!("remote operation failed"))),
+        }
+    }
+}
+
+fn run(repo: &Repository, op: RemoteOp, progress: &Mutex<Transfer>) -> Result<String> {
+    let branch = current_branch(repo)?;
+    let mut remote = upstream_remote(repo, &branch)?;
+    let remote_name = remote.name().unwrap_or("remote").to_string();
+
+    match op {
+        RemoteOp::Fetch => {
+            fetch(repo, &mut remote, progress)?;
+            Ok(format!("Fetched {}", remote_name))
+        }
+        RemoteOp::Pull => {
+            fetch(repo, &mut remote, progress)?;
+            pull(repo, &branch)
+        }
+        RemoteOp::Push => {
+            let tracked = repo.branch_upstream_name(&branch).is_ok();
+            push(repo, &mut remote, &branch, progress)?;
+
+            // Like `git push -u`, so pulls and the header know where it went
+            if !tracked && set_upstream(repo).is_ok() {
+                return Ok(format!(
+                    "Pushed {} to {} and set it as upstream",
+                    short_name(&branch),
+                    remote_name
+                ));
+            }
+            Ok(format!("Pushed {} to {}", short_name(&branch), remote_name))
+        }
+        RemoteOp::Prune => prune(repo, &remote_name),
+    }
+}
+
+/// Makes the checked-out branch track the branch of the same name on its
+/// remote, like `git branch --set-upstream-to`. That branch has to exist
+/// already; pushing creates it, and tracks it too.
+pub fn set_upstream(repo: &Repository) -> Result<String> {
+    let branch = current_branch(repo)?;
+    let remote = upstream_remote(repo, &branch)?;
+    let upstream = format!(
+        "{}/{}",
+        remote.name().unwrap_or("origin"),
+        short_name(&branch)
+    );
+
+    if repo.find_branch(&upstream, BranchType::Remote).is_err() {
+        bail!("{} doesn't exist yet; push to create it", upstream);
+    }
+    repo.find_branch(short_name(&branch), BranchType::Local)?
+        .set_upstream(Some(&upstream))?;
+
+    Ok(format!("{} now tracks {}", short_name(&branch), upstream))
+}
+
+/// Full ref name of the checked-out branch, e.g. `refs/heads/main`.
+fn current_branch(repo: &Repository) -> Result<String> {
+    let head = repo.head().context("no commits yet")?;
+    if !head.is_branch() {
+        bail!("HEAD is detached; check out a branch first");
+    }
+    Ok(head.name().unwrap_or_default().to_string())
+}
+
+/// The remote the checked-out branch tracks, else `origin`.
+pub fn default_remote(repo: &Repository) -> Result<Remote<'_>> {
+    match current_branch(repo) {
+        Ok(branch) => upstream_remote(repo, &branch),
+        Err(_) => repo
+            .find_remote("origin")
+            .context("no remote \"origin\" configured"),
+    }
+}
+
+/// The remote the branch tracks, else `origin`.
+fn upstream_remote<'r>(repo: &'r Repository, branch: &str) -> Result<Remote<'r>> {
+    let name = repo
+        .branch_upstream_remote(branch)
+        .ok()
+        .and_then(|buf| buf.as_str().map(String::from))
+        .unwrap_or_else(|| String::from("origin"));
+
+    repo.find_remote(&name)
+        .with_context(|| format!("no remote \"{}\" configured", name))
+}
+
+fn fetch(repo: &Repository, remote: &mut Remote, progress: &Mutex<Transfer>) -> Result<()> {
+    let config = repo.config()?;
+    let mut callbacks = callbacks(&config);
+    callbacks.transfer_progress(|stats| {
+        if let Ok(mut transfer) = progress.lock() {
+            *transfer = Transfer {
+                done: stats.received_objects(),
+                total: stats.total_objects(),
+                bytes: stats.received_bytes(),
+            };
+        }
+        true
+    });
+
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(callbacks);
+    options.download_tags(AutotagOption::Auto);
+
+    // An empty refspec list means the remote's configured fetch refspecs
+    remote.fetch::<&str>(&[], Some(&mut options), None)?;
+    Ok(())
+}
+
+/// Deletes the remote-tracking branches of remote `name` whose branches are
+/// gone from it, like `git remote prune`. Returns what to tell the user.
+pub fn prune(repo: &Repository, name: &str) -> Result<String> {
+    let tracking = |repo: &Repository| -> Result<Vec<String>> {
+        let prefix = format!("{}/", name);
+        let mut names = Vec::new();
+        for branch in repo.branches(Some(BranchType::Remote))? {
+            if let Some(branch) = branch?.0.name()?
+                && branch.starts_with(&prefix)
+            {
+                names.push(branch.to_string());
+            }
+        }
+        Ok(names)
+    };
+    let before = tracking(repo)?;
+
+    let config = repo.config()?;
+    let mut remote = repo
+        .find_remote(name)
+        .with_context(|| format!("no remote \"{}\" configured", name))?;
+    let mut connection = remote.connect_auth(Direction::Fetch, Some(callbacks(&config)), None)?;
+    connection.remote().prune(Some(callbacks(&config)))?;
+    drop(connection);
+
+    let after = tracking(repo)?;
+    let pruned: Vec<&str> = before
+        .iter()
+        .filter(|b| !after.contains(b))
+        .map(String::as_str)
+        .collect();
+    Ok(match pruned.len() {
+        0 => format!("Nothing to prune from {}", name),
+        n => format!(
+            "Pruned {} stale branch{} from {}: {}",
+            n,
+            if n == 1 { "" } else { "es" },
+            name,
+            pruned.join(", ")
+        ),
+    })
+}
+
+/// How `git pull` joins diverged histories, from `pull.rebase` and `pull.ff`.
+enum PullMode {
+    Merge,
+    Rebase,
+    FastForwardOnly,
+}
+
+fn pull_mode(repo: &Repository) -> Result<PullMode> {
+    let config = repo.config()?;
+    Ok(if config.get_bool("pull.rebase").unwrap_or(false) {
+        PullMode::Rebase
+    } else if config.get_string("pull.ff").is_ok_and(|ff| ff == "only") {
+        PullMode::FastForwardOnly
+    } else {
+        PullMode::Merge
+    })
+}
+
+/// Brings the branch up to its upstream like `git pull`: a fast-forward when
+/// possible, otherwise a merge or rebase as git is configured to.
+fn pull(repo: &Repository, branch: &str) -> Result<String> {
+    let upstream = repo
+        .find_branch(short_name(branch), git2::BranchType::Local)?
+        .upstream()
+        .map_err(|_| anyhow!("{} has no upstream branch", short_name(branch)))?;
+    let upstream_name = upstream.name()?.unwrap_or("upstream").to_string();
+    let target = upstream
+        .get()
+        .target()
+        .ok_or_else(|| anyhow!("upstream branch has no commit"))?;
+
+    let annotated = repo.find_annotated_commit(target)?;
+    let (analysis, _) = repo.merge_analysis(&[&annotated])?;
+
+    if analysis.is_up_to_date() {
+        return Ok(String::from("Already up to date"));
+    }
+    if analysis.is_fast_forward() {
+        // Safe checkout refuses to overwrite local changes the update would touch
+        let commit = repo.find_commit(target)?;
+        repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().safe()))?;
+        repo.find_reference(branch)?
+            .set_target(target, "pull: fast-forward")?;
+        return Ok(format!("Fast-forwarded to {:.7}", target));
+    }
+
+    match pull_mode(repo)? {
+        PullMode::FastForwardOnly => {
+            bail!("not a fast-forward and pull.ff is \"only\"; merge or rebase manually")
+        }
+        PullMode::Merge => merge(repo, &annotated, &upstream_name),
+        PullMode::Rebase => rebase(repo, &annotated, &upstream_name),
+    }
+}
+
+/// Merges the upstream into HEAD and commits the result. Conflicts are left
+/// in the index and worktree for the user to resolve and commit.
+fn merge(repo: &Repository, upstream: &AnnotatedCommit, name: &str) -> Result<String> {
+    let mut checkout = CheckoutBuilder::new();
+    checkout.safe();
+    repo.merge(&[upstream], None, Some(&mut checkout))?;
+
+    let mut index = repo.index()?;
+    if index.has_conflicts() {
+        return Ok(format!(
+            "Merging {} left conflicts; resolve them and commit",
+            name
+        ));
+    }
+
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let signature = repo.signature()?;
+    let head = repo.head()?.peel_to_commit()?;
+    let theirs = repo.find_commit(upstream.id())?;
+    let oid = repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        &format!("Merge remote-tracking branch '{}'", name),
+        &tree,
+        &[&head, &theirs],
+    )?;
+    repo.cleanup_state()?;
+
+    Ok(format!("Merged {}: {:.7}", name, oid))
+}
+
+/// Replays the local commits onto the upstream. A conflict aborts the whole
+/// rebase, leaving the branch as it was.
+fn rebase(repo: &Repository, upstream: &AnnotatedCommit, name: &str) -> Result<String> {
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(false);
+    if !repo.statuses(Some(&mut opts))?.is_empty() {
+        bail!("commit or stash your changes before pulling with rebase");
+    }
+
+    let signature = repo.signature()?;
+    let mut rebase = repo.rebase(None, Some(upstream), None, None)?;
+    let mut replayed = 0;
+
+    while let Some(operation) = rebase.next() {
+        if let Err(e) = operation {
+            rebase.abort()?;
+            return Err(e.into());
+        }
+        if repo.index()?.has_conflicts() {
+            rebase.abort()?;
+            bail!("rebasing onto {} hit conflicts; nothing was changed", name);
+        }
+
+        match rebase.commit(None, &signature, None) {
+            Ok(_) => replayed += 1,
+            // Already upstream, so the commit simply drops out
+            Err(e) if e.code() == ErrorCode::Applied => {}
+            Err(e) => {
+                rebase.abort()?;
+                return Err(e.into());
+            }
+        }
+    }
+    rebase.finish(Some(&signature))?;
+
+    Ok(format!(
+        "Rebased {} commit{} onto {}",
+        replayed,
+        if replayed == 1 { "" } else { "s" },
+        name
+    ))
+}
+
+fn push(
+    repo: &Repository,
+    remote: &mut Remote,
+    branch: &str,
+    progress: &Mutex<Transfer>,
+) -> Result<()> {
+    // Push to the tracked branch, or to the same name when nothing is tracked
+    let destination = repo
+        .branch_upstream_merge(branch)
+        .ok()
+        .and_then(|buf| buf.as_str().map(String::from))
+        .unwrap_or_else(|| branch.to_string());
+
+    push_refspecs(
+        repo,
+        remote,
+        &[format!("{}:{}", branch, destination)],
+        progress,
+    )
+}
+
+/// Pushes `refspecs` to `remote`, failing when it turns any of them down.
+pub fn push_refspecs(
+    repo: &Repository,
+    remote: &mut Remote,
+    refspecs: &[String],
+    progress: &Mutex<Transfer>,
+) -> Result<()> {
+    let config = repo.config()?;
+    let rejection = RefCell::new(None);
+    {
+        let mut callbacks = callbacks(&config);
+        callbacks.push_transfer_progress(|done, total, bytes| {
+            if let Ok(mut transfer) = progress.lock() {
+                *transfer = Transfer { done, total, bytes };
+            }
+        });
+        callbacks.push_update_reference(|_, status| {
+            if let Some(status) = status {
+                *rejection.borrow_mut() = Some(status.to_string());
+            }
+            Ok(())
+        });
+
+        let mut options = PushOptions::new();
+        options.remote_callbacks(callbacks);
+        remote.push(refspecs, Some(&mut options))?;
+    }
+
+    // Rejections such as non-fast-forward are reported here, not as errors
+    if let Some(reason) = rejection.into_inner() {
+        bail!("push rejected: {}", reason);
+    }
+    Ok(())
+}
+
+/// Credentials from ssh-agent for SSH remotes and git's credential helpers for
+/// HTTPS, giving up after a few rounds instead of letting libgit2 retry forever.
+fn callbacks(config: &git2::Config) -> RemoteCallbacks<'_> {
+    let mut attempts = 0;
+    let mut callbacks = RemoteCallbacks::new();
+
+    callbacks.credentials(move |url, username, allowed| {
+        attempts += 1;
+        if attempts > 3 {
+            return Err(git2::Error::from_str("authentication failed"));
+        }
+
+        if allowed.contains(CredentialType::SSH_KEY) {
+            return Cred::ssh_key_from_agent(username.unwrap_or("git"));
+        }
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            return Cred::credential_helper(config, url, username);
+        }
+        if allowed.contains(CredentialType::USERNAME) {
+            return Cred::username(username.unwrap_or("git"));
+        }
+        Cred::default()
+    });
+
+    callbacks
+}
+
+fn short_name(branch: &str) -> &str {
+    branch.strip_prefix("refs/heads/").unwrap_or(branch)
+}
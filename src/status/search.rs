@@ -0,0 +1,181 @@
+use crate::theme::Theme;
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+use std::ops::Range;
+
+/// A `/` search through the diff pane: where the query matches in the
+/// shown diff, and which match `n` and `N` are on.
+pub struct DiffSearch {
+    pub query: String,
+    /// Still typing the query; Enter keeps it, Esc drops the search
+    pub editing: bool,
+    /// Each match as its diff line and which match of that line it is
+    matches: Vec<(usize, usize)>,
+    current: usize,
+}
+
+impl DiffSearch {
+    pub fn new() -> Self {
+        DiffSearch {
+            query: String::new(),
+            editing: true,
+            matches: Vec::new(),
+            current: 0,
+        }
+    }
+
+    /// Looks for the query in `lines` again, as after typing or once another
+    /// diff is shown, starting from the first match at or after line `from`.
+    pub fn find(&mut self, lines: &[Line], gutter: bool, from: usize) {
+        self.matches = lines
+            .iter()
+            .enumerate()
+            .flat_map(|(i, line)| {
+                let count = ranges(&text(line, gutter), &self.query).len();
+                (0..count).map(move |n| (i, n))
+            })
+            .collect();
+        self.current = self
+            .matches
+            .iter()
+            .position(|&(line, _)| line >= from)
+            .unwrap_or(0);
+    }
+
+    /// The diff line of the match `n` and `N` are on.
+    pub fn line(&self) -> Option<usize> {
+        self.matches.get(self.current).map(|&(line, _)| line)
+    }
+
+    /// Which match of line `line` the current one is, when it's on that line.
+    pub fn current_on(&self, line: usize) -> Option<usize> {
+        self.matches
+            .get(self.current)
+            .filter(|&&(l, _)| l == line)
+            .map(|&(_, n)| n)
+    }
+
+    /// Moves to the next match, or the previous one, going round at the ends.
+    pub fn step(&mut self, forward: bool) {
+        let count = self.matches.len();
+        if count > 0 {
+            self.current = if forward {
+                (self.current + 1) % count
+            } else {
+                (self.current + count - 1) % count
+            };
+        }
+    }
+
+    /// How far the current match starts into line `line`, in characters,
+    /// for scrolling sideways to it.
+    pub fn column(&self, line: &Line, gutter: bool) -> usize {
+        let Some(&(_, n)) = self.matches.get(self.current) else {
+            return 0;
+        };
+        let skipped = if gutter {
+            line.spans.first().map_or(0, |s| s.content.chars().count())
+        } else {
+            0
+        };
+        let text = text(line, gutter);
+        ranges(&text, &self.query)
+            .get(n)
+            .map_or(0, |r| skipped + text[..r.start].chars().count())
+    }
+
+    /// `3/17`, or `no matches`.
+    pub fn counter(&self) -> String {
+        if self.matches.is_empty() {
+            String::from("no matches")
+        } else {
+            format!("{}/{}", self.current + 1, self.matches.len())
+        }
+    }
+
+    /// `line` with every match of the query marked; match `current` of it,
+    /// when the current one is on it, stands out more.
+    pub fn highlight(&self, line: &mut Line, gutter: bool, current: Option<usize>, theme: &Theme) {
+        let found = ranges(&text(line, gutter), &self.query);
+        if found.is_empty() {
+            return;
+        }
+        // Not reversed, which changed words already are
+        let style = if theme.monochrome {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default()
+                .fg(Color::Black)
+                .bg(theme.accent)
+                .remove_modifier(Modifier::REVERSED)
+        };
+
+        // Offsets count from after the gutter, which is left alone
+        let (kept, spans) = line
+            .spans
+            .split_at(usize::from(gutter).min(line.spans.len()));
+        let mut marked: Vec<Span> = kept.to_vec();
+        let mut offset = 0;
+        for span in spans {
+            let content = span.content.as_ref();
+            let end = offset + content.len();
+            let mut at = offset;
+            for (n, range) in found.iter().enumerate() {
+                let (start, stop) = (range.start.max(at), range.end.min(end));
+                if start >= stop {
+                    continue;
+                }
+                if start > at {
+                    marked.push(Span::styled(
+                        content[at - offset..start - offset].to_string(),
+                        span.style,
+                    ));
+                }
+                let mut style = span.style.patch(style);
+                if current == Some(n) {
+                    style = style.add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+                }
+                marked.push(Span::styled(
+                    content[start - offset..stop - offset].to_string(),
+                    style,
+                ));
+                at = stop;
+            }
+            if at < end {
+                marked.push(Span::styled(content[at - offset..].to_string(), span.style));
+            }
+            offset = end;
+        }
+        line.spans = marked;
+    }
+}
+
+/// The line's text past its line number gutter.
+fn text(line: &Line, gutter: bool) -> String {
+    line.spans
+        .iter()
+        .skip(usize::from(gutter))
+        .map(|s| s.content.as_ref())
+        .collect()
+}
+
+/// Where `query` occurs in `text`, without overlapping. Case is ignored
+/// unless the query has a capital in it, as with smartcase in vim.
+fn ranges(text: &str, query: &str) -> Vec<Range<usize>> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let fold = !query.chars().any(char::is_uppercase);
+    // ASCII folding keeps byte offsets the same as in `text`
+    let (haystack, needle) = if fold {
+        (text.to_ascii_lowercase(), query.to_ascii_lowercase())
+    } else {
+        (text.to_string(), query.to_string())
+    };
+    haystack
+        .match_indices(&needle)
+        .map(|(i, m)| i..i + m.len())
+        .collect()
+}
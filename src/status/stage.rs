@@ -0,0 +1,333 @@
+use anyhow::{Result, anyhow, bail};
+use git2::{ApplyLocation, ApplyOptions, Delta, Diff, IndexAddOption, Patch, Repository, Status};
+use helper_git_core::diff::{DiffSettings, file_diff};
+use helper_git_core::status::{FileEntry, Section};
+
+/// Stages hunk `hunk` of an unstaged entry, or unstages it from a staged one.
+pub fn toggle_hunk(
+    repo: &Repository,
+    entry: &FileEntry,
+    settings: DiffSettings,
+    hunk: usize,
+) -> Result<()> {
+    if entry.rename.is_some() {
+        return toggle_rename(repo, entry);
+    }
+
+    // libgit2 cannot apply an untracked delta, but its one hunk is the whole file.
+    // `add_path` skips the ignore rules, so an ignored file is force-added.
+    if entry.section == Section::Worktree
+        && repo
+            .status_file(&entry.path)?
+            .intersects(Status::WT_NEW | Status::IGNORED)
+    {
+        let mut index = repo.index()?;
+        index.add_path(&entry.path)?;
+        index.write()?;
+        return Ok(());
+    }
+
+    // Unstaging applies the reversed HEAD→index diff back onto the index
+    let reverse = entry.section == Section::Index;
+    let diff = file_diff(repo, entry, settings, reverse)?;
+
+    apply_hunks(repo, &diff, |i| i == hunk)
+}
+
+/// Stages or unstages the one `+` or `-` line `line` of hunk `hunk`, by
+/// applying that hunk with every other change in it left out.
+pub fn toggle_line(
+    repo: &Repository,
+    entry: &FileEntry,
+    settings: DiffSettings,
+    hunk: usize,
+    line: usize,
+) -> Result<()> {
+    if entry.rename.is_some() {
+        bail!("renames can only be staged whole");
+    }
+
+    let diff = file_diff(repo, entry, settings, false)?;
+    let patch = Patch::from_diff(&diff, 0)?.ok_or_else(|| anyhow!("nothing to stage"))?;
+    if patch.delta().status() != Delta::Modified {
+        bail!("only modified files can be staged line by line");
+    }
+
+    // Unstaging applies the reverse of the HEAD→index change, so the sides swap
+    let unstage = entry.section == Section::Index;
+    let (header, count) = patch.hunk(hunk)?;
+    let mut body: Vec<u8> = Vec::new();
+    let (mut old_lines, mut new_lines) = (0, 0);
+
+    for i in 0..count {
+        let diff_line = patch.line_in_hunk(hunk, i)?;
+        let origin = match (diff_line.origin(), unstage) {
+            ('+', true) => '-',
+            ('-', true) => '+',
+            (origin, _) => origin,
+        };
+
+        // An addition left out is dropped; a deletion left out stays as context
+        let kept = match origin {
+            ' ' => ' ',
+            '+' if i == line => '+',
+            '-' if i == line => '-',
+            '-' => ' ',
+            _ => continue,
+        };
+        if kept != '+' {
+            old_lines += 1;
+        }
+        if kept != '-' {
+            new_lines += 1;
+        }
+
+        body.push(kept as u8);
+        body.extend_from_slice(diff_line.content());
+        if !diff_line.content().ends_with(b"\n") {
+            body.extend_from_slice(b"\n\\ No newline at end of file\n");
+        }
+    }
+
+    if old_lines == new_lines {
+        bail!("not a changed line");
+    }
+
+    let start = if unstage {
+        header.new_start()
+    } else {
+        header.old_start()
+    };
+    // The path goes in as git has it, which needn't be UTF-8
+    let path = entry.path.as_os_str().as_encoded_bytes();
+    let header: [&[u8]; 9] = [
+        b"diff --git a/",
+        path,
+        b" b/",
+        path,
+        b"\n--- a/",
+        path,
+        b"\n+++ b/",
+        path,
+        b"\n",
+    ];
+    let mut text = header.concat();
+    text.extend(format!("@@ -{start},{old_lines} +{start},{new_lines} @@\n").into_bytes());
+    text.extend(body);
+
+    let reduced = Diff::from_buffer(&text)?;
+    repo.apply(&reduced, ApplyLocation::Index, None)?;
+    Ok(())
+}
+
+/// Stages a whole worktree file like `git add`, or unstages a whole staged one
+/// like `git restore --staged`.
+pub fn toggle_file(repo: &Repository, entry: &FileEntry) -> Result<()> {
+    if entry.rename.is_some() {
+        return toggle_rename(repo, entry);
+    }
+
+    let path = &entry.path;
+
+    if entry.section == Section::Index {
+        match repo.head() {
+            Ok(head) => {
+                let head = head.peel_to_commit()?;
+                repo.reset_default(Some(head.as_object()), [path])?;
+            }
+            // Before the first commit everything staged is new, so it just leaves the index
+            Err(_) => {
+                let mut index = repo.index()?;
+                index.remove_path(path)?;
+                index.write()?;
+            }
+        }
+        return Ok(());
+    }
+
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("cannot stage: repository has no working directory"))?;
+
+    // Adding also clears any conflict entries, which is how git marks a file resolved
+    let mut index = repo.index()?;
+    if workdir.join(path).symlink_metadata().is_ok() {
+        index.add_path(path)?;
+    } else {
+        index.remove_path(path)?;
+    }
+    index.write()?;
+    Ok(())
+}
+
+/// Stages or unstages a rename as a whole: both paths move together, since
+/// half a rename is just a delete or an add.
+fn toggle_rename(repo: &Repository, entry: &FileEntry) -> Result<()> {
+    let Some(rename) = &entry.rename else {
+        return Ok(());
+    };
+    let paths = [rename.from.as_path(), entry.path.as_path()];
+
+    if entry.section == Section::Index {
+        // Like `git reset -- old new`; before the first commit nothing was renamed
+        let head = repo.head()?.peel_to_commit()?;
+        repo.reset_default(Some(head.as_object()), paths)?;
+        return Ok(());
+    }
+
+    let mut index = repo.index()?;
+    index.remove_path(&rename.from)?;
+    index.add_path(&entry.path)?;
+    index.write()?;
+    Ok(())
+}
+
+/// Stages every worktree change like `git add -A`: new and modified files are
+/// added, deleted ones removed from the index.
+pub fn stage_all(repo: &Repository) -> Result<()> {
+    let mut index = repo.index()?;
+    index.add_all(["*"], IndexAddOption::DEFAULT, None)?;
+    index.update_all(["*"], None)?;
+    index.write()?;
+    Ok(())
+}
+
+/// Resets the whole index to HEAD like `git reset`, leaving the worktree alone.
+pub fn unstage_all(repo: &Repository) -> Result<()> {
+    let mut index = repo.index()?;
+
+    // Before the first commit everything staged is simply new
+    match repo.head() {
+        Ok(head) => index.read_tree(&head.peel_to_tree()?)?,
+        Err(_) => index.clear()?,
+    }
+
+    index.write()?;
+    Ok(())
+}
+
+/// Applies the hunks of `diff` selected by `keep` (by position) to the index.
+fn apply_hunks(repo: &Repository, diff: &Diff, keep: impl Fn(usize) -> bool) -> Result<()> {
+    let mut seen = 0;
+    let mut opts = ApplyOptions::new();
+    opts.hunk_callback(|_| {
+        let selected = keep(seen);
+        seen += 1;
+        selected
+    });
+
+    repo.apply(diff, ApplyLocation::Index, Some(&mut opts))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helper_git_core::testing::{commit, repo, write};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    fn staged(repo: &Repository, path: &str) -> String {
+        let entry = repo.index().unwrap().get_path(Path::new(path), 0).unwrap();
+        let blob = repo.find_blob(entry.id).unwrap();
+        String::from_utf8(blob.content().to_vec()).unwrap()
+    }
+
+    fn numbered(changed: &[usize]) -> String {
+        (1..=20)
+            .map(|i| {
+                if changed.contains(&i) {
+                    format!("changed {}\n", i)
+                } else {
+                    format!("line {}\n", i)
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn one_hunk_of_two_is_staged_and_unstaged() {
+        let (_dir, repo) = repo();
+        write(&repo, "a.txt", &numbered(&[]));
+        commit(&repo, "first");
+        // Far enough apart for their context not to join them
+        write(&repo, "a.txt", &numbered(&[2, 18]));
+
+        let entry = |section| FileEntry {
+            path: PathBuf::from("a.txt"),
+            section,
+            rename: None,
+        };
+        let settings = DiffSettings::default();
+
+        toggle_hunk(&repo, &entry(Section::Worktree), settings, 1).unwrap();
+        assert_eq!(staged(&repo, "a.txt"), numbered(&[18]));
+
+        toggle_hunk(&repo, &entry(Section::Index), settings, 0).unwrap();
+        assert_eq!(staged(&repo, "a.txt"), numbered(&[]));
+    }
+
+    #[test]
+    fn single_lines_of_a_hunk_are_staged() {
+        let (_dir, repo) = repo();
+        write(&repo, "a.txt", "a\nb\nc\nd\n");
+        commit(&repo, "first");
+        // One hunk: ` a`, `-b`, `+x`, `+y`, ` c`, ` d`
+        write(&repo, "a.txt", "a\nx\ny\nc\nd\n");
+        let entry = FileEntry {
+            path: PathBuf::from("a.txt"),
+            section: Section::Worktree,
+            rename: None,
+        };
+        // Context as in the status view, so the hunks have the lines commented
+        let settings = DiffSettings {
+            context_lines: 3,
+            ..Default::default()
+        };
+
+        toggle_line(&repo, &entry, settings, 0, 3).unwrap();
+        assert_eq!(staged(&repo, "a.txt"), "a\nb\ny\nc\nd\n");
+
+        // Against the index now: ` a`, `-b`, `+x`, ` y`, ` c`, ` d`
+        toggle_line(&repo, &entry, settings, 0, 1).unwrap();
+        assert_eq!(staged(&repo, "a.txt"), "a\ny\nc\nd\n");
+    }
+
+    #[test]
+    fn single_lines_of_a_hunk_are_unstaged() {
+        let (_dir, repo) = repo();
+        write(&repo, "a.txt", "a\nb\nc\nd\n");
+        commit(&repo, "first");
+        write(&repo, "a.txt", "a\nx\ny\nc\nd\n");
+        let entry = FileEntry {
+            path: PathBuf::from("a.txt"),
+            section: Section::Index,
+            rename: None,
+        };
+        toggle_file(
+            &repo,
+            &FileEntry {
+                section: Section::Worktree,
+                ..entry.clone()
+            },
+        )
+        .unwrap();
+        let settings = DiffSettings {
+            context_lines: 3,
+            ..Default::default()
+        };
+
+        // Staged: ` a`, `-b`, `+x`, `+y`, ` c`, ` d`
+        toggle_line(&repo, &entry, settings, 0, 3).unwrap();
+        assert_eq!(staged(&repo, "a.txt"), "a\nx\nc\nd\n");
+
+        // Staged now: ` a`, `-b`, `+x`, ` c`, ` d`
+        toggle_line(&repo, &entry, settings, 0, 1).unwrap();
+        assert_eq!(staged(&repo, "a.txt"), "a\nb\nx\nc\nd\n");
+        // The worktree keeps every change
+        assert_eq!(
+            fs::read_to_string(repo.workdir().unwrap().join("a.txt")).unwrap(),
+            "a\nx\ny\nc\nd\n"
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use super::diff::FileDiff;
+use crate::theme::Theme;
+use anyhow::Result;
+use git2::{Oid, Repository, Submodule, SubmoduleIgnore, SubmoduleStatus};
+use helper_git_core::status::{FileEntry, Section};
+use ratatui::{style::Style, text::Line};
+
+/// Commits listed per direction before the rest are only counted.
+const LOG_LIMIT: usize = 50;
+
+/// What changed in a submodule: the recorded commit on either side of the
+/// entry's section, and whether its own worktree is dirty. A text diff of a
+/// gitlink is just two hashes, so this replaces it.
+pub fn summary(
+    repo: &Repository,
+    submodule: &Submodule,
+    entry: &FileEntry,
+    theme: &Theme,
+) -> Result<FileDiff> {
+    let (old, new) = match entry.section {
+        Section::Index => (submodule.head_id(), submodule.index_id()),
+        Section::Worktree | Section::Conflicted => (submodule.index_id(), submodule.workdir_id()),
+    };
+
+    // Commit summaries live in the submodule's own repository, if it is checked out
+    let inner = submodule.open().ok();
+    let describe = |id: Option<Oid>| match id {
+        Some(id) => {
+            let summary = inner
+                .as_ref()
+                .and_then(|r| r.find_commit(id).ok())
+                .and_then(|c| c.summary().map(String::from));
+            match summary {
+                Some(summary) => format!("{:.7} {}", id, summary),
+                None => format!("{:.7}", id),
+            }
+        }
+        None => String::from("(none)"),
+    };
+
+    let mut lines = vec![
+        Line::styled(
+            format!("Submodule: {}", entry.path.display()),
+            Style::default().fg(theme.file_header),
+        ),
+        Line::from(""),
+    ];
+
+    if old != new {
+        lines.push(Line::styled(
+            format!("-Subproject commit {}", describe(old)),
+            Style::default().fg(theme.deletion),
+        ));
+        lines.push(Line::styled(
+            format!("+Subproject commit {}", describe(new)),
+            Style::default().fg(theme.addition),
+        ));
+    } else {
+        lines.push(Line::from(format!("Subproject commit {}", describe(new))));
+    }
+
+    // Like `git diff --submodule=log`: the commits gained, and those dropped
+    // when it went back or sideways
+    if let (Some(inner), Some(old), Some(new)) = (&inner, old, new)
+        && old != new
+    {
+        let gained = commits_between(inner, old, new);
+        let dropped = commits_between(inner, new, old);
+        if !gained.is_empty() || !dropped.is_empty() {
+            lines.push(Line::from(""));
+        }
+        for (commits, mark, color) in [
+            (gained, '>', theme.addition),
+            (dropped, '<', theme.deletion),
+        ] {
+            let more = commits.len().saturating_sub(LOG_LIMIT);
+            for text in commits.into_iter().take(LOG_LIMIT) {
+                lines.push(Line::styled(
+                    format!("  {} {}", mark, text),
+                    Style::default().fg(color),
+                ));
+            }
+            if more > 0 {
+                lines.push(Line::styled(
+                    format!("  {} … {} more", mark, more),
+                    Style::default().dim(),
+                ));
+            }
+        }
+    }
+
+    // Dirt inside the submodule only matters for the worktree side
+    if entry.section != Section::Index {
+        // The submodule was found by this name, so it has one
+        let name = submodule.name().unwrap_or_default();
+        let status = repo.submodule_status(name, SubmoduleIgnore::None)?;
+
+        let mut notes = Vec::new();
+        if status.contains(SubmoduleStatus::WD_UNINITIALIZED) {
+            notes.push("not initialized");
+        }
+        if status.intersects(SubmoduleStatus::WD_INDEX_MODIFIED | SubmoduleStatus::WD_WD_MODIFIED) {
+            notes.push("has uncommitted changes");
+        }
+        if status.contains(SubmoduleStatus::WD_UNTRACKED) {
+            notes.push("has untracked files");
+        }
+
+        if !notes.is_empty() {
+            lines.push(Line::from(""));
+            for note in notes {
+                lines.push(Line::styled(
+                    format!("Submodule {}", note),
+                    Style::default().fg(theme.modified),
+                ));
+            }
+        }
+    }
+
+    Ok(FileDiff {
+        lines,
+        ..Default::default()
+    })
+}
+
+/// `hash summary` of each commit reachable from `to` but not from `from`,
+/// newest first; empty when either isn't in the submodule (not fetched yet).
+fn commits_between(repo: &Repository, from: Oid, to: Oid) -> Vec<String> {
+    let Ok(mut walk) = repo.revwalk() else {
+        return Vec::new();
+    };
+    if walk.push(to).is_err() || walk.hide(from).is_err() {
+        return Vec::new();
+    }
+    walk.filter_map(|id| repo.find_commit(id.ok()?).ok())
+        .map(|commit| format!("{:.7} {}", commit.id(), commit.summary().unwrap_or("")))
+        .collect()
+}
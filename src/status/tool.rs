@@ -0,0 +1,204 @@
+use anyhow::{Context, Result, anyhow, bail};
+use git2::{Oid, Repository};
+use helper_git_core::status::{FileEntry, Section};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+/// Tools that work without a `difftool.<tool>.cmd`: name, program, and its
+/// arguments for a diff and for a merge, as shell words over the `$LOCAL`,
+/// `$REMOTE`, `$BASE` and `$MERGED` files the way git passes them.
+#[rustfmt::skip]
+const TOOLS: &[(&str, &str, &str, &str)] = &[
+    ("meld", "meld", r#""$LOCAL" "$REMOTE""#, r#""$LOCAL" "$MERGED" "$REMOTE" --output "$MERGED""#),
+    ("kdiff3", "kdiff3", r#""$LOCAL" "$REMOTE""#, r#""$BASE" "$LOCAL" "$REMOTE" -o "$MERGED""#),
+    ("vimdiff", "vim", r#"-d "$LOCAL" "$REMOTE""#, r#"-d -c "wincmd J" "$MERGED" "$LOCAL" "$BASE" "$REMOTE""#),
+    ("nvimdiff", "nvim", r#"-d "$LOCAL" "$REMOTE""#, r#"-d -c "wincmd J" "$MERGED" "$LOCAL" "$BASE" "$REMOTE""#),
+    ("vscode", "code", r#"--wait --diff "$LOCAL" "$REMOTE""#, r#"--wait --merge "$REMOTE" "$LOCAL" "$BASE" "$MERGED""#),
+    ("opendiff", "opendiff", r#""$LOCAL" "$REMOTE""#, r#""$LOCAL" "$REMOTE" -ancestor "$BASE" -merge "$MERGED""#),
+    ("bc", "bcompare", r#""$LOCAL" "$REMOTE""#, r#""$LOCAL" "$REMOTE" "$BASE" "$MERGED""#),
+    ("p4merge", "p4merge", r#""$LOCAL" "$REMOTE""#, r#""$BASE" "$REMOTE" "$LOCAL" "$MERGED""#),
+    ("tkdiff", "tkdiff", r#""$LOCAL" "$REMOTE""#, r#"-a "$BASE" -o "$MERGED" "$LOCAL" "$REMOTE""#),
+];
+
+/// Opens `entry` in the configured `diff.tool`, or in `merge.tool` when it is
+/// conflicted, and waits for the tool to exit. Sides that aren't files on
+/// disk are written out to a temporary directory first. A merge whose result
+/// has no conflict markers left is staged as resolved, and what happened comes
+/// back as the message to show.
+pub fn open(repo: &Repository, entry: &FileEntry) -> Result<Option<String>> {
+    let merge = entry.section == Section::Conflicted;
+    let (tool, command) = command(repo, merge)?;
+
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("repository has no working directory"))?;
+    let worktree_file = workdir.join(&entry.path);
+    let old_path = entry.rename.as_ref().map_or(&entry.path, |r| &r.from);
+    let scratch = Scratch::new()?;
+
+    // Set like git sets them for its tools
+    let (local, remote, base, merged) = if merge {
+        let index = repo.index()?;
+        let stage = |n| index.get_path(&entry.path, n).map(|e| e.id);
+        (
+            scratch.write(repo, &entry.path, "LOCAL", stage(2))?,
+            scratch.write(repo, &entry.path, "REMOTE", stage(3))?,
+            scratch.write(repo, &entry.path, "BASE", stage(1))?,
+            worktree_file.clone(),
+        )
+    } else {
+        let head = repo.head().and_then(|h| h.peel_to_tree()).ok();
+        let in_head = |path: &Path| {
+            head.as_ref()
+                .and_then(|tree| tree.get_path(path).ok())
+                .map(|e| e.id())
+        };
+        let index = repo.index()?;
+        let in_index = |path: &Path| index.get_path(path, 0).map(|e| e.id);
+
+        // The worktree file itself, not a copy, so edits made in the tool stick
+        let (local, remote) = match entry.section {
+            Section::Index => (
+                scratch.write(repo, old_path, "LOCAL", in_head(old_path))?,
+                scratch.write(repo, &entry.path, "REMOTE", in_index(&entry.path))?,
+            ),
+            _ if worktree_file.is_file() => (
+                scratch.write(repo, old_path, "LOCAL", in_index(old_path))?,
+                worktree_file.clone(),
+            ),
+            _ => (
+                scratch.write(repo, old_path, "LOCAL", in_index(old_path))?,
+                scratch.write(repo, &entry.path, "REMOTE", None)?,
+            ),
+        };
+        // A diff has no base; tools that ask for one get an empty file
+        let base = scratch.write(repo, &entry.path, "BASE", None)?;
+        (local, remote.clone(), base, remote)
+    };
+
+    // Diff tools often exit non-zero just because the files differ, so as in
+    // `git difftool` the exit code is not trusted; a merge is judged by its result
+    Command::new("sh")
+        .arg("-c")
+        .arg(&command)
+        .env("LOCAL", &local)
+        .env("REMOTE", &remote)
+        .env("BASE", &base)
+        .env("MERGED", &merged)
+        .status()
+        .with_context(|| format!("cannot run {}", tool))?;
+
+    if !merge {
+        return Ok(None);
+    }
+
+    let content = fs::read(&worktree_file)?;
+    let markers = String::from_utf8_lossy(&content)
+        .lines()
+        .any(|line| line.starts_with("<<<<<<<") || line.starts_with(">>>>>>>"));
+    if markers {
+        return Ok(Some(format!(
+            "{} still has conflict markers",
+            entry.path.display()
+        )));
+    }
+
+    let mut index = repo.index()?;
+    index.add_path(&entry.path)?;
+    index.write()?;
+    Ok(Some(format!("Resolved {}", entry.path.display())))
+}
+
+/// The tool's name and the shell command that runs it, from
+/// `<diff|merge>tool.<tool>.cmd` or otherwise the built-in list, with
+/// `.path` naming another program. `git difftool` falls back to
+/// `merge.tool` too.
+fn command(repo: &Repository, merge: bool) -> Result<(String, String)> {
+    let config = repo.config()?;
+    let (kind, section) = if merge {
+        ("merge", "mergetool")
+    } else {
+        ("diff", "difftool")
+    };
+
+    let tool = config
+        .get_string(&format!("{}.tool", kind))
+        .or_else(|e| {
+            if merge {
+                Err(e)
+            } else {
+                config.get_string("merge.tool")
+            }
+        })
+        .map_err(|_| {
+            anyhow!(
+                "no {}.tool configured; set one with `git config {}.tool meld`",
+                kind,
+                kind
+            )
+        })?;
+
+    if let Ok(command) = config.get_string(&format!("{}.{}.cmd", section, tool)) {
+        return Ok((tool, command));
+    }
+
+    let Some((_, program, diff_args, merge_args)) = TOOLS.iter().find(|(name, ..)| *name == tool)
+    else {
+        bail!(
+            "unknown {} \"{}\"; set {}.{}.cmd to run it",
+            kind,
+            tool,
+            section,
+            tool
+        );
+    };
+    let program = config
+        .get_string(&format!("{}.{}.path", section, tool))
+        .unwrap_or_else(|_| program.to_string());
+    let args = if merge { merge_args } else { diff_args };
+
+    Ok((tool, format!("\"{}\" {}", program, args)))
+}
+
+/// A temporary directory for the sides that only exist as blobs, removed
+/// again once the tool is done.
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new() -> Result<Self> {
+        let dir = std::env::temp_dir().join(format!("helper-git-{}", process::id()));
+        fs::create_dir_all(&dir)?;
+        Ok(Scratch(dir))
+    }
+
+    /// Writes blob `id` (empty when `None`) as `name_SIDE.ext`, keeping the
+    /// extension so tools still pick the right syntax.
+    fn write(
+        &self,
+        repo: &Repository,
+        path: &Path,
+        side: &str,
+        id: Option<Oid>,
+    ) -> Result<PathBuf> {
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("file");
+        let name = match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) => format!("{}_{}.{}", stem, side, ext),
+            None => format!("{}_{}", stem, side),
+        };
+
+        let content = match id {
+            Some(id) => repo.find_blob(id)?.content().to_vec(),
+            None => Vec::new(),
+        };
+        let file = self.0.join(name);
+        fs::write(&file, content)?;
+        Ok(file)
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
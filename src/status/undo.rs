@@ -0,0 +1,177 @@
+use super::discard;
+use anyhow::{Result, anyhow, bail};
+use git2::{Oid, Repository, ResetType, Status};
+use helper_git_core::status::FileEntry;
+use std::path::PathBuf;
+
+/// How many steps are kept; older ones fall off the bottom.
+const DEPTH: usize = 50;
+
+/// How to put back what one action changed.
+enum Undo {
+    /// The index as a tree before the action, and after it
+    Index { before: Oid, after: Oid },
+    /// Files thrown away by a discard or a stash, kept as this stash commit
+    Stash { stash: Oid, paths: Vec<PathBuf> },
+    /// A commit or an amend: HEAD before it (none on an unborn branch) and
+    /// the commit it made
+    Commit { before: Option<Oid>, after: Oid },
+}
+
+struct Step {
+    /// `staging a hunk of src/main.rs`, for `Undid …` and the palette
+    what: String,
+    undo: Undo,
+}
+
+/// What the status view did this session that `u` can take back, newest
+/// last. Each step checks the repository is still as the action left it, so
+/// an undo never throws away what was done since by other means.
+#[derive(Default)]
+pub struct UndoStack {
+    steps: Vec<Step>,
+}
+
+/// The index as a tree, to compare or go back to; `None` while it has
+/// conflicts, which a tree can't hold.
+pub fn index_tree(repo: &Repository) -> Option<Oid> {
+    repo.index().and_then(|mut index| index.write_tree()).ok()
+}
+
+/// The commit HEAD is on; `None` on an unborn branch.
+pub fn head(repo: &Repository) -> Option<Oid> {
+    repo.head().ok().and_then(|head| head.target())
+}
+
+impl UndoStack {
+    fn push(&mut self, what: String, undo: Undo) {
+        if self.steps.len() == DEPTH {
+            self.steps.remove(0);
+        }
+        self.steps.push(Step { what, undo });
+    }
+
+    /// Records a change to the index that started from `before`, unless it
+    /// changed nothing.
+    pub fn index(&mut self, repo: &Repository, what: impl Into<String>, before: Option<Oid>) {
+        if let (Some(before), Some(after)) = (before, index_tree(repo))
+            && before != after
+        {
+            self.push(what.into(), Undo::Index { before, after });
+        }
+    }
+
+    /// Records `entries` saved to the stash as `stash` before they were
+    /// thrown away.
+    pub fn stash(&mut self, what: impl Into<String>, stash: Oid, entries: &[FileEntry]) {
+        let paths = entries.iter().map(|e| e.path.clone()).collect();
+        self.push(what.into(), Undo::Stash { stash, paths });
+    }
+
+    /// Records the commit HEAD moved to from `before`, if it moved.
+    pub fn commit(&mut self, repo: &Repository, what: &str, before: Option<Oid>) {
+        if let Some(after) = head(repo)
+            && before != Some(after)
+        {
+            self.push(
+                format!("{} {:.7}", what, after),
+                Undo::Commit { before, after },
+            );
+        }
+    }
+
+    /// What the last `count` steps did, newest first.
+    pub fn recent(&self, count: usize) -> Vec<String> {
+        self.steps
+            .iter()
+            .rev()
+            .take(count)
+            .map(|step| step.what.clone())
+            .collect()
+    }
+
+    /// Takes back the newest step and says what was undone. A step the
+    /// repository has moved on from is dropped; one that failed to apply
+    /// stays, to try again once what was in the way is gone.
+    pub fn undo(&mut self, repo: &Repository) -> Result<String> {
+        let Some(step) = self.steps.pop() else {
+            bail!("nothing to undo");
+        };
+
+        // ---------- Still as the action left it ----------
+        let moved = match &step.undo {
+            Undo::Index { after, .. } => {
+                (index_tree(repo) != Some(*after)).then_some("the index has changed since")
+            }
+            Undo::Stash { stash, paths } => {
+                if stash_index(repo, *stash).is_none() {
+                    Some("its stash entry has been dropped")
+                } else {
+                    // Each is as it was left: like HEAD, or gone when it was untracked
+                    paths
+                        .iter()
+                        .any(|path| {
+                            repo.status_file(path)
+                                .is_ok_and(|status| status != Status::CURRENT)
+                        })
+                        .then_some("the files have changed since")
+                }
+            }
+            Undo::Commit { after, .. } => {
+                (head(repo) != Some(*after)).then_some("HEAD has moved since")
+            }
+        };
+        if let Some(reason) = moved {
+            bail!("can't undo {}: {}", step.what, reason);
+        }
+
+        let result = (|| -> Result<()> {
+            match &step.undo {
+                Undo::Index { before, .. } => {
+                    let mut index = repo.index()?;
+                    index.read_tree(&repo.find_tree(*before)?)?;
+                    index.write()?;
+                }
+                Undo::Stash { stash, paths } => {
+                    let at = stash_index(repo, *stash).unwrap_or(0);
+                    discard::restore(repo, *stash, at, paths)?;
+                }
+                // Like `git reset --soft HEAD@{1}`, the index keeps what was committed
+                Undo::Commit {
+                    before: Some(before),
+                    ..
+                } => {
+                    let commit = repo.find_object(*before, None)?;
+                    repo.reset(&commit, ResetType::Soft, None)?;
+                }
+                // The first commit leaves the branch unborn again, as `git update-ref -d HEAD`
+                Undo::Commit { before: None, .. } => repo.head()?.delete()?,
+            }
+            Ok(())
+        })();
+
+        match result {
+            Ok(()) => Ok(format!("Undid {}", step.what)),
+            Err(e) => {
+                let message = format!("can't undo {}: {}", step.what, e);
+                self.steps.push(step);
+                Err(anyhow!(message))
+            }
+        }
+    }
+}
+
+/// Where the stash commit `stash` is in the stash list, if it's still there.
+fn stash_index(repo: &Repository, stash: Oid) -> Option<usize> {
+    let mut at = None;
+    // Walking the stash reflog needs the repository mutably
+    let mut repo = Repository::open(repo.path()).ok()?;
+    repo.stash_foreach(|i, _, oid| {
+        if *oid == stash {
+            at = Some(i);
+        }
+        at.is_none()
+    })
+    .ok()?;
+    at
+}
@@ -0,0 +1,109 @@
+use anyhow::{Result, anyhow};
+use git2::Repository;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+/// How long the tree has to stay quiet before a burst of changes (a checkout,
+/// a build, an editor's save dance) counts as one.
+const SETTLE: Duration = Duration::from_millis(200);
+
+/// Watches the worktree, and the parts of `.git` that change what `git
+/// status` says, so the status view can refresh itself.
+pub struct Watcher {
+    // Dropping it stops the watch
+    _watcher: RecommendedWatcher,
+    events: Receiver<Vec<PathBuf>>,
+    last_change: Option<Instant>,
+    /// Worktree paths written since the last settled change
+    written: Vec<PathBuf>,
+}
+
+impl Watcher {
+    pub fn spawn(repo: &Repository) -> Result<Self> {
+        let workdir = repo
+            .workdir()
+            .ok_or_else(|| anyhow!("repository has no working directory"))?
+            .to_path_buf();
+        let git_dir = repo.path().to_path_buf();
+        let (watch_workdir, watch_git_dir) = (workdir.clone(), git_dir.clone());
+        // The handler runs on notify's thread, which needs a handle of its own
+        let handler_repo = Repository::open(repo.path())?;
+
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+            if let Some(paths) = written(&event, &handler_repo, &workdir, &git_dir) {
+                let _ = tx.send(paths);
+            }
+        })?;
+
+        watcher.watch(&watch_workdir, RecursiveMode::Recursive)?;
+        // A worktree made with `git worktree add` keeps its git dir elsewhere
+        if !watch_git_dir.starts_with(&watch_workdir) {
+            watcher.watch(&watch_git_dir, RecursiveMode::Recursive)?;
+        }
+
+        Ok(Watcher {
+            _watcher: watcher,
+            events: rx,
+            last_change: None,
+            written: Vec::new(),
+        })
+    }
+
+    /// Whether something changed since the last time this said so, once the
+    /// changes have settled, with the worktree paths that were written.
+    /// Changes inside `.git` name no paths.
+    pub fn changed(&mut self) -> Option<Vec<PathBuf>> {
+        for paths in self.events.try_iter() {
+            self.written.extend(paths);
+            self.last_change = Some(Instant::now());
+        }
+
+        match self.last_change {
+            Some(at) if at.elapsed() >= SETTLE => {
+                self.last_change = None;
+                self.written.sort();
+                self.written.dedup();
+                Some(std::mem::take(&mut self.written))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Writes to tracked or untracked files, and to the index, HEAD and refs,
+/// with the worktree paths among them relative to the worktree; `None` when
+/// the event touched none of those. Reads are left out, or the status scan
+/// itself would trigger the next one.
+fn written(
+    event: &Event,
+    repo: &Repository,
+    workdir: &Path,
+    git_dir: &Path,
+) -> Option<Vec<PathBuf>> {
+    if matches!(event.kind, EventKind::Access(_)) {
+        return None;
+    }
+
+    let mut relevant = false;
+    let mut paths = Vec::new();
+    for path in &event.paths {
+        if let Ok(inside) = path.strip_prefix(git_dir) {
+            relevant |= inside == Path::new("index")
+                || inside == Path::new("HEAD")
+                || inside == Path::new("packed-refs")
+                || inside.starts_with("refs");
+        } else if let Ok(relative) = path.strip_prefix(workdir)
+            && !repo.status_should_ignore(relative).unwrap_or(false)
+        {
+            relevant = true;
+            paths.push(relative.to_path_buf());
+        }
+    }
+    relevant.then_some(paths)
+}
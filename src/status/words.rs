@@ -0,0 +1,174 @@
+use crate::theme::Theme;
+use ratatui::{
+    style::{Color, Modifier},
+    text::{Line, Span},
+};
+use std::ops::Range;
+
+/// Past this many token pairs a line is more rewritten than edited, and
+/// comparing it would cost more than it shows.
+const MAX_PAIRS: usize = 40_000;
+
+type Ranges = Vec<Range<usize>>;
+
+/// Picks out the changed words of each `-` line and the `+` line that
+/// replaced it, like `git diff --word-diff`, so a small edit in a long line
+/// stands out. `origins` holds each line's diff origin.
+pub fn emphasize(lines: &mut [Line<'static>], origins: &[char], theme: &Theme) {
+    let mut i = 0;
+    while i < lines.len() {
+        let deleted = run(origins, i, '-');
+        if deleted == 0 {
+            i += 1;
+            continue;
+        }
+        let added = run(origins, i + deleted, '+');
+
+        // Paired in order, the way the lines of a change usually line up
+        for k in 0..deleted.min(added) {
+            let (old, new) = (i + k, i + deleted + k);
+            if let Some((old_changed, new_changed)) =
+                changed(&text(&lines[old]), &text(&lines[new]))
+            {
+                mark(&mut lines[old], &old_changed, theme.deletion_emphasis);
+                mark(&mut lines[new], &new_changed, theme.addition_emphasis);
+            }
+        }
+        i += deleted + added;
+    }
+}
+
+fn run(origins: &[char], from: usize, origin: char) -> usize {
+    origins
+        .get(from..)
+        .map_or(0, |rest| rest.iter().take_while(|&&o| o == origin).count())
+}
+
+fn text(line: &Line) -> String {
+    line.spans
+        .iter()
+        .map(|span| span.content.as_ref())
+        .collect()
+}
+
+/// Words, runs of whitespace and single punctuation characters, as byte ranges.
+fn tokens(text: &str) -> Vec<Range<usize>> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let same_kind =
+            |d: char| (is_word(c) && is_word(d)) || (c.is_whitespace() && d.is_whitespace());
+
+        let mut end = start + c.len_utf8();
+        while let Some(&(i, d)) = chars.peek()
+            && same_kind(d)
+        {
+            end = i + d.len_utf8();
+            chars.next();
+        }
+        tokens.push(start..end);
+    }
+
+    tokens
+}
+
+/// The byte ranges of `old` and `new` outside their longest common run of
+/// tokens, or `None` when the lines share too little for that to help.
+fn changed(old: &str, new: &str) -> Option<(Ranges, Ranges)> {
+    let (a, b) = (tokens(old), tokens(new));
+    if a.len() * b.len() > MAX_PAIRS {
+        return None;
+    }
+    let same = |i: usize, j: usize| old[a[i].clone()] == new[b[j].clone()];
+
+    // lengths[i * width + j]: longest common subsequence of a[i..] and b[j..]
+    let width = b.len() + 1;
+    let mut lengths = vec![0u32; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i * width + j] = if same(i, j) {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let (mut old_changed, mut new_changed) = (Vec::new(), Vec::new());
+    let mut common = 0;
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && same(i, j) {
+            common += a[i].len();
+            i += 1;
+            j += 1;
+        } else if j == b.len()
+            || (i < a.len() && lengths[(i + 1) * width + j] >= lengths[i * width + j + 1])
+        {
+            push(&mut old_changed, a[i].clone());
+            i += 1;
+        } else {
+            push(&mut new_changed, b[j].clone());
+            j += 1;
+        }
+    }
+
+    // A rewritten line reads better whole than as confetti
+    if common * 2 < old.len().max(new.len()) {
+        return None;
+    }
+    Some((old_changed, new_changed))
+}
+
+/// Adds `range` to `ranges`, merged into the last one when the two touch.
+fn push(ranges: &mut Ranges, range: Range<usize>) {
+    match ranges.last_mut() {
+        Some(last) if last.end == range.start => last.end = range.end,
+        _ => ranges.push(range),
+    }
+}
+
+/// Restyles the parts of `line` inside `ranges`, splitting spans at their
+/// edges. Highlighted spans get the stronger `emphasis` background; plain
+/// ones, colored by the line alone, are reversed.
+fn mark(line: &mut Line<'static>, ranges: &[Range<usize>], emphasis: Color) {
+    if ranges.is_empty() {
+        return;
+    }
+
+    let mut spans = Vec::new();
+    let mut offset = 0;
+    for span in std::mem::take(&mut line.spans) {
+        let bounds = offset..offset + span.content.len();
+
+        let mut cuts = vec![bounds.start, bounds.end];
+        for range in ranges {
+            cuts.extend(
+                [range.start, range.end]
+                    .into_iter()
+                    .filter(|e| bounds.contains(e)),
+            );
+        }
+        cuts.sort_unstable();
+        cuts.dedup();
+
+        for piece in cuts.windows(2) {
+            let (start, end) = (piece[0], piece[1]);
+            let inside = ranges.iter().any(|r| r.start <= start && end <= r.end);
+            let style = match (inside, span.style.bg) {
+                (false, _) => span.style,
+                (true, Some(_)) => span.style.bg(emphasis),
+                (true, None) => span.style.add_modifier(Modifier::REVERSED),
+            };
+            spans.push(Span::styled(
+                span.content[start - offset..end - offset].to_string(),
+                style,
+            ));
+        }
+        offset = bounds.end;
+    }
+
+    line.spans = spans;
+}
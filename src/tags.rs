@@ -0,0 +1,379 @@
+mod tag;
+
+use crate::help;
+use crate::keymap::{Action, Keymap, Scope};
+use crate::panes::{self, Panes};
+use crate::status::message::{InputResult, MessageInput};
+use crate::status::remote::{RemoteOp, RemoteTask};
+use crate::theme::Theme;
+use crate::tui::{TICK, Tui};
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode};
+use git2::Repository;
+use ratatui::{
+    prelude::*,
+    widgets::{ListItem, ListState},
+};
+use tag::TagInfo;
+
+/// A new tag so far.
+struct NewTag {
+    name: String,
+    target: String,
+    sign: bool,
+}
+
+/// What is being typed on the helper line for a new tag.
+enum Prompt {
+    Name(NewTag),
+    Target(NewTag),
+}
+
+/// Whatever the helper line is waiting for a yes or no to.
+enum Confirm {
+    Delete(String),
+    DeleteRemote(String),
+}
+
+/// Every tag with what it points at, the selected one shown in full
+/// alongside. Tags can be created on any commit, lightweight, annotated or
+/// signed, deleted, and pushed to or deleted from the branch's remote.
+pub fn tags(
+    tui: &mut Tui,
+    repo: &Repository,
+    list_width: u16,
+    keymap: &Keymap,
+    theme: &Theme,
+) -> Result<()> {
+    let mut tags = tag::load(repo)?;
+    let mut panes: Panes<String> = Panes::new(list_width);
+    // `tag.gpgSign` signs every annotated tag, as it does for `git tag -a`
+    let sign_by_default = repo.config()?.get_bool("tag.gpgsign").unwrap_or(false);
+
+    // ---------- UI State ----------
+    let mut message: Option<String> = None;
+    let mut prompt: Option<Prompt> = None;
+    let mut message_input: Option<(NewTag, MessageInput)> = None;
+    let mut confirm: Option<Confirm> = None;
+    let mut task: Option<RemoteTask> = None;
+    let mut show_help = false;
+
+    // ---------- Main Loop ----------
+    loop {
+        if let Some(done) = task.as_ref().and_then(|t| t.poll()) {
+            message = Some(match done {
+                Ok(text) => text,
+                Err(e) => format!("Error: {}", e),
+            });
+            task = None;
+        }
+
+        let selected = panes.selected().and_then(|i| tags.get(i));
+
+        // ---------- Preview ----------
+        panes.preview(selected, |t| t.name.clone(), |t| tag::show(repo, t, theme));
+
+        // ---------- Helper line ----------
+        let warning = Style::default()
+            .fg(theme.warning)
+            .add_modifier(Modifier::BOLD);
+        let help_line = match (&prompt, &confirm, &task, message.clone()) {
+            (Some(Prompt::Name(new) | Prompt::Target(new)), ..) => {
+                let (label, text) = match &prompt {
+                    Some(Prompt::Name(_)) if new.sign => ("New signed tag: ", &new.name),
+                    Some(Prompt::Name(_)) => ("New tag: ", &new.name),
+                    _ => ("On commit: ", &new.target),
+                };
+                Line::from(vec![
+                    Span::styled(label, Style::default().fg(theme.accent)),
+                    Span::raw(text.clone()),
+                    Span::styled("█", Style::default().fg(theme.accent)),
+                ])
+            }
+            (None, Some(Confirm::Delete(name)), ..) => {
+                Line::styled(format!("Delete tag {}? (y/n)", name), warning)
+            }
+            (None, Some(Confirm::DeleteRemote(name)), ..) => Line::styled(
+                format!("Delete tag {} from the remote? (y/n)", name),
+                warning,
+            ),
+            (None, None, Some(task), _) => Line::from(task.label()),
+            (None, None, None, Some(text)) => Line::from(text),
+            (None, None, None, None) => help_line(keymap),
+        };
+
+        let width = tags
+            .iter()
+            .map(|t| t.name.chars().count())
+            .max()
+            .unwrap_or(0);
+        let items: Vec<ListItem> = tags
+            .iter()
+            .map(|t| ListItem::new(tag_line(t, width, theme)))
+            .collect();
+        let list = panes::list(items, "No tags yet", theme);
+
+        tui.draw(|frame| {
+            let (main, help) = panes::split(frame.area());
+            panes.render(frame, main, &list, " Tags ", " Tag ", theme);
+            let asking = prompt.is_some() || confirm.is_some();
+            panes::render_help(frame, help, help_line, asking);
+
+            if let Some((_, input)) = &message_input {
+                input.render(frame, theme);
+            }
+
+            if show_help {
+                help::render(frame, keymap, theme);
+            }
+        })?;
+
+        // ---------- Input Handling ----------
+        if !event::poll(TICK)? {
+            continue;
+        }
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        message = None;
+
+        let selected = panes.selected().and_then(|i| tags.get(i));
+
+        // A question swallows every key until it is answered
+        if let Some(question) = &confirm {
+            match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    match question {
+                        Confirm::Delete(name) => {
+                            message = Some(match tag::delete(repo, name) {
+                                Ok(text) => text,
+                                Err(e) => format!("Error: {}", e),
+                            });
+                            reload(repo, &mut tags, &mut panes.list_state, None)?;
+                        }
+                        Confirm::DeleteRemote(name) => {
+                            let name = name.clone();
+                            task = Some(RemoteTask::spawn_job(
+                                repo,
+                                RemoteOp::Push,
+                                move |repo, progress| tag::push(repo, &name, true, progress),
+                            ));
+                        }
+                    }
+                    confirm = None;
+                }
+                KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => confirm = None,
+                _ => {}
+            }
+            continue;
+        }
+
+        if let Some(current) = &mut prompt {
+            let text = match current {
+                Prompt::Name(new) => &mut new.name,
+                Prompt::Target(new) => &mut new.target,
+            };
+            match key.code {
+                KeyCode::Esc => prompt = None,
+                KeyCode::Backspace => {
+                    text.pop();
+                }
+                KeyCode::Char(c) => text.push(c),
+                KeyCode::Enter => {
+                    prompt = match prompt.take() {
+                        Some(Prompt::Name(mut new)) => {
+                            new.name = new.name.trim().to_string();
+                            Some(Prompt::Target(new))
+                        }
+                        Some(Prompt::Target(new)) => {
+                            let title = if new.sign {
+                                format!("Message for signed tag {}", new.name)
+                            } else {
+                                format!(
+                                    "Message for tag {} (empty for a lightweight tag)",
+                                    new.name
+                                )
+                            };
+                            message_input = Some((new, MessageInput::new(title, String::new())));
+                            None
+                        }
+                        None => None,
+                    };
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        if let Some((new, input)) = &mut message_input {
+            match input.handle(&key) {
+                InputResult::Editing => {}
+                InputResult::Cancelled => message_input = None,
+                InputResult::Submitted(text) => {
+                    let created = if new.sign {
+                        // gpg or ssh-keygen may ask for a passphrase
+                        tui.suspend(|| tag::create(repo, &new.name, &new.target, &text, true))?
+                    } else {
+                        tag::create(repo, &new.name, &new.target, &text, false)
+                    };
+                    match created {
+                        Ok(text) => {
+                            message = Some(text);
+                            reload(repo, &mut tags, &mut panes.list_state, Some(&new.name))?;
+                        }
+                        Err(e) => message = Some(format!("Error: {}", e)),
+                    }
+                    message_input = None;
+                }
+            }
+            continue;
+        }
+
+        if show_help {
+            if matches!(
+                keymap.resolve(&key, Scope::Tags),
+                Some(Action::Help | Action::Quit)
+            ) {
+                show_help = false;
+            }
+            continue;
+        }
+
+        match keymap.resolve(&key, Scope::Tags) {
+            Some(Action::Quit) => break,
+
+            Some(Action::Help) => show_help = true,
+
+            Some(Action::Refresh) => {
+                let name = selected.map(|t| t.name.clone());
+                reload(repo, &mut tags, &mut panes.list_state, name.as_deref())?;
+                panes.forget();
+            }
+
+            Some(action @ (Action::NewTag | Action::NewSignedTag)) => {
+                prompt = Some(Prompt::Name(NewTag {
+                    name: String::new(),
+                    target: String::from("HEAD"),
+                    sign: action == Action::NewSignedTag || sign_by_default,
+                }));
+            }
+
+            Some(Action::DeleteTag) => {
+                if let Some(tag) = selected {
+                    confirm = Some(Confirm::Delete(tag.name.clone()));
+                }
+            }
+
+            Some(action @ (Action::PushTag | Action::DeleteRemoteTag)) => match selected {
+                Some(_) if task.is_some() => {
+                    message = Some(String::from("Error: wait for the last push to finish"));
+                }
+                Some(tag) if action == Action::DeleteRemoteTag => {
+                    confirm = Some(Confirm::DeleteRemote(tag.name.clone()));
+                }
+                Some(tag) => {
+                    let name = tag.name.clone();
+                    task = Some(RemoteTask::spawn_job(
+                        repo,
+                        RemoteOp::Push,
+                        move |repo, progress| tag::push(repo, &name, false, progress),
+                    ));
+                }
+                None => {}
+            },
+
+            Some(action) => {
+                panes.navigate(action, tags.len());
+            }
+
+            None => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// `hg tags` outside the status view.
+pub fn run(repo: &Repository, list_width: u16, keymap: &Keymap, theme: &Theme) -> Result<()> {
+    let mut tui = Tui::new()?;
+    tags(&mut tui, repo, list_width, keymap, theme)
+}
+
+/// Reloads the tags, selecting `name` if it still exists, or else the same
+/// row (clamped).
+fn reload(
+    repo: &Repository,
+    tags: &mut Vec<TagInfo>,
+    list_state: &mut ListState,
+    name: Option<&str>,
+) -> Result<()> {
+    *tags = tag::load(repo)?;
+
+    let by_name = name.and_then(|name| tags.iter().position(|t| t.name == name));
+    let row = list_state
+        .selected()
+        .unwrap_or(0)
+        .min(tags.len().saturating_sub(1));
+    list_state.select(Some(by_name.unwrap_or(row)));
+    Ok(())
+}
+
+/// `v1.2.0  1a2b3c4 signed Release 1.2`; a lightweight tag's commit summary
+/// is dimmed, there being no message of its own.
+fn tag_line(tag: &TagInfo, width: usize, theme: &Theme) -> Line<'static> {
+    let mut spans = vec![
+        Span::styled(
+            format!("{:<width$}", tag.name),
+            Style::default().fg(theme.accent),
+        ),
+        Span::raw(" "),
+        Span::styled(
+            format!("{:.7}", tag.target),
+            Style::default().fg(theme.commit_sha),
+        ),
+        Span::raw(" "),
+    ];
+
+    match &tag.annotation {
+        Some(annotation) => {
+            if annotation.signed {
+                spans.push(Span::styled("signed ", Style::default().fg(theme.staged)));
+            }
+            spans.push(Span::raw(tag.subject().to_string()));
+        }
+        None => spans.push(Span::styled(
+            tag.subject().to_string(),
+            Style::default().dim(),
+        )),
+    }
+
+    Line::from(spans)
+}
+
+fn help_line(keymap: &Keymap) -> Line<'static> {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let mut spans = vec![Span::raw(" ")];
+
+    for (i, (action, label)) in [
+        (Action::NewTag, "new"),
+        (Action::NewSignedTag, "new signed"),
+        (Action::DeleteTag, "delete"),
+        (Action::PushTag, "push"),
+        (Action::DeleteRemoteTag, "delete on remote"),
+        (Action::SwitchFocus, "switch focus"),
+        (Action::Help, "help"),
+        (Action::Quit, "quit"),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        if i > 0 {
+            spans.push(Span::raw(" • "));
+        }
+        spans.push(Span::styled(keymap.label(action), bold));
+        spans.push(Span::raw(format!(" {}", label)));
+    }
+
+    Line::from(spans)
+}
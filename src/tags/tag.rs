@@ -0,0 +1,215 @@
+use crate::log;
+use crate::sign;
+use crate::status::remote::{self, Transfer};
+use crate::theme::Theme;
+use anyhow::{Result, bail};
+use git2::{ObjectType, Oid, Reference, Repository, Signature, Time};
+use ratatui::{style::Style, text::Line};
+use std::sync::Mutex;
+
+/// What an annotated tag adds to the name and target.
+pub struct Annotation {
+    pub tagger: Option<String>,
+    pub when: Option<Time>,
+    /// The message, any signature taken off the end
+    pub message: String,
+    pub signed: bool,
+}
+
+/// One row of the tags view, like a line of `git tag -n`.
+pub struct TagInfo {
+    pub name: String,
+    /// What it names, peeled through the annotation
+    pub target: Oid,
+    /// The target commit's summary, empty when it isn't a commit
+    pub summary: String,
+    /// `None` for a lightweight tag
+    pub annotation: Option<Annotation>,
+}
+
+impl TagInfo {
+    /// The annotation's first line, else the target commit's summary.
+    pub fn subject(&self) -> &str {
+        match &self.annotation {
+            Some(annotation) => annotation.message.lines().next().unwrap_or(""),
+            None => &self.summary,
+        }
+    }
+}
+
+/// Signature blocks git appends to a signed tag's message.
+const SIGNATURE_STARTS: &[&str] = &[
+    "-----BEGIN PGP SIGNATURE-----",
+    "-----BEGIN SSH SIGNATURE-----",
+    "-----BEGIN SIGNED MESSAGE-----",
+];
+
+/// Every tag, sorted by name as `git tag` lists them.
+pub fn load(repo: &Repository) -> Result<Vec<TagInfo>> {
+    let mut tags = Vec::new();
+
+    for name in repo.tag_names(None)?.iter().flatten() {
+        let reference = repo.find_reference(&format!("refs/tags/{}", name))?;
+        let target = reference.peel(ObjectType::Any)?;
+        let summary = target
+            .as_commit()
+            .and_then(|c| c.summary())
+            .unwrap_or("")
+            .to_string();
+
+        let annotation = reference.peel_to_tag().ok().map(|tag| {
+            let message = tag.message().unwrap_or("");
+            let start = SIGNATURE_STARTS
+                .iter()
+                .filter_map(|marker| message.find(marker))
+                .min();
+            Annotation {
+                tagger: tag.tagger().map(|t| t.to_string()),
+                when: tag.tagger().map(|t| t.when()),
+                message: message[..start.unwrap_or(message.len())]
+                    .trim_end()
+                    .to_string(),
+                signed: start.is_some(),
+            }
+        });
+
+        tags.push(TagInfo {
+            name: name.to_string(),
+            target: target.id(),
+            summary,
+            annotation,
+        });
+    }
+
+    tags.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(tags)
+}
+
+/// The tag's header and message, like `git show <tag>` prints them, then
+/// its target commit.
+pub fn show(repo: &Repository, tag: &TagInfo, theme: &Theme) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+
+    if let Some(annotation) = &tag.annotation {
+        lines.push(Line::styled(
+            format!("tag {}", tag.name),
+            Style::default().fg(theme.accent),
+        ));
+        if let Some(tagger) = &annotation.tagger {
+            lines.push(Line::from(format!("Tagger: {}", tagger)));
+        }
+        if let Some(when) = annotation.when {
+            lines.push(Line::from(format!("Date:   {}", log::relative_date(when))));
+        }
+        lines.push(Line::from(""));
+        for line in annotation.message.lines() {
+            lines.push(Line::from(format!("    {}", line)));
+        }
+        if annotation.signed {
+            lines.push(Line::styled("    (signed)", Style::default().dim()));
+        }
+        lines.push(Line::from(""));
+    }
+
+    match log::show_commit(repo, tag.target, theme) {
+        Ok(commit) => lines.extend(commit.lines),
+        Err(_) => lines.push(Line::from(format!("{} (not a commit)", tag.target))),
+    }
+    lines
+}
+
+/// Tags `target` as `name`: lightweight when `message` is empty, otherwise
+/// annotated, and signed as well when `sign` is true. Signing runs gpg or
+/// ssh-keygen, which may want the terminal. Returns what to tell the user.
+pub fn create(
+    repo: &Repository,
+    name: &str,
+    target: &str,
+    message: &str,
+    sign: bool,
+) -> Result<String> {
+    let refname = format!("refs/tags/{}", name);
+    if name.is_empty() || !Reference::is_valid_name(&refname) {
+        bail!("\"{}\" isn't a valid tag name", name);
+    }
+    if repo.find_reference(&refname).is_ok() {
+        bail!("tag {} already exists", name);
+    }
+    let commit = repo.revparse_single(target)?.peel_to_commit()?;
+    let object = commit.as_object();
+    let message = message.trim_end();
+
+    if message.is_empty() {
+        if sign {
+            bail!("a signed tag needs a message");
+        }
+        repo.tag_lightweight(name, object, false)?;
+        return Ok(format!("Tagged {:.7} as {}", commit.id(), name));
+    }
+
+    let tagger = repo.signature()?;
+    if !sign {
+        repo.tag(name, object, &tagger, &format!("{}\n", message), false)?;
+        return Ok(format!("Tagged {:.7} as {}", commit.id(), name));
+    }
+
+    // The tag object as git writes it, with the signature of everything
+    // before it appended to the message
+    let mut buffer = format!(
+        "object {}\ntype commit\ntag {}\ntagger {}\n\n{}\n",
+        commit.id(),
+        name,
+        signature_line(&tagger),
+        message
+    );
+    let signature = sign::sign(repo, &buffer)?;
+    buffer.push_str(&signature);
+
+    let oid = repo.odb()?.write(ObjectType::Tag, buffer.as_bytes())?;
+    repo.reference(&refname, oid, false, &format!("tag: {}", name))?;
+    Ok(format!("Signed tag {} on {:.7}", name, commit.id()))
+}
+
+pub fn delete(repo: &Repository, name: &str) -> Result<String> {
+    repo.tag_delete(name)?;
+    Ok(format!("Deleted tag {}", name))
+}
+
+/// Pushes the tag to the default remote, or deletes it there, as `git push
+/// origin <tag>` and `git push origin :refs/tags/<tag>` do.
+pub fn push(
+    repo: &Repository,
+    name: &str,
+    delete: bool,
+    progress: &Mutex<Transfer>,
+) -> Result<String> {
+    let mut remote = remote::default_remote(repo)?;
+    let refname = format!("refs/tags/{}", name);
+    let refspec = if delete {
+        format!(":{}", refname)
+    } else {
+        format!("{0}:{0}", refname)
+    };
+    remote::push_refspecs(repo, &mut remote, &[refspec], progress)?;
+
+    let remote_name = remote.name().unwrap_or("remote");
+    Ok(if delete {
+        format!("Deleted tag {} from {}", name, remote_name)
+    } else {
+        format!("Pushed tag {} to {}", name, remote_name)
+    })
+}
+
+/// `Name <email> 1700000000 +0100`, as the header lines of git objects have it.
+fn signature_line(signature: &Signature) -> String {
+    let when = signature.when();
+    let offset = when.offset_minutes();
+    format!(
+        "{} {} {}{:02}{:02}",
+        signature,
+        when.seconds(),
+        if offset < 0 { '-' } else { '+' },
+        offset.abs() / 60,
+        offset.abs() % 60
+    )
+}
@@ -0,0 +1,282 @@
+use crate::image::Graphics;
+use crate::whitespace::Whitespace;
+use anyhow::{Result, bail};
+use ratatui::style::{Color, Modifier, Style};
+
+/// Every color the UI uses, so a light terminal background can get its own
+/// palette instead of the hardcoded dark one.
+#[derive(Clone)]
+#[cfg_attr(not(feature = "syntax"), allow(dead_code))]
+pub struct Theme {
+    /// `+` lines and whole new files
+    pub addition: Color,
+    /// `-` lines and whole deleted files
+    pub deletion: Color,
+    /// Behind syntax-highlighted `+` and `-` lines, in place of the plain colors
+    pub addition_bg: Color,
+    pub deletion_bg: Color,
+    /// Behind the changed words within a highlighted `+` or `-` line
+    pub addition_emphasis: Color,
+    pub deletion_emphasis: Color,
+    /// syntect theme for highlighted diffs; `None` keeps every diff plain
+    pub syntax: Option<&'static str>,
+    /// `diff --git`, `---`/`+++` and similar header lines
+    pub file_header: Color,
+    /// The old/new line number gutter
+    pub gutter: Color,
+    /// Labels in the Staged section
+    pub staged: Color,
+    /// Untracked and deleted files in the Unstaged and Untracked sections
+    pub untracked: Color,
+    /// Modified files in the Unstaged section
+    pub modified: Color,
+    /// Ignored files, when shown
+    pub ignored: Color,
+    /// Conflicted files and their conflict markers
+    pub conflict: Color,
+    /// Background of the selected list row
+    pub highlight_bg: Color,
+    /// Background of the selected hunk
+    pub hunk_bg: Color,
+    /// Border of the focused panel
+    pub focused_border: Color,
+    /// Filter prompt and cursor
+    pub accent: Color,
+    /// Confirmation prompts for destructive actions
+    pub warning: Color,
+    pub commit_sha: Color,
+    pub commit_author: Color,
+    pub commit_date: Color,
+    /// No colors at all: bold, dim and reverse alone set things apart
+    pub monochrome: bool,
+    /// Whether the terminal takes 24-bit colors; otherwise RGB ones are
+    /// matched to the nearest of the 256
+    pub truecolor: bool,
+    /// How the terminal draws images, for previews of changed ones; `None`
+    /// leaves them as their binary diff
+    pub images: Option<Graphics>,
+    /// Tab width and whitespace markers in diffs
+    pub whitespace: Whitespace,
+}
+
+impl Theme {
+    pub const NAMES: &[&str] = &["dark", "light", "plain"];
+
+    pub fn by_name(name: &str) -> Result<Self> {
+        match name {
+            "dark" => Ok(Self::dark()),
+            "light" => Ok(Self::light()),
+            "plain" => Ok(Self::plain()),
+            _ => bail!(
+                "unknown theme \"{}\" (available: {})",
+                name,
+                Self::NAMES.join(", ")
+            ),
+        }
+    }
+
+    pub fn dark() -> Self {
+        Theme {
+            addition: Color::Green,
+            deletion: Color::Red,
+            addition_bg: Color::Indexed(22),
+            deletion_bg: Color::Indexed(52),
+            addition_emphasis: Color::Indexed(28),
+            deletion_emphasis: Color::Indexed(88),
+            syntax: Some("base16-eighties.dark"),
+            file_header: Color::Blue,
+            gutter: Color::DarkGray,
+            staged: Color::Green,
+            untracked: Color::Red,
+            modified: Color::Yellow,
+            ignored: Color::DarkGray,
+            conflict: Color::Magenta,
+            highlight_bg: Color::DarkGray,
+            hunk_bg: Color::Indexed(236),
+            focused_border: Color::Yellow,
+            accent: Color::Yellow,
+            warning: Color::Red,
+            commit_sha: Color::Yellow,
+            commit_author: Color::Blue,
+            commit_date: Color::Green,
+            monochrome: false,
+            truecolor: truecolor(),
+            images: None,
+            whitespace: Whitespace::default(),
+        }
+    }
+
+    /// Darker foregrounds and pale backgrounds; yellow is unreadable on white.
+    pub fn light() -> Self {
+        Theme {
+            addition: Color::Indexed(28),
+            deletion: Color::Indexed(124),
+            addition_bg: Color::Indexed(194),
+            deletion_bg: Color::Indexed(224),
+            addition_emphasis: Color::Indexed(157),
+            deletion_emphasis: Color::Indexed(217),
+            syntax: Some("InspiredGitHub"),
+            file_header: Color::Indexed(25),
+            gutter: Color::Indexed(245),
+            staged: Color::Indexed(28),
+            untracked: Color::Indexed(124),
+            modified: Color::Indexed(130),
+            ignored: Color::Indexed(248),
+            conflict: Color::Indexed(90),
+            highlight_bg: Color::Indexed(252),
+            hunk_bg: Color::Indexed(254),
+            focused_border: Color::Indexed(25),
+            accent: Color::Indexed(130),
+            warning: Color::Indexed(124),
+            commit_sha: Color::Indexed(130),
+            commit_author: Color::Indexed(25),
+            commit_date: Color::Indexed(28),
+            monochrome: false,
+            truecolor: truecolor(),
+            images: None,
+            whitespace: Whitespace::default(),
+        }
+    }
+
+    /// The terminal's own colors throughout, for `NO_COLOR` and terminals
+    /// without color.
+    pub fn plain() -> Self {
+        Theme {
+            addition: Color::Reset,
+            deletion: Color::Reset,
+            addition_bg: Color::Reset,
+            deletion_bg: Color::Reset,
+            addition_emphasis: Color::Reset,
+            deletion_emphasis: Color::Reset,
+            syntax: None,
+            file_header: Color::Reset,
+            gutter: Color::Reset,
+            staged: Color::Reset,
+            untracked: Color::Reset,
+            modified: Color::Reset,
+            ignored: Color::Reset,
+            conflict: Color::Reset,
+            highlight_bg: Color::Reset,
+            hunk_bg: Color::Reset,
+            focused_border: Color::Reset,
+            accent: Color::Reset,
+            warning: Color::Reset,
+            commit_sha: Color::Reset,
+            commit_author: Color::Reset,
+            commit_date: Color::Reset,
+            monochrome: true,
+            truecolor: false,
+            images: None,
+            whitespace: Whitespace::default(),
+        }
+    }
+
+    /// Overrides one color, named like its field, from a config value such
+    /// as `"red"`, `"#50fa7b"` or a 256-color index like `"236"`.
+    pub fn set_color(&mut self, name: &str, value: &str) -> Result<()> {
+        let truecolor = self.truecolor;
+        let field = match name {
+            "addition" => &mut self.addition,
+            "deletion" => &mut self.deletion,
+            "addition_bg" => &mut self.addition_bg,
+            "deletion_bg" => &mut self.deletion_bg,
+            "addition_emphasis" => &mut self.addition_emphasis,
+            "deletion_emphasis" => &mut self.deletion_emphasis,
+            "file_header" => &mut self.file_header,
+            "gutter" => &mut self.gutter,
+            "staged" => &mut self.staged,
+            "untracked" => &mut self.untracked,
+            "modified" => &mut self.modified,
+            "ignored" => &mut self.ignored,
+            "conflict" => &mut self.conflict,
+            "highlight_bg" => &mut self.highlight_bg,
+            "hunk_bg" => &mut self.hunk_bg,
+            "focused_border" => &mut self.focused_border,
+            "accent" => &mut self.accent,
+            "warning" => &mut self.warning,
+            "commit_sha" => &mut self.commit_sha,
+            "commit_author" => &mut self.commit_author,
+            "commit_date" => &mut self.commit_date,
+            _ => bail!("unknown color \"{}\"", name),
+        };
+
+        let Ok(color) = value.parse::<Color>() else {
+            bail!("invalid color \"{}\" for {}", value, name);
+        };
+        *field = fit(color, truecolor);
+
+        Ok(())
+    }
+
+    /// `color` as this terminal can show it.
+    #[cfg_attr(not(feature = "syntax"), allow(dead_code))]
+    pub fn fit(&self, color: Color) -> Color {
+        fit(color, self.truecolor)
+    }
+
+    /// Border of the focused panel; bold when there is no color to show it.
+    pub fn focus(&self) -> Style {
+        let style = Style::default().fg(self.focused_border);
+        if self.monochrome {
+            style.add_modifier(Modifier::BOLD)
+        } else {
+            style
+        }
+    }
+}
+
+/// The de-facto way terminals announce 24-bit color.
+fn truecolor() -> bool {
+    std::env::var("COLORTERM").is_ok_and(|v| v == "truecolor" || v == "24bit")
+}
+
+fn fit(color: Color, truecolor: bool) -> Color {
+    match color {
+        Color::Rgb(r, g, b) if !truecolor => Color::Indexed(nearest_indexed(r, g, b)),
+        color => color,
+    }
+}
+
+/// The closest entry of the 6×6×6 cube or the gray ramp of the 256 colors.
+fn nearest_indexed(r: u8, g: u8, b: u8) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let level = |c: u8| {
+        (0..6)
+            .min_by_key(|&i| (LEVELS[i] as i32 - c as i32).abs())
+            .unwrap_or(0)
+    };
+    let distance = |(r2, g2, b2): (u8, u8, u8)| {
+        [(r, r2), (g, g2), (b, b2)]
+            .iter()
+            .map(|&(a, b)| (a as i32 - b as i32).pow(2))
+            .sum::<i32>()
+    };
+
+    let (ri, gi, bi) = (level(r), level(g), level(b));
+    let cube = 16 + 36 * ri as u8 + 6 * gi as u8 + bi as u8;
+    let cube_rgb = (LEVELS[ri], LEVELS[gi], LEVELS[bi]);
+
+    // Grays run from 8 to 238 in steps of 10
+    let average = (r as u32 + g as u32 + b as u32) / 3;
+    let step = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray = 8 + 10 * step;
+
+    if distance((gray, gray, gray)) < distance(cube_rgb) {
+        232 + step
+    } else {
+        cube
+    }
+}
+
+/// Dark, unless `NO_COLOR` is set or the terminal can't do color; a theme
+/// picked by name in the config or on the command line still wins.
+impl Default for Theme {
+    fn default() -> Self {
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        if no_color || std::env::var("TERM").is_ok_and(|term| term == "dumb") {
+            Self::plain()
+        } else {
+            Self::dark()
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use crate::image::{self, Graphics, Picture};
+use anyhow::Result;
+use crossterm::{
+    ExecutableCommand, QueueableCommand,
+    cursor::MoveTo,
+    event::{DisableMouseCapture, EnableMouseCapture},
+    style::{Attribute, ContentStyle},
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+};
+use ratatui::{backend::IntoCrossterm, buffer::Buffer, prelude::*};
+use std::io::{Stdout, Write, stdout};
+use std::time::{Duration, Instant};
+
+/// How long the views wait for input before redrawing anyway.
+pub const TICK: Duration = Duration::from_millis(250);
+
+/// Raw-mode, alternate-screen terminal that is restored when dropped, so an
+/// early `?` return (or a panic) never leaves the user's shell in raw mode.
+pub struct Tui {
+    pub terminal: Terminal<CrosstermBackend<Stdout>>,
+    /// How long each frame took to draw, kept only when `HG_FRAME_TIMES` is
+    /// set; summed up on stderr once the terminal is restored
+    frame_times: Option<Vec<Duration>>,
+    /// The pictures drawn over the screen, by id, with the area each was
+    /// given and the part of it it took
+    pictures: Vec<(u64, Rect, Rect)>,
+    graphics: Option<Graphics>,
+    /// Whether the view asked for its pictures again after the last frame;
+    /// a view that doesn't is another one, which they're taken off for
+    kept: bool,
+    /// The last frame, while pictures are up, to paint back over them
+    screen: Option<Buffer>,
+    area: Rect,
+    /// Whether mouse events are reported, which takes text selection away
+    /// from the terminal; only views that handle the mouse turn it on
+    mouse: bool,
+}
+
+impl Tui {
+    pub fn new() -> Result<Self> {
+        enable_raw_mode()?;
+        stdout().execute(EnterAlternateScreen)?;
+
+        let backend = CrosstermBackend::new(stdout());
+        let terminal = Terminal::new(backend)?;
+
+        Ok(Self {
+            terminal,
+            frame_times: std::env::var_os("HG_FRAME_TIMES").map(|_| Vec::new()),
+            pictures: Vec::new(),
+            graphics: None,
+            kept: false,
+            screen: None,
+            area: Rect::default(),
+            mouse: false,
+        })
+    }
+
+    /// Has the terminal report the mouse, or stop and leave selecting text
+    /// to it again.
+    pub fn capture_mouse(&mut self, on: bool) -> Result<()> {
+        if on != self.mouse {
+            if on {
+                stdout().execute(EnableMouseCapture)?;
+            } else {
+                stdout().execute(DisableMouseCapture)?;
+            }
+            self.mouse = on;
+        }
+        Ok(())
+    }
+
+    /// Draws one frame with `render`, timing it for `HG_FRAME_TIMES`.
+    pub fn draw(&mut self, render: impl FnOnce(&mut Frame)) -> Result<()> {
+        let start = Instant::now();
+        if !self.kept {
+            self.show_pictures(None, &[])?;
+        }
+        self.kept = false;
+
+        let frame = self.terminal.draw(render)?;
+        let resized = frame.area != self.area;
+        self.area = frame.area;
+        if !self.pictures.is_empty() {
+            self.screen = Some(frame.buffer.clone());
+        }
+        // A resize clears the screen, pictures and all
+        if resized {
+            self.forget_pictures()?;
+        }
+        if let Some(times) = &mut self.frame_times {
+            times.push(start.elapsed());
+        }
+        Ok(())
+    }
+
+    /// Draws `pictures` over the frame just drawn, each in its area, with
+    /// `graphics`. They're only sent again once they change, and the view
+    /// asks for them after every frame it wants them in.
+    pub fn show_pictures(
+        &mut self,
+        graphics: Option<Graphics>,
+        pictures: &[(&Picture, Rect)],
+    ) -> Result<()> {
+        self.kept = true;
+        let pictures = if graphics.is_some() { pictures } else { &[] };
+        if self.pictures.len() == pictures.len()
+            && self
+                .pictures
+                .iter()
+                .zip(pictures)
+                .all(|(&(id, area, _), (picture, wanted))| id == picture.id && area == *wanted)
+        {
+            return Ok(());
+        }
+
+        // Off with the old ones: kitty deletes them, the others are painted over
+        match self.graphics {
+            Some(Graphics::Kitty) => self.forget_pictures()?,
+            Some(_) => {
+                if let Some(screen) = &self.screen {
+                    let cells = self.pictures.iter().flat_map(|&(_, _, taken)| {
+                        taken
+                            .positions()
+                            .filter_map(|p| Some((p.x, p.y, screen.cell(p)?)))
+                    });
+                    self.terminal.backend_mut().draw(cells)?;
+                }
+                self.pictures.clear();
+            }
+            None => {}
+        }
+
+        if let Some(graphics) = graphics {
+            let cell = image::cell_size();
+            let out = self.terminal.backend_mut();
+            for (picture, area) in pictures {
+                let taken = image::fit(picture, *area, cell);
+                out.queue(MoveTo(taken.x, taken.y))?;
+                out.write_all(image::sequence(picture, graphics, taken, cell).as_bytes())?;
+                self.pictures.push((picture.id, *area, taken));
+            }
+        }
+        self.graphics = graphics;
+        Write::flush(self.terminal.backend_mut())?;
+        Ok(())
+    }
+
+    /// Takes kitty's pictures off the screen, and stops keeping track of
+    /// anyone's, once the screen is cleared or left.
+    fn forget_pictures(&mut self) -> Result<()> {
+        if self.graphics == Some(Graphics::Kitty) && !self.pictures.is_empty() {
+            let out = self.terminal.backend_mut();
+            out.write_all(image::KITTY_CLEAR.as_bytes())?;
+            Write::flush(out)?;
+        }
+        self.pictures.clear();
+        self.screen = None;
+        Ok(())
+    }
+
+    /// Hands the terminal back to the shell while `f` runs (an editor, say)
+    /// and takes it over again afterwards, however `f` went.
+    pub fn suspend<T>(&mut self, f: impl FnOnce() -> T) -> Result<T> {
+        self.forget_pictures()?;
+        restore();
+        let result = f();
+
+        enable_raw_mode()?;
+        stdout().execute(EnterAlternateScreen)?;
+        if self.mouse {
+            stdout().execute(EnableMouseCapture)?;
+        }
+        // Whatever the child drew is still on screen as far as ratatui knows
+        self.terminal.clear()?;
+
+        Ok(result)
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        let _ = self.forget_pictures();
+        restore();
+        if let Some(mut times) = self.frame_times.take()
+            && !times.is_empty()
+        {
+            times.sort();
+            let at = |share: f64| times[((times.len() - 1) as f64 * share) as usize];
+            eprintln!(
+                "{} frames: median {:.2?}, p99 {:.2?}, max {:.2?}",
+                times.len(),
+                at(0.5),
+                at(0.99),
+                at(1.0)
+            );
+        }
+    }
+}
+
+/// `line` as text for output that skips the screen, like a pipe; with
+/// `color`, its colors and emphasis come along as ANSI escapes.
+pub fn ansi(line: &Line, color: bool) -> String {
+    let mut text = String::new();
+
+    for span in &line.spans {
+        if !color {
+            text.push_str(&span.content);
+            continue;
+        }
+
+        let style = line.style.patch(span.style);
+        let mut content = ContentStyle::new();
+        content.foreground_color = style.fg.map(IntoCrossterm::into_crossterm);
+        content.background_color = style.bg.map(IntoCrossterm::into_crossterm);
+        for (modifier, attribute) in [
+            (Modifier::BOLD, Attribute::Bold),
+            (Modifier::DIM, Attribute::Dim),
+            (Modifier::ITALIC, Attribute::Italic),
+            (Modifier::UNDERLINED, Attribute::Underlined),
+            (Modifier::REVERSED, Attribute::Reverse),
+        ] {
+            if style.add_modifier.contains(modifier) {
+                content.attributes.set(attribute);
+            }
+        }
+        text.push_str(&content.apply(&span.content).to_string());
+    }
+
+    text
+}
+
+fn restore() {
+    let _ = stdout().execute(DisableMouseCapture);
+    let _ = disable_raw_mode();
+    let _ = stdout().execute(LeaveAlternateScreen);
+}
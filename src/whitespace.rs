@@ -0,0 +1,395 @@
+use crate::theme::Theme;
+use git2::Repository;
+use ratatui::{
+    style::{Modifier, Style},
+    text::{Line, Span},
+};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// How whitespace shows in diffs: how wide a tab is, whether tabs and
+/// trailing spaces get markers, and whether added trailing whitespace is
+/// flagged the way `git diff` colors it.
+#[derive(Clone)]
+pub struct Whitespace {
+    /// The config file's `tab_width`, for when nothing closer says
+    configured: u8,
+    /// `tabwidth=` from `core.whitespace`, else `configured`; an
+    /// `.editorconfig` section for the file still comes first
+    tab_width: u8,
+    /// `→` for each tab and `·` for each trailing space, on every line
+    pub markers: bool,
+    /// Flag trailing whitespace on added lines, unless `core.whitespace`
+    /// turns that check off
+    trailing: bool,
+    /// Where the paths in diffs are relative to, for finding their
+    /// `.editorconfig` files; `None` in a bare repository
+    workdir: Option<Arc<Path>>,
+}
+
+impl Default for Whitespace {
+    fn default() -> Self {
+        Whitespace {
+            configured: 8,
+            tab_width: 8,
+            markers: false,
+            trailing: true,
+            workdir: None,
+        }
+    }
+}
+
+impl Whitespace {
+    pub fn new(tab_width: u8, markers: bool) -> Self {
+        Whitespace {
+            configured: tab_width,
+            tab_width,
+            markers,
+            ..Default::default()
+        }
+    }
+
+    /// These settings for the diffs of `repo`, with what its
+    /// `core.whitespace` says taken in.
+    pub fn for_repo(&self, repo: &Repository) -> Self {
+        let (mut tab_width, mut trailing) = (self.configured, true);
+        let rules = repo.config().and_then(|c| c.get_string("core.whitespace"));
+        for rule in rules.iter().flat_map(|r| r.split(',')).map(str::trim) {
+            match rule {
+                "-blank-at-eol" | "-trailing-space" => trailing = false,
+                "blank-at-eol" | "trailing-space" => trailing = true,
+                _ => {
+                    if let Some(width) = rule.strip_prefix("tabwidth=")
+                        && let Ok(width @ 1..=63) = width.parse()
+                    {
+                        tab_width = width;
+                    }
+                }
+            }
+        }
+
+        let workdir = repo.workdir().map(Arc::from);
+        Whitespace {
+            tab_width,
+            trailing,
+            workdir,
+            ..self.clone()
+        }
+    }
+
+    /// How wide a tab is in `path`: what its `.editorconfig` says, else
+    /// `core.whitespace`, else the config file.
+    pub fn tab_width(&self, path: &Path) -> usize {
+        self.workdir
+            .as_ref()
+            .and_then(|dir| editorconfig(&dir.join(path)))
+            .unwrap_or(self.tab_width)
+            .into()
+    }
+}
+
+/// Expands the tabs of every `+`, `-` and context line of `lines` to their
+/// file's tab stops, and flags trailing whitespace on the added ones.
+/// `files` says which line each file starts at. A `-` line and the `+` line
+/// replacing it that differ only in whitespace get markers whether or not
+/// they're on, since otherwise the two look the same.
+pub fn show(
+    lines: &mut [Line<'static>],
+    origins: &[char],
+    files: &[(usize, PathBuf)],
+    theme: &Theme,
+) {
+    let settings = &theme.whitespace;
+    let mut widths = vec![usize::from(settings.tab_width); lines.len()];
+    for (k, (start, path)) in files.iter().enumerate() {
+        let end = files.get(k + 1).map_or(lines.len(), |&(next, _)| next);
+        let width = settings.tab_width(path);
+        widths[*start..end].fill(width);
+    }
+
+    let mut marked = vec![settings.markers; lines.len()];
+    if !settings.markers {
+        let mut i = 0;
+        while i < lines.len() {
+            let deleted = run(origins, i, '-');
+            if deleted == 0 {
+                i += 1;
+                continue;
+            }
+            let added = run(origins, i + deleted, '+');
+            for k in 0..deleted.min(added) {
+                let (old, new) = (i + k, i + deleted + k);
+                let (a, b) = (text(&lines[old]), text(&lines[new]));
+                if a != b && squeezed(&a) == squeezed(&b) {
+                    marked[old] = true;
+                    marked[new] = true;
+                }
+            }
+            i += deleted + added;
+        }
+    }
+
+    let flag = trailing_style(theme);
+    for (i, line) in lines.iter_mut().enumerate() {
+        let trailing = match origins[i] {
+            '+' => flag,
+            '-' | ' ' => None,
+            _ => continue,
+        };
+        show_line(line, widths[i], marked[i], trailing);
+    }
+}
+
+/// How added trailing whitespace stands out, if it does.
+pub fn trailing_style(theme: &Theme) -> Option<Style> {
+    theme.whitespace.trailing.then(|| {
+        if theme.monochrome {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default().bg(theme.warning)
+        }
+    })
+}
+
+/// Rewrites one line's spans with its tabs expanded to stops `width` apart,
+/// `markers` in place of tabs and trailing spaces, and `trailing` patched
+/// onto its trailing whitespace.
+pub fn show_line(line: &mut Line<'static>, width: usize, markers: bool, trailing: Option<Style>) {
+    let text = text(line);
+    let end = text.trim_end().len();
+    if !text.contains('\t') && (end == text.len() || (!markers && trailing.is_none())) {
+        return;
+    }
+    let marker = Style::default().add_modifier(Modifier::DIM);
+
+    let mut pieces: Vec<(String, Style)> = Vec::new();
+    let mut emit = |shown: &str, style: Style| match pieces.last_mut() {
+        Some((last, last_style)) if *last_style == style => last.push_str(shown),
+        _ => pieces.push((shown.to_string(), style)),
+    };
+    let (mut offset, mut column) = (0, 0);
+    for span in &line.spans {
+        for (i, c) in span.content.char_indices() {
+            let style = match trailing {
+                Some(flag) if offset + i >= end => span.style.patch(flag),
+                _ => span.style,
+            };
+            match c {
+                '\t' => {
+                    let stop = width - column % width;
+                    if markers {
+                        emit("→", style.patch(marker));
+                        emit(&" ".repeat(stop - 1), style);
+                    } else {
+                        emit(&" ".repeat(stop), style);
+                    }
+                    column += stop;
+                }
+                ' ' if markers && offset + i >= end => {
+                    emit("·", style.patch(marker));
+                    column += 1;
+                }
+                c => {
+                    emit(c.encode_utf8(&mut [0; 4]), style);
+                    column += 1;
+                }
+            }
+        }
+        offset += span.content.len();
+    }
+
+    line.spans = pieces
+        .into_iter()
+        .map(|(content, style)| Span::styled(content, style))
+        .collect();
+}
+
+fn run(origins: &[char], from: usize, origin: char) -> usize {
+    origins
+        .get(from..)
+        .map_or(0, |rest| rest.iter().take_while(|&&o| o == origin).count())
+}
+
+fn text(line: &Line) -> String {
+    line.spans
+        .iter()
+        .map(|span| span.content.as_ref())
+        .collect()
+}
+
+/// `text` with all its whitespace taken out.
+fn squeezed(text: &str) -> String {
+    text.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+// ---------- .editorconfig ----------
+
+/// One `.editorconfig` file: whether it's the last to look at going up,
+/// and each section's glob with the widths it sets or unsets.
+struct EditorConfig {
+    root: bool,
+    sections: Vec<(String, Vec<(String, String)>)>,
+}
+
+/// The tab width `.editorconfig` files give `path`, an absolute one: its
+/// `tab_width`, else a numeric `indent_size`. Files nearer to it win, and
+/// within a file later sections do; `root = true` stops the search upward.
+fn editorconfig(path: &Path) -> Option<u8> {
+    let mut found = Vec::new();
+    for dir in path.ancestors().skip(1) {
+        let Ok(text) = fs::read_to_string(dir.join(".editorconfig")) else {
+            continue;
+        };
+        let config = parse(&text);
+        let root = config.root;
+        found.push((dir, config));
+        if root {
+            break;
+        }
+    }
+
+    let (mut tab_width, mut indent_size) = (None, None);
+    for (dir, config) in found.iter().rev() {
+        let Ok(relative) = path.strip_prefix(dir) else {
+            continue;
+        };
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        for (glob, properties) in &config.sections {
+            if !section_matches(glob, &relative) {
+                continue;
+            }
+            for (key, value) in properties {
+                let width = value.parse::<u8>().ok().filter(|&w| w > 0);
+                match key.as_str() {
+                    "tab_width" => tab_width = width,
+                    // `indent_size = tab` leaves the width to `tab_width`
+                    "indent_size" if value != "tab" => indent_size = width,
+                    _ => {}
+                }
+            }
+        }
+    }
+    tab_width.or(indent_size)
+}
+
+fn parse(text: &str) -> EditorConfig {
+    let mut config = EditorConfig {
+        root: false,
+        sections: Vec::new(),
+    };
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+        if let Some(glob) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            config.sections.push((glob.to_string(), Vec::new()));
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let (key, value) = (key.trim().to_lowercase(), value.trim().to_lowercase());
+        match config.sections.last_mut() {
+            Some((_, properties)) => properties.push((key, value)),
+            // Before any section only `root` means anything
+            None if key == "root" => config.root = value == "true",
+            None => {}
+        }
+    }
+    config
+}
+
+/// Whether a section's `glob` takes in `path`, relative to its
+/// `.editorconfig`. A glob without a `/` matches the name at any depth.
+fn section_matches(glob: &str, path: &str) -> bool {
+    let (glob, path) = if glob.contains('/') {
+        (glob.strip_prefix('/').unwrap_or(glob), path)
+    } else {
+        (glob, path.rsplit('/').next().unwrap_or(path))
+    };
+    let path: Vec<char> = path.chars().collect();
+    expand_braces(glob)
+        .iter()
+        .any(|glob| glob_matches(&glob.chars().collect::<Vec<_>>(), &path))
+}
+
+/// `{a,b}` alternatives spelled out: `*.{js,ts}` is `*.js` and `*.ts`.
+/// Braces without a comma stay as they are.
+fn expand_braces(glob: &str) -> Vec<String> {
+    let Some(open) = glob.find('{') else {
+        return vec![glob.to_string()];
+    };
+    let (mut depth, mut close, mut commas) = (0, None, Vec::new());
+    for (i, c) in glob[open..].char_indices().map(|(i, c)| (open + i, c)) {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    close = Some(i);
+                    break;
+                }
+            }
+            ',' if depth == 1 => commas.push(i),
+            _ => {}
+        }
+    }
+    let Some(close) = close.filter(|_| !commas.is_empty()) else {
+        // Kept literal; what comes after may still have some
+        let (head, tail) = glob.split_at(open + 1);
+        return expand_braces(tail)
+            .into_iter()
+            .map(|rest| format!("{}{}", head, rest))
+            .collect();
+    };
+
+    let (head, tail) = (&glob[..open], &glob[close + 1..]);
+    let mut bounds = vec![open];
+    bounds.extend(&commas);
+    bounds.push(close);
+    bounds
+        .windows(2)
+        .flat_map(|pair| expand_braces(&format!("{}{}{}", head, &glob[pair[0] + 1..pair[1]], tail)))
+        .collect()
+}
+
+/// `*` matches within a directory, `**` across them, `?` one character
+/// other than `/`, `[abc]`, `[a-z]` and `[!abc]` one of a set.
+fn glob_matches(glob: &[char], path: &[char]) -> bool {
+    match glob {
+        [] => path.is_empty(),
+        ['*', '*', rest @ ..] => (0..=path.len()).any(|i| glob_matches(rest, &path[i..])),
+        ['*', rest @ ..] => (0..=path.len())
+            .take_while(|&i| !path[..i].contains(&'/'))
+            .any(|i| glob_matches(rest, &path[i..])),
+        ['?', rest @ ..] => {
+            path.first().is_some_and(|&c| c != '/') && glob_matches(rest, &path[1..])
+        }
+        ['[', rest @ ..] if let Some(end) = rest.iter().skip(1).position(|&c| c == ']') => {
+            let Some((&c, path_rest)) = path.split_first() else {
+                return false;
+            };
+            let (set, rest) = (&rest[..end + 1], &rest[end + 2..]);
+            let (negated, set) = match set {
+                ['!' | '^', set @ ..] => (true, set),
+                set => (false, set),
+            };
+            let mut inside = false;
+            let mut i = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == '-' {
+                    inside |= (set[i]..=set[i + 2]).contains(&c);
+                    i += 3;
+                } else {
+                    inside |= set[i] == c;
+                    i += 1;
+                }
+            }
+            inside != negated && c != '/' && glob_matches(rest, path_rest)
+        }
+        ['\\', c, rest @ ..] | [c, rest @ ..] => {
+            path.first() == Some(c) && glob_matches(rest, &path[1..])
+        }
+    }
+}
@@ -0,0 +1,353 @@
+mod entry;
+
+use crate::help;
+use crate::keymap::{Action, Keymap, Scope};
+use crate::panes::{self, Panes};
+use crate::theme::Theme;
+use crate::tui::{TICK, Tui};
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode};
+use entry::WorktreeInfo;
+use git2::Repository;
+use ratatui::{
+    prelude::*,
+    widgets::{ListItem, ListState},
+};
+use std::path::{Path, PathBuf};
+
+/// What is being typed on the helper line for a new worktree.
+enum Prompt {
+    Branch(String),
+    /// Its directory, the branch already given
+    Path {
+        branch: String,
+        path: String,
+    },
+}
+
+/// Every worktree of the repository with what it has checked out and
+/// whether it's locked or has uncommitted changes, the selected one's
+/// changes shown alongside. Worktrees can be added for a branch, removed and
+/// pruned. Returns the worktree picked to open hg in, if any.
+pub fn worktree(
+    tui: &mut Tui,
+    repo: &Repository,
+    list_width: u16,
+    keymap: &Keymap,
+    theme: &Theme,
+) -> Result<Option<PathBuf>> {
+    let mut worktrees = entry::load(repo)?;
+    let mut panes: Panes<PathBuf> = Panes::new(list_width);
+
+    let mut message: Option<String> = None;
+    let mut prompt: Option<Prompt> = None;
+    let mut confirm_remove = false;
+    let mut show_help = false;
+
+    // ---------- Main Loop ----------
+    loop {
+        let selected = panes.selected().and_then(|i| worktrees.get(i));
+
+        // ---------- Preview ----------
+        panes.preview(selected, |w| w.path.clone(), |w| entry::show(w, theme));
+
+        // ---------- Helper line ----------
+        let help_line = match (&prompt, selected, message.clone()) {
+            (Some(current), ..) => {
+                let (label, text) = match current {
+                    Prompt::Branch(branch) => (String::from("Branch for the worktree: "), branch),
+                    Prompt::Path { branch, path } => (format!("Check {} out in: ", branch), path),
+                };
+                Line::from(vec![
+                    Span::styled(label, Style::default().fg(theme.accent)),
+                    Span::raw(text.clone()),
+                    Span::styled("█", Style::default().fg(theme.accent)),
+                ])
+            }
+            (None, Some(worktree), _) if confirm_remove => Line::styled(
+                match worktree.changes.len() {
+                    0 => format!("Remove worktree {}? (y/n)", worktree.path.display()),
+                    n => format!(
+                        "Remove worktree {} and lose its {} uncommitted changes? (y/n)",
+                        worktree.path.display(),
+                        n
+                    ),
+                },
+                Style::default()
+                    .fg(theme.warning)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            (None, _, Some(text)) => Line::from(text),
+            (None, ..) => help_line(keymap),
+        };
+
+        let width = worktrees
+            .iter()
+            .map(|w| w.path.to_string_lossy().chars().count())
+            .max()
+            .unwrap_or(0);
+        let items: Vec<ListItem> = worktrees
+            .iter()
+            .map(|w| ListItem::new(worktree_line(w, width, theme)))
+            .collect();
+        let list = panes::list(items, "No worktrees", theme);
+
+        tui.draw(|frame| {
+            let (main, help) = panes::split(frame.area());
+            panes.render(frame, main, &list, " Worktrees ", " Worktree ", theme);
+            panes::render_help(frame, help, help_line, prompt.is_some() || confirm_remove);
+
+            if show_help {
+                help::render(frame, keymap, theme);
+            }
+        })?;
+
+        // ---------- Input Handling ----------
+        if !event::poll(TICK)? {
+            continue;
+        }
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        message = None;
+
+        let selected = panes.selected().and_then(|i| worktrees.get(i));
+
+        // A pending remove swallows every key until it is answered
+        if confirm_remove {
+            match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    if let Some(worktree) = selected {
+                        message = Some(match entry::remove(repo, worktree) {
+                            Ok(text) => text,
+                            Err(e) => format!("Error: {}", e),
+                        });
+                    }
+                    confirm_remove = false;
+                    reload(repo, &mut worktrees, &mut panes.list_state, None)?;
+                    panes.forget();
+                }
+                KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => confirm_remove = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        if let Some(current) = &mut prompt {
+            let text = match current {
+                Prompt::Branch(text) | Prompt::Path { path: text, .. } => text,
+            };
+            match key.code {
+                KeyCode::Esc => prompt = None,
+                KeyCode::Backspace => {
+                    text.pop();
+                }
+                KeyCode::Char(c) => text.push(c),
+                KeyCode::Enter => match prompt.take() {
+                    Some(Prompt::Branch(branch)) => {
+                        let branch = branch.trim().to_string();
+                        let path = default_path(repo, &branch).to_string_lossy().into_owned();
+                        prompt = Some(Prompt::Path { branch, path });
+                    }
+                    Some(Prompt::Path { branch, path }) => {
+                        let path = absolute(Path::new(path.trim()))?;
+                        match entry::add(repo, &branch, &path) {
+                            Ok(text) => {
+                                message = Some(text);
+                                reload(repo, &mut worktrees, &mut panes.list_state, Some(&path))?;
+                                panes.forget();
+                            }
+                            Err(e) => message = Some(format!("Error: {}", e)),
+                        }
+                    }
+                    None => {}
+                },
+                _ => {}
+            }
+            continue;
+        }
+
+        if show_help {
+            if matches!(
+                keymap.resolve(&key, Scope::Worktree),
+                Some(Action::Help | Action::Quit)
+            ) {
+                show_help = false;
+            }
+            continue;
+        }
+
+        match keymap.resolve(&key, Scope::Worktree) {
+            Some(Action::Quit) => break,
+
+            Some(Action::Help) => show_help = true,
+
+            Some(Action::Refresh) => {
+                let path = selected.map(|w| w.path.clone());
+                reload(repo, &mut worktrees, &mut panes.list_state, path.as_deref())?;
+                panes.forget();
+            }
+
+            Some(Action::OpenWorktree) => match selected {
+                Some(worktree) if worktree.current => {
+                    message = Some(String::from("That's the worktree open here"));
+                }
+                Some(worktree) if worktree.missing() => {
+                    message = Some(String::from("Error: its directory is gone"));
+                }
+                Some(worktree) => return Ok(Some(worktree.path.clone())),
+                None => {}
+            },
+
+            Some(Action::AddWorktree) => prompt = Some(Prompt::Branch(String::new())),
+
+            Some(Action::RemoveWorktree) => match selected {
+                Some(worktree) if worktree.name.is_none() => {
+                    message = Some(String::from("Error: the main worktree can't be removed"));
+                }
+                Some(worktree) if worktree.current => {
+                    message = Some(String::from("Error: that's the worktree open here"));
+                }
+                Some(_) => confirm_remove = true,
+                None => {}
+            },
+
+            Some(Action::PruneWorktrees) => {
+                message = Some(match entry::prune(repo) {
+                    Ok(text) => text,
+                    Err(e) => format!("Error: {}", e),
+                });
+                reload(repo, &mut worktrees, &mut panes.list_state, None)?;
+                panes.forget();
+            }
+
+            Some(action) => {
+                panes.navigate(action, worktrees.len());
+            }
+
+            None => {}
+        }
+    }
+
+    Ok(None)
+}
+
+/// `hg worktree` outside the status view; the worktree picked to open hg in
+/// is handed back for the status view to run there.
+pub fn run(
+    repo: &Repository,
+    list_width: u16,
+    keymap: &Keymap,
+    theme: &Theme,
+) -> Result<Option<PathBuf>> {
+    let mut tui = Tui::new()?;
+    worktree(&mut tui, repo, list_width, keymap, theme)
+}
+
+/// Reloads the worktrees, selecting the one at `path` if there is one, or
+/// else the same row (clamped).
+fn reload(
+    repo: &Repository,
+    worktrees: &mut Vec<WorktreeInfo>,
+    list_state: &mut ListState,
+    path: Option<&Path>,
+) -> Result<()> {
+    *worktrees = entry::load(repo)?;
+
+    let by_path = path.and_then(|path| worktrees.iter().position(|w| w.path == path));
+    let row = list_state
+        .selected()
+        .unwrap_or(0)
+        .min(worktrees.len().saturating_sub(1));
+    list_state.select(Some(by_path.unwrap_or(row)));
+    Ok(())
+}
+
+/// Beside the main worktree, named after it and the branch:
+/// `../project-feature-x` for `feature/x`.
+fn default_path(repo: &Repository, branch: &str) -> PathBuf {
+    let main = repo.commondir().parent().unwrap_or(repo.commondir());
+    let project = main
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let dir = format!("{}-{}", project, branch.replace('/', "-"));
+    main.parent().unwrap_or(main).join(dir)
+}
+
+/// A typed relative path is taken from where hg was started, as git takes it.
+fn absolute(path: &Path) -> Result<PathBuf> {
+    Ok(if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(path)
+    })
+}
+
+/// `/src/project-feature  feature ↑1  3 changes  locked`
+fn worktree_line(worktree: &WorktreeInfo, width: usize, theme: &Theme) -> Line<'static> {
+    let path = worktree.path.to_string_lossy().into_owned();
+    let mut spans = vec![
+        Span::styled(
+            format!("{:<width$}", path),
+            if worktree.current {
+                Style::default()
+                    .fg(theme.accent)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(theme.accent)
+            },
+        ),
+        Span::raw(" "),
+    ];
+
+    match &worktree.head {
+        Some(head) => spans.push(Span::styled(
+            head.to_string(),
+            Style::default().fg(theme.staged),
+        )),
+        None => spans.push(Span::styled("missing", Style::default().fg(theme.warning))),
+    }
+    match worktree.changes.len() {
+        0 => {}
+        1 => spans.push(Span::styled(
+            "  1 change",
+            Style::default().fg(theme.modified),
+        )),
+        n => spans.push(Span::styled(
+            format!("  {} changes", n),
+            Style::default().fg(theme.modified),
+        )),
+    }
+    if worktree.lock.is_some() {
+        spans.push(Span::styled("  locked", Style::default().fg(theme.warning)));
+    }
+    Line::from(spans)
+}
+
+fn help_line(keymap: &Keymap) -> Line<'static> {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let mut spans = vec![Span::raw(" ")];
+
+    for (i, (action, label)) in [
+        (Action::OpenWorktree, "open"),
+        (Action::AddWorktree, "add"),
+        (Action::RemoveWorktree, "remove"),
+        (Action::PruneWorktrees, "prune"),
+        (Action::SwitchFocus, "switch focus"),
+        (Action::Help, "help"),
+        (Action::Quit, "quit"),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        if i > 0 {
+            spans.push(Span::raw(" • "));
+        }
+        spans.push(Span::styled(keymap.label(action), bold));
+        spans.push(Span::raw(format!(" {}", label)));
+    }
+
+    Line::from(spans)
+}
@@ -0,0 +1,258 @@
+use crate::theme::Theme;
+use anyhow::{Result, anyhow, bail};
+use git2::{BranchType, Repository, WorktreeAddOptions, WorktreeLockStatus, WorktreePruneOptions};
+use helper_git_core::repo::RepoSummary;
+use helper_git_core::status::{StatusFilter, collect_status};
+use ratatui::{
+    style::{Modifier, Style},
+    text::{Line, Span},
+};
+use std::path::{Path, PathBuf};
+
+/// One row of the worktree view, like a line of `git worktree list`.
+pub struct WorktreeInfo {
+    /// `None` for the main worktree, which has no name of its own
+    pub name: Option<String>,
+    pub path: PathBuf,
+    /// What it has checked out; `None` when its directory is gone
+    pub head: Option<RepoSummary>,
+    /// The reason given for `git worktree lock`, empty when there was none
+    pub lock: Option<String>,
+    /// Its `git status --porcelain` lines
+    pub changes: Vec<String>,
+    /// The worktree this repository was opened in
+    pub current: bool,
+}
+
+impl WorktreeInfo {
+    /// Its directory is gone, so `git worktree prune` would drop it.
+    pub fn missing(&self) -> bool {
+        self.head.is_none()
+    }
+}
+
+/// The main worktree (unless the repository is bare), then every linked one
+/// by name.
+pub fn load(repo: &Repository) -> Result<Vec<WorktreeInfo>> {
+    let here = repo.workdir().and_then(|dir| dir.canonicalize().ok());
+    let mut worktrees = Vec::new();
+
+    // Inside a linked worktree the main one is opened through the common dir
+    let main = Repository::open(repo.commondir())?;
+    if let Some(dir) = main.workdir() {
+        let path = dir.components().collect();
+        worktrees.push(info(&main, None, path, None, here.as_deref())?);
+    }
+
+    let mut linked = Vec::new();
+    for name in main.worktrees()?.iter().flatten() {
+        let worktree = main.find_worktree(name)?;
+        let lock = match worktree.is_locked()? {
+            WorktreeLockStatus::Unlocked => None,
+            WorktreeLockStatus::Locked(reason) => Some(reason.unwrap_or_default()),
+        };
+        // Without the trailing slash libgit2 leaves on some of them
+        let path: PathBuf = worktree.path().components().collect();
+        let entry = match worktree.validate() {
+            Ok(()) => {
+                let inner = Repository::open_from_worktree(&worktree)?;
+                info(&inner, Some(name), path, lock, here.as_deref())?
+            }
+            Err(_) => WorktreeInfo {
+                name: Some(name.to_string()),
+                path,
+                head: None,
+                lock,
+                changes: Vec::new(),
+                current: false,
+            },
+        };
+        linked.push(entry);
+    }
+
+    linked.sort_by(|a, b| a.name.cmp(&b.name));
+    worktrees.extend(linked);
+    Ok(worktrees)
+}
+
+fn info(
+    repo: &Repository,
+    name: Option<&str>,
+    path: PathBuf,
+    lock: Option<String>,
+    here: Option<&Path>,
+) -> Result<WorktreeInfo> {
+    let changes = collect_status(repo, false, &StatusFilter::default())?
+        .iter()
+        .map(|file| file.porcelain())
+        .collect();
+    let current = here.is_some() && path.canonicalize().ok().as_deref() == here;
+
+    Ok(WorktreeInfo {
+        name: name.map(String::from),
+        path,
+        head: Some(RepoSummary::load(repo)),
+        lock,
+        changes,
+        current,
+    })
+}
+
+/// Where it is, what it has checked out, whether it's locked, then its
+/// uncommitted changes.
+pub fn show(worktree: &WorktreeInfo, theme: &Theme) -> Vec<Line<'static>> {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let mut lines = vec![
+        Line::styled(
+            format!("worktree {}", worktree.path.display()),
+            Style::default().fg(theme.accent),
+        ),
+        Line::from(match &worktree.name {
+            Some(name) => format!("Name:   {}", name),
+            None => String::from("Name:   (main worktree)"),
+        }),
+    ];
+
+    let Some(head) = &worktree.head else {
+        lines.push(Line::from(""));
+        lines.push(Line::styled(
+            "Its directory is gone; prune to forget it",
+            Style::default().fg(theme.warning),
+        ));
+        return lines;
+    };
+
+    lines.push(Line::from(vec![
+        Span::raw("HEAD:   "),
+        Span::styled(
+            if head.detached {
+                format!("detached at {}", head.head)
+            } else {
+                head.to_string()
+            },
+            Style::default().fg(theme.staged),
+        ),
+    ]));
+    if let Some(commit) = &head.last_commit {
+        lines.push(Line::from(format!("        {}", commit)));
+    }
+    if let Some(reason) = &worktree.lock {
+        lines.push(Line::styled(
+            if reason.is_empty() {
+                String::from("Locked")
+            } else {
+                format!("Locked: {}", reason)
+            },
+            Style::default().fg(theme.warning),
+        ));
+    }
+    lines.push(Line::from(""));
+
+    if worktree.changes.is_empty() {
+        lines.push(Line::styled("Nothing to commit", Style::default().dim()));
+        return lines;
+    }
+    lines.push(Line::styled(
+        format!("{} uncommitted changes", worktree.changes.len()),
+        bold,
+    ));
+    for change in &worktree.changes {
+        lines.push(Line::from(format!("  {}", change)));
+    }
+    lines
+}
+
+/// Checks `branch` out in a new worktree at `path`, named after the
+/// directory as `git worktree add` names it. A branch that doesn't exist yet
+/// is made from HEAD first, like `git worktree add -b`.
+pub fn add(repo: &Repository, branch: &str, path: &Path) -> Result<String> {
+    if branch.is_empty() {
+        bail!("name a branch to check out");
+    }
+    if path.exists() {
+        bail!("{} already exists", path.display());
+    }
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .ok_or_else(|| anyhow!("{} has no directory name", path.display()))?;
+    if repo.find_worktree(&name).is_ok() {
+        bail!("there is already a worktree named {}", name);
+    }
+
+    let (branch, created) = match repo.find_branch(branch, BranchType::Local) {
+        Ok(branch) => (branch, false),
+        Err(_) => {
+            let head = repo.head()?.peel_to_commit()?;
+            (repo.branch(branch, &head, false)?, true)
+        }
+    };
+    let branch_name = branch.name()?.unwrap_or("").to_string();
+
+    let mut opts = WorktreeAddOptions::new();
+    opts.reference(Some(branch.get()));
+    let added = repo.worktree(&name, path, Some(&opts));
+    if added.is_err() && created {
+        // Leave nothing behind for a worktree that was never made
+        if let Ok(mut branch) = repo.find_branch(&branch_name, BranchType::Local) {
+            let _ = branch.delete();
+        }
+    }
+    added?;
+
+    Ok(if created {
+        format!(
+            "Added worktree {} at {} on new branch {}",
+            name,
+            path.display(),
+            branch_name
+        )
+    } else {
+        format!(
+            "Added worktree {} at {} on {}",
+            name,
+            path.display(),
+            branch_name
+        )
+    })
+}
+
+/// Deletes the worktree's directory, uncommitted changes and all, and
+/// forgets it; its branch is kept.
+pub fn remove(repo: &Repository, worktree: &WorktreeInfo) -> Result<String> {
+    let Some(name) = &worktree.name else {
+        bail!("the main worktree can't be removed");
+    };
+    if worktree.current {
+        bail!("{} is the worktree open here", name);
+    }
+    if worktree.lock.is_some() {
+        bail!(
+            "{} is locked; unlock it with git worktree unlock first",
+            name
+        );
+    }
+    let mut opts = WorktreePruneOptions::new();
+    opts.valid(true).working_tree(true);
+    repo.find_worktree(name)?.prune(Some(&mut opts))?;
+    Ok(format!("Removed worktree {}", name))
+}
+
+/// Forgets every unlocked worktree whose directory is gone, as `git worktree
+/// prune` does.
+pub fn prune(repo: &Repository) -> Result<String> {
+    let mut pruned = Vec::new();
+    for name in repo.worktrees()?.iter().flatten() {
+        let worktree = repo.find_worktree(name)?;
+        if worktree.is_prunable(None)? {
+            worktree.prune(None)?;
+            pruned.push(name.to_string());
+        }
+    }
+
+    Ok(match pruned.len() {
+        0 => String::from("Nothing to prune"),
+        1 => format!("Pruned worktree {}", pruned[0]),
+        n => format!("Pruned {} worktrees: {}", n, pruned.join(", ")),
+    })
+}